@@ -143,6 +143,24 @@ pub fn contains_duplicates<T: Ord>(vec: &Vec<T>) -> bool {
     false
 }
 
+// Appends " (2)", " (3)", etc to repeated labels, so every label is unique. The first occurrence
+// is unchanged.
+pub fn deduplicate_labels(labels: Vec<String>) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    let mut result = Vec::new();
+    for label in labels {
+        let mut unique = label.clone();
+        let mut cnt = 1;
+        while seen.contains(&unique) {
+            cnt += 1;
+            unique = format!("{} ({})", label, cnt);
+        }
+        seen.insert(unique.clone());
+        result.push(unique);
+    }
+    result
+}
+
 // Use when your key is just PartialEq, not Ord or Hash.
 pub struct VecMap<K, V> {
     inner: Vec<(K, V)>,
@@ -45,6 +45,21 @@ pub fn write_json<T: Serialize>(path: String, obj: &T) {
     // TODO not yet
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_file(path: String, contents: String) {
+    std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap())
+        .expect("Creating parent dir failed");
+    if let Err(err) = std::fs::write(&path, contents) {
+        panic!("Can't write_file({}): {}", path, err);
+    }
+    println!("Wrote {}", path);
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write_file(path: String, contents: String) {
+    // TODO not yet
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub fn slurp_file(path: &str) -> Result<Vec<u8>, Error> {
     let mut file = File::open(path)?;
@@ -102,6 +117,15 @@ pub fn serialized_size_bytes<T: Serialize>(obj: &T) -> usize {
     bincode::serialized_size(obj).unwrap() as usize
 }
 
+// Like write_binary and read_binary, but keeping the bytes in memory
+pub fn to_binary_bytes<T: Serialize>(obj: &T) -> Vec<u8> {
+    bincode::serialize(obj).unwrap()
+}
+
+pub fn from_binary_bytes<T: DeserializeOwned>(bytes: &[u8]) -> T {
+    bincode::deserialize(bytes).unwrap()
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub fn write_binary<T: Serialize>(path: String, obj: &T) {
     if let Err(err) = maybe_write_binary(&path, obj) {
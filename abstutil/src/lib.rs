@@ -10,15 +10,15 @@ mod time;
 pub use crate::cli::CmdArgs;
 pub use crate::clone::Cloneable;
 pub use crate::collections::{
-    contains_duplicates, retain_btreemap, retain_btreeset, wraparound_get, Counter, MultiMap,
-    VecMap,
+    contains_duplicates, deduplicate_labels, retain_btreemap, retain_btreeset, wraparound_get,
+    Counter, MultiMap, VecMap,
 };
 pub use crate::error::Error;
 pub use crate::io::{
     basename, deserialize_btreemap, deserialize_multimap, file_exists, find_next_file,
-    find_prev_file, list_all_objects, load_all_objects, maybe_read_binary, maybe_read_json,
+    find_prev_file, from_binary_bytes, list_all_objects, load_all_objects, maybe_read_binary, maybe_read_json,
     read_binary, read_json, serialize_btreemap, serialize_multimap, serialized_size_bytes,
-    slurp_file, to_json, write_binary, write_json, FileWithProgress,
+    slurp_file, to_binary_bytes, to_json, write_binary, write_file, write_json, FileWithProgress,
 };
 pub use crate::logs::Warn;
 pub use crate::random::{fork_rng, WeightedUsizeChoice};
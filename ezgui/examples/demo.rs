@@ -96,8 +96,9 @@ impl App {
                         .outline(3.0, Color::BLACK)
                         .margin(5),
                 ]),
-                Plot::new_usize(
+                Plot::new_time_vs_usize(
                     ctx,
+                    "Timeseries",
                     vec![
                         Series {
                             label: "Linear".to_string(),
@@ -106,6 +107,7 @@ impl App {
                             pts: (0..(self.elapsed.inner_seconds() as usize))
                                 .map(|s| (Time::START_OF_DAY + Duration::seconds(s as f64), s))
                                 .collect(),
+                            actions: Vec::new(),
                         },
                         Series {
                             label: "Quadratic".to_string(),
@@ -115,12 +117,14 @@ impl App {
                                     (Time::START_OF_DAY + Duration::seconds(s as f64), s.pow(2))
                                 })
                                 .collect(),
+                            actions: Vec::new(),
                         },
                     ],
                     PlotOptions {
                         // Without this, the plot doesn't stretch to cover times in between whole
                         // seconds.
                         max_x: Some(Time::START_OF_DAY + self.elapsed),
+                        log_y: false,
                     },
                 ),
             ])
@@ -31,8 +31,8 @@ pub use crate::runner::{run, EventLoopMode, Settings, GUI};
 pub use crate::screen_geom::{ScreenDims, ScreenPt, ScreenRectangle};
 pub use crate::text::{Line, Text, TextSpan, HOTKEY_COLOR};
 pub use crate::widgets::{
-    Autocomplete, Button, Choice, Filler, Histogram, ItemSlider, JustDraw, ModalMenu, Plot,
-    PlotOptions, Series, Slider, Warper, WarpingItemSlider, Wizard, WrappedWizard,
+    Autocomplete, BarChart, Button, Choice, Filler, Histogram, ItemSlider, JustDraw, ModalMenu,
+    Plot, PlotOptions, Series, Slider, Warper, WarpingItemSlider, Wizard, WrappedWizard, Xvalue,
 };
 
 pub enum InputResult<T: Clone> {
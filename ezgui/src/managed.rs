@@ -1,7 +1,7 @@
 use crate::layout::Widget;
 use crate::widgets::{Checkbox, Dropdown, PopupMenu, TextBox};
 use crate::{
-    Button, Choice, Color, Drawable, EventCtx, Filler, GeomBatch, GfxCtx, Histogram,
+    BarChart, Button, Choice, Color, Drawable, EventCtx, Filler, GeomBatch, GfxCtx, Histogram,
     HorizontalAlignment, JustDraw, Line, MultiKey, Plot, RewriteColor, ScreenDims, ScreenPt,
     ScreenRectangle, Slider, Text, VerticalAlignment,
 };
@@ -39,6 +39,7 @@ enum WidgetType {
     DurationPlot(Plot<Duration>),
     UsizePlot(Plot<usize>),
     Histogram(Histogram),
+    BarChart(BarChart),
     Row(Vec<ManagedWidget>),
     Column(Vec<ManagedWidget>),
     Nothing,
@@ -359,6 +360,10 @@ impl ManagedWidget {
         ManagedWidget::new(WidgetType::Histogram(histogram))
     }
 
+    pub(crate) fn bar_chart(chart: BarChart) -> ManagedWidget {
+        ManagedWidget::new(WidgetType::BarChart(chart))
+    }
+
     pub fn row(widgets: Vec<ManagedWidget>) -> ManagedWidget {
         ManagedWidget::new(WidgetType::Row(
             widgets
@@ -424,10 +429,17 @@ impl ManagedWidget {
             WidgetType::Menu(ref name) => {
                 menus.get_mut(name).unwrap().event(ctx);
             }
-            WidgetType::Filler(_)
-            | WidgetType::DurationPlot(_)
-            | WidgetType::UsizePlot(_)
-            | WidgetType::Histogram(_) => {}
+            WidgetType::DurationPlot(ref mut plot) => {
+                if let Some(action) = plot.event(ctx) {
+                    return Some(Outcome::Clicked(action));
+                }
+            }
+            WidgetType::UsizePlot(ref mut plot) => {
+                if let Some(action) = plot.event(ctx) {
+                    return Some(Outcome::Clicked(action));
+                }
+            }
+            WidgetType::Filler(_) | WidgetType::Histogram(_) | WidgetType::BarChart(_) => {}
             WidgetType::Row(ref mut widgets) | WidgetType::Column(ref mut widgets) => {
                 for w in widgets {
                     if let Some(o) = w.event(ctx, sliders, menus, redo_layout) {
@@ -466,6 +478,7 @@ impl ManagedWidget {
             WidgetType::DurationPlot(ref plot) => plot.draw(g),
             WidgetType::UsizePlot(ref plot) => plot.draw(g),
             WidgetType::Histogram(ref hgram) => hgram.draw(g),
+            WidgetType::BarChart(ref chart) => chart.draw(g),
             WidgetType::Row(ref widgets) | WidgetType::Column(ref widgets) => {
                 for w in widgets {
                     w.draw(g, sliders, menus);
@@ -498,6 +511,7 @@ impl ManagedWidget {
             WidgetType::DurationPlot(ref widget) => widget,
             WidgetType::UsizePlot(ref widget) => widget,
             WidgetType::Histogram(ref widget) => widget,
+            WidgetType::BarChart(ref widget) => widget,
             WidgetType::Row(ref widgets) => {
                 let mut style = Style {
                     flex_direction: FlexDirection::Row,
@@ -625,6 +639,9 @@ impl ManagedWidget {
             WidgetType::Histogram(ref mut widget) => {
                 widget.set_pos(top_left);
             }
+            WidgetType::BarChart(ref mut widget) => {
+                widget.set_pos(top_left);
+            }
             WidgetType::Row(ref mut widgets) => {
                 // layout() doesn't return absolute position; it's relative to the container.
                 for widget in widgets {
@@ -670,10 +687,14 @@ impl ManagedWidget {
             | WidgetType::Filler(_)
             | WidgetType::Checkbox(_)
             | WidgetType::TextBox(_)
-            | WidgetType::Dropdown(_)
-            | WidgetType::DurationPlot(_)
-            | WidgetType::UsizePlot(_) => {}
-            WidgetType::Histogram(_) => {}
+            | WidgetType::Dropdown(_) => {}
+            WidgetType::DurationPlot(ref plot) => {
+                actions.extend(plot.click_actions().cloned());
+            }
+            WidgetType::UsizePlot(ref plot) => {
+                actions.extend(plot.click_actions().cloned());
+            }
+            WidgetType::Histogram(_) | WidgetType::BarChart(_) => {}
             WidgetType::Btn(ref btn) => {
                 if actions.contains(&btn.action) {
                     panic!(
@@ -692,6 +713,46 @@ impl ManagedWidget {
         }
     }
 
+    fn get_checkboxes(&self, checkboxes: &mut HashMap<String, bool>) {
+        match self.widget {
+            WidgetType::Checkbox(ref checkbox) => {
+                if let Some(ref name) = self.id {
+                    checkboxes.insert(name.clone(), checkbox.enabled);
+                }
+            }
+            WidgetType::Row(ref widgets) | WidgetType::Column(ref widgets) => {
+                for w in widgets {
+                    w.get_checkboxes(checkboxes);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn sync_plot_legends(&mut self, checkboxes: &HashMap<String, bool>) {
+        match self.widget {
+            WidgetType::DurationPlot(ref mut plot) => plot.sync_legend(checkboxes),
+            WidgetType::UsizePlot(ref mut plot) => plot.sync_legend(checkboxes),
+            WidgetType::Row(ref mut widgets) | WidgetType::Column(ref mut widgets) => {
+                for w in widgets {
+                    w.sync_plot_legends(checkboxes);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Plots zoom when scrolled, so the panel shouldn't also scroll.
+    fn is_over_plot(&self, pt: ScreenPt) -> bool {
+        match self.widget {
+            WidgetType::DurationPlot(_) | WidgetType::UsizePlot(_) => self.rect.contains(pt),
+            WidgetType::Row(ref widgets) | WidgetType::Column(ref widgets) => {
+                widgets.iter().any(|w| w.is_over_plot(pt))
+            }
+            _ => false,
+        }
+    }
+
     pub fn is_btn(&self, name: &str) -> bool {
         if let WidgetType::Btn(ref btn) = self.widget {
             btn.action == name
@@ -714,6 +775,7 @@ impl ManagedWidget {
             WidgetType::DurationPlot(_) => false,
             WidgetType::UsizePlot(_) => false,
             WidgetType::Histogram(_) => false,
+            WidgetType::BarChart(_) => false,
             WidgetType::Row(ref widgets) | WidgetType::Column(ref widgets) => {
                 for widget in widgets {
                     if let Some(w) = widget.find(name) {
@@ -744,6 +806,7 @@ impl ManagedWidget {
             WidgetType::DurationPlot(_) => false,
             WidgetType::UsizePlot(_) => false,
             WidgetType::Histogram(_) => false,
+            WidgetType::BarChart(_) => false,
             WidgetType::Row(ref mut widgets) | WidgetType::Column(ref mut widgets) => {
                 for widget in widgets {
                     if let Some(w) = widget.find_mut(name) {
@@ -923,7 +986,7 @@ impl Composite {
             && ctx
                 .canvas
                 .get_cursor_in_screen_space()
-                .map(|pt| self.top_level.rect.contains(pt))
+                .map(|pt| self.top_level.rect.contains(pt) && !self.top_level.is_over_plot(pt))
                 .unwrap_or(false)
         {
             if let Some((dx, dy)) = ctx.input.get_mouse_scroll() {
@@ -950,6 +1013,12 @@ impl Composite {
         let result =
             self.top_level
                 .event(ctx, &mut self.sliders, &mut self.menus, &mut redo_layout);
+        if redo_layout {
+            // A checkbox may have toggled a series in a plot's legend
+            let mut checkboxes = HashMap::new();
+            self.top_level.get_checkboxes(&mut checkboxes);
+            self.top_level.sync_plot_legends(&checkboxes);
+        }
         if self.scroll_offset() != before || redo_layout {
             self.recompute_layout(ctx, true);
         }
@@ -0,0 +1,167 @@
+use crate::layout::Widget;
+use crate::widgets::plot::Yvalue;
+use crate::{
+    Color, Drawable, EventCtx, GeomBatch, GfxCtx, Line, ManagedWidget, ScreenDims, ScreenPt, Text,
+};
+use geom::{Angle, Distance, Polygon, Pt2D};
+
+// Vertical bars, one per category, sharing a Y scale that starts at 0
+pub struct BarChart {
+    draw: Drawable,
+
+    rect_labels: Vec<(Polygon, Text)>,
+
+    top_left: ScreenPt,
+    dims: ScreenDims,
+}
+
+impl BarChart {
+    pub fn new(ctx: &EventCtx, bars: Vec<(String, usize, Color)>) -> ManagedWidget {
+        let mut batch = GeomBatch::new();
+        let mut rect_labels = Vec::new();
+
+        let width = 0.20 * ctx.canvas.window_width;
+        let height = 0.15 * ctx.canvas.window_height;
+
+        let max_y = bars.iter().map(|(_, cnt, _)| *cnt).max().unwrap_or(0);
+        let rects = bar_rectangles(
+            &bars.iter().map(|(_, cnt, _)| *cnt).collect::<Vec<_>>(),
+            width,
+            height,
+        );
+
+        let mut outlines = Vec::new();
+        for ((label, cnt, color), rect) in bars.iter().zip(rects) {
+            if let Some(rect) = rect {
+                batch.push(*color, rect.clone());
+                outlines.push(rect.to_outline(Distance::meters(1.5)));
+                rect_labels.push((
+                    rect,
+                    Text::from(Line(format!("{}: {}", label, cnt.prettyprint()))),
+                ));
+            }
+        }
+        batch.extend(Color::BLACK, outlines);
+
+        let chart = BarChart {
+            draw: ctx.upload(batch),
+            rect_labels,
+
+            top_left: ScreenPt::new(0.0, 0.0),
+            dims: ScreenDims::new(width, height),
+        };
+
+        let x_axis = ManagedWidget::row(
+            bars.into_iter()
+                .map(|(label, _, _)| {
+                    let mut batch = GeomBatch::new();
+                    for (color, poly) in Text::from(Line(label)).render_ctx(ctx).consume() {
+                        batch.push(color, poly.rotate(Angle::new_degs(-15.0)));
+                    }
+                    ManagedWidget::draw_batch(ctx, batch.autocrop())
+                })
+                .collect(),
+        )
+        .padding(10);
+
+        let num_y_labels = 3;
+        let mut col = Vec::new();
+        for i in 0..num_y_labels {
+            let percent_y = (i as f64) / ((num_y_labels - 1) as f64);
+            col.push(ManagedWidget::draw_text(
+                ctx,
+                Text::from(Line(max_y.from_percent(percent_y).prettyprint())),
+            ));
+        }
+        col.reverse();
+        let y_axis = ManagedWidget::col(col).padding(10);
+
+        // Don't let the x-axis fill the parent container
+        ManagedWidget::row(vec![ManagedWidget::col(vec![
+            ManagedWidget::row(vec![
+                y_axis.evenly_spaced(),
+                ManagedWidget::bar_chart(chart),
+            ]),
+            x_axis.evenly_spaced(),
+        ])])
+    }
+
+    pub(crate) fn draw(&self, g: &mut GfxCtx) {
+        g.redraw_at(self.top_left, &self.draw);
+
+        if let Some(cursor) = g.canvas.get_cursor_in_screen_space() {
+            let pt = Pt2D::new(cursor.x - self.top_left.x, cursor.y - self.top_left.y);
+            for (rect, lbl) in &self.rect_labels {
+                if rect.contains_pt(pt) {
+                    g.draw_mouse_tooltip(lbl.clone());
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Widget for BarChart {
+    fn get_dims(&self) -> ScreenDims {
+        self.dims
+    }
+
+    fn set_pos(&mut self, top_left: ScreenPt) {
+        self.top_left = top_left;
+    }
+}
+
+// One rectangle per value, in screen-space where Y points down, sharing a scale that starts at 0.
+// Bars are evenly spaced with a gap between them. Zero-height bars aren't drawn.
+fn bar_rectangles(values: &[usize], width: f64, height: f64) -> Vec<Option<Polygon>> {
+    let max_y = values.iter().max().cloned().unwrap_or(0);
+    let slot_width = width / (values.len().max(1) as f64);
+    let bar_width = 0.8 * slot_width;
+    values
+        .iter()
+        .enumerate()
+        .map(|(idx, value)| {
+            let x1 = slot_width * (idx as f64) + 0.1 * slot_width;
+            let percent_y = if max_y == 0 {
+                0.0
+            } else {
+                (*value as f64) / (max_y as f64)
+            };
+            Polygon::rectangle_two_corners(
+                // Top-left
+                Pt2D::new(x1, height * (1.0 - percent_y)),
+                // Bottom-right
+                Pt2D::new(x1 + bar_width, height),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tallest_bar_is_largest_value() {
+        let height = 100.0;
+        let bars = bar_rectangles(&[30, 120, 0, 60], 400.0, height);
+        assert_eq!(bars.len(), 4);
+        // Nothing to draw for an empty bar
+        assert!(bars[2].is_none());
+
+        // For 30, 120, and 60
+        let tops: Vec<f64> = bars
+            .iter()
+            .flatten()
+            .map(|rect| rect.get_bounds().min_y)
+            .collect();
+        // Y points down, so the tallest bar has the smallest top
+        assert_eq!(tops[1], 0.0);
+        assert!(tops.iter().all(|y| *y >= tops[1]));
+        assert_eq!(tops[2], 0.5 * height);
+        // Every bar rests on the bottom
+        for rect in bars.iter().flatten() {
+            assert_eq!(rect.get_bounds().max_y, height);
+        }
+    }
+}
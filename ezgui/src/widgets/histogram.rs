@@ -3,7 +3,7 @@ use crate::{
     Color, Drawable, EventCtx, GeomBatch, GfxCtx, Line, ManagedWidget, ScreenDims, ScreenPt, Text,
 };
 use abstutil::prettyprint_usize;
-use geom::{Distance, Duration, Polygon, Pt2D};
+use geom::{exact_percentile, Distance, Duration, Polygon, Pt2D};
 
 // The X axis is Durations, with positive meaning "faster" (considered good) and negative "slower"
 pub struct Histogram {
@@ -11,13 +11,19 @@ pub struct Histogram {
 
     // TODO Bit sad to pretty much duplicate the geometry?
     rect_labels: Vec<(Polygon, Text)>,
+    // Sorted
+    dts: Vec<Duration>,
+    // (min, max, count) of each bar
+    buckets: Vec<(Duration, Duration, usize)>,
 
     top_left: ScreenPt,
     dims: ScreenDims,
 }
 
 impl Histogram {
-    pub fn new(unsorted_dts: Vec<Duration>, ctx: &EventCtx) -> ManagedWidget {
+    pub fn new(mut unsorted_dts: Vec<Duration>, ctx: &EventCtx) -> ManagedWidget {
+        unsorted_dts.sort();
+        let dts = unsorted_dts;
         let mut batch = GeomBatch::new();
         let mut rect_labels = Vec::new();
 
@@ -25,7 +31,8 @@ impl Histogram {
         let height = 0.15 * ctx.canvas.window_height;
 
         let num_buckets = 10;
-        let (min_x, max_x, bars) = bucketize(unsorted_dts, num_buckets);
+        let (min_x, max_x, bars) = bucketize(dts.clone(), num_buckets);
+        let buckets = bars.clone();
 
         let min_y = 0;
         let max_y = bars.iter().map(|(_, _, cnt)| *cnt).max().unwrap();
@@ -69,6 +76,8 @@ impl Histogram {
         let histogram = Histogram {
             draw: ctx.upload(batch),
             rect_labels,
+            dts,
+            buckets,
 
             top_left: ScreenPt::new(0.0, 0.0),
             dims: ScreenDims::new(width, height),
@@ -101,6 +110,18 @@ impl Histogram {
         col.reverse();
         let y_axis = ManagedWidget::col(col);
 
+        let summary = match (histogram.percentile(50.0), histogram.percentile(90.0)) {
+            (Some(p50), Some(p90)) => {
+                let (lowest, _, _) = histogram.min_bucket();
+                let (_, highest, _) = histogram.max_bucket();
+                format!(
+                    "50%ile {}, 90%ile {}, range [{}, {}]",
+                    p50, p90, lowest, highest
+                )
+            }
+            _ => "no trips".to_string(),
+        };
+
         // Don't let the x-axis fill the parent container
         ManagedWidget::row(vec![ManagedWidget::col(vec![
             ManagedWidget::row(vec![
@@ -108,9 +129,30 @@ impl Histogram {
                 ManagedWidget::histogram(histogram),
             ]),
             x_axis.evenly_spaced(),
+            ManagedWidget::draw_text(ctx, Text::from(Line(summary))),
         ])])
     }
 
+    // None if there's no data
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        exact_percentile(&self.dts, p)
+    }
+
+    // The leftmost bar, as (min, max, count)
+    pub fn min_bucket(&self) -> (Duration, Duration, usize) {
+        self.buckets[0]
+    }
+
+    // The rightmost bar, as (min, max, count)
+    pub fn max_bucket(&self) -> (Duration, Duration, usize) {
+        *self.buckets.last().unwrap()
+    }
+
+    // Every bar, as (min, max, count)
+    pub fn buckets(&self) -> &Vec<(Duration, Duration, usize)> {
+        &self.buckets
+    }
+
     pub(crate) fn draw(&self, g: &mut GfxCtx) {
         g.redraw_at(self.top_left, &self.draw);
 
@@ -1,4 +1,5 @@
 mod autocomplete;
+mod bar_chart;
 mod button;
 mod checkbox;
 mod dropdown;
@@ -15,6 +16,7 @@ mod warper;
 mod wizard;
 
 pub use self::autocomplete::Autocomplete;
+pub use self::bar_chart::BarChart;
 pub use self::button::Button;
 pub use self::checkbox::Checkbox;
 pub(crate) use self::dropdown::Dropdown;
@@ -22,7 +24,7 @@ pub use self::filler::Filler;
 pub use self::histogram::Histogram;
 pub use self::modal_menu::ModalMenu;
 pub use self::no_op::JustDraw;
-pub use self::plot::{Plot, PlotOptions, Series};
+pub use self::plot::{Plot, PlotOptions, Series, Xvalue};
 pub(crate) use self::popup_menu::PopupMenu;
 pub(crate) use self::screenshot::{screenshot_current, screenshot_everything};
 pub use self::slider::{ItemSlider, Slider, WarpingItemSlider};
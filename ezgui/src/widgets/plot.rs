@@ -1,42 +1,75 @@
 use crate::layout::Widget;
 use crate::{
-    Color, Drawable, EventCtx, GeomBatch, GfxCtx, Line, ManagedWidget, ScreenDims, ScreenPt,
-    ScreenRectangle, Text,
+    Button, Color, Drawable, EventCtx, GeomBatch, GfxCtx, Line, ManagedWidget, ScreenDims,
+    ScreenPt, ScreenRectangle, Text,
 };
 use abstutil::prettyprint_usize;
-use geom::{Angle, Bounds, Circle, Distance, Duration, FindClosest, PolyLine, Pt2D, Time};
+use geom::{
+    nearest_within, Angle, Bounds, Circle, Distance, Duration, FindClosest, PolyLine, Polygon,
+    Pt2D, Time,
+};
+use std::collections::{HashMap, HashSet};
 
-// The X is always time
+// The X axis can be anything implementing Xvalue, but the type is erased after construction, so
+// widgets only need to care about the Y type.
 pub struct Plot<T> {
-    draw: Drawable,
+    // Scopes the legend checkboxes to this plot
+    name: String,
+    // Just the Y grid lines; everything depending on X is redrawn when the window changes.
+    draw_grid: Drawable,
+    draw_x_grid: Drawable,
+    // One per series, so they can be hidden individually
+    draw_series: Vec<(String, Drawable)>,
+    // Series labels toggled off from the legend
+    hidden: HashSet<String>,
+    // Enough to redraw the series when the window changes
+    series: Vec<SeriesGeom>,
+    style: Style,
 
     // The geometry here is in screen-space.
-    max_x: Time,
+    max_x: f64,
+    // The range of X values currently shown, starting with everything. Scrolling zooms and
+    // dragging pans.
+    window: (f64, f64),
+    // While dragging, the X value grabbed
+    drag_from: Option<f64>,
+    prettyprint_x: fn(f64) -> String,
     max_y: Box<dyn Yvalue<T>>,
+    // If the Y axis is log-scaled, this is log10(max_y)
+    log_max_y: Option<f64>,
     closest: FindClosest<String>,
+    // Points with an action: (series label, point, action produced by clicking it)
+    points: Vec<(String, Pt2D, String)>,
 
     top_left: ScreenPt,
     dims: ScreenDims,
 }
 
-pub struct PlotOptions {
-    pub max_x: Option<Time>,
+pub struct PlotOptions<X> {
+    pub max_x: Option<X>,
+    // Useful when one series dwarfs the others. Values under 1 are squished to the bottom.
+    pub log_y: bool,
 }
 
-impl PlotOptions {
-    pub fn new() -> PlotOptions {
-        PlotOptions { max_x: None }
+impl<X> PlotOptions<X> {
+    pub fn new() -> PlotOptions<X> {
+        PlotOptions {
+            max_x: None,
+            log_y: false,
+        }
     }
 }
 
 impl<T: 'static + Ord + PartialEq + Copy + core::fmt::Debug + Yvalue<T>> Plot<T> {
     // TODO I want to store y_zero in the trait, but then we can't Box max_y.
     // Returns (plot, legend, X axis labels, Y axis labels)
-    fn new(
+    fn new<X: Xvalue>(
         ctx: &EventCtx,
-        series: Vec<Series<T>>,
+        name: &str,
+        series: Vec<Series<X, T>>,
         y_zero: T,
-        opts: PlotOptions,
+        opts: PlotOptions<X>,
+        style: Style,
     ) -> (Plot<T>, ManagedWidget, ManagedWidget, ManagedWidget) {
         let mut batch = GeomBatch::new();
 
@@ -59,25 +92,39 @@ impl<T: 'static + Ord + PartialEq + Copy + core::fmt::Debug + Yvalue<T>> Plot<T>
                                     .to_polygon(),
                             )]),
                         ),
-                        ManagedWidget::draw_text(ctx, Text::from(Line(&s.label))),
+                        // Clicking the label toggles the series. The Composite syncs this back
+                        // to the plot by the checkbox name.
+                        ManagedWidget::custom_checkbox(
+                            true,
+                            Button::text_no_bg(
+                                Text::from(Line(&s.label).fg(Color::grey(0.5))),
+                                Text::from(Line(&s.label).fg(Color::ORANGE)),
+                                None,
+                                &format!("show {}", s.label),
+                                false,
+                                ctx,
+                            ),
+                            Button::text_no_bg(
+                                Text::from(Line(&s.label)),
+                                Text::from(Line(&s.label).fg(Color::ORANGE)),
+                                None,
+                                &format!("hide {}", s.label),
+                                false,
+                                ctx,
+                            ),
+                        )
+                        .named(&legend_checkbox_name(name, &s.label)),
                     ])
                 })
                 .collect(),
         );
 
-        // Assume min_x is Time::START_OF_DAY and min_y is y_zero
-        let max_x = opts.max_x.unwrap_or_else(|| {
+        // Assume min_x is 0 and min_y is y_zero
+        let max_x = opts.max_x.map(|x| x.to_f64()).unwrap_or_else(|| {
             series
                 .iter()
-                .map(|s| {
-                    s.pts
-                        .iter()
-                        .map(|(t, _)| *t)
-                        .max()
-                        .unwrap_or(Time::START_OF_DAY)
-                })
-                .max()
-                .unwrap_or(Time::START_OF_DAY)
+                .flat_map(|s| s.pts.iter().map(|(x, _)| x.to_f64()))
+                .fold(0.0, f64::max)
         });
         let max_y = series
             .iter()
@@ -90,16 +137,24 @@ impl<T: 'static + Ord + PartialEq + Copy + core::fmt::Debug + Yvalue<T>> Plot<T>
             })
             .max()
             .unwrap_or(y_zero);
+        let log_max_y = if opts.log_y {
+            Some(log_scale(max_y.to_f64()))
+        } else {
+            None
+        };
 
         // Grid lines for the Y scale. Draw up to 10 lines max to cover the order of magnitude of
         // the range.
         // TODO This caps correctly, but if the max is 105, then suddenly we just have 2 grid
         // lines.
-        {
-            let order_of_mag = 10.0_f64.powf(max_y.to_f64().log10().ceil());
-            for i in 0..10 {
-                let y = max_y.from_f64(order_of_mag / 10.0 * (i as f64));
-                let pct = y.to_percent(max_y);
+        if let Some(log_max) = log_max_y {
+            // One line per power of 10
+            for i in 0..=(log_max.ceil() as usize) {
+                let pct = if log_max == 0.0 {
+                    0.0
+                } else {
+                    (i as f64) / log_max
+                };
                 if pct > 1.0 {
                     break;
                 }
@@ -112,76 +167,77 @@ impl<T: 'static + Ord + PartialEq + Copy + core::fmt::Debug + Yvalue<T>> Plot<T>
                     .make_polygons(Distance::meters(5.0)),
                 );
             }
-        }
-        // X axis grid
-        if max_x != Time::START_OF_DAY {
-            let order_of_mag = 10.0_f64.powf(max_x.inner_seconds().log10().ceil());
+        } else {
+            let order_of_mag = 10.0_f64.powf(max_y.to_f64().log10().ceil());
             for i in 0..10 {
-                let x = Time::START_OF_DAY + Duration::seconds(order_of_mag / 10.0 * (i as f64));
-                let pct = x.to_percent(max_x);
+                let y = max_y.from_f64(order_of_mag / 10.0 * (i as f64));
+                let pct = y.to_percent(max_y);
                 if pct > 1.0 {
                     break;
                 }
                 batch.push(
                     Color::BLACK,
                     PolyLine::new(vec![
-                        Pt2D::new(pct * width, 0.0),
-                        Pt2D::new(pct * width, height),
+                        Pt2D::new(0.0, (1.0 - pct) * height),
+                        Pt2D::new(width, (1.0 - pct) * height),
                     ])
                     .make_polygons(Distance::meters(5.0)),
                 );
             }
         }
+        let series = series
+            .into_iter()
+            .map(|s| {
+                if !s.actions.is_empty() {
+                    assert_eq!(s.actions.len(), s.pts.len());
+                }
+                SeriesGeom {
+                    label: s.label,
+                    color: s.color,
+                    pts: s
+                        .pts
+                        .into_iter()
+                        .map(|(x, y)| {
+                            let percent_y = y_to_percent(y, max_y, log_max_y.is_some());
+                            // Y inversion! :D
+                            (x.to_f64(), (1.0 - percent_y) * height)
+                        })
+                        .collect(),
+                    actions: s.actions,
+                }
+            })
+            .collect();
 
-        let mut closest = FindClosest::new(&Bounds::from(&vec![
-            Pt2D::new(0.0, 0.0),
-            Pt2D::new(width, height),
-        ]));
-        for s in series {
-            if max_x == Time::START_OF_DAY {
-                continue;
-            }
-            let mut pts = Vec::new();
-            for (t, y) in s.pts {
-                let percent_x = t.to_percent(max_x);
-                let percent_y = y.to_percent(max_y);
-                pts.push(Pt2D::new(
-                    percent_x * width,
-                    // Y inversion! :D
-                    (1.0 - percent_y) * height,
-                ));
-            }
-            pts.dedup();
-            if pts.len() >= 2 {
-                closest.add(s.label.clone(), &pts);
-                batch.push(
-                    s.color,
-                    // The input data might be nice and deduped, but after trimming precision for
-                    // Pt2D, there might be small repeats. Just plow ahead and draw anyway.
-                    PolyLine::unchecked_new(pts)
-                        .make_polygons_with_miter_threshold(Distance::meters(5.0), 10.0),
-                );
-            }
-        }
-
-        let plot = Plot {
-            draw: ctx.upload(batch),
-            closest,
+        let mut plot = Plot {
+            name: name.to_string(),
+            draw_grid: ctx.upload(batch),
+            draw_x_grid: ctx.upload(GeomBatch::new()),
+            draw_series: Vec::new(),
+            hidden: HashSet::new(),
+            series,
+            style,
+            closest: FindClosest::new(&Bounds::new()),
+            points: Vec::new(),
             max_x,
+            window: (0.0, max_x),
+            drag_from: None,
+            prettyprint_x: prettyprint_x::<X>,
             max_y: Box::new(max_y),
+            log_max_y,
 
             top_left: ScreenPt::new(0.0, 0.0),
             dims: ScreenDims::new(width, height),
         };
+        plot.project(ctx);
 
         let num_x_labels = 3;
         let mut row = Vec::new();
         for i in 0..num_x_labels {
             let percent_x = (i as f64) / ((num_x_labels - 1) as f64);
-            let t = max_x.percent_of(percent_x);
+            let x = prettyprint_x::<X>(percent_x * max_x);
             // TODO Need ticks now to actually see where this goes
             let mut batch = GeomBatch::new();
-            for (color, poly) in Text::from(Line(t.to_string())).render_ctx(ctx).consume() {
+            for (color, poly) in Text::from(Line(x)).render_ctx(ctx).consume() {
                 batch.push(color, poly.rotate(Angle::new_degs(-15.0)));
             }
             row.push(ManagedWidget::draw_batch(ctx, batch.autocrop()));
@@ -194,7 +250,9 @@ impl<T: 'static + Ord + PartialEq + Copy + core::fmt::Debug + Yvalue<T>> Plot<T>
             let percent_y = (i as f64) / ((num_y_labels - 1) as f64);
             col.push(ManagedWidget::draw_text(
                 ctx,
-                Text::from(Line(max_y.from_percent(percent_y).prettyprint())),
+                Text::from(Line(
+                    y_from_percent(&max_y, percent_y, log_max_y).prettyprint(),
+                )),
             ));
         }
         col.reverse();
@@ -204,26 +262,48 @@ impl<T: 'static + Ord + PartialEq + Copy + core::fmt::Debug + Yvalue<T>> Plot<T>
     }
 
     pub(crate) fn draw(&self, g: &mut GfxCtx) {
-        g.redraw_at(self.top_left, &self.draw);
+        g.redraw_at(self.top_left, &self.draw_grid);
+        g.redraw_at(self.top_left, &self.draw_x_grid);
+        for (label, draw) in &self.draw_series {
+            if !self.hidden.contains(label) {
+                g.redraw_at(self.top_left, draw);
+            }
+        }
 
         if let Some(cursor) = g.canvas.get_cursor_in_screen_space() {
             if ScreenRectangle::top_left(self.top_left, self.dims).contains(cursor) {
                 let radius = Distance::meters(15.0);
+                let cursor_pt = Pt2D::new(cursor.x - self.top_left.x, cursor.y - self.top_left.y);
+                let mut matches: Vec<(String, Pt2D)> = self
+                    .closest
+                    .all_close_pts(cursor_pt, radius)
+                    .into_iter()
+                    .map(|(label, pt, _)| (label, pt))
+                    .collect();
+                // Clickable points on a line are already described by the line itself
+                let line_labels: HashSet<String> =
+                    matches.iter().map(|(label, _)| label.clone()).collect();
+                for (label, pt, _) in &self.points {
+                    if pt.dist_to(cursor_pt) <= radius && !line_labels.contains(label) {
+                        matches.push((label.clone(), *pt));
+                    }
+                }
                 let mut txt = Text::new();
-                for (label, pt, _) in self.closest.all_close_pts(
-                    Pt2D::new(cursor.x - self.top_left.x, cursor.y - self.top_left.y),
-                    radius,
-                ) {
-                    // TODO If some/all of the matches have the same t, write it once?
-                    let t = self.max_x.percent_of(pt.x() / self.dims.width);
+                for (label, pt) in matches {
+                    if self.hidden.contains(&label) {
+                        continue;
+                    }
+                    // TODO If some/all of the matches have the same x, write it once?
+                    let x = (self.prettyprint_x)(self.unproject_x(pt.x()));
                     let y_percent = 1.0 - (pt.y() / self.dims.height);
 
                     // TODO Draw this info in the ColorLegend
                     txt.add(Line(format!(
                         "{}: at {}, {}",
                         label,
-                        t,
-                        self.max_y.from_percent(y_percent).prettyprint()
+                        x,
+                        y_from_percent(self.max_y.as_ref(), y_percent, self.log_max_y)
+                            .prettyprint()
                     )));
                 }
                 if !txt.is_empty() {
@@ -237,13 +317,207 @@ impl<T: 'static + Ord + PartialEq + Copy + core::fmt::Debug + Yvalue<T>> Plot<T>
     }
 }
 
+impl<T> Plot<T> {
+    pub fn hidden_series(&self) -> &HashSet<String> {
+        &self.hidden
+    }
+
+    // Clicking the point closest to the cursor produces its action. Scrolling zooms the X axis
+    // around the cursor, and dragging pans it.
+    pub(crate) fn event(&mut self, ctx: &mut EventCtx) -> Option<String> {
+        if let Some(from) = self.drag_from {
+            if ctx.input.left_mouse_button_released() {
+                self.drag_from = None;
+            } else if let Some(pt) = ctx.input.get_moved_mouse() {
+                // Keep the grabbed X value under the cursor
+                let dx = from - self.unproject_x(pt.x - self.top_left.x);
+                self.set_window(ctx, (self.window.0 + dx, self.window.1 + dx));
+            }
+            return None;
+        }
+
+        let cursor = ctx.canvas.get_cursor_in_screen_space()?;
+        if !ScreenRectangle::top_left(self.top_left, self.dims).contains(cursor) {
+            return None;
+        }
+        let cursor_pt = Pt2D::new(cursor.x - self.top_left.x, cursor.y - self.top_left.y);
+
+        if let Some((_, dy)) = ctx.input.get_mouse_scroll() {
+            let x = self.unproject_x(cursor_pt.x());
+            let factor = ZOOM_STEP.powf(-dy);
+            let (x1, x2) = self.window;
+            self.set_window(ctx, (x - (x - x1) * factor, x + (x2 - x) * factor));
+            return None;
+        }
+
+        let action = nearest_within(
+            self.points
+                .iter()
+                .filter(|(label, _, _)| !self.hidden.contains(label))
+                .map(|(_, pt, action)| (action, *pt)),
+            cursor_pt,
+            Distance::meters(15.0),
+        );
+        match action {
+            Some(action) => {
+                if ctx.normal_left_click() {
+                    return Some(action.clone());
+                }
+            }
+            None => {
+                if ctx.input.left_mouse_button_pressed() {
+                    self.drag_from = Some(self.unproject_x(cursor_pt.x()));
+                }
+            }
+        }
+        None
+    }
+
+    fn set_window(&mut self, ctx: &EventCtx, window: (f64, f64)) {
+        let window = clamp_window(window, self.max_x);
+        if window != self.window {
+            self.window = window;
+            self.project(ctx);
+        }
+    }
+
+    fn project_x(&self, x: f64) -> f64 {
+        project_x(x, self.window, self.dims.width)
+    }
+
+    fn unproject_x(&self, screen_x: f64) -> f64 {
+        let (x1, x2) = self.window;
+        if x1 == x2 {
+            return x1;
+        }
+        x1 + screen_x / self.dims.width * (x2 - x1)
+    }
+
+    // Redraw everything depending on the X axis for the current window.
+    fn project(&mut self, ctx: &EventCtx) {
+        let width = self.dims.width;
+        let height = self.dims.height;
+        let (x1, x2) = self.window;
+
+        let mut grid = GeomBatch::new();
+        if x2 > x1 {
+            let step = 10.0_f64.powf((x2 - x1).log10().ceil()) / 10.0;
+            let mut x = (x1 / step).ceil() * step;
+            while x <= x2 {
+                let screen_x = self.project_x(x);
+                grid.push(
+                    Color::BLACK,
+                    PolyLine::new(vec![Pt2D::new(screen_x, 0.0), Pt2D::new(screen_x, height)])
+                        .make_polygons(Distance::meters(5.0)),
+                );
+                x += step;
+            }
+        }
+        // The axis labels don't change, so say what's visible
+        if (x1, x2) != (0.0, self.max_x) {
+            let txt = Text::from(Line(format!(
+                "{} - {}",
+                (self.prettyprint_x)(x1),
+                (self.prettyprint_x)(x2)
+            )));
+            grid.add_translated(txt.render_ctx(ctx), 10.0, 10.0);
+        }
+        self.draw_x_grid = ctx.upload(grid);
+
+        let mut closest = FindClosest::new(&Bounds::from(&vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(width, height),
+        ]));
+        let mut points = Vec::new();
+        let mut draw_series = Vec::new();
+        let mut prev_pts: Option<Vec<Pt2D>> = None;
+        for s in &self.series {
+            let mut series_batch = GeomBatch::new();
+            if x2 <= x1 {
+                // Nothing to draw, but callers may still expect the actions to exist
+                for action in s.actions.iter().flatten() {
+                    points.push((s.label.clone(), Pt2D::new(0.0, height), action.clone()));
+                }
+                continue;
+            }
+            for ((x, y), action) in s.pts.iter().zip(s.actions.iter()) {
+                if let Some(action) = action {
+                    if *x >= x1 && *x <= x2 {
+                        points.push((
+                            s.label.clone(),
+                            Pt2D::new(self.project_x(*x), *y),
+                            action.clone(),
+                        ));
+                    }
+                }
+            }
+
+            if let Style::Scatter = self.style {
+                for (x, y) in &s.pts {
+                    if *x >= x1 && *x <= x2 {
+                        series_batch.push(
+                            s.color,
+                            Circle::new(Pt2D::new(self.project_x(*x), *y), Distance::meters(5.0))
+                                .to_polygon(),
+                        );
+                    }
+                }
+                draw_series.push((s.label.clone(), ctx.upload(series_batch)));
+                continue;
+            }
+
+            let mut pts: Vec<Pt2D> = clip_to_window(&s.pts, self.window)
+                .into_iter()
+                .map(|(x, y)| Pt2D::new(self.project_x(x), y))
+                .collect();
+            if let Style::Bands = self.style {
+                let lower = prev_pts
+                    .take()
+                    .unwrap_or_else(|| pts.iter().map(|pt| Pt2D::new(pt.x(), height)).collect());
+                if let Some(band) = fill_between(&lower, &pts) {
+                    series_batch.push(s.color.alpha(0.5), band);
+                }
+                prev_pts = Some(pts.clone());
+            }
+            pts.dedup();
+            if pts.len() >= 2 {
+                closest.add(s.label.clone(), &pts);
+                series_batch.push(
+                    s.color,
+                    // The input data might be nice and deduped, but after trimming precision for
+                    // Pt2D, there might be small repeats. Just plow ahead and draw anyway.
+                    PolyLine::unchecked_new(pts)
+                        .make_polygons_with_miter_threshold(Distance::meters(5.0), 10.0),
+                );
+            }
+            draw_series.push((s.label.clone(), ctx.upload(series_batch)));
+        }
+        self.closest = closest;
+        self.points = points;
+        self.draw_series = draw_series;
+    }
+
+    pub(crate) fn click_actions(&self) -> impl Iterator<Item = &String> {
+        self.points.iter().map(|(_, _, action)| action)
+    }
+
+    pub(crate) fn sync_legend(&mut self, checkboxes: &HashMap<String, bool>) {
+        self.hidden = hidden_series(
+            &self.name,
+            self.draw_series.iter().map(|(label, _)| label),
+            checkboxes,
+        );
+    }
+}
+
 impl Plot<usize> {
-    pub fn new_usize(
+    pub fn new_usize<X: Xvalue>(
         ctx: &EventCtx,
-        series: Vec<Series<usize>>,
-        opts: PlotOptions,
+        name: &str,
+        series: Vec<Series<X, usize>>,
+        opts: PlotOptions<X>,
     ) -> ManagedWidget {
-        let (plot, legend, x_axis, y_axis) = Plot::new(ctx, series, 0, opts);
+        let (plot, legend, x_axis, y_axis) = Plot::new(ctx, name, series, 0, opts, Style::Lines);
         // Don't let the x-axis fill the parent container
         ManagedWidget::row(vec![ManagedWidget::col(vec![
             legend,
@@ -254,15 +528,47 @@ impl Plot<usize> {
             x_axis.evenly_spaced(),
         ])])
     }
+
+    // Each series is drawn on top of the previous ones, so the top band traces the sum of
+    // everything.
+    pub fn new_stacked_usize<X: Xvalue>(
+        ctx: &EventCtx,
+        name: &str,
+        series: Vec<Series<X, usize>>,
+        opts: PlotOptions<X>,
+    ) -> ManagedWidget {
+        let (plot, legend, x_axis, y_axis) =
+            Plot::new(ctx, name, stack(series), 0, opts, Style::Bands);
+        ManagedWidget::row(vec![ManagedWidget::col(vec![
+            legend,
+            ManagedWidget::row(vec![
+                y_axis.evenly_spaced(),
+                ManagedWidget::usize_plot(plot),
+            ]),
+            x_axis.evenly_spaced(),
+        ])])
+    }
+
+    // The usual case of something counted over the course of a day
+    pub fn new_time_vs_usize(
+        ctx: &EventCtx,
+        name: &str,
+        series: Vec<Series<Time, usize>>,
+        opts: PlotOptions<Time>,
+    ) -> ManagedWidget {
+        Plot::new_usize(ctx, name, series, opts)
+    }
 }
 
 impl Plot<Duration> {
-    pub fn new_duration(
+    pub fn new_duration<X: Xvalue>(
         ctx: &EventCtx,
-        series: Vec<Series<Duration>>,
-        opts: PlotOptions,
+        name: &str,
+        series: Vec<Series<X, Duration>>,
+        opts: PlotOptions<X>,
     ) -> ManagedWidget {
-        let (plot, legend, x_axis, y_axis) = Plot::new(ctx, series, Duration::ZERO, opts);
+        let (plot, legend, x_axis, y_axis) =
+            Plot::new(ctx, name, series, Duration::ZERO, opts, Style::Lines);
         // Don't let the x-axis fill the parent container
         ManagedWidget::row(vec![ManagedWidget::col(vec![
             legend,
@@ -273,6 +579,25 @@ impl Plot<Duration> {
             x_axis.evenly_spaced(),
         ])])
     }
+
+    // Each point is drawn as a dot, without connecting them.
+    pub fn new_scatter_duration<X: Xvalue>(
+        ctx: &EventCtx,
+        name: &str,
+        series: Vec<Series<X, Duration>>,
+        opts: PlotOptions<X>,
+    ) -> ManagedWidget {
+        let (plot, legend, x_axis, y_axis) =
+            Plot::new(ctx, name, series, Duration::ZERO, opts, Style::Scatter);
+        ManagedWidget::row(vec![ManagedWidget::col(vec![
+            legend,
+            ManagedWidget::row(vec![
+                y_axis.evenly_spaced(),
+                ManagedWidget::duration_plot(plot),
+            ]),
+            x_axis.evenly_spaced(),
+        ])])
+    }
 }
 
 impl<T> Widget for Plot<T> {
@@ -285,6 +610,193 @@ impl<T> Widget for Plot<T> {
     }
 }
 
+// Every checkbox in a panel shares one namespace, so legend entries are prefixed by their plot.
+// Otherwise two plots with a "walk" series would toggle each other.
+fn legend_checkbox_name(plot: &str, label: &str) -> String {
+    format!("{}/{}", plot, label)
+}
+
+// Which series of a plot are toggled off, given the state of every checkbox in the panel. Series
+// without a legend checkbox stay visible.
+fn hidden_series<'a, I: IntoIterator<Item = &'a String>>(
+    plot: &str,
+    labels: I,
+    checkboxes: &HashMap<String, bool>,
+) -> HashSet<String> {
+    labels
+        .into_iter()
+        .filter(|label| checkboxes.get(&legend_checkbox_name(plot, label)) == Some(&false))
+        .cloned()
+        .collect()
+}
+
+// A series in screen-space, except X hasn't been projected yet
+struct SeriesGeom {
+    label: String,
+    color: Color,
+    pts: Vec<(f64, f64)>,
+    actions: Vec<Option<String>>,
+}
+
+// Each scroll step zooms by this factor
+const ZOOM_STEP: f64 = 1.25;
+// Don't zoom in further than this fraction of the full X range
+const MIN_WINDOW: f64 = 0.01;
+
+// Where X lands horizontally, when only [x1, x2] fits in the width
+fn project_x(x: f64, (x1, x2): (f64, f64), width: f64) -> f64 {
+    if x1 == x2 {
+        return 0.0;
+    }
+    (x - x1) / (x2 - x1) * width
+}
+
+// Keep the window inside [0, max_x], preserving its length where possible
+fn clamp_window((x1, x2): (f64, f64), max_x: f64) -> (f64, f64) {
+    let len = (x2 - x1).max(max_x * MIN_WINDOW).min(max_x);
+    let x1 = x1.max(0.0).min(max_x - len);
+    (x1, x1 + len)
+}
+
+// Keep the points inside the window, plus interpolated points where the line crosses either edge.
+// Assumes the points are sorted by X, so lines sharing X values get clipped the same way.
+fn clip_to_window(pts: &Vec<(f64, f64)>, (x1, x2): (f64, f64)) -> Vec<(f64, f64)> {
+    let mut result = Vec::new();
+    for (idx, (x, y)) in pts.iter().enumerate() {
+        if idx > 0 {
+            let (prev_x, prev_y) = pts[idx - 1];
+            for edge in vec![x1, x2] {
+                if prev_x < edge && *x > edge {
+                    let pct = (edge - prev_x) / (*x - prev_x);
+                    result.push((edge, prev_y + pct * (*y - prev_y)));
+                }
+            }
+        }
+        if *x >= x1 && *x <= x2 {
+            result.push((*x, *y));
+        }
+    }
+    result
+}
+
+enum Style {
+    Lines,
+    // The series must already be stacked and sampled on the same X values. The area between each
+    // series and the one before it is filled in.
+    Bands,
+    // One dot per point
+    Scatter,
+}
+
+// Resample every series onto the union of all X values, then replace each value with the running
+// total of it and all previous series. Each series must be sorted by X.
+fn stack_series(series: Vec<Vec<(f64, usize)>>) -> Vec<Vec<(f64, usize)>> {
+    let mut xs: Vec<f64> = series
+        .iter()
+        .flat_map(|pts| pts.iter().map(|(x, _)| *x))
+        .collect();
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    xs.dedup();
+
+    let mut totals = vec![0; xs.len()];
+    series
+        .into_iter()
+        .map(|pts| {
+            for (total, x) in totals.iter_mut().zip(xs.iter()) {
+                *total += value_at(&pts, *x);
+            }
+            xs.iter().cloned().zip(totals.iter().cloned()).collect()
+        })
+        .collect()
+}
+
+// Linearly interpolate between samples. Before the first sample, the value is 0; after the last,
+// it stays at the last value.
+fn value_at(pts: &Vec<(f64, usize)>, x: f64) -> usize {
+    if pts.is_empty() || x < pts[0].0 {
+        return 0;
+    }
+    for pair in pts.windows(2) {
+        let (x1, y1) = pair[0];
+        let (x2, y2) = pair[1];
+        if x >= x1 && x <= x2 {
+            if x1 == x2 {
+                return y2;
+            }
+            let pct = (x - x1) / (x2 - x1);
+            return ((y1 as f64) + pct * ((y2 as f64) - (y1 as f64))).round() as usize;
+        }
+    }
+    pts.last().unwrap().1
+}
+
+// Point actions don't survive the resampling.
+fn stack<X: Xvalue>(series: Vec<Series<X, usize>>) -> Vec<Series<X, usize>> {
+    let stacked = stack_series(
+        series
+            .iter()
+            .map(|s| s.pts.iter().map(|(x, y)| (x.to_f64(), *y)).collect())
+            .collect(),
+    );
+    series
+        .into_iter()
+        .zip(stacked)
+        .map(|(s, pts)| Series {
+            label: s.label,
+            color: s.color,
+            pts: pts.into_iter().map(|(x, y)| (X::from_f64(x), y)).collect(),
+            actions: Vec::new(),
+        })
+        .collect()
+}
+
+// The two lines must have the same number of points, each pair sharing an X value.
+fn fill_between(lower: &Vec<Pt2D>, upper: &Vec<Pt2D>) -> Option<Polygon> {
+    assert_eq!(lower.len(), upper.len());
+    let n = lower.len();
+    if n < 2 {
+        return None;
+    }
+    let mut pts = lower.clone();
+    pts.extend(upper.clone());
+    let mut indices = Vec::new();
+    for i in 0..n - 1 {
+        indices.extend(vec![i, i + 1, n + i + 1]);
+        indices.extend(vec![i, n + i + 1, n + i]);
+    }
+    Some(Polygon::precomputed(pts, indices))
+}
+
+// Values under 1 are squished to the bottom.
+fn log_scale(y: f64) -> f64 {
+    y.max(1.0).log10()
+}
+
+// Where y lands on a log-scaled axis from 0 to max_y, as [0.0, 1.0]
+fn log_y_percent(y: f64, max_y: f64) -> f64 {
+    let log_max = log_scale(max_y);
+    if log_max == 0.0 {
+        0.0
+    } else {
+        log_scale(y) / log_max
+    }
+}
+
+fn y_to_percent<T: Yvalue<T>>(y: T, max_y: T, log_y: bool) -> f64 {
+    if log_y {
+        log_y_percent(y.to_f64(), max_y.to_f64())
+    } else {
+        y.to_percent(max_y)
+    }
+}
+
+fn y_from_percent<T>(max_y: &dyn Yvalue<T>, percent: f64, log_max_y: Option<f64>) -> T {
+    match log_max_y {
+        Some(log_max) => max_y.from_f64(10.0_f64.powf(percent * log_max)),
+        None => max_y.from_percent(percent),
+    }
+}
+
 pub trait Yvalue<T> {
     // percent is [0.0, 1.0]
     fn from_percent(&self, percent: f64) -> T;
@@ -338,9 +850,193 @@ impl Yvalue<Duration> for Duration {
     }
 }
 
-pub struct Series<T> {
+pub struct Series<X, T> {
     pub label: String,
     pub color: Color,
-    // X-axis is time. Assume this is sorted by X.
-    pub pts: Vec<(Time, T)>,
+    // Assume this is sorted by X.
+    pub pts: Vec<(X, T)>,
+    // Either empty, or aligned with pts. Clicking near a point with an action produces
+    // Outcome::Clicked.
+    pub actions: Vec<Option<String>>,
+}
+
+// The X axis always starts at 0.
+pub trait Xvalue: 'static + Copy + PartialOrd {
+    fn to_f64(self) -> f64;
+    fn from_f64(x: f64) -> Self;
+    fn prettyprint(self) -> String;
+}
+
+impl Xvalue for Time {
+    fn to_f64(self) -> f64 {
+        self.inner_seconds()
+    }
+    fn from_f64(x: f64) -> Time {
+        Time::START_OF_DAY + Duration::seconds(x)
+    }
+    fn prettyprint(self) -> String {
+        self.to_string()
+    }
+}
+impl Xvalue for Duration {
+    fn to_f64(self) -> f64 {
+        self.inner_seconds()
+    }
+    fn from_f64(x: f64) -> Duration {
+        Duration::seconds(x)
+    }
+    fn prettyprint(self) -> String {
+        self.to_string()
+    }
+}
+impl Xvalue for Distance {
+    fn to_f64(self) -> f64 {
+        self.inner_meters()
+    }
+    fn from_f64(x: f64) -> Distance {
+        Distance::meters(x)
+    }
+    fn prettyprint(self) -> String {
+        self.to_string()
+    }
+}
+// Percentiles and other plain numbers
+impl Xvalue for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+    fn from_f64(x: f64) -> f64 {
+        x
+    }
+    fn prettyprint(self) -> String {
+        format!("{:.1}", self)
+    }
+}
+
+// Plot forgets the X type after construction, but still needs to label points.
+fn prettyprint_x<X: Xvalue>(x: f64) -> String {
+    X::from_f64(x).prettyprint()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hidden_series_per_plot() {
+        let labels: Vec<String> = vec!["walk", "bike", "drive"]
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect();
+        // Two plots in the same panel, both with a "walk" series
+        let mut checkboxes = HashMap::new();
+        checkboxes.insert(legend_checkbox_name("Finished trips", "walk"), false);
+        checkboxes.insert(legend_checkbox_name("Finished trips", "bike"), true);
+        checkboxes.insert(legend_checkbox_name("Active agents", "walk"), true);
+
+        let hidden = hidden_series("Finished trips", &labels, &checkboxes);
+        let drawn: Vec<&String> = labels.iter().filter(|l| !hidden.contains(*l)).collect();
+        assert_eq!(drawn, vec!["bike", "drive"]);
+
+        // Hiding walking in one plot doesn't touch the other
+        assert!(hidden_series("Active agents", &labels, &checkboxes).is_empty());
+    }
+
+    #[test]
+    fn log_y_keeps_small_series_visible() {
+        // Like active cars vs active buses
+        let big = 10_000.0;
+        let small = 10.0;
+        let height = 100.0;
+
+        // On a linear axis, the small series is flattened into the bottom pixel.
+        assert!(small / big * height < 1.0);
+
+        let small_y = log_y_percent(small, big) * height;
+        let big_y = log_y_percent(big, big) * height;
+        assert_eq!(big_y, height);
+        assert!(small_y >= 0.2 * height);
+        assert!(big_y - small_y <= 0.8 * height);
+
+        // Zero and negative values sit on the bottom, instead of blowing up
+        assert_eq!(log_y_percent(0.0, big), 0.0);
+        assert_eq!(log_y_percent(-5.0, big), 0.0);
+        assert_eq!(log_y_percent(0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn stacked_series_top_is_total() {
+        // Sampled at different X values
+        let walk = vec![(0.0, 5), (10.0, 15), (20.0, 5)];
+        let bike = vec![(5.0, 2), (15.0, 4)];
+        let drive = vec![(0.0, 10), (20.0, 30)];
+        let stacked = stack_series(vec![walk, bike, drive]);
+
+        let xs = vec![0.0, 5.0, 10.0, 15.0, 20.0];
+        for pts in &stacked {
+            assert_eq!(pts.iter().map(|(x, _)| *x).collect::<Vec<_>>(), xs);
+        }
+        // At X=10, walk is sampled, bike is halfway between its samples, and drive is halfway
+        // along its line.
+        assert_eq!(stacked[2][2], (10.0, 15 + 3 + 20));
+        // Before bike's first sample, it contributes nothing. After its last, it holds steady.
+        assert_eq!(stacked[2][0], (0.0, 5 + 0 + 10));
+        assert_eq!(stacked[2][4], (20.0, 5 + 4 + 30));
+        // Each band sits on top of the previous one
+        for (lower, upper) in stacked.iter().zip(stacked.iter().skip(1)) {
+            for ((_, y1), (_, y2)) in lower.iter().zip(upper.iter()) {
+                assert!(y1 <= y2);
+            }
+        }
+    }
+
+    #[test]
+    fn plot_zoom_window() {
+        // A whole day on the X axis, in seconds
+        let max_x = 86400.0;
+        let width = 400.0;
+
+        // Panning past either end keeps the window's length
+        assert_eq!(clamp_window((-3600.0, 3600.0), max_x), (0.0, 7200.0));
+        assert_eq!(
+            clamp_window((max_x - 3600.0, max_x + 3600.0), max_x),
+            (max_x - 7200.0, max_x)
+        );
+        // Zooming out too far shows everything, and zooming in too far stops at a minimum
+        assert_eq!(clamp_window((-10.0, max_x + 10.0), max_x), (0.0, max_x));
+        let (x1, x2) = clamp_window((3600.0, 3601.0), max_x);
+        assert_eq!(x1, 3600.0);
+        assert!((x2 - x1 - 0.01 * max_x).abs() < 1e-6);
+
+        // Zoomed into rush hour, 7-9am fills the whole width
+        let window = clamp_window((7.0 * 3600.0, 9.0 * 3600.0), max_x);
+        assert_eq!(project_x(7.0 * 3600.0, window, width), 0.0);
+        assert_eq!(project_x(8.0 * 3600.0, window, width), 0.5 * width);
+        assert_eq!(project_x(9.0 * 3600.0, window, width), width);
+
+        // Lines crossing the edges of the window are cut there
+        let pts = vec![(0.0, 0.0), (8.0 * 3600.0, 10.0), (max_x, 10.0)];
+        assert_eq!(
+            clip_to_window(&pts, window),
+            vec![
+                (7.0 * 3600.0, 8.75),
+                (8.0 * 3600.0, 10.0),
+                (9.0 * 3600.0, 10.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn distance_x_axis_labels() {
+        // Like a distance-vs-duration plot, which only keeps X around as f64 after construction
+        let pts = vec![
+            (Distance::meters(150.0), Duration::minutes(2)),
+            (Distance::meters(1500.0), Duration::minutes(9)),
+        ];
+        let max_x = pts.iter().map(|(x, _)| x.to_f64()).fold(0.0, f64::max);
+        assert_eq!(prettyprint_x::<Distance>(max_x), "1500m");
+        assert_eq!(prettyprint_x::<Distance>(0.5 * max_x), "750m");
+        // The same value on a time axis is labeled differently
+        assert_ne!(prettyprint_x::<Duration>(max_x), "1500m");
+    }
 }
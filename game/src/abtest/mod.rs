@@ -74,7 +74,13 @@ impl State for ABTestMode {
             }
             let (finished, unfinished, by_mode) = app.primary.sim.num_trips();
             txt.add(Line(format!("Finished trips: {}", finished)));
-            txt.add(Line(format!("Unfinished trips: {}", unfinished)));
+            let not_departed = app.primary.sim.num_trips_not_departed();
+            txt.add(Line(format!(
+                "Unfinished trips: {} ({} in progress, {} not departed yet)",
+                unfinished,
+                unfinished - not_departed,
+                not_departed
+            )));
             txt.add(Line(format!(
                 "Peds {}, Bikes {}, Cars {}, Buses {}",
                 by_mode[&TripMode::Walk],
@@ -92,7 +98,7 @@ impl State for ABTestMode {
         }
 
         if app.opts.dev && ctx.input.new_was_pressed(&lctrl(Key::D).unwrap()) {
-            return Transition::Push(Box::new(DebugMode::new(ctx)));
+            return Transition::Push(Box::new(DebugMode::new(ctx, app)));
         }
 
         if self.menu.action("swap") {
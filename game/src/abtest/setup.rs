@@ -182,6 +182,16 @@ fn launch_test(test: &ABTest, app: &mut App, ctx: &mut EventCtx) -> ABTestMode {
                                     .sim_flags
                                     .opts
                                     .clear_laggy_head_early,
+                                vehicle_sizes: current_flags.sim_flags.opts.vehicle_sizes,
+                                parking_skip_probability: current_flags
+                                    .sim_flags
+                                    .opts
+                                    .parking_skip_probability,
+                                enable_walking: current_flags.sim_flags.opts.enable_walking,
+                                enable_transit: current_flags.sim_flags.opts.enable_transit,
+                                enable_parking: current_flags.sim_flags.opts.enable_parking,
+                                lazy_spawning: current_flags.sim_flags.opts.lazy_spawning,
+                                snapshot_every: current_flags.sim_flags.opts.snapshot_every,
                             },
                         },
                         ..current_flags.clone()
@@ -305,6 +305,26 @@ impl App {
         None
     }
 
+    // Everything on screen right now that show_objs doesn't filter out, including agents.
+    pub fn visible_objects(
+        &self,
+        ctx: &EventCtx,
+        source: &dyn GetDrawAgents,
+        show_objs: &dyn ShowObject,
+    ) -> Vec<ID> {
+        let mut cache = self.primary.draw_map.agents.borrow_mut();
+        self.get_renderables_back_to_front(
+            ctx.canvas.get_screen_bounds(),
+            ctx.prerender,
+            &mut cache,
+            source,
+            show_objs,
+        )
+        .into_iter()
+        .map(|obj| obj.get_id())
+        .collect()
+    }
+
     // TODO This could probably belong to DrawMap again, but it's annoying to plumb things that
     // State does, like show_icons_for() and show().
     fn get_renderables_back_to_front<'a>(
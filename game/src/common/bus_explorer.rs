@@ -52,12 +52,14 @@ impl ShowBusRoute {
             }
         }
 
+        let ridership = app.primary.sim.get_analytics().bus_ridership(id);
         let mut labels = Vec::new();
         for (idx, bs) in route.stops.iter().enumerate() {
-            labels.push((
-                Text::from(Line(format!("{}", idx + 1))).with_bg(),
-                map.get_bs(*bs).sidewalk_pos.pt(map),
-            ));
+            let mut txt = Text::from(Line(format!("{}", idx + 1)));
+            if let Some((on, off)) = ridership.get(bs) {
+                txt.add(Line(format!("{} boarded, {} alighted", on, off)).size(12));
+            }
+            labels.push((txt.with_bg(), map.get_bs(*bs).sidewalk_pos.pt(map)));
         }
 
         ShowBusRoute {
@@ -822,8 +822,9 @@ fn throughput<F: Fn(&Analytics, Time) -> BTreeMap<TripMode, Vec<(Time, usize)>>>
         .into_iter()
         .map(|(m, pts)| Series {
             label: m.to_string(),
-            color: color_for_mode(m, app),
+            color: app.cs.for_mode(m),
             pts,
+            actions: Vec::new(),
         })
         .collect::<Vec<_>>();
     if app.has_prebaked().is_some() {
@@ -831,13 +832,14 @@ fn throughput<F: Fn(&Analytics, Time) -> BTreeMap<TripMode, Vec<(Time, usize)>>>
         for (m, pts) in get_data(app.prebaked(), Time::END_OF_DAY) {
             series.push(Series {
                 label: format!("{} (baseline)", m),
-                color: color_for_mode(m, app).alpha(0.3),
+                color: app.cs.for_mode(m).alpha(0.3),
                 pts,
+                actions: Vec::new(),
             });
         }
     }
 
-    Plot::new_usize(ctx, series, PlotOptions::new())
+    Plot::new_usize(ctx, "Throughput", series, PlotOptions::new())
 }
 
 fn intersection_delay(
@@ -872,6 +874,7 @@ fn intersection_delay(
             label: stat.to_string(),
             color: rotating_color_map(idx),
             pts,
+            actions: Vec::new(),
         });
     }
     if app.has_prebaked().is_some() {
@@ -883,20 +886,12 @@ fn intersection_delay(
                 label: format!("{} (baseline)", stat),
                 color: rotating_color_map(idx).alpha(0.3),
                 pts,
+                actions: Vec::new(),
             });
         }
     }
 
-    Plot::new_duration(ctx, all_series, PlotOptions::new())
-}
-
-fn color_for_mode(m: TripMode, app: &App) -> Color {
-    match m {
-        TripMode::Walk => app.cs.get("unzoomed pedestrian"),
-        TripMode::Bike => app.cs.get("unzoomed bike"),
-        TripMode::Transit => app.cs.get("unzoomed bus"),
-        TripMode::Drive => app.cs.get("unzoomed car"),
-    }
+    Plot::new_duration(ctx, "Delay", all_series, PlotOptions::new())
 }
 
 fn trip_details(
@@ -1156,10 +1151,24 @@ fn trip_details(
     if let Some(t) = trip_end_time {
         table.push(("Trip end".to_string(), t.ampm_tostring()));
     }
+    if let Some(dt) = app.primary.sim.transit_wait_time(trip) {
+        table.push(("Waited for bus".to_string(), dt.to_string()));
+    }
+    if let Some(reason) = app.primary.sim.abort_reason(trip) {
+        table.push(("Aborted".to_string(), reason.to_string()));
+    }
     let mut col = vec![ManagedWidget::row(timeline)
         .evenly_spaced()
         .margin_above(25)];
     col.extend(make_table(ctx, table));
+    let legs = app.primary.sim.describe_trip(trip);
+    if !legs.is_empty() {
+        let mut txt = Text::from(Line("Remaining legs"));
+        for leg in legs {
+            txt.add(Line(format!("- {}", leg)));
+        }
+        col.push(ManagedWidget::draw_text(ctx, txt));
+    }
 
     (
         ManagedWidget::col(col),
@@ -22,6 +22,8 @@ pub enum Overlays {
     IntersectionDelay(Time, Colorer),
     TrafficJams(Time, Colorer),
     CumulativeThroughput(Time, Colorer),
+    BuildingOccupancy(Time, Colorer),
+    SidewalkCrowding(Time, Colorer),
     BikeNetwork(Colorer),
     BusNetwork(Colorer),
     Edits(Colorer),
@@ -59,6 +61,16 @@ impl Overlays {
                     app.overlay = Overlays::cumulative_throughput(ctx, app);
                 }
             }
+            Overlays::BuildingOccupancy(t, _) => {
+                if now != t {
+                    app.overlay = Overlays::building_occupancy(ctx, app);
+                }
+            }
+            Overlays::SidewalkCrowding(t, _) => {
+                if now != t {
+                    app.overlay = Overlays::sidewalk_crowding(ctx, app);
+                }
+            }
             Overlays::IntersectionDemand(t, i, _, _) => {
                 if now != t {
                     app.overlay = Overlays::intersection_demand(i, ctx, app);
@@ -102,6 +114,8 @@ impl Overlays {
             | Overlays::IntersectionDelay(_, ref mut heatmap)
             | Overlays::TrafficJams(_, ref mut heatmap)
             | Overlays::CumulativeThroughput(_, ref mut heatmap)
+            | Overlays::BuildingOccupancy(_, ref mut heatmap)
+            | Overlays::SidewalkCrowding(_, ref mut heatmap)
             | Overlays::Edits(ref mut heatmap) => {
                 heatmap.legend.align_above(ctx, minimap);
                 if heatmap.event(ctx) {
@@ -191,6 +205,8 @@ impl Overlays {
             | Overlays::IntersectionDelay(_, ref heatmap)
             | Overlays::TrafficJams(_, ref heatmap)
             | Overlays::CumulativeThroughput(_, ref heatmap)
+            | Overlays::BuildingOccupancy(_, ref heatmap)
+            | Overlays::SidewalkCrowding(_, ref heatmap)
             | Overlays::Edits(ref heatmap) => {
                 heatmap.draw(g);
             }
@@ -219,6 +235,8 @@ impl Overlays {
             | Overlays::IntersectionDelay(_, ref heatmap)
             | Overlays::TrafficJams(_, ref heatmap)
             | Overlays::CumulativeThroughput(_, ref heatmap)
+            | Overlays::BuildingOccupancy(_, ref heatmap)
+            | Overlays::SidewalkCrowding(_, ref heatmap)
             | Overlays::Edits(ref heatmap) => Some(heatmap),
             Overlays::BusRoute(_, _, ref s) => Some(&s.colorer),
             _ => None,
@@ -230,6 +248,8 @@ impl Overlays {
             WrappedComposite::text_button(ctx, "None", hotkey(Key::N)),
             WrappedComposite::text_button(ctx, "map edits", hotkey(Key::E)),
             WrappedComposite::text_button(ctx, "worst traffic jams", hotkey(Key::G)),
+            WrappedComposite::text_button(ctx, "building occupancy", hotkey(Key::O)),
+            WrappedComposite::text_button(ctx, "sidewalk crowding", hotkey(Key::W)),
             ManagedWidget::btn(Button::rectangle_svg(
                 "../data/system/assets/layers/parking_avail.svg",
                 "parking availability",
@@ -281,6 +301,14 @@ impl Overlays {
                 "worst traffic jams",
                 Button::inactive_button(ctx, "worst traffic jams"),
             )),
+            Overlays::BuildingOccupancy(_, _) => Some((
+                "building occupancy",
+                Button::inactive_button(ctx, "building occupancy"),
+            )),
+            Overlays::SidewalkCrowding(_, _) => Some((
+                "sidewalk crowding",
+                Button::inactive_button(ctx, "sidewalk crowding"),
+            )),
             Overlays::CumulativeThroughput(_, _) => Some((
                 "throughput",
                 ManagedWidget::draw_svg(ctx, "../data/system/assets/layers/throughput.svg"),
@@ -349,6 +377,20 @@ impl Overlays {
                 Some(Transition::Pop)
             }),
         )
+        .maybe_cb(
+            "building occupancy",
+            Box::new(|ctx, app| {
+                app.overlay = Overlays::building_occupancy(ctx, app);
+                Some(Transition::Pop)
+            }),
+        )
+        .maybe_cb(
+            "sidewalk crowding",
+            Box::new(|ctx, app| {
+                app.overlay = Overlays::sidewalk_crowding(ctx, app);
+                Some(Transition::Pop)
+            }),
+        )
         .maybe_cb(
             "throughput",
             Box::new(|ctx, app| {
@@ -488,6 +530,14 @@ impl Overlays {
 
     pub fn traffic_jams(ctx: &mut EventCtx, app: &App) -> Overlays {
         let jams = app.primary.sim.delayed_intersections(Duration::minutes(5));
+        // The whole jam connected to the longest lasting problem
+        let gridlock: HashSet<IntersectionID> = app
+            .primary
+            .sim
+            .find_gridlock(Duration::minutes(5), &app.primary.map)
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect();
 
         // TODO Silly colors
         let others = Color::hex("#7FFA4D");
@@ -502,10 +552,15 @@ impl Overlays {
             ],
         );
 
-        for (idx, (i, _)) in jams.into_iter().enumerate() {
-            if idx == 0 {
-                colorer.add_i(i, earliest);
-            } else if idx <= 5 {
+        for i in &gridlock {
+            colorer.add_i(*i, earliest);
+        }
+        for (idx, (i, _)) in jams
+            .into_iter()
+            .filter(|(i, _)| !gridlock.contains(i))
+            .enumerate()
+        {
+            if idx < 5 {
                 colorer.add_i(i, early);
             } else {
                 colorer.add_i(i, others);
@@ -569,6 +624,71 @@ impl Overlays {
         Overlays::CumulativeThroughput(app.primary.sim.time(), colorer.build(ctx, app))
     }
 
+    fn building_occupancy(ctx: &mut EventCtx, app: &App) -> Overlays {
+        let now = app.primary.sim.time();
+        let occupancy = app.primary.sim.get_analytics().all_occupancy_at(now);
+
+        let few = Color::hex("#7FFA4D");
+        let some = Color::hex("#F4DA22");
+        let many = Color::hex("#EB5757");
+        let mut colorer = Colorer::new(
+            Text::from(Line(format!(
+                "{} people inside buildings",
+                prettyprint_usize(occupancy.iter().map(|(_, cnt)| *cnt).sum())
+            ))),
+            vec![
+                ("1-4 people", few),
+                ("5-19 people", some),
+                (">= 20 people", many),
+            ],
+        );
+        for (b, cnt) in occupancy {
+            let color = if cnt < 5 {
+                few
+            } else if cnt < 20 {
+                some
+            } else {
+                many
+            };
+            colorer.add_b(b, color);
+        }
+
+        Overlays::BuildingOccupancy(now, colorer.build(ctx, app))
+    }
+
+    fn sidewalk_crowding(ctx: &mut EventCtx, app: &App) -> Overlays {
+        let map = &app.primary.map;
+        let densities = app.primary.sim.all_sidewalk_densities(map);
+
+        let few = Color::hex("#7FFA4D");
+        let some = Color::hex("#F4DA22");
+        let many = Color::hex("#EB5757");
+        let mut colorer = Colorer::new(
+            Text::from(Line(format!(
+                "{} sidewalks with pedestrians",
+                prettyprint_usize(densities.len())
+            ))),
+            vec![
+                ("< 1 person per 10m", few),
+                ("1-4 people per 10m", some),
+                (">= 5 people per 10m", many),
+            ],
+        );
+        for (l, density) in densities {
+            let per_10m = density * 10.0;
+            let color = if per_10m < 1.0 {
+                few
+            } else if per_10m < 5.0 {
+                some
+            } else {
+                many
+            };
+            colorer.add_l(l, color, map);
+        }
+
+        Overlays::SidewalkCrowding(app.primary.sim.time(), colorer.build(ctx, app))
+    }
+
     fn bike_network(ctx: &mut EventCtx, app: &App) -> Overlays {
         let color = Color::hex("#7FFA4D");
         let mut colorer = Colorer::new(
@@ -814,6 +934,7 @@ impl Overlays {
                 pts: delays_per_stop
                     .remove(&route.stops[idx2])
                     .unwrap_or_else(Vec::new),
+                actions: Vec::new(),
             });
         }
         Overlays::BusDelaysOverTime(
@@ -828,7 +949,7 @@ impl Overlays {
                         ),
                         WrappedComposite::text_button(ctx, "X", None).align_right(),
                     ]),
-                    Plot::new_duration(ctx, series, PlotOptions::new()).margin(10),
+                    Plot::new_duration(ctx, "Delays", series, PlotOptions::new()).margin(10),
                 ])
                 .bg(colors::PANEL_BG),
             )
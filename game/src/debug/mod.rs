@@ -5,18 +5,19 @@ mod routes;
 
 use crate::app::{App, ShowLayers, ShowObject};
 use crate::colors;
-use crate::common::{tool_panel, CommonState};
+use crate::common::{tool_panel, CommonState, Warping};
 use crate::game::{msg, DrawBaselayer, State, Transition, WizardState};
 use crate::helpers::ID;
 use crate::managed::{WrappedComposite, WrappedOutcome};
 use crate::render::DrawOptions;
+use abstutil::prettyprint_usize;
 use ezgui::{
-    hotkey, lctrl, Color, Composite, Drawable, EventCtx, EventLoopMode, GeomBatch, GfxCtx,
+    hotkey, lctrl, Choice, Color, Composite, Drawable, EventCtx, EventLoopMode, GeomBatch, GfxCtx,
     HorizontalAlignment, Key, Line, ManagedWidget, Outcome, Text, VerticalAlignment, Wizard,
 };
 use geom::Duration;
-use map_model::IntersectionID;
-use sim::Sim;
+use map_model::{osm_matcher, IntersectionID, OsmMatch};
+use sim::{Sim, VisibleEntity};
 use std::collections::HashSet;
 
 pub struct DebugMode {
@@ -33,8 +34,8 @@ pub struct DebugMode {
 }
 
 impl DebugMode {
-    pub fn new(ctx: &mut EventCtx) -> DebugMode {
-        DebugMode {
+    pub fn new(ctx: &mut EventCtx, app: &App) -> DebugMode {
+        let mut mode = DebugMode {
             composite: Composite::new(
                 ManagedWidget::col(vec![
                     ManagedWidget::row(vec![
@@ -53,12 +54,17 @@ impl DebugMode {
                             (lctrl(Key::H), "unhide everything"),
                             (hotkey(Key::R), "toggle route for all agents"),
                             (None, "screenshot everything"),
+                            (None, "export visible entities"),
                             (hotkey(Key::Slash), "search OSM metadata"),
                             (lctrl(Key::Slash), "clear OSM search results"),
                             (hotkey(Key::O), "save sim state"),
                             (hotkey(Key::Y), "load previous sim state"),
                             (hotkey(Key::U), "load next sim state"),
                             (None, "pick a savestate to load"),
+                            (None, "bookmark this moment"),
+                            (None, "jump to a bookmark"),
+                            (None, "find stuck agents"),
+                            (None, "kill all stuck cars"),
                         ]
                         .into_iter()
                         .map(|(key, action)| WrappedComposite::text_button(ctx, action, key))
@@ -79,18 +85,33 @@ impl DebugMode {
             search_results: None,
             all_routes: routes::AllRoutesViewer::Inactive,
             highlighted_agents: None,
-        }
+        };
+        mode.reset_info(ctx, app);
+        mode
     }
 
-    fn reset_info(&mut self, ctx: &mut EventCtx) {
+    fn reset_info(&mut self, ctx: &mut EventCtx, app: &App) {
         let mut txt = Text::new();
+        txt.add(Line(format!(
+            "{} commands scheduled (at most {} so far)",
+            prettyprint_usize(app.primary.sim.scheduler_queue_len()),
+            prettyprint_usize(app.primary.sim.get_analytics().max_scheduler_queue_len)
+        )));
         if !self.hidden.is_empty() {
             txt.add(Line(format!("Hiding {} things", self.hidden.len())));
         }
         if let Some(ref results) = self.search_results {
+            let counts = results
+                .per_map
+                .iter()
+                .map(|(label, ids)| format!("{} in the {} map", ids.len(), label))
+                .collect::<Vec<_>>()
+                .join(", ");
             txt.add(Line(format!(
-                "Search for {} has {} results",
-                results.query, results.num_matches
+                "Search for {} has {} results ({} match)",
+                results.query,
+                counts,
+                if results.regex { "regex" } else { "substring" }
             )));
         }
         if let routes::AllRoutesViewer::Active(ref traces) = self.all_routes {
@@ -177,22 +198,48 @@ impl State for DebugMode {
                 "pick a savestate to load" => {
                     return Transition::Push(WizardState::new(Box::new(load_savestate)));
                 }
+                "bookmark this moment" => {
+                    return Transition::Push(WizardState::new(Box::new(add_bookmark)));
+                }
+                "jump to a bookmark" => {
+                    return Transition::Push(WizardState::new(Box::new(jump_to_bookmark)));
+                }
+                "find stuck agents" => {
+                    return Transition::Push(WizardState::new(Box::new(find_stuck_agents)));
+                }
+                "kill all stuck cars" => {
+                    let num = app
+                        .primary
+                        .sim
+                        .kill_all_stuck_cars(&app.primary.map, Duration::minutes(5));
+                    app.primary
+                        .sim
+                        .normal_step(&app.primary.map, Duration::seconds(0.1));
+                    app.primary.current_selection = None;
+                    return Transition::Push(msg(
+                        "Gridlock recovery",
+                        vec![format!(
+                            "Killed {} cars stuck in gridlock for more than 5 minutes",
+                            num
+                        )],
+                    ));
+                }
                 "unhide everything" => {
                     self.hidden.clear();
                     app.primary.current_selection =
                         app.calculate_current_selection(ctx, &app.primary.sim, self, true, false);
-                    self.reset_info(ctx);
+                    self.reset_info(ctx, app);
                 }
                 "toggle route for all agents" => {
                     self.all_routes.toggle(app);
-                    self.reset_info(ctx);
+                    self.reset_info(ctx, app);
                 }
                 "search OSM metadata" => {
                     return Transition::Push(WizardState::new(Box::new(search_osm)));
                 }
                 "clear OSM search results" => {
                     self.search_results = None;
-                    self.reset_info(ctx);
+                    self.reset_info(ctx, app);
                 }
                 "screenshot everything" => {
                     let bounds = app.primary.map.get_bounds();
@@ -204,6 +251,13 @@ impl State for DebugMode {
                         max_y: bounds.max_y,
                     });
                 }
+                "export visible entities" => {
+                    let path = export_visible_entities(ctx, app, self);
+                    return Transition::Push(msg(
+                        "Exported visible entities",
+                        vec![format!("Wrote {}", path)],
+                    ));
+                }
                 "toggle buildings" => {
                     self.layers.show_buildings = !self.layers.show_buildings;
                     app.primary.current_selection =
@@ -247,7 +301,7 @@ impl State for DebugMode {
                 println!("Hiding {:?}", id);
                 app.primary.current_selection = None;
                 self.hidden.insert(id);
-                self.reset_info(ctx);
+                self.reset_info(ctx, app);
             }
         }
 
@@ -262,14 +316,16 @@ impl State for DebugMode {
                     .normal_step(&app.primary.map, Duration::seconds(0.1));
                 app.primary.current_selection = None;
             } else if app.per_obj.action(ctx, Key::G, "find front of blockage") {
-                return Transition::Push(msg(
-                    "Blockage results",
-                    vec![format!(
-                        "{} is ultimately blocked by {}",
-                        id,
-                        app.primary.sim.find_blockage_front(id, &app.primary.map)
-                    )],
-                ));
+                let mut lines = vec![format!(
+                    "{} is ultimately blocked by {}",
+                    id,
+                    app.primary.sim.find_blockage_front(id, &app.primary.map)
+                )];
+                lines.push("Chain of cars waiting on each other:".to_string());
+                for car in app.primary.sim.blockage_chain(id) {
+                    lines.push(format!("- {}", car));
+                }
+                return Transition::Push(msg("Blockage results", lines));
             }
         }
         if let Some(ID::Intersection(id)) = app.primary.current_selection {
@@ -375,59 +431,86 @@ impl ShowObject for DebugMode {
 
 fn search_osm(wiz: &mut Wizard, ctx: &mut EventCtx, app: &mut App) -> Option<Transition> {
     let filter = wiz.wrap(ctx).input_string("Search for what?")?;
-    let mut num_matches = 0;
-    let mut batch = GeomBatch::new();
+
+    let (matches, regex) = osm_matcher(&filter);
 
     // TODO Case insensitive
-    let map = &app.primary.map;
-    let color = app.cs.get_def("search result", Color::RED);
-    for r in map.all_roads() {
-        if r.osm_tags
-            .iter()
-            .any(|(k, v)| format!("{} = {}", k, v).contains(&filter))
-        {
-            num_matches += 1;
-            batch.push(color, r.get_thick_polygon(map).unwrap());
-        }
-    }
-    for b in map.all_buildings() {
-        if b.osm_tags
-            .iter()
-            .any(|(k, v)| format!("{} = {}", k, v).contains(&filter))
-            || b.amenities
-                .iter()
-                .any(|(n, a)| n.contains(&filter) || a.contains(&filter))
-        {
-            num_matches += 1;
-            batch.push(color, b.polygon.clone());
-        }
+    // In A/B test mode, search both maps, coloring the results from each differently.
+    let mut maps = vec![(
+        "primary",
+        &app.primary.map,
+        app.cs.get_def("search result", Color::RED),
+    )];
+    if let Some(ref s) = app.secondary {
+        maps.push((
+            "secondary",
+            &s.map,
+            app.cs.get_def("secondary search result", Color::BLUE),
+        ));
     }
-    for a in map.all_areas() {
-        if a.osm_tags
-            .iter()
-            .any(|(k, v)| format!("{} = {}", k, v).contains(&filter))
-        {
-            num_matches += 1;
-            batch.push(color, a.polygon.clone());
+    let mut batch = GeomBatch::new();
+    let mut per_map = Vec::new();
+    for (label, map, color) in maps {
+        let ids = map.search_osm(&*matches);
+        for id in &ids {
+            match id {
+                OsmMatch::Road(r) => {
+                    batch.push(color, map.get_r(*r).get_thick_polygon(map).unwrap())
+                }
+                OsmMatch::Building(b) => batch.push(color, map.get_b(*b).polygon.clone()),
+                OsmMatch::Area(a) => batch.push(color, map.get_a(*a).polygon.clone()),
+            }
         }
+        per_map.push((label, ids));
     }
 
     let results = SearchResults {
         query: filter,
-        num_matches,
+        per_map,
+        regex,
         draw: batch.upload(ctx),
     };
 
-    Some(Transition::PopWithData(Box::new(|state, _, ctx| {
+    Some(Transition::PopWithData(Box::new(|state, app, ctx| {
         let mut mode = state.downcast_mut::<DebugMode>().unwrap();
         mode.search_results = Some(results);
-        mode.reset_info(ctx);
+        mode.reset_info(ctx, app);
     })))
 }
 
+fn find_stuck_agents(wiz: &mut Wizard, ctx: &mut EventCtx, app: &mut App) -> Option<Transition> {
+    let mut wizard = wiz.wrap(ctx);
+    let mins = wizard.input_usize_prefilled("Stuck for how many minutes?", "5".to_string())?;
+    let stuck = app.primary.sim.find_stuck_agents(Duration::minutes(mins));
+    if stuck.is_empty() {
+        return Some(Transition::Replace(msg(
+            "No stuck agents",
+            vec![format!(
+                "Nobody's been stuck for more than {} minutes",
+                mins
+            )],
+        )));
+    }
+    let (_, agent) = wizard.choose("Warp to which agent?", || {
+        stuck
+            .iter()
+            .map(|(a, dt)| Choice::new(format!("{} stuck for {}", a, dt), ID::from_agent(*a)))
+            .collect()
+    })?;
+    Some(Transition::Replace(Warping::new(
+        ctx,
+        agent.canonical_point(&app.primary).unwrap(),
+        Some(10.0),
+        Some(agent),
+        &mut app.primary,
+    )))
+}
+
 struct SearchResults {
     query: String,
-    num_matches: usize,
+    // Per map ("primary" or "secondary"), what matched
+    per_map: Vec<(&'static str, Vec<OsmMatch>)>,
+    regex: bool,
     draw: Drawable,
 }
 
@@ -445,3 +528,54 @@ fn load_savestate(wiz: &mut Wizard, ctx: &mut EventCtx, app: &mut App) -> Option
     });
     Some(Transition::Pop)
 }
+
+fn add_bookmark(wiz: &mut Wizard, ctx: &mut EventCtx, app: &mut App) -> Option<Transition> {
+    let name = wiz.wrap(ctx).input_string("Name this bookmark")?;
+    app.primary.sim.bookmark(name);
+    Some(Transition::Pop)
+}
+
+fn jump_to_bookmark(wiz: &mut Wizard, ctx: &mut EventCtx, app: &mut App) -> Option<Transition> {
+    let bookmarks = app.primary.sim.list_bookmarks();
+    if bookmarks.is_empty() {
+        return Some(Transition::Replace(msg(
+            "No bookmarks",
+            vec!["Bookmark a moment first"],
+        )));
+    }
+    let (_, name) = wiz.wrap(ctx).choose("Jump to which bookmark?", || {
+        bookmarks
+            .iter()
+            .map(|(name, time)| Choice::new(format!("{} at {}", name, time), name.clone()))
+            .collect()
+    })?;
+    app.primary.sim.restore_bookmark(&name);
+    app.recalculate_current_selection(ctx);
+    Some(Transition::Pop)
+}
+
+// Returns the path written
+fn export_visible_entities(ctx: &EventCtx, app: &App, show_objs: &dyn ShowObject) -> String {
+    let entities: Vec<VisibleEntity> = app
+        .visible_objects(ctx, &app.primary.sim, show_objs)
+        .into_iter()
+        .filter_map(|id| {
+            let center = id.canonical_point(&app.primary)?;
+            // Like "Lane(LaneID(123))"
+            let debug = format!("{:?}", id);
+            let kind = debug.split('(').next().unwrap().to_string();
+            Some(VisibleEntity {
+                id: debug[kind.len() + 1..debug.len() - 1].to_string(),
+                kind,
+                center,
+            })
+        })
+        .collect();
+    let path = format!(
+        "../visible_entities_{}_{}.json",
+        app.primary.map.get_name(),
+        app.primary.sim.time().as_filename()
+    );
+    abstutil::write_json(path.clone(), &entities);
+    path
+}
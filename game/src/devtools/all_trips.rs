@@ -34,9 +34,9 @@ impl TripsVisualizer {
             let flags = &app.primary.current_flags.sim_flags;
             let maybe_trips =
                 timer.parallelize("calculate paths with geometry", all_trips, |trip| {
-                    if let Some(spawn_trip) = trip.to_spawn_trip(map) {
-                        let mut rng = flags.make_rng();
-                        let spec = spawn_trip.to_trip_spec(&mut rng);
+                    if let Some(spec) = trip.to_spawn_trip(map).and_then(|t| {
+                        t.to_trip_spec(&mut flags.make_rng(), map, sim.vehicle_sizes())
+                    }) {
                         let req = sim.trip_spec_to_path_req(&spec, map);
                         if let Some(route) = map
                             .pathfind(req.clone())
@@ -1,18 +1,22 @@
 use crate::app::App;
 use crate::colors;
-use crate::common::{tool_panel, Colorer, CommonState, Warping};
-use crate::game::{State, Transition, WizardState};
+use crate::common::{tool_panel, ColorLegend, Colorer, CommonState, Warping};
+use crate::game::{msg, State, Transition, WizardState};
 use crate::helpers::ID;
 use crate::managed::{WrappedComposite, WrappedOutcome};
-use abstutil::{prettyprint_usize, Counter, MultiMap};
+use abstutil::{deduplicate_labels, prettyprint_usize, Counter, MultiMap};
 use ezgui::{
-    hotkey, lctrl, Choice, Color, Composite, Drawable, EventCtx, GeomBatch, GfxCtx,
-    HorizontalAlignment, Key, Line, ManagedWidget, Outcome, Slider, Text, VerticalAlignment,
+    hotkey, lctrl, Choice, Color, Composite, Drawable, EventCtx, EventLoopMode, GeomBatch, GfxCtx,
+    HorizontalAlignment, Key, Line, ManagedWidget, MultiKey, Outcome, Slider, Text,
+    VerticalAlignment,
 };
-use geom::{Distance, Line, PolyLine, Polygon};
-use map_model::{BuildingID, IntersectionID, Map};
-use sim::{DrivingGoal, IndividTrip, Scenario, SidewalkPOI, SidewalkSpot, SpawnTrip};
-use std::collections::BTreeSet;
+use geom::{Distance, Line, PolyLine, Polygon, Pt2D, Speed, Time};
+use map_model::{BuildingID, IntersectionID, Map, Position};
+use sim::{
+    demand_arrow_width, demand_geojson, straight_line_dot, CarAppearingStart, DrivingGoal,
+    IndividTrip, Scenario, SidewalkPOI, SidewalkSpot, SpawnTrip, TripEndpoint,
+};
+use std::collections::{BTreeMap, BTreeSet};
 
 pub struct ScenarioManager {
     composite: Composite,
@@ -26,8 +30,10 @@ pub struct ScenarioManager {
     trips_from_border: MultiMap<IntersectionID, usize>,
     trips_to_border: MultiMap<IntersectionID, usize>,
     bldg_colors: Colorer,
+    // Negative for buildings short of parked cars
+    parking_audit: BTreeMap<BuildingID, isize>,
 
-    demand: Option<Drawable>,
+    demand: Option<Demand>,
 }
 
 impl ScenarioManager {
@@ -39,11 +45,19 @@ impl ScenarioManager {
         for (idx, trip) in scenario.population.individ_trips.iter().enumerate() {
             // trips_from_bldg and trips_from_border
             match &trip.trip {
-                // TODO CarAppearing might be from a border
-                SpawnTrip::CarAppearing { .. } => {}
+                SpawnTrip::CarAppearing { ref start, .. } => match start {
+                    CarAppearingStart::Bldg(b) => {
+                        trips_from_bldg.insert(*b, idx);
+                    }
+                    CarAppearingStart::Border(i) => {
+                        trips_from_border.insert(*i, idx);
+                    }
+                    CarAppearingStart::Lane(_) => {}
+                },
                 SpawnTrip::MaybeUsingParkedCar(b, _) => {
                     trips_from_bldg.insert(*b, idx);
                 }
+                SpawnTrip::WalkFromLane(_, _, _) => {}
                 SpawnTrip::UsingBike(ref spot, _)
                 | SpawnTrip::JustWalking(ref spot, _)
                 | SpawnTrip::UsingTransit(ref spot, _, _, _, _) => match spot.connection {
@@ -55,6 +69,14 @@ impl ScenarioManager {
                     }
                     _ => {}
                 },
+                SpawnTrip::MultiStop(ref stops, _) => match stops[0] {
+                    TripEndpoint::Bldg(b) => {
+                        trips_from_bldg.insert(b, idx);
+                    }
+                    TripEndpoint::Border(i) => {
+                        trips_from_border.insert(i, idx);
+                    }
+                },
             }
 
             // trips_to_bldg and trips_to_border
@@ -65,11 +87,12 @@ impl ScenarioManager {
                     DrivingGoal::ParkNear(b) => {
                         trips_to_bldg.insert(*b, idx);
                     }
-                    DrivingGoal::Border(i, _) => {
+                    DrivingGoal::Border(i, _) | DrivingGoal::AnyLane(i, _) => {
                         trips_to_border.insert(*i, idx);
                     }
                 },
                 SpawnTrip::JustWalking(_, ref spot)
+                | SpawnTrip::WalkFromLane(_, _, ref spot)
                 | SpawnTrip::UsingTransit(_, ref spot, _, _, _) => match spot.connection {
                     SidewalkPOI::Building(b) => {
                         trips_to_bldg.insert(b, idx);
@@ -79,6 +102,14 @@ impl ScenarioManager {
                     }
                     _ => {}
                 },
+                SpawnTrip::MultiStop(ref stops, _) => match stops.last().unwrap() {
+                    TripEndpoint::Bldg(b) => {
+                        trips_to_bldg.insert(*b, idx);
+                    }
+                    TripEndpoint::Border(i) => {
+                        trips_to_border.insert(*i, idx);
+                    }
+                },
             }
         }
 
@@ -108,10 +139,18 @@ impl ScenarioManager {
         let (filled_spots, free_parking_spots) = app.primary.sim.get_all_parking_spots();
         assert!(filled_spots.is_empty());
 
+        let parking_audit: BTreeMap<BuildingID, isize> = scenario
+            .audit_parking(&app.primary.map)
+            .into_iter()
+            .collect();
+        let total_shortfall: isize = parking_audit.values().filter(|x| **x < 0).sum();
+        let capacity_warning = scenario.check_capacity(&app.primary.sim);
+
         ScenarioManager {
-            composite: WrappedComposite::quick_menu(
+            composite: make_top_panel(
                 ctx,
                 format!("Scenario {}", scenario.scenario_name),
+                capacity_warning,
                 vec![
                     format!(
                         "{} total trips",
@@ -126,6 +165,11 @@ impl ScenarioManager {
                         "{} parking spots",
                         prettyprint_usize(free_parking_spots.len()),
                     ),
+                    format!(
+                        "{} buildings short {} cars at some point",
+                        prettyprint_usize(parking_audit.values().filter(|x| **x < 0).count()),
+                        prettyprint_usize(-total_shortfall as usize),
+                    ),
                 ],
                 vec![
                     (hotkey(Key::D), "dot map"),
@@ -140,11 +184,51 @@ impl ScenarioManager {
             trips_from_border,
             trips_to_border,
             bldg_colors: bldg_colors.build(ctx, app),
+            parking_audit,
             demand: None,
         }
     }
 }
 
+// Like WrappedComposite::quick_menu, but with a red banner if the scenario can't be seeded
+fn make_top_panel(
+    ctx: &mut EventCtx,
+    title: String,
+    warning: Option<String>,
+    info: Vec<String>,
+    actions: Vec<(Option<MultiKey>, &str)>,
+) -> Composite {
+    let mut col = vec![ManagedWidget::row(vec![
+        ManagedWidget::draw_text(ctx, Text::from(Line(title).roboto_bold())),
+        WrappedComposite::text_button(ctx, "X", hotkey(Key::Escape)).align_right(),
+    ])];
+    if let Some(warning) = warning {
+        col.push(ManagedWidget::draw_text(
+            ctx,
+            Text::from(Line(warning).fg(Color::RED)),
+        ));
+    }
+    col.push(ManagedWidget::draw_text(ctx, {
+        let mut txt = Text::new();
+        for l in info {
+            txt.add(Line(l));
+        }
+        txt
+    }));
+    col.push(
+        ManagedWidget::row(
+            actions
+                .into_iter()
+                .map(|(key, action)| WrappedComposite::text_button(ctx, action, key))
+                .collect(),
+        )
+        .flex_wrap(ctx, 60),
+    );
+    Composite::new(ManagedWidget::col(col).padding(10).bg(colors::PANEL_BG))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+        .build(ctx)
+}
+
 impl State for ScenarioManager {
     fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
         match self.composite.event(ctx) {
@@ -164,6 +248,30 @@ impl State for ScenarioManager {
             None => {}
         }
 
+        if let Some(ref mut d) = self.demand {
+            match d.legend.event(ctx) {
+                Some(Outcome::Clicked(x)) => match x.as_ref() {
+                    "export to GeoJSON" => {
+                        let path = format!(
+                            "../data/demand_{}_{}.geojson",
+                            self.scenario.scenario_name,
+                            match d.home {
+                                OD::Bldg(b) => format!("bldg{}", b.0),
+                                OD::Border(i) => format!("border{}", i.0),
+                            }
+                        );
+                        abstutil::write_file(path.clone(), d.geojson.clone());
+                        return Transition::Push(msg(
+                            "Exported demand",
+                            vec![format!("Wrote {}", path)],
+                        ));
+                    }
+                    _ => unreachable!(),
+                },
+                None => {}
+            }
+        }
+
         ctx.canvas_movement();
         if ctx.redo_mouseover() {
             app.recalculate_current_selection(ctx);
@@ -237,8 +345,15 @@ impl State for ScenarioManager {
     fn draw(&self, g: &mut GfxCtx, app: &App) {
         // TODO Let common contribute draw_options...
         self.bldg_colors.draw(g);
-        if let Some(ref p) = self.demand {
-            g.redraw(p);
+        if let Some(ref d) = self.demand {
+            g.redraw(&d.draw);
+            if let Some((pt, cnt)) = d.busiest {
+                g.draw_text_at(
+                    Text::from(Line(format!("{} trips", prettyprint_usize(cnt)))).with_bg(),
+                    pt,
+                );
+            }
+            d.legend.draw(g);
         }
 
         self.composite.draw(g);
@@ -253,6 +368,15 @@ impl State for ScenarioManager {
                 self.trips_to_bldg.get(b).len(),
                 self.scenario.population.individ_parked_cars[&b]
             )));
+            match self.parking_audit.get(&b) {
+                Some(x) if *x < 0 => {
+                    osd.append(Line(format!(", {} short", -x)));
+                }
+                Some(x) if *x > 0 => {
+                    osd.append(Line(format!(", {} spare", x)));
+                }
+                _ => {}
+            }
             CommonState::draw_custom_osd(g, app, osd);
         } else if let Some(ID::Intersection(i)) = app.primary.current_selection {
             let mut osd = CommonState::default_osd(ID::Intersection(i), app);
@@ -292,15 +416,26 @@ fn make_trip_picker(
             .choose(
                 &format!("Trips from/to this {}, by {} people", noun, people.len()),
                 || {
-                    // TODO Panics if there are two duplicate trips (b1124 in montlake)
+                    // Some scenarios have duplicate trips (b1124 in montlake). The index makes
+                    // labels unique, but be paranoid; Choice labels must not collide.
+                    let labels = deduplicate_labels(
+                        indices
+                            .iter()
+                            .map(|idx| {
+                                format!(
+                                    "Trip #{}: {}",
+                                    idx,
+                                    describe(&scenario.population.individ_trips[*idx], home)
+                                )
+                            })
+                            .collect(),
+                    );
                     indices
                         .iter()
-                        .map(|idx| {
+                        .zip(labels.into_iter())
+                        .map(|(idx, label)| {
                             let trip = &scenario.population.individ_trips[*idx];
-                            Choice::new(
-                                describe(trip, home),
-                                other_endpt(trip, home, &app.primary.map),
-                            )
+                            Choice::new(label, other_endpt(trip, home, &app.primary.map))
                         })
                         .collect()
                 },
@@ -325,7 +460,7 @@ fn describe(trip: &IndividTrip, home: OD) -> String {
                 b.to_string()
             }
         }
-        DrivingGoal::Border(i, _) => {
+        DrivingGoal::Border(i, _) | DrivingGoal::AnyLane(i, _) => {
             if OD::Border(*i) == home {
                 "HERE".to_string()
             } else {
@@ -361,7 +496,23 @@ fn describe(trip: &IndividTrip, home: OD) -> String {
             trip.person,
             trip.depart,
             if *is_bike { "bike" } else { "car" },
-            start.lane(),
+            match start {
+                CarAppearingStart::Lane(pos) => pos.lane().to_string(),
+                CarAppearingStart::Bldg(b) => {
+                    if OD::Bldg(*b) == home {
+                        "HERE".to_string()
+                    } else {
+                        b.to_string()
+                    }
+                }
+                CarAppearingStart::Border(i) => {
+                    if OD::Border(*i) == home {
+                        "HERE".to_string()
+                    } else {
+                        i.to_string()
+                    }
+                }
+            },
             driving_goal(goal)
         ),
         SpawnTrip::MaybeUsingParkedCar(start_bldg, goal) => format!(
@@ -389,6 +540,14 @@ fn describe(trip: &IndividTrip, home: OD) -> String {
             sidewalk_spot(start),
             sidewalk_spot(goal)
         ),
+        SpawnTrip::WalkFromLane(l, dist, goal) => format!(
+            "{} at {}: appear {} along {}, walk to {}",
+            trip.person,
+            trip.depart,
+            dist,
+            l,
+            sidewalk_spot(goal)
+        ),
         SpawnTrip::UsingTransit(start, goal, route, _, _) => format!(
             "{} at {}: bus from {} to {} using {}",
             trip.person,
@@ -397,23 +556,57 @@ fn describe(trip: &IndividTrip, home: OD) -> String {
             sidewalk_spot(goal),
             route
         ),
+        SpawnTrip::MultiStop(stops, mode) => format!(
+            "{} at {}: {} between {}",
+            trip.person,
+            trip.depart,
+            mode,
+            stops
+                .iter()
+                .map(|stop| match stop {
+                    TripEndpoint::Bldg(b) => {
+                        if OD::Bldg(*b) == home {
+                            "HERE".to_string()
+                        } else {
+                            b.to_string()
+                        }
+                    }
+                    TripEndpoint::Border(i) => {
+                        if OD::Border(*i) == home {
+                            "HERE".to_string()
+                        } else {
+                            i.to_string()
+                        }
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
     }
 }
 
 fn other_endpt(trip: &IndividTrip, home: OD, map: &Map) -> ID {
     let driving_goal = |goal: &DrivingGoal| match goal {
         DrivingGoal::ParkNear(b) => ID::Building(*b),
-        DrivingGoal::Border(i, _) => ID::Intersection(*i),
+        DrivingGoal::Border(i, _) | DrivingGoal::AnyLane(i, _) => ID::Intersection(*i),
     };
     let sidewalk_spot = |spot: &SidewalkSpot| match &spot.connection {
         SidewalkPOI::Building(b) => ID::Building(*b),
         SidewalkPOI::Border(i) => ID::Intersection(*i),
         x => panic!("other_endpt for {:?}?", x),
     };
+    let endpoint = |stop: &TripEndpoint| match stop {
+        TripEndpoint::Bldg(b) => ID::Building(*b),
+        TripEndpoint::Border(i) => ID::Intersection(*i),
+    };
 
     let (from, to) = match &trip.trip {
         SpawnTrip::CarAppearing { start, goal, .. } => (
-            ID::Intersection(map.get_l(start.lane()).src_i),
+            match start {
+                CarAppearingStart::Lane(pos) => ID::Intersection(map.get_l(pos.lane()).src_i),
+                CarAppearingStart::Bldg(b) => ID::Building(*b),
+                CarAppearingStart::Border(i) => ID::Intersection(*i),
+            },
             driving_goal(goal),
         ),
         SpawnTrip::MaybeUsingParkedCar(start_bldg, goal) => {
@@ -421,9 +614,13 @@ fn other_endpt(trip: &IndividTrip, home: OD, map: &Map) -> ID {
         }
         SpawnTrip::UsingBike(start, goal) => (sidewalk_spot(start), driving_goal(goal)),
         SpawnTrip::JustWalking(start, goal) => (sidewalk_spot(start), sidewalk_spot(goal)),
+        SpawnTrip::WalkFromLane(l, _, goal) => {
+            (ID::Intersection(map.get_l(*l).src_i), sidewalk_spot(goal))
+        }
         SpawnTrip::UsingTransit(start, goal, _, _, _) => {
             (sidewalk_spot(start), sidewalk_spot(goal))
         }
+        SpawnTrip::MultiStop(stops, _) => (endpoint(&stops[0]), endpoint(stops.last().unwrap())),
     };
     let home_id = match home {
         OD::Bldg(b) => ID::Building(b),
@@ -438,18 +635,26 @@ fn other_endpt(trip: &IndividTrip, home: OD, map: &Map) -> ID {
     }
 }
 
+struct Demand {
+    draw: Drawable,
+    legend: Composite,
+    // Where to label the thickest arrow, and how many trips it represents
+    busiest: Option<(Pt2D, usize)>,
+    home: OD,
+    // The same arrows, ready to export
+    geojson: String,
+}
+
 // TODO Understand demand better.
 // - Be able to select an area, see trips to/from it
-// - Weight the arrow size by how many trips go there
-// - Legend, counting the number of trips
 fn show_demand(
     scenario: &Scenario,
     from: &BTreeSet<usize>,
     to: &BTreeSet<usize>,
     home: OD,
     app: &App,
-    ctx: &EventCtx,
-) -> Drawable {
+    ctx: &mut EventCtx,
+) -> Demand {
     let mut from_ids = Counter::new();
     for idx in from {
         from_ids.inc(other_endpt(
@@ -468,53 +673,119 @@ fn show_demand(
     }
     let from_count = from_ids.consume();
     let mut to_count = to_ids.consume();
-    let max_count =
-        (*from_count.values().max().unwrap()).max(*to_count.values().max().unwrap()) as f64;
+    let endpoints: Vec<(Pt2D, usize, usize)> = from_count
+        .keys()
+        .chain(to_count.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|id| {
+            (
+                id.canonical_point(&app.primary).unwrap(),
+                from_count.get(id).cloned().unwrap_or(0),
+                to_count.get(id).cloned().unwrap_or(0),
+            )
+        })
+        .collect();
+    let max_count = from_count
+        .values()
+        .chain(to_count.values())
+        .max()
+        .cloned()
+        .unwrap_or(0);
 
     let mut batch = GeomBatch::new();
+    let mut busiest = None;
     let home_pt = match home {
         OD::Bldg(b) => app.primary.map.get_b(b).polygon.center(),
         OD::Border(i) => app.primary.map.get_i(i).polygon.center(),
     };
+    let geojson = abstutil::to_json(&demand_geojson(
+        home_pt,
+        endpoints,
+        app.primary.map.get_gps_bounds(),
+    ));
 
     for (id, cnt) in from_count {
+        let pl = PolyLine::new(vec![home_pt, id.canonical_point(&app.primary).unwrap()]);
         // Bidirectional?
         if let Some(other_cnt) = to_count.remove(&id) {
-            let width = Distance::meters(1.0)
-                + ((cnt.max(other_cnt) as f64) / max_count) * Distance::meters(2.0);
+            let cnt = cnt.max(other_cnt);
+            if cnt == max_count && busiest.is_none() {
+                busiest = Some((pl.middle(), cnt));
+            }
             batch.push(
                 Color::PURPLE.alpha(0.8),
-                PolyLine::new(vec![home_pt, id.canonical_point(&app.primary).unwrap()])
-                    .make_polygons(width),
+                pl.make_polygons(demand_arrow_width(cnt, max_count)),
             );
         } else {
-            let width = Distance::meters(1.0) + ((cnt as f64) / max_count) * Distance::meters(2.0);
+            if cnt == max_count && busiest.is_none() {
+                busiest = Some((pl.middle(), cnt));
+            }
             batch.push(
                 Color::RED.alpha(0.8),
-                PolyLine::new(vec![home_pt, id.canonical_point(&app.primary).unwrap()])
-                    .make_arrow(width)
-                    .unwrap(),
+                pl.make_arrow(demand_arrow_width(cnt, max_count)).unwrap(),
             );
         }
     }
     for (id, cnt) in to_count {
-        let width = Distance::meters(1.0) + ((cnt as f64) / max_count) * Distance::meters(2.0);
+        let pl = PolyLine::new(vec![id.canonical_point(&app.primary).unwrap(), home_pt]);
+        if cnt == max_count && busiest.is_none() {
+            busiest = Some((pl.middle(), cnt));
+        }
         batch.push(
             Color::BLUE.alpha(0.8),
-            PolyLine::new(vec![id.canonical_point(&app.primary).unwrap(), home_pt])
-                .make_arrow(width)
-                .unwrap(),
+            pl.make_arrow(demand_arrow_width(cnt, max_count)).unwrap(),
         );
     }
 
-    batch.upload(ctx)
+    let legend = Composite::new(
+        ManagedWidget::col(vec![
+            ManagedWidget::draw_text(
+                ctx,
+                Text::from(Line("Trips to and from here").roboto_bold()),
+            ),
+            ColorLegend::row(ctx, Color::RED, "outbound"),
+            ColorLegend::row(ctx, Color::BLUE, "inbound"),
+            ColorLegend::row(ctx, Color::PURPLE, "both directions"),
+            ManagedWidget::draw_text(
+                ctx,
+                Text::from(Line(format!(
+                    "Width grows with the square root of trips, up to {}",
+                    prettyprint_usize(max_count)
+                ))),
+            ),
+            WrappedComposite::text_button(ctx, "export to GeoJSON", None),
+        ])
+        .padding(10)
+        .bg(colors::PANEL_BG),
+    )
+    .aligned(HorizontalAlignment::Left, VerticalAlignment::Center)
+    .build(ctx);
+
+    Demand {
+        draw: batch.upload(ctx),
+        legend,
+        busiest,
+        home,
+        geojson,
+    }
 }
 
+// How fast dots move along their straight lines
+const DOT_SPEED: Speed = Speed::const_meters_per_second(5.0);
+// How much simulated time passes per real second while playing
+const PLAYBACK_SPEED: f64 = 600.0;
+
 struct DotMap {
     composite: Composite,
 
-    lines: Vec<Line>,
-    draw: Option<(f64, Drawable)>,
+    // Each trip's straight line and departure time
+    lines: Vec<(Line, Time)>,
+    // When the last trip finishes
+    end_time: Time,
+    time: Time,
+    playing: bool,
+    draw: Option<(Time, Drawable)>,
 }
 
 impl DotMap {
@@ -534,36 +805,85 @@ impl DotMap {
                     SpawnTrip::JustWalking(start, goal) => {
                         (start.sidewalk_pos.pt(map), goal.sidewalk_pos.pt(map))
                     }
+                    SpawnTrip::WalkFromLane(l, dist, goal) => {
+                        (Position::new(*l, *dist).pt(map), goal.sidewalk_pos.pt(map))
+                    }
                     SpawnTrip::UsingTransit(start, goal, _, _, _) => {
                         (start.sidewalk_pos.pt(map), goal.sidewalk_pos.pt(map))
                     }
+                    SpawnTrip::MultiStop(stops, _) => {
+                        (stops[0].pt(map), stops.last().unwrap().pt(map))
+                    }
                 };
-                Line::maybe_new(start, end)
+                Line::maybe_new(start, end).map(|l| (l, trip.depart))
             })
-            .collect();
+            .collect::<Vec<_>>();
+        let end_time = lines
+            .iter()
+            .map(|(l, depart)| *depart + l.length() / DOT_SPEED)
+            .max()
+            .unwrap_or(Time::START_OF_DAY);
         DotMap {
-            composite: Composite::new(
-                ManagedWidget::col(vec![
-                    ManagedWidget::row(vec![
-                        ManagedWidget::draw_text(
-                            ctx,
-                            Text::from(Line("Dot map of all trips").roboto_bold()),
-                        ),
-                        WrappedComposite::text_button(ctx, "X", hotkey(Key::Escape)).align_right(),
-                    ]),
-                    ManagedWidget::slider("time slider"),
-                ])
-                .padding(10)
-                .bg(colors::PANEL_BG),
-            )
-            .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
-            .slider("time slider", Slider::horizontal(ctx, 150.0, 25.0))
-            .build(ctx),
-
+            composite: DotMap::make_panel(ctx, Time::START_OF_DAY, false),
             lines,
+            end_time,
+            time: Time::START_OF_DAY,
+            playing: false,
             draw: None,
         }
     }
+
+    fn make_panel(ctx: &mut EventCtx, time: Time, playing: bool) -> Composite {
+        Composite::new(
+            ManagedWidget::col(vec![
+                ManagedWidget::row(vec![
+                    ManagedWidget::draw_text(
+                        ctx,
+                        Text::from(Line("Dot map of all trips").roboto_bold()),
+                    ),
+                    WrappedComposite::text_button(ctx, "X", hotkey(Key::Escape)).align_right(),
+                ]),
+                ManagedWidget::row(vec![
+                    WrappedComposite::text_button(
+                        ctx,
+                        if playing { "pause" } else { "play" },
+                        hotkey(Key::Space),
+                    )
+                    .margin(5),
+                    ManagedWidget::draw_text(ctx, Text::from(Line(time.ampm_tostring())))
+                        .named("time")
+                        .margin(5),
+                ]),
+                ManagedWidget::slider("time slider"),
+            ])
+            .padding(10)
+            .bg(colors::PANEL_BG),
+        )
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+        .slider("time slider", Slider::horizontal(ctx, 150.0, 25.0))
+        .build(ctx)
+    }
+
+    fn set_time(&mut self, ctx: &mut EventCtx, time: Time, move_slider: bool) {
+        self.time = time;
+        self.composite.replace(
+            ctx,
+            "time",
+            ManagedWidget::draw_text(ctx, Text::from(Line(time.ampm_tostring())))
+                .named("time")
+                .margin(5),
+        );
+        if move_slider {
+            let pct = if self.end_time == Time::START_OF_DAY {
+                0.0
+            } else {
+                (time - Time::START_OF_DAY) / (self.end_time - Time::START_OF_DAY)
+            };
+            self.composite
+                .slider_mut("time slider")
+                .set_percent(ctx, pct.min(1.0));
+        }
+    }
 }
 
 impl State for DotMap {
@@ -575,27 +895,58 @@ impl State for DotMap {
                 "X" => {
                     return Transition::Pop;
                 }
+                "play" | "pause" => {
+                    self.playing = !self.playing;
+                    self.composite = DotMap::make_panel(ctx, self.time, self.playing);
+                    self.set_time(ctx, self.time, true);
+                }
                 _ => unreachable!(),
             },
             None => {}
         }
 
-        let pct = self.composite.slider("time slider").get_percent();
+        if self.playing {
+            if let Some(real_dt) = ctx.input.nonblocking_is_update_event() {
+                ctx.input.use_update_event();
+                let time = self.time + PLAYBACK_SPEED * real_dt;
+                if time >= self.end_time {
+                    self.playing = false;
+                    self.composite = DotMap::make_panel(ctx, self.end_time, self.playing);
+                    self.set_time(ctx, self.end_time, true);
+                } else {
+                    self.set_time(ctx, time, true);
+                }
+            }
+        } else {
+            let pct = self.composite.slider("time slider").get_percent();
+            let time = Time::START_OF_DAY + pct * (self.end_time - Time::START_OF_DAY);
+            if time != self.time {
+                self.set_time(ctx, time, false);
+            }
+        }
 
-        if self.draw.as_ref().map(|(p, _)| pct != *p).unwrap_or(true) {
+        if self
+            .draw
+            .as_ref()
+            .map(|(t, _)| self.time != *t)
+            .unwrap_or(true)
+        {
             let mut batch = GeomBatch::new();
             let radius = Distance::meters(5.0);
-            for l in &self.lines {
-                // Circles are too expensive. :P
-                batch.push(
-                    Color::RED,
-                    Polygon::rectangle_centered(l.percent_along(pct), radius, radius),
-                );
+            for (l, depart) in &self.lines {
+                if let Some(pt) = straight_line_dot(l, *depart, DOT_SPEED, self.time) {
+                    // Circles are too expensive. :P
+                    batch.push(Color::RED, Polygon::rectangle_centered(pt, radius, radius));
+                }
             }
-            self.draw = Some((pct, batch.upload(ctx)));
+            self.draw = Some((self.time, batch.upload(ctx)));
         }
 
-        Transition::Keep
+        if self.playing {
+            Transition::KeepWithMode(EventLoopMode::Animation)
+        } else {
+            Transition::Keep
+        }
     }
 
     fn draw(&self, g: &mut GfxCtx, _: &App) {
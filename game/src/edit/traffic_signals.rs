@@ -738,7 +738,9 @@ impl State for PreviewTrafficSignal {
             None => {}
         }
 
-        self.time_panel.event(ctx, app);
+        if let Some(t) = self.time_panel.event(ctx, app) {
+            return t;
+        }
         // TODO Ideally here reset to midnight would jump back to when the preview started?
         if let Some(t) = self.speed.event(ctx, app, None) {
             return t;
@@ -5,7 +5,7 @@ use ezgui::{Color, Line, Text, TextSpan};
 use geom::{Duration, Pt2D};
 use map_model::{AreaID, BuildingID, BusStopID, IntersectionID, LaneID, RoadID, TurnID};
 use serde_derive::{Deserialize, Serialize};
-use sim::{AgentID, CarID, PedestrianID, TripID};
+use sim::{AgentID, CarID, PedestrianID, TripID, TripMode};
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 // Aside from Road and Trip, everything here can actually be selected.
@@ -125,6 +125,16 @@ impl ColorScheme {
             panic!("Color {} undefined", name);
         }
     }
+
+    // Match the unzoomed agents, so every chart and panel agrees
+    pub fn for_mode(&self, m: TripMode) -> Color {
+        match m {
+            TripMode::Walk => self.get("unzoomed pedestrian"),
+            TripMode::Bike => self.get("unzoomed bike"),
+            TripMode::Transit => self.get("unzoomed bus"),
+            TripMode::Drive => self.get("unzoomed car"),
+        }
+    }
 }
 
 include!(concat!(env!("OUT_DIR"), "/init_colors.rs"));
@@ -247,3 +257,20 @@ pub fn cmp_count_more(now: usize, baseline: usize) -> TextSpan {
         Line("same as baseline")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_mode_has_its_own_color() {
+        let cs = ColorScheme::load(None);
+        let colors: Vec<Color> = TripMode::all()
+            .into_iter()
+            .map(|m| cs.for_mode(m))
+            .collect();
+        for (idx, c) in colors.iter().enumerate() {
+            assert!(!colors[idx + 1..].contains(c), "{:?} is used twice", c);
+        }
+    }
+}
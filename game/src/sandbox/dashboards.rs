@@ -1,39 +1,52 @@
 use crate::app::App;
 use crate::colors;
 use crate::common::ShowBusRoute;
-use crate::game::{State, Transition};
+use crate::game::{msg, State, Transition};
 use crate::helpers::ID;
 use crate::helpers::{cmp_count_fewer, cmp_count_more, cmp_duration_shorter};
 use crate::managed::{Callback, ManagedGUIState, WrappedComposite};
 use crate::sandbox::SandboxMode;
 use abstutil::prettyprint_usize;
-use abstutil::Counter;
 use ezgui::{
-    hotkey, Button, Color, Composite, EventCtx, Histogram, Key, Line, ManagedWidget, Plot,
-    PlotOptions, Series, Text,
+    hotkey, BarChart, Button, Color, Composite, EventCtx, Histogram, Key, Line, ManagedWidget,
+    Plot, PlotOptions, Series, Text, TextSpan,
 };
-use geom::{Duration, Statistic, Time};
+use geom::{Distance, Duration, Statistic, Time};
 use map_model::BusRouteID;
-use sim::{TripID, TripMode};
-use std::collections::BTreeMap;
+use sim::{
+    sort_finished_trips, TripEnd, TripEndpointFilter, TripID, TripMode, TripSortKey, TripStart,
+};
+use std::collections::{BTreeMap, BTreeSet};
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone)]
 pub enum Tab {
     TripsSummary,
-    IndividualFinishedTrips(Option<TripMode>),
+    // The bool is true for descending order
+    IndividualFinishedTrips(Option<TripMode>, TripEndpointFilter, (TripSortKey, bool)),
+    DistanceVsTime,
     ParkingOverhead,
+    CompareBaseline,
     ExploreBusRoute,
 }
 
+impl Tab {
+    // The longest trips first, not restricted to any mode
+    pub fn finished_trips(filter: TripEndpointFilter) -> Tab {
+        Tab::IndividualFinishedTrips(None, filter, (TripSortKey::Duration, true))
+    }
+}
+
 // Oh the dashboards melted, but we still had the radio
 pub fn make(ctx: &mut EventCtx, app: &App, tab: Tab) -> Box<dyn State> {
     let tab_data = vec![
         (Tab::TripsSummary, "Trips summary"),
         (
-            Tab::IndividualFinishedTrips(None),
+            Tab::finished_trips(TripEndpointFilter::any()),
             "Individual finished trips",
         ),
+        (Tab::DistanceVsTime, "Distance vs time"),
         (Tab::ParkingOverhead, "Parking overhead analysis"),
+        (Tab::CompareBaseline, "Compare with baseline"),
         (Tab::ExploreBusRoute, "Explore a bus route"),
     ];
 
@@ -50,10 +63,16 @@ pub fn make(ctx: &mut EventCtx, app: &App, tab: Tab) -> Box<dyn State> {
         .collect::<Vec<_>>();
 
     let (content, cbs) = match tab {
-        Tab::TripsSummary => (trips_summary_prebaked(ctx, app), Vec::new()),
-        Tab::IndividualFinishedTrips(None) => pick_finished_trips_mode(ctx),
-        Tab::IndividualFinishedTrips(Some(m)) => pick_finished_trips(m, ctx, app),
+        Tab::TripsSummary => trips_summary_prebaked(ctx, app),
+        Tab::IndividualFinishedTrips(None, ref filter, sort) => {
+            pick_finished_trips_mode(ctx, app, filter, sort)
+        }
+        Tab::IndividualFinishedTrips(Some(m), ref filter, sort) => {
+            pick_finished_trips(m, filter, sort, ctx, app)
+        }
+        Tab::DistanceVsTime => distance_vs_time(ctx, app),
         Tab::ParkingOverhead => (parking_overhead(ctx, app), Vec::new()),
+        Tab::CompareBaseline => (compare_baseline(ctx, app), Vec::new()),
         Tab::ExploreBusRoute => pick_bus_route(ctx, app),
     };
 
@@ -80,7 +99,7 @@ pub fn make(ctx: &mut EventCtx, app: &App, tab: Tab) -> Box<dyn State> {
         if t != tab {
             c = c.cb(
                 label,
-                Box::new(move |ctx, app| Some(Transition::Replace(make(ctx, app, t)))),
+                Box::new(move |ctx, app| Some(Transition::Replace(make(ctx, app, t.clone())))),
             );
         }
     }
@@ -91,18 +110,21 @@ pub fn make(ctx: &mut EventCtx, app: &App, tab: Tab) -> Box<dyn State> {
     ManagedGUIState::fullscreen(c)
 }
 
-fn trips_summary_prebaked(ctx: &EventCtx, app: &App) -> ManagedWidget {
+fn trips_summary_prebaked(ctx: &EventCtx, app: &App) -> (ManagedWidget, Vec<(String, Callback)>) {
     if app.has_prebaked().is_none() {
         return trips_summary_not_prebaked(ctx, app);
     }
+    let mut cbs: Vec<(String, Callback)> = Vec::new();
 
-    let (now_all, now_aborted, now_per_mode) = app
+    let now = app
         .primary
         .sim
         .get_analytics()
-        .trip_times(app.primary.sim.time());
+        .summarize(app.primary.sim.time());
+    let baseline = app.prebaked().summarize(app.primary.sim.time());
+    let (now_all, now_aborted, now_per_mode) = (now.all, now.num_aborted, now.per_mode);
     let (baseline_all, baseline_aborted, baseline_per_mode) =
-        app.prebaked().trip_times(app.primary.sim.time());
+        (baseline.all, baseline.num_aborted, baseline.per_mode);
 
     // TODO Include unfinished count
     let mut txt = Text::new();
@@ -119,6 +141,8 @@ fn trips_summary_prebaked(ctx: &EventCtx, app: &App) -> ManagedWidget {
         cmp_count_fewer(now_aborted, baseline_aborted),
         Line(")"),
     ]);
+    add_abort_reasons(app, &mut txt);
+    add_through_traffic(app, &mut txt);
     // TODO Refactor
     txt.add_appended(vec![
         Line(format!(
@@ -157,10 +181,11 @@ fn trips_summary_prebaked(ctx: &EventCtx, app: &App) -> ManagedWidget {
             }
         }
     }
+    txt.add(distance_traveled(app));
 
-    ManagedWidget::col(vec![
+    let widget = ManagedWidget::col(vec![
         ManagedWidget::draw_text(ctx, txt),
-        finished_trips_plot(ctx, app).bg(colors::SECTION_BG),
+        finished_trips_plot(ctx, app, &mut cbs).bg(colors::SECTION_BG),
         ManagedWidget::draw_text(
             ctx,
             Text::from(Line("Are trips faster or slower than the baseline?")),
@@ -176,33 +201,65 @@ fn trips_summary_prebaked(ctx: &EventCtx, app: &App) -> ManagedWidget {
         ManagedWidget::draw_text(ctx, Text::from(Line("Active agents").roboto_bold())),
         Plot::new_usize(
             ctx,
+            "Active agents",
             vec![
                 Series {
                     label: "Baseline".to_string(),
                     color: Color::BLUE.alpha(0.5),
                     pts: app.prebaked().active_agents(Time::END_OF_DAY),
+                    actions: Vec::new(),
                 },
-                Series {
-                    label: "Current simulation".to_string(),
-                    color: Color::RED,
+                active_agents_series(app, "Current simulation", &mut cbs),
+            ],
+            PlotOptions::new(),
+        ),
+        ManagedWidget::draw_text(ctx, Text::from(Line("Trip time percentiles").roboto_bold())),
+        Plot::new_duration(
+            ctx,
+            "Trip time percentiles",
+            TripMode::all()
+                .into_iter()
+                .map(|mode| Series {
+                    label: mode.to_string(),
+                    color: app.cs.for_mode(mode),
                     pts: app
                         .primary
                         .sim
                         .get_analytics()
-                        .active_agents(app.primary.sim.time()),
-                },
-            ],
+                        .trip_time_percentiles(mode, app.primary.sim.time()),
+                    actions: Vec::new(),
+                })
+                .collect(),
             PlotOptions::new(),
         ),
-    ])
+    ]);
+    (widget, cbs)
+}
+
+fn add_abort_reasons(app: &App, txt: &mut Text) {
+    for (reason, cnt) in app.primary.sim.aborted_trips_by_reason() {
+        txt.add(Line(format!("- {}: {}", reason, prettyprint_usize(cnt))));
+    }
+}
+
+fn add_through_traffic(app: &App, txt: &mut Text) {
+    txt.add(Line(format!(
+        "{} trips are through traffic",
+        prettyprint_usize(app.primary.sim.count_through_trips())
+    )));
 }
 
-fn trips_summary_not_prebaked(ctx: &EventCtx, app: &App) -> ManagedWidget {
-    let (all, aborted, per_mode) = app
+fn trips_summary_not_prebaked(
+    ctx: &EventCtx,
+    app: &App,
+) -> (ManagedWidget, Vec<(String, Callback)>) {
+    let mut cbs: Vec<(String, Callback)> = Vec::new();
+    let summary = app
         .primary
         .sim
         .get_analytics()
-        .trip_times(app.primary.sim.time());
+        .summarize(app.primary.sim.time());
+    let (all, aborted, per_mode) = (summary.all, summary.num_aborted, summary.per_mode);
 
     // TODO Include unfinished count
     let mut txt = Text::new();
@@ -215,6 +272,8 @@ fn trips_summary_not_prebaked(ctx: &EventCtx, app: &App) -> ManagedWidget {
         "{} aborted trips",
         prettyprint_usize(aborted)
     )));
+    add_abort_reasons(app, &mut txt);
+    add_through_traffic(app, &mut txt);
     txt.add(Line(format!(
         "{} total trips",
         prettyprint_usize(all.count())
@@ -239,75 +298,144 @@ fn trips_summary_not_prebaked(ctx: &EventCtx, app: &App) -> ManagedWidget {
             }
         }
     }
+    txt.add(distance_traveled(app));
 
-    ManagedWidget::col(vec![
+    let widget = ManagedWidget::col(vec![
         ManagedWidget::draw_text(ctx, txt),
-        finished_trips_plot(ctx, app).bg(colors::SECTION_BG),
+        BarChart::new(
+            ctx,
+            TripMode::all()
+                .into_iter()
+                .map(|mode| {
+                    (
+                        mode.to_string(),
+                        per_mode[&mode].count(),
+                        app.cs.for_mode(mode),
+                    )
+                })
+                .collect(),
+        )
+        .bg(colors::SECTION_BG),
+        finished_trips_plot(ctx, app, &mut cbs).bg(colors::SECTION_BG),
         ManagedWidget::draw_text(ctx, Text::from(Line("Active agents").roboto_bold())),
         Plot::new_usize(
             ctx,
-            vec![Series {
-                label: "Active agents".to_string(),
-                color: Color::RED,
-                pts: app
-                    .primary
-                    .sim
-                    .get_analytics()
-                    .active_agents(app.primary.sim.time()),
-            }],
+            "Active agents",
+            vec![active_agents_series(app, "Active agents", &mut cbs)],
             PlotOptions::new(),
         ),
-    ])
+    ]);
+    (widget, cbs)
 }
 
-fn finished_trips_plot(ctx: &EventCtx, app: &App) -> ManagedWidget {
-    let mut lines: Vec<(String, Color, Option<TripMode>)> = TripMode::all()
-        .into_iter()
-        .map(|m| (m.to_string(), color_for_mode(m, app), Some(m)))
-        .collect();
-    lines.push(("aborted".to_string(), Color::PURPLE.alpha(0.5), None));
-
-    // What times do we use for interpolation?
-    let num_x_pts = 100;
-    let mut times = Vec::new();
-    for i in 0..num_x_pts {
-        let percent_x = (i as f64) / ((num_x_pts - 1) as f64);
-        let t = app.primary.sim.time().percent_of(percent_x);
-        times.push(t);
+// Clicking a point opens the trip that last started or finished there.
+fn active_agents_series(
+    app: &App,
+    label: &str,
+    cbs: &mut Vec<(String, Callback)>,
+) -> Series<Time, usize> {
+    let mut pts = Vec::new();
+    let mut trips = Vec::new();
+    for (t, cnt, trip) in app
+        .primary
+        .sim
+        .get_analytics()
+        .active_agents_with_trips(app.primary.sim.time())
+    {
+        pts.push((t, cnt));
+        trips.push(trip);
+    }
+    Series {
+        label: label.to_string(),
+        color: Color::RED,
+        pts,
+        actions: trip_actions(trips, cbs),
     }
+}
 
-    // Gather the data
-    let mut counts = Counter::new();
-    let mut pts_per_mode: BTreeMap<Option<TripMode>, Vec<(Time, usize)>> =
-        lines.iter().map(|(_, _, m)| (*m, Vec::new())).collect();
-    for (t, _, m, _) in &app.primary.sim.get_analytics().finished_trips {
-        counts.inc(*m);
-        if *t > times[0] {
-            times.remove(0);
-            for (_, _, mode) in &lines {
-                pts_per_mode
-                    .get_mut(mode)
-                    .unwrap()
-                    .push((*t, counts.get(*mode)));
+// Each trip becomes a point action that opens its info panel.
+fn trip_actions(
+    trips: Vec<Option<TripID>>,
+    cbs: &mut Vec<(String, Callback)>,
+) -> Vec<Option<String>> {
+    let mut seen = BTreeSet::new();
+    trips
+        .into_iter()
+        .map(|trip| {
+            let id = trip?;
+            if seen.insert(id) {
+                cbs.push((id.to_string(), show_trip(id)));
             }
-        }
-    }
-    // Don't forget the last batch
+            Some(id.to_string())
+        })
+        .collect()
+}
+
+fn distance_traveled(app: &App) -> TextSpan {
+    Line(format!(
+        "Distance traveled: {}",
+        app.primary
+            .sim
+            .get_analytics()
+            .vmt_by_mode()
+            .into_iter()
+            .map(|(mode, dist)| format!("{} {}", dist, mode))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+// Clicking a point opens the last trip to finish there.
+fn finished_trips_plot(
+    ctx: &EventCtx,
+    app: &App,
+    cbs: &mut Vec<(String, Callback)>,
+) -> ManagedWidget {
+    let mut lines: Vec<(String, Color, Option<TripMode>)> = TripMode::all()
+        .into_iter()
+        .map(|m| (m.to_string(), app.cs.for_mode(m), Some(m)))
+        .collect();
+    lines.push((
+        "aborted".to_string(),
+        app.cs.get_def("aborted trip", Color::PURPLE.alpha(0.5)),
+        None,
+    ));
+
+    let now = app.primary.sim.time();
+    let mut pts_per_mode: BTreeMap<Option<TripMode>, (Vec<(Time, usize)>, Vec<Option<String>>)> =
+        BTreeMap::new();
     for (_, _, mode) in &lines {
-        pts_per_mode
-            .get_mut(mode)
-            .unwrap()
-            .push((app.primary.sim.time(), counts.get(*mode)));
+        let analytics = app.primary.sim.get_analytics();
+        let series = analytics.finished_trips_series(*mode);
+        let mut pts = vec![(Time::START_OF_DAY, 0)];
+        pts.extend(series.iter().cloned());
+        // Extend the line to the present
+        pts.push((now, series.last().map(|(_, cnt)| *cnt).unwrap_or(0)));
+
+        let mut trips = vec![None];
+        trips.extend(
+            analytics
+                .finished_trips_series_trips(*mode)
+                .iter()
+                .map(|id| Some(*id)),
+        );
+        trips.push(None);
+        pts_per_mode.insert(*mode, (pts, trip_actions(trips, cbs)));
     }
 
     let plot = Plot::new_usize(
         ctx,
+        "Finished trips",
         lines
             .into_iter()
-            .map(|(label, color, m)| Series {
-                label,
-                color,
-                pts: pts_per_mode.remove(&m).unwrap(),
+            .map(|(label, color, m)| {
+                let (pts, actions) = pts_per_mode.remove(&m).unwrap();
+                Series {
+                    label,
+                    color,
+                    pts,
+                    actions,
+                }
             })
             .collect(),
         PlotOptions::new(),
@@ -318,85 +446,341 @@ fn finished_trips_plot(ctx: &EventCtx, app: &App) -> ManagedWidget {
     ])
 }
 
-fn pick_finished_trips_mode(ctx: &EventCtx) -> (ManagedWidget, Vec<(String, Callback)>) {
+fn pick_finished_trips_mode(
+    ctx: &EventCtx,
+    app: &App,
+    filter: &TripEndpointFilter,
+    sort: (TripSortKey, bool),
+) -> (ManagedWidget, Vec<(String, Callback)>) {
     let mut buttons = Vec::new();
     let mut cbs: Vec<(String, Callback)> = Vec::new();
 
     for mode in TripMode::all() {
         buttons.push(WrappedComposite::text_button(ctx, &mode.to_string(), None));
+        let filter = filter.clone();
         cbs.push((
             mode.to_string(),
             Box::new(move |ctx, app| {
                 Some(Transition::Replace(make(
                     ctx,
                     app,
-                    Tab::IndividualFinishedTrips(Some(mode)),
+                    Tab::IndividualFinishedTrips(Some(mode), filter.clone(), sort),
                 )))
             }),
         ));
     }
 
-    (ManagedWidget::row(buttons).flex_wrap(ctx, 80), cbs)
+    let mut col = vec![ManagedWidget::row(buttons).flex_wrap(ctx, 80)];
+    if *filter != TripEndpointFilter::any() {
+        col.push(ManagedWidget::row(vec![
+            ManagedWidget::draw_text(ctx, Text::from(Line(describe_filter(filter, app)))),
+            WrappedComposite::text_button(ctx, "clear filter", None),
+        ]));
+        cbs.push((
+            "clear filter".to_string(),
+            Box::new(move |ctx, app| {
+                Some(Transition::Replace(make(
+                    ctx,
+                    app,
+                    Tab::IndividualFinishedTrips(None, TripEndpointFilter::any(), sort),
+                )))
+            }),
+        ));
+    }
+
+    (ManagedWidget::col(col), cbs)
+}
+
+fn describe_filter(filter: &TripEndpointFilter, app: &App) -> String {
+    let mut parts = Vec::new();
+    match filter.start {
+        Some(TripStart::Bldg(b)) => {
+            parts.push(format!(
+                "from {}",
+                app.primary.map.get_b(b).just_address(&app.primary.map)
+            ));
+        }
+        Some(TripStart::Border(i)) => {
+            parts.push(format!("from border #{}", i.0));
+        }
+        None => {}
+    }
+    match filter.end {
+        Some(TripEnd::Bldg(b)) => {
+            parts.push(format!(
+                "to {}",
+                app.primary.map.get_b(b).just_address(&app.primary.map)
+            ));
+        }
+        Some(TripEnd::Border(i)) => {
+            parts.push(format!("to border #{}", i.0));
+        }
+        Some(TripEnd::ServeBusRoute(r)) => {
+            parts.push(format!("serving {}", app.primary.map.get_br(r).name));
+        }
+        None => {}
+    }
+    format!("Only trips {}", parts.join(" and "))
 }
 
 fn pick_finished_trips(
     mode: TripMode,
+    filter: &TripEndpointFilter,
+    sort: (TripSortKey, bool),
     ctx: &EventCtx,
     app: &App,
 ) -> (ManagedWidget, Vec<(String, Callback)>) {
     let mut buttons = Vec::new();
     let mut cbs: Vec<(String, Callback)> = Vec::new();
 
+    cbs.push((
+        "download CSV".to_string(),
+        Box::new(|_, app| {
+            let path = format!(
+                "{}/finished_trips_{}.csv",
+                app.primary.sim.save_dir(),
+                app.primary.sim.time().as_filename()
+            );
+            abstutil::write_file(path.clone(), app.primary.sim.finished_trips_csv());
+            Some(Transition::Push(msg(
+                "Exported finished trips",
+                vec![format!("Wrote {}", path)],
+            )))
+        }),
+    ));
+
     let mut filtered: Vec<&(Time, TripID, Option<TripMode>, Duration)> = app
         .primary
         .sim
         .get_analytics()
         .finished_trips
         .iter()
-        .filter(|(_, _, m, _)| *m == Some(mode))
+        .filter(|(_, id, m, _)| {
+            if *m != Some(mode) {
+                return false;
+            }
+            let (start, end) = app.primary.sim.trip_endpoints(*id);
+            filter.matches(&start, &end)
+        })
         .collect();
-    filtered.sort_by_key(|(_, _, _, dt)| *dt);
-    filtered.reverse();
+    sort_finished_trips(&mut filtered, sort.0, sort.1);
     for (_, id, _, dt) in filtered {
         let label = format!("{} taking {}", id, dt);
         buttons.push(WrappedComposite::text_button(ctx, &label, None));
-        let trip = *id;
-        cbs.push((
-            label,
-            Box::new(move |_, _| {
-                Some(Transition::PopWithData(Box::new(move |state, app, ctx| {
-                    state
-                        .downcast_mut::<SandboxMode>()
-                        .unwrap()
-                        .controls
-                        .common
-                        .as_mut()
-                        .unwrap()
-                        .launch_info_panel(ID::Trip(trip), ctx, app);
-                })))
-            }),
-        ));
+        cbs.push((label, show_trip(*id)));
     }
 
     // TODO Indicate the current mode
-    let (mode_picker, more_cbs) = pick_finished_trips_mode(ctx);
+    let (mode_picker, more_cbs) = pick_finished_trips_mode(ctx, app, filter, sort);
     cbs.extend(more_cbs);
 
+    let mut sort_buttons = vec![ManagedWidget::draw_text(ctx, Text::from(Line("Sort by:")))];
+    for (key, label) in vec![
+        (TripSortKey::FinishTime, "finish time"),
+        (TripSortKey::Duration, "duration"),
+        (TripSortKey::ID, "trip ID"),
+    ] {
+        if key == sort.0 {
+            sort_buttons.push(Button::inactive_selected_button(ctx, label).margin(5));
+        } else {
+            sort_buttons.push(WrappedComposite::text_button(ctx, label, None).margin(5));
+            let filter = filter.clone();
+            cbs.push((
+                label.to_string(),
+                Box::new(move |ctx, app| {
+                    Some(Transition::Replace(make(
+                        ctx,
+                        app,
+                        Tab::IndividualFinishedTrips(Some(mode), filter.clone(), (key, sort.1)),
+                    )))
+                }),
+            ));
+        }
+    }
+    let flip = if sort.1 { "ascending" } else { "descending" };
+    sort_buttons.push(WrappedComposite::text_button(ctx, flip, None).margin(5));
+    {
+        let filter = filter.clone();
+        cbs.push((
+            flip.to_string(),
+            Box::new(move |ctx, app| {
+                Some(Transition::Replace(make(
+                    ctx,
+                    app,
+                    Tab::IndividualFinishedTrips(Some(mode), filter.clone(), (sort.0, !sort.1)),
+                )))
+            }),
+        ));
+    }
+
     (
         ManagedWidget::col(vec![
             mode_picker,
+            WrappedComposite::text_button(ctx, "download CSV", hotkey(Key::D)),
+            ManagedWidget::row(sort_buttons),
             ManagedWidget::row(buttons).flex_wrap(ctx, 80),
         ]),
         cbs,
     )
 }
 
+fn distance_vs_time(ctx: &EventCtx, app: &App) -> (ManagedWidget, Vec<(String, Callback)>) {
+    let mut cbs: Vec<(String, Callback)> = Vec::new();
+    let mut per_mode: BTreeMap<TripMode, (Vec<(Distance, Duration)>, Vec<Option<String>>)> =
+        TripMode::all()
+            .into_iter()
+            .map(|m| (m, (Vec::new(), Vec::new())))
+            .collect();
+    for (id, mode, dist, dt) in app
+        .primary
+        .sim
+        .get_analytics()
+        .distance_vs_time(app.primary.sim.time())
+    {
+        let (pts, actions) = per_mode.get_mut(&mode).unwrap();
+        pts.push((dist, dt));
+        actions.push(Some(id.to_string()));
+        cbs.push((id.to_string(), show_trip(id)));
+    }
+
+    let mut series = Vec::new();
+    for (mode, (pts, actions)) in per_mode {
+        series.push(Series {
+            label: mode.to_string(),
+            color: app.cs.for_mode(mode),
+            pts,
+            actions,
+        });
+    }
+
+    (
+        ManagedWidget::col(vec![
+            ManagedWidget::draw_text(
+                ctx,
+                Text::from(Line(
+                    "Distance traveled vs trip time (click a trip to see it)",
+                )),
+            ),
+            Plot::new_scatter_duration(ctx, "Distance vs time", series, PlotOptions::new())
+                .margin(10),
+        ]),
+        cbs,
+    )
+}
+
+fn show_trip(trip: TripID) -> Callback {
+    Box::new(move |_, _| {
+        Some(Transition::PopWithData(Box::new(move |state, app, ctx| {
+            state
+                .downcast_mut::<SandboxMode>()
+                .unwrap()
+                .controls
+                .common
+                .as_mut()
+                .unwrap()
+                .launch_info_panel(ID::Trip(trip), ctx, app);
+        })))
+    })
+}
+
+fn compare_baseline(ctx: &EventCtx, app: &App) -> ManagedWidget {
+    if app.has_prebaked().is_none() {
+        return ManagedWidget::draw_text(
+            ctx,
+            Text::from(Line("There's no baseline run to compare against")),
+        );
+    }
+    let diff = app
+        .primary
+        .sim
+        .get_analytics()
+        .diff(app.prebaked(), app.primary.sim.time());
+
+    let mut txt = Text::new();
+    txt.add_appended(vec![
+        Line("Compared to the baseline as of "),
+        Line(diff.time.ampm_tostring()).roboto_bold(),
+    ]);
+    txt.highlight_last_line(Color::BLUE);
+    txt.add_appended(vec![
+        Line(prettyprint_usize(diff.num_faster)).fg(Color::GREEN),
+        Line(" trips got faster, "),
+        Line(prettyprint_usize(diff.num_slower)).fg(Color::RED),
+        Line(" got slower"),
+    ]);
+    for mode in TripMode::all() {
+        txt.add(Line(mode.to_string()).roboto_bold());
+        let delta = diff.trip_count_deltas[&mode];
+        txt.add(if delta > 0 {
+            Line(format!("{} more trips", prettyprint_usize(delta as usize))).fg(Color::GREEN)
+        } else if delta < 0 {
+            Line(format!(
+                "{} fewer trips",
+                prettyprint_usize(-delta as usize)
+            ))
+            .fg(Color::RED)
+        } else {
+            Line("same number of trips")
+        });
+        if let Some(dt) = diff.median_trip_time_deltas.get(&mode) {
+            let mut line = vec![Line("Median trip time: ")];
+            line.extend(cmp_duration_shorter(*dt, Duration::ZERO));
+            txt.add_appended(line);
+        }
+    }
+    ManagedWidget::draw_text(ctx, txt)
+}
+
 fn parking_overhead(ctx: &EventCtx, app: &App) -> ManagedWidget {
+    let results = app.primary.sim.get_analytics().analyze_parking_phases();
     let mut txt = Text::new();
-    for line in app.primary.sim.get_analytics().analyze_parking_phases() {
+    for line in vec![
+        format!("Consider all trips with both a walking and driving portion"),
+        format!(
+            "The portion of the trip spent walking to the parked car, looking for parking, and \
+             walking from the parking space to the final destination are all overhead."
+        ),
+        format!(
+            "So what's the distribution of overhead percentages look like? 0% is ideal -- the \
+             entire trip is spent just driving between the original source and destination."
+        ),
+        results.overhead.describe(),
+    ] {
         txt.add_wrapped(line, 0.9 * ctx.canvas.window_width);
     }
-    ManagedWidget::draw_text(ctx, txt)
+    if let (Some(parking), Some(walking)) =
+        (results.median_parking_time(), results.median_walking_time())
+    {
+        txt.add(Line(format!(
+            "Per trip, the median time spent looking for parking is {}, and walking is {}",
+            parking, walking
+        )));
+    }
+    ManagedWidget::col(vec![
+        ManagedWidget::draw_text(ctx, txt),
+        ManagedWidget::draw_text(
+            ctx,
+            Text::from(Line("Parking spots occupied").roboto_bold()),
+        ),
+        Plot::new_usize(
+            ctx,
+            "Parking spots occupied",
+            vec![Series {
+                label: "Percent full".to_string(),
+                color: Color::RED,
+                pts: app
+                    .primary
+                    .sim
+                    .get_analytics()
+                    .parking_over_time()
+                    .into_iter()
+                    .map(|(t, pct)| (t, pct.round() as usize))
+                    .collect(),
+                actions: Vec::new(),
+            }],
+            PlotOptions::new(),
+        ),
+    ])
 }
 
 fn pick_bus_route(ctx: &EventCtx, app: &App) -> (ManagedWidget, Vec<(String, Callback)>) {
@@ -428,13 +812,3 @@ fn pick_bus_route(ctx: &EventCtx, app: &App) -> (ManagedWidget, Vec<(String, Cal
 
     (ManagedWidget::row(buttons).flex_wrap(ctx, 80), cbs)
 }
-
-// TODO Refactor
-fn color_for_mode(m: TripMode, app: &App) -> Color {
-    match m {
-        TripMode::Walk => app.cs.get("unzoomed pedestrian"),
-        TripMode::Bike => app.cs.get("unzoomed bike"),
-        TripMode::Transit => app.cs.get("unzoomed bus"),
-        TripMode::Drive => app.cs.get("unzoomed car"),
-    }
-}
@@ -246,5 +246,7 @@ fn spawn(
         stop_time: Time::START_OF_DAY + Duration::minutes(5),
         start_from_border: map.get_i(from).some_outgoing_road(map),
         goal: OriginDestination::EndOfRoad(map.get_i(to).some_incoming_road(map)),
+        vehicle_spec: None,
+        ped_speed: None,
     });
 }
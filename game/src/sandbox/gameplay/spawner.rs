@@ -20,7 +20,8 @@ use rand::seq::SliceRandom;
 use rand::Rng;
 use rand_xorshift::XorShiftRng;
 use sim::{
-    BorderSpawnOverTime, DrivingGoal, OriginDestination, Scenario, SidewalkSpot, Sim, TripSpec,
+    BorderSpawnOverTime, DrivingGoal, OriginDestination, Scenario, SidewalkSpot, Sim, TripEndpoint,
+    TripMode, TripSpec,
 };
 
 const SMALL_DT: Duration = Duration::const_seconds(0.1);
@@ -38,7 +39,8 @@ enum Source {
     BikeFromBldg(BuildingID, Position),
     WalkFromBldgThenMaybeUseCar(BuildingID),
     WalkFromSidewalk(Position),
-    Drive(Position),
+    // A car or bike suddenly appearing on a lane
+    Appear(Position, TripMode),
 }
 
 #[derive(PartialEq)]
@@ -60,11 +62,8 @@ impl AgentSpawner {
                 let spots = app.primary.sim.get_free_offstreet_spots(id);
                 if !spots.is_empty() && app.per_obj.action(ctx, Key::F6, "seed a parked car here") {
                     let mut rng = app.primary.current_flags.sim_flags.make_rng();
-                    app.primary.sim.seed_parked_car(
-                        Scenario::rand_car(&mut rng),
-                        spots[0],
-                        Some(id),
-                    );
+                    let vehicle = app.primary.sim.vehicle_sizes().rand_car(&mut rng);
+                    app.primary.sim.seed_parked_car(vehicle, spots[0], Some(id));
                     return None;
                 }
                 if app.per_obj.action(ctx, Key::F3, "spawn a walking trip") {
@@ -110,7 +109,7 @@ impl AgentSpawner {
                                 "Spawning a car",
                                 "Pick a building or border as a destination",
                             ),
-                            from: Source::Drive(pos),
+                            from: Source::Appear(pos, TripMode::Drive),
                             maybe_goal: None,
                             colorer: c.build(ctx, app),
                         }));
@@ -148,7 +147,28 @@ impl AgentSpawner {
                             "Spawning a car",
                             "Pick a building or border as a destination",
                         ),
-                        from: Source::Drive(Position::new(id, map.get_l(id).length() / 2.0)),
+                        from: Source::Appear(
+                            Position::new(id, map.get_l(id).length() / 2.0),
+                            TripMode::Drive,
+                        ),
+                        maybe_goal: None,
+                        colorer: c.build(ctx, app),
+                    }));
+                } else if PathConstraints::Bike.can_use(map.get_l(id), map)
+                    && app
+                        .per_obj
+                        .action(ctx, Key::F4, "spawn a bike starting here")
+                {
+                    return Some(Box::new(AgentSpawner {
+                        composite: make_top_bar(
+                            ctx,
+                            "Spawning a bike",
+                            "Pick a building or border as a destination",
+                        ),
+                        from: Source::Appear(
+                            Position::new(id, map.get_l(id).length() / 2.0),
+                            TripMode::Bike,
+                        ),
                         maybe_goal: None,
                         colorer: c.build(ctx, app),
                     }));
@@ -229,7 +249,8 @@ impl State for AgentSpawner {
                     PathConstraints::Pedestrian,
                 ),
                 Source::WalkFromSidewalk(pos) => (pos, PathConstraints::Pedestrian),
-                Source::Drive(pos) => (pos, PathConstraints::Car),
+                Source::Appear(pos, TripMode::Bike) => (pos, PathConstraints::Bike),
+                Source::Appear(pos, _) => (pos, PathConstraints::Car),
             };
             let end = match new_goal {
                 Goal::Building(to) => {
@@ -325,9 +346,9 @@ pub fn spawn_agents_around(i: IntersectionID, app: &mut App) {
         if lane.is_driving() || lane.is_biking() {
             for _ in 0..10 {
                 let vehicle_spec = if rng.gen_bool(0.7) && lane.is_driving() {
-                    Scenario::rand_car(&mut rng)
+                    sim.vehicle_sizes().rand_car(&mut rng)
                 } else {
-                    Scenario::rand_bike(&mut rng)
+                    sim.vehicle_sizes().rand_bike(&mut rng)
                 };
                 if vehicle_spec.length > lane.length() {
                     continue;
@@ -431,6 +452,20 @@ fn schedule_trip(
                 );
             }
         }
+        Source::Appear(pos, mode) => {
+            let goal = match raw_goal {
+                Goal::Building(b) => TripEndpoint::Bldg(b),
+                Goal::Border(i) => TripEndpoint::Border(i),
+            };
+            match TripSpec::appearing_on_lane(*pos, &goal, *mode, sim.vehicle_sizes(), map, rng) {
+                Ok(spec) => {
+                    sim.schedule_trip(sim.time(), spec, map);
+                }
+                Err(err) => {
+                    return Some(err);
+                }
+            }
+        }
         Source::BikeFromBldg(b, _) => {
             let goal = match raw_goal {
                 Goal::Building(to) => DrivingGoal::ParkNear(to),
@@ -450,7 +485,7 @@ fn schedule_trip(
                 sim.time(),
                 TripSpec::UsingBike {
                     start: SidewalkSpot::building(*b, map),
-                    vehicle: Scenario::rand_bike(rng),
+                    vehicle: sim.vehicle_sizes().rand_bike(rng),
                     goal,
                     ped_speed: Scenario::rand_ped_speed(rng),
                 },
@@ -474,22 +509,6 @@ fn schedule_trip(
                 }
             };
             match src {
-                Source::Drive(from) => {
-                    if let Some(start_pos) = TripSpec::spawn_car_at(*from, map) {
-                        sim.schedule_trip(
-                            sim.time(),
-                            TripSpec::CarAppearing {
-                                start_pos,
-                                vehicle_spec: Scenario::rand_car(rng),
-                                goal,
-                                ped_speed: Scenario::rand_ped_speed(rng),
-                            },
-                            map,
-                        );
-                    } else {
-                        return Some(format!("Can't make a car appear at {:?}", from));
-                    }
-                }
                 Source::WalkFromBldgThenMaybeUseCar(b) => {
                     sim.schedule_trip(
                         sim.time(),
@@ -670,6 +689,8 @@ fn create_swarm(app: &mut App, from: LaneID, to: LaneID, count: usize, duration:
                 .get_directed_parent(&app.primary.map),
         ),
         percent_use_transit: 0.0,
+        vehicle_spec: None,
+        ped_speed: None,
     });
     let mut rng = app.primary.current_flags.sim_flags.make_rng();
     scenario.instantiate(
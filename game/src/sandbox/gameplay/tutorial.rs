@@ -733,7 +733,7 @@ impl Stage {
                 &app.primary.map,
                 &mut app.primary.current_flags.sim_flags.make_rng(),
                 &mut Timer::throwaway(),
-            )
+            );
         }))
     }
 
@@ -791,6 +791,8 @@ fn make_bike_lane_scenario(map: &Map) -> Scenario {
         stop_time: Time::START_OF_DAY + Duration::seconds(10.0),
         start_from_border: RoadID(303).backwards(),
         goal: OriginDestination::GotoBldg(BuildingID(3)),
+        vehicle_spec: None,
+        ped_speed: None,
     });
     s
 }
@@ -815,6 +817,8 @@ fn make_bus_lane_scenario(map: &Map) -> Scenario {
             stop_time: Time::START_OF_DAY + Duration::seconds(10.0),
             start_from_border: src,
             goal: OriginDestination::EndOfRoad(RoadID(0).forwards()),
+            vehicle_spec: None,
+            ped_speed: None,
         });
     }
     s
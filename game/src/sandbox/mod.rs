@@ -24,7 +24,7 @@ pub use gameplay::spawner::spawn_agents_around;
 pub use gameplay::GameplayMode;
 use geom::{Duration, Polygon, Statistic, Time};
 use map_model::MapEdits;
-use sim::TripMode;
+use sim::{TripEnd, TripEndpointFilter, TripMode, TripStart};
 pub use speed::{SpeedControls, TimePanel};
 
 pub struct SandboxMode {
@@ -87,7 +87,7 @@ impl SandboxMode {
 
     fn examine_objects(&self, ctx: &mut EventCtx, app: &mut App) -> Option<Transition> {
         if app.opts.dev && ctx.input.new_was_pressed(&lctrl(Key::D).unwrap()) {
-            return Some(Transition::Push(Box::new(DebugMode::new(ctx))));
+            return Some(Transition::Push(Box::new(DebugMode::new(ctx, app))));
         }
 
         if let Some(ID::Building(b)) = app.primary.current_selection {
@@ -117,6 +117,50 @@ impl SandboxMode {
                 ))));
             }
         }
+        if let Some(trip) = app
+            .primary
+            .current_selection
+            .as_ref()
+            .and_then(|id| id.agent_id())
+            .and_then(|a| app.primary.sim.agent_to_trip(a))
+        {
+            if self.controls.speed.is_some()
+                && app
+                    .per_obj
+                    .action(ctx, Key::W, "pause when this trip is done")
+            {
+                app.primary.sim.watch_trip(Some(trip));
+            }
+        }
+        // Trips only start and end at buildings and borders
+        if let Some((start, end)) = match app.primary.current_selection {
+            Some(ID::Building(b)) => Some((TripStart::Bldg(b), TripEnd::Bldg(b))),
+            Some(ID::Intersection(i)) if app.primary.map.get_i(i).is_border() => {
+                Some((TripStart::Border(i), TripEnd::Border(i)))
+            }
+            _ => None,
+        } {
+            if app.per_obj.action(ctx, Key::O, "finished trips from here") {
+                return Some(Transition::Push(dashboards::make(
+                    ctx,
+                    app,
+                    dashboards::Tab::finished_trips(TripEndpointFilter {
+                        start: Some(start),
+                        end: None,
+                    }),
+                )));
+            }
+            if app.per_obj.action(ctx, Key::V, "finished trips to here") {
+                return Some(Transition::Push(dashboards::make(
+                    ctx,
+                    app,
+                    dashboards::Tab::finished_trips(TripEndpointFilter {
+                        start: None,
+                        end: Some(end),
+                    }),
+                )));
+            }
+        }
         if let Some(ID::Intersection(i)) = app.primary.current_selection {
             if app.primary.map.get_i(i).is_traffic_signal()
                 && app.per_obj.action(ctx, Key::C, "show current demand")
@@ -230,7 +274,9 @@ impl State for SandboxMode {
         }
 
         if let Some(ref mut tp) = self.controls.time_panel {
-            tp.event(ctx, app);
+            if let Some(t) = tp.event(ctx, app) {
+                return t;
+            }
         }
 
         if let Some(ref mut tp) = self.controls.tool_panel {
@@ -358,6 +404,7 @@ impl AgentMeter {
         use abstutil::prettyprint_usize;
 
         let (finished, unfinished, by_mode) = app.primary.sim.num_trips();
+        let not_departed = app.primary.sim.num_trips_not_departed();
 
         let mut rows = vec![
             ManagedWidget::draw_text(ctx, Text::from(Line("Active agents"))),
@@ -406,6 +453,11 @@ impl AgentMeter {
                     prettyprint_usize(finished),
                     pct as usize
                 )));
+                txt.add(Line(format!(
+                    "{} in progress, {} not departed yet",
+                    prettyprint_usize(unfinished - not_departed),
+                    prettyprint_usize(not_departed)
+                )));
                 ManagedWidget::draw_text(ctx, txt)
             },
             {
@@ -1,40 +1,42 @@
 use crate::app::App;
 use crate::colors;
 use crate::common::{Overlays, Warping};
-use crate::game::{msg, State, Transition};
+use crate::game::{msg, State, Transition, WizardState};
 use crate::helpers::ID;
 use crate::managed::{WrappedComposite, WrappedOutcome};
 use crate::sandbox::{GameplayMode, SandboxMode};
+use abstutil::Timer;
 use ezgui::{
     hotkey, Button, Color, Composite, EventCtx, EventLoopMode, GeomBatch, GfxCtx,
     HorizontalAlignment, Key, Line, ManagedWidget, Outcome, Plot, PlotOptions, RewriteColor,
-    Series, Slider, Text, VerticalAlignment,
+    Series, Slider, Text, VerticalAlignment, Wizard,
 };
 use geom::{Duration, Polygon, Time};
 use instant::Instant;
+use map_model::{IntersectionID, Map, RoadID};
+use serde_derive::{Deserialize, Serialize};
+use sim::{AgentID, PedestrianID, Sim, StopCondition, StopReason, TripID};
 
 pub struct SpeedControls {
     pub composite: WrappedComposite,
 
     paused: bool,
-    setting: SpeedSetting,
+    // How many sim seconds pass per real second
+    multiplier: f64,
+    custom_speed: Option<Wizard>,
 }
 
-#[derive(Clone, Copy, PartialEq, PartialOrd)]
-enum SpeedSetting {
-    // 1 sim second per real second
-    Realtime,
-    // 5 sim seconds per real second
-    Fast,
-    // 30 sim seconds per real second
-    Faster,
-    // 1 sim hour per real second
-    Fastest,
-}
+// The arrow keys step through multipliers by this factor.
+const SPEED_STEP: f64 = 2.0;
+// The arrow keys don't go faster than this; typing in a speed can.
+const MAX_STEPPED_SPEED: f64 = 3600.0;
+const MIN_SPEED: f64 = 0.1;
+// When time warping, look for gridlock this often. Agents stuck this long count.
+const GRIDLOCK_THRESHOLD: Duration = Duration::const_seconds(5.0 * 60.0);
 
 impl SpeedControls {
     // TODO Could use custom_checkbox here, but not sure it'll make things that much simpler.
-    fn make_panel(ctx: &mut EventCtx, paused: bool, setting: SpeedSetting) -> WrappedComposite {
+    fn make_panel(ctx: &mut EventCtx, paused: bool, multiplier: f64) -> WrappedComposite {
         let mut row = Vec::new();
         row.push(
             ManagedWidget::btn(if paused {
@@ -62,10 +64,10 @@ impl SpeedControls {
         row.push(
             ManagedWidget::row(
                 vec![
-                    (SpeedSetting::Realtime, "real-time speed"),
-                    (SpeedSetting::Fast, "5x speed"),
-                    (SpeedSetting::Faster, "30x speed"),
-                    (SpeedSetting::Fastest, "3600x speed"),
+                    (1.0, "real-time speed"),
+                    (5.0, "5x speed"),
+                    (30.0, "30x speed"),
+                    (3600.0, "3600x speed"),
                 ]
                 .into_iter()
                 .map(|(s, label)| {
@@ -80,7 +82,7 @@ impl SpeedControls {
                             "../data/system/assets/speed/triangle.svg",
                             label,
                             None,
-                            if setting >= s {
+                            if multiplier >= s {
                                 RewriteColor::NoOp
                             } else {
                                 RewriteColor::ChangeAll(Color::WHITE.alpha(0.2))
@@ -92,6 +94,28 @@ impl SpeedControls {
                     )
                     .margin(5)
                 })
+                .chain(std::iter::once(
+                    ManagedWidget::btn(Button::text_no_bg(
+                        Text::from(
+                            Line(format!("{}x", multiplier))
+                                .fg(Color::WHITE)
+                                .size(21)
+                                .roboto(),
+                        ),
+                        Text::from(
+                            Line(format!("{}x", multiplier))
+                                .fg(colors::HOVERING)
+                                .size(21)
+                                .roboto(),
+                        ),
+                        None,
+                        "custom speed",
+                        false,
+                        ctx,
+                    ))
+                    .margin(5)
+                    .centered_vert(),
+                ))
                 .collect(),
             )
             .bg(colors::SECTION_BG)
@@ -101,6 +125,14 @@ impl SpeedControls {
         row.push(
             ManagedWidget::row(
                 vec![
+                    ManagedWidget::btn(Button::text_no_bg(
+                        Text::from(Line("-1m").fg(Color::WHITE).size(21).roboto()),
+                        Text::from(Line("-1m").fg(colors::HOVERING).size(21).roboto()),
+                        None,
+                        "step backwards 1 minute",
+                        false,
+                        ctx,
+                    )),
                     ManagedWidget::btn(Button::text_no_bg(
                         Text::from(Line("+1h").fg(Color::WHITE).size(21).roboto()),
                         Text::from(Line("+1h").fg(colors::HOVERING).size(21).roboto()),
@@ -117,6 +149,14 @@ impl SpeedControls {
                         false,
                         ctx,
                     )),
+                    ManagedWidget::btn(Button::text_no_bg(
+                        Text::from(Line("next").fg(Color::WHITE).size(21).roboto()),
+                        Text::from(Line("next").fg(colors::HOVERING).size(21).roboto()),
+                        hotkey(Key::Dot),
+                        "skip to next event",
+                        false,
+                        ctx,
+                    )),
                     ManagedWidget::btn(Button::rectangle_svg(
                         "../data/system/assets/speed/jump_to_time.svg",
                         "jump to specific time",
@@ -164,6 +204,42 @@ impl SpeedControls {
                 None
             }),
         )
+        .cb(
+            "skip to next event",
+            Box::new(|ctx, app| {
+                // Nothing is scheduled, so time can't move forward meaningfully.
+                let dt = app.primary.sim.time_until_next_event()?;
+                app.primary.sim.normal_step(&app.primary.map, dt);
+                if let Some(ref mut s) = app.secondary {
+                    s.sim.normal_step(&s.map, dt);
+                }
+                app.recalculate_current_selection(ctx);
+                None
+            }),
+        )
+        .cb(
+            "step backwards 1 minute",
+            Box::new(|ctx, app| {
+                let now = app.primary.sim.time();
+                let target = if now - Time::START_OF_DAY > Duration::minutes(1) {
+                    now - Duration::minutes(1)
+                } else {
+                    Time::START_OF_DAY
+                };
+                if app.primary.sim.rewind_to(&app.primary.map, target) {
+                    app.recalculate_current_selection(ctx);
+                    None
+                } else {
+                    Some(Transition::Push(msg(
+                        "Error",
+                        vec![
+                            "Sorry, the simulation hasn't kept a snapshot from that far back.",
+                            "Run with --snapshot_every to keep some.",
+                        ],
+                    )))
+                }
+            }),
+        )
         .cb(
             "step forwards 1 hour",
             Box::new(|ctx, app| {
@@ -171,55 +247,77 @@ impl SpeedControls {
                     ctx,
                     app,
                     app.primary.sim.time() + Duration::hours(1),
-                    false,
+                    Vec::new(),
                 ))))
             }),
         )
     }
 
     pub fn new(ctx: &mut EventCtx) -> SpeedControls {
-        let composite = SpeedControls::make_panel(ctx, false, SpeedSetting::Realtime);
+        let multiplier = SpeedSetting::load(path_speed_setting()).multiplier;
+        let composite = SpeedControls::make_panel(ctx, false, multiplier);
         SpeedControls {
             composite,
             paused: false,
-            setting: SpeedSetting::Realtime,
+            multiplier,
+            custom_speed: None,
         }
     }
 
+    fn set_multiplier(&mut self, ctx: &mut EventCtx, multiplier: f64) {
+        self.multiplier = multiplier;
+        self.composite = SpeedControls::make_panel(ctx, self.paused, self.multiplier);
+        SpeedSetting { multiplier }.save(path_speed_setting());
+    }
+
     pub fn event(
         &mut self,
         ctx: &mut EventCtx,
         app: &mut App,
         maybe_mode: Option<&GameplayMode>,
     ) -> Option<Transition> {
+        if let Some(ref mut wizard) = self.custom_speed {
+            if let Some(multiplier) = wizard.wrap(ctx).input_something(
+                "How many times faster than real-time? (Like 120x)",
+                Some(format!("{}", self.multiplier)),
+                Box::new(parse_speed),
+            ) {
+                self.custom_speed = None;
+                self.set_multiplier(ctx, multiplier);
+            } else if wizard.aborted() {
+                self.custom_speed = None;
+            }
+            return None;
+        }
+
         match self.composite.event(ctx, app) {
             Some(WrappedOutcome::Transition(t)) => {
                 return Some(t);
             }
             Some(WrappedOutcome::Clicked(x)) => match x.as_ref() {
                 "real-time speed" => {
-                    self.setting = SpeedSetting::Realtime;
-                    self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
+                    self.set_multiplier(ctx, 1.0);
                     return None;
                 }
                 "5x speed" => {
-                    self.setting = SpeedSetting::Fast;
-                    self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
+                    self.set_multiplier(ctx, 5.0);
                     return None;
                 }
                 "30x speed" => {
-                    self.setting = SpeedSetting::Faster;
-                    self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
+                    self.set_multiplier(ctx, 30.0);
                     return None;
                 }
                 "3600x speed" => {
-                    self.setting = SpeedSetting::Fastest;
-                    self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
+                    self.set_multiplier(ctx, 3600.0);
+                    return None;
+                }
+                "custom speed" => {
+                    self.custom_speed = Some(Wizard::new());
                     return None;
                 }
                 "play" => {
                     self.paused = false;
-                    self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
+                    self.composite = SpeedControls::make_panel(ctx, self.paused, self.multiplier);
                     return None;
                 }
                 "pause" => {
@@ -253,61 +351,40 @@ impl SpeedControls {
         }
 
         if ctx.input.new_was_pressed(&hotkey(Key::LeftArrow).unwrap()) {
-            match self.setting {
-                SpeedSetting::Realtime => self.pause(ctx),
-                SpeedSetting::Fast => {
-                    self.setting = SpeedSetting::Realtime;
-                    self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
-                }
-                SpeedSetting::Faster => {
-                    self.setting = SpeedSetting::Fast;
-                    self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
-                }
-                SpeedSetting::Fastest => {
-                    self.setting = SpeedSetting::Faster;
-                    self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
-                }
+            if self.multiplier <= 1.0 {
+                self.pause(ctx);
+            } else {
+                self.set_multiplier(ctx, (self.multiplier / SPEED_STEP).max(1.0));
             }
         }
         if ctx.input.new_was_pressed(&hotkey(Key::RightArrow).unwrap()) {
-            match self.setting {
-                SpeedSetting::Realtime => {
-                    if self.paused {
-                        self.paused = false;
-                        self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
-                    } else {
-                        self.setting = SpeedSetting::Fast;
-                        self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
-                    }
-                }
-                SpeedSetting::Fast => {
-                    self.setting = SpeedSetting::Faster;
-                    self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
-                }
-                SpeedSetting::Faster => {
-                    self.setting = SpeedSetting::Fastest;
-                    self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
-                }
-                SpeedSetting::Fastest => {}
+            if self.paused {
+                self.paused = false;
+                self.composite = SpeedControls::make_panel(ctx, self.paused, self.multiplier);
+            } else if self.multiplier < MAX_STEPPED_SPEED {
+                self.set_multiplier(ctx, (self.multiplier * SPEED_STEP).min(MAX_STEPPED_SPEED));
             }
         }
 
         if !self.paused {
             if let Some(real_dt) = ctx.input.nonblocking_is_update_event() {
                 ctx.input.use_update_event();
-                let multiplier = match self.setting {
-                    SpeedSetting::Realtime => 1.0,
-                    SpeedSetting::Fast => 5.0,
-                    SpeedSetting::Faster => 30.0,
-                    SpeedSetting::Fastest => 3600.0,
-                };
-                let dt = multiplier * real_dt;
+                let dt = self.multiplier * real_dt;
                 // TODO This should match the update frequency in ezgapp. Plumb along the deadline
                 // or frequency to here.
                 app.primary
                     .sim
                     .time_limited_step(&app.primary.map, dt, Duration::seconds(0.033));
                 app.recalculate_current_selection(ctx);
+
+                if let Some(trip) = app.primary.sim.watched_trip_done() {
+                    app.primary.sim.watch_trip(None);
+                    self.pause(ctx);
+                    return Some(Transition::Push(msg(
+                        "Trip done",
+                        vec![format!("{} is over, so the simulation is paused", trip)],
+                    )));
+                }
             }
         }
 
@@ -316,20 +393,22 @@ impl SpeedControls {
 
     pub fn draw(&self, g: &mut GfxCtx) {
         self.composite.draw(g);
+        if let Some(ref wizard) = self.custom_speed {
+            wizard.draw(g);
+        }
     }
 
     pub fn pause(&mut self, ctx: &mut EventCtx) {
         if !self.paused {
             self.paused = true;
-            self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
+            self.composite = SpeedControls::make_panel(ctx, self.paused, self.multiplier);
         }
     }
 
     pub fn resume_realtime(&mut self, ctx: &mut EventCtx) {
-        if self.paused || self.setting != SpeedSetting::Realtime {
+        if self.paused || self.multiplier != 1.0 {
             self.paused = false;
-            self.setting = SpeedSetting::Realtime;
-            self.composite = SpeedControls::make_panel(ctx, self.paused, self.setting);
+            self.set_multiplier(ctx, 1.0);
         }
     }
 
@@ -338,6 +417,45 @@ impl SpeedControls {
     }
 }
 
+// The last speed the player picked, remembered across sessions. Whether the sim is paused isn't
+// remembered; starting paused would be confusing.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct SpeedSetting {
+    multiplier: f64,
+}
+
+impl SpeedSetting {
+    // Falls back to real-time if the file is missing or has something unusable.
+    fn load(path: String) -> SpeedSetting {
+        abstutil::maybe_read_json::<SpeedSetting>(path, &mut Timer::throwaway())
+            .ok()
+            .filter(|s| s.multiplier >= MIN_SPEED)
+            .unwrap_or(SpeedSetting { multiplier: 1.0 })
+    }
+
+    fn save(&self, path: String) {
+        abstutil::write_json(path, self);
+    }
+}
+
+// Accepts things like "120" or "12.5x". Anything too slow is clamped to a minimum speed.
+fn parse_speed(line: String) -> Option<f64> {
+    let x = line
+        .trim()
+        .trim_end_matches('x')
+        .trim()
+        .parse::<f64>()
+        .ok()?;
+    if !x.is_finite() {
+        return None;
+    }
+    Some(x.max(MIN_SPEED))
+}
+
+fn path_speed_setting() -> String {
+    format!("../data/player/speed_setting.json")
+}
+
 // TODO Text entry would be great
 struct JumpToTime {
     composite: Composite,
@@ -378,29 +496,25 @@ impl JumpToTime {
                         .margin(10),
                     WrappedComposite::text_bg_button(ctx, "Go!", hotkey(Key::Enter))
                         .centered_horiz(),
+                    ManagedWidget::row(vec![
+                        WrappedComposite::text_button(ctx, "until a trip finishes", None),
+                        WrappedComposite::text_button(ctx, "until the next traffic jam", None),
+                        WrappedComposite::text_button(
+                            ctx,
+                            "until an agent reaches somewhere",
+                            None,
+                        ),
+                    ])
+                    .evenly_spaced(),
                     ManagedWidget::draw_text(ctx, Text::from(Line("Active agents").roboto_bold())),
                     // TODO Sync the slider / plot.
-                    Plot::new_usize(
+                    Plot::new_time_vs_usize(
                         ctx,
-                        vec![if app.has_prebaked().is_some() {
-                            Series {
-                                label: "Baseline".to_string(),
-                                color: Color::BLUE,
-                                pts: app.prebaked().active_agents(Time::END_OF_DAY),
-                            }
-                        } else {
-                            Series {
-                                label: "Current simulation".to_string(),
-                                color: Color::RED,
-                                pts: app
-                                    .primary
-                                    .sim
-                                    .get_analytics()
-                                    .active_agents(app.primary.sim.time()),
-                            }
-                        }],
+                        "Active agents",
+                        active_agents_series(app),
                         PlotOptions {
                             max_x: Some(Time::END_OF_DAY),
+                            log_y: false,
                         },
                     ),
                 ])
@@ -412,6 +526,28 @@ impl JumpToTime {
     }
 }
 
+// With a baseline, show where the current sim is relative to it. Otherwise, break down the
+// current sim by mode.
+fn active_agents_series(app: &App) -> Vec<Series<Time, usize>> {
+    let baseline = app.has_prebaked().map(|_| app.prebaked());
+    app.primary
+        .sim
+        .get_analytics()
+        .active_agents_series(app.primary.sim.time(), baseline)
+        .into_iter()
+        .map(|(label, mode, pts)| Series {
+            color: match mode {
+                Some(m) => app.cs.for_mode(m),
+                None if label == "Baseline" => Color::BLUE.alpha(0.5),
+                None => Color::RED,
+            },
+            label,
+            pts,
+            actions: Vec::new(),
+        })
+        .collect()
+}
+
 impl State for JumpToTime {
     fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
         match self.composite.event(ctx) {
@@ -420,13 +556,23 @@ impl State for JumpToTime {
                     return Transition::Pop;
                 }
                 "Go!" => {
-                    let traffic_jams = self.composite.is_checked("Stop when there's a traffic jam");
+                    let conditions = if self.composite.is_checked("Stop when there's a traffic jam")
+                    {
+                        vec![StopCondition::Gridlock(GRIDLOCK_THRESHOLD)]
+                    } else {
+                        Vec::new()
+                    };
                     if self.target < app.primary.sim.time() {
+                        // Short rewinds don't need to start over from midnight
+                        if app.primary.sim.rewind_to(&app.primary.map, self.target) {
+                            app.recalculate_current_selection(ctx);
+                            return Transition::Pop;
+                        }
                         if let Some(mode) = self.maybe_mode.take() {
                             app.primary.clear_sim();
                             return Transition::ReplaceThenPush(
                                 Box::new(SandboxMode::new(ctx, app, mode)),
-                                Box::new(TimeWarpScreen::new(ctx, app, self.target, traffic_jams)),
+                                Box::new(TimeWarpScreen::new(ctx, app, self.target, conditions)),
                             );
                         } else {
                             return Transition::Replace(msg(
@@ -439,7 +585,27 @@ impl State for JumpToTime {
                         ctx,
                         app,
                         self.target,
-                        traffic_jams,
+                        conditions,
+                    )));
+                }
+                "until a trip finishes" => {
+                    return Transition::Replace(jump_to_trip_finish());
+                }
+                "until an agent reaches somewhere" => {
+                    return Transition::Replace(jump_to_agent_location());
+                }
+                "until the next traffic jam" => {
+                    if app.primary.sim.is_done() {
+                        return Transition::Replace(msg(
+                            "Error",
+                            vec!["Every trip is done, so there won't be any more traffic jams."],
+                        ));
+                    }
+                    return Transition::Replace(Box::new(TimeWarpScreen::new(
+                        ctx,
+                        app,
+                        Time::END_OF_DAY,
+                        vec![StopCondition::Gridlock(GRIDLOCK_THRESHOLD)],
                     )));
                 }
                 _ => unreachable!(),
@@ -478,26 +644,116 @@ impl State for JumpToTime {
     }
 }
 
+fn jump_to_trip_finish() -> Box<dyn State> {
+    WizardState::new(Box::new(|wiz, ctx, app| {
+        let id = TripID(
+            wiz.wrap(ctx)
+                .input_usize("Jump until which trip finishes?")?,
+        );
+        // The baseline run is the best guess for when the trip finishes.
+        let scheduled_finish = if app.has_prebaked().is_some() {
+            app.prebaked()
+                .finished_trips
+                .iter()
+                .find(|(_, t, _, _)| *t == id)
+                .map(|(t, _, _, _)| *t)
+        } else {
+            None
+        };
+        match app
+            .primary
+            .sim
+            .time_warp_target_for_trip(id, scheduled_finish)
+        {
+            Ok(target) => Some(Transition::Replace(Box::new(TimeWarpScreen::new(
+                ctx,
+                app,
+                target,
+                vec![StopCondition::TripDone(id)],
+            )))),
+            Err(err) => Some(Transition::Replace(msg("Error", vec![err]))),
+        }
+    }))
+}
+
+fn jump_to_agent_location() -> Box<dyn State> {
+    WizardState::new(Box::new(|wiz, ctx, app| {
+        let mut wizard = wiz.wrap(ctx);
+        let agent_line = wizard.input_string("Stop when which agent (like c12 or p3)...")?;
+        let location_line =
+            wizard.input_string("... reaches which road or intersection (like r5 or i7)?")?;
+        let agent = match parse_agent(agent_line.clone(), &app.primary.sim) {
+            Some(a) if app.primary.sim.does_agent_exist(a) => a,
+            _ => {
+                return Some(Transition::Replace(msg(
+                    "Error",
+                    vec![format!("{} isn't on the map right now", agent_line)],
+                )));
+            }
+        };
+        let cond = match parse_agent_location(location_line.clone(), agent, &app.primary.map) {
+            Some(c) => c,
+            None => {
+                return Some(Transition::Replace(msg(
+                    "Error",
+                    vec![format!("{} isn't a road or intersection", location_line)],
+                )));
+            }
+        };
+        Some(Transition::Replace(Box::new(TimeWarpScreen::new(
+            ctx,
+            app,
+            Time::END_OF_DAY,
+            vec![cond],
+        ))))
+    }))
+}
+
+fn parse_agent(line: String, sim: &Sim) -> Option<AgentID> {
+    let line = line.trim();
+    let idx = line.get(1..)?.parse::<usize>().ok()?;
+    match line.chars().next()? {
+        'c' => sim.lookup_car_id(idx).map(AgentID::Car),
+        'p' => Some(AgentID::Pedestrian(PedestrianID(idx))),
+        _ => None,
+    }
+}
+
+fn parse_agent_location(line: String, agent: AgentID, map: &Map) -> Option<StopCondition> {
+    let line = line.trim();
+    let idx = line.get(1..)?.parse::<usize>().ok()?;
+    match line.chars().next()? {
+        'r' if idx < map.all_roads().len() => Some(StopCondition::AgentOnRoad(agent, RoadID(idx))),
+        'i' if idx < map.all_intersections().len() => Some(StopCondition::AgentInIntersection(
+            agent,
+            IntersectionID(idx),
+        )),
+        _ => None,
+    }
+}
+
 // Display a nicer screen for jumping forwards in time, allowing cancellation.
 pub struct TimeWarpScreen {
     target: Time,
     started: Instant,
-    traffic_jams: bool,
+    // Any of these stop the warp early
+    conditions: Vec<StopCondition>,
     composite: Composite,
 }
 
 impl TimeWarpScreen {
-    fn new(ctx: &mut EventCtx, app: &mut App, target: Time, traffic_jams: bool) -> TimeWarpScreen {
-        if traffic_jams {
-            app.primary
-                .sim
-                .set_gridlock_checker(Some(Duration::minutes(5)));
-        }
+    fn new(
+        ctx: &mut EventCtx,
+        app: &mut App,
+        target: Time,
+        conditions: Vec<StopCondition>,
+    ) -> TimeWarpScreen {
+        app.primary.sim.set_stop_conditions(conditions.clone());
 
         TimeWarpScreen {
             target,
             started: Instant::now(),
-            traffic_jams,
+            conditions,
             composite: Composite::new(
                 ManagedWidget::col(vec![
                     ManagedWidget::draw_text(ctx, Text::new()).named("text"),
@@ -516,20 +772,40 @@ impl State for TimeWarpScreen {
     fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
         if ctx.input.nonblocking_is_update_event().is_some() {
             ctx.input.use_update_event();
-            if let Some(problems) = app.primary.sim.time_limited_step(
+            match app.primary.sim.time_limited_step(
                 &app.primary.map,
                 self.target - app.primary.sim.time(),
                 Duration::seconds(0.033),
             ) {
-                let id = ID::Intersection(problems[0].0);
-                app.overlay = Overlays::traffic_jams(ctx, app);
-                return Transition::Replace(Warping::new(
-                    ctx,
-                    id.canonical_point(&app.primary).unwrap(),
-                    Some(10.0),
-                    Some(id),
-                    &mut app.primary,
-                ));
+                Some(StopReason::Gridlock(problems)) => {
+                    let id = ID::Intersection(problems[0].0);
+                    app.overlay = Overlays::traffic_jams(ctx, app);
+                    return Transition::Replace(Warping::new(
+                        ctx,
+                        id.canonical_point(&app.primary).unwrap(),
+                        Some(10.0),
+                        Some(id),
+                        &mut app.primary,
+                    ));
+                }
+                Some(StopReason::Met(StopCondition::AgentOnRoad(agent, _)))
+                | Some(StopReason::Met(StopCondition::AgentInIntersection(agent, _))) => {
+                    let id = ID::from_agent(agent);
+                    if let Some(pt) = id.canonical_point(&app.primary) {
+                        return Transition::Replace(Warping::new(
+                            ctx,
+                            pt,
+                            Some(10.0),
+                            Some(id),
+                            &mut app.primary,
+                        ));
+                    }
+                    return Transition::Pop;
+                }
+                Some(StopReason::Met(_)) => {
+                    return Transition::Pop;
+                }
+                None => {}
             }
             // TODO secondary for a/b test mode
 
@@ -539,6 +815,9 @@ impl State for TimeWarpScreen {
                 "Simulating until it's {}",
                 self.target.ampm_tostring()
             )));
+            for cond in &self.conditions {
+                txt.add(Line(format!("... or until {}", cond)));
+            }
             txt.add(Line(format!(
                 "It's currently {}",
                 app.primary.sim.time().ampm_tostring()
@@ -580,9 +859,7 @@ impl State for TimeWarpScreen {
     }
 
     fn on_destroy(&mut self, _: &mut EventCtx, app: &mut App) {
-        if self.traffic_jams {
-            app.primary.sim.set_gridlock_checker(None);
-        }
+        app.primary.sim.set_stop_conditions(Vec::new());
     }
 }
 
@@ -619,7 +896,7 @@ impl TimePanel {
                                 Polygon::rectangle(percent * width, height),
                             );
                         }
-                        ManagedWidget::draw_batch(ctx, batch)
+                        ManagedWidget::draw_batch(ctx, batch).named("time bar")
                     },
                     ManagedWidget::row(vec![
                         ManagedWidget::draw_text(ctx, Text::from(Line("00:00").size(12).roboto())),
@@ -639,14 +916,91 @@ impl TimePanel {
         }
     }
 
-    pub fn event(&mut self, ctx: &mut EventCtx, app: &mut App) {
+    pub fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Option<Transition> {
         if self.time != app.primary.sim.time() {
             *self = TimePanel::new(ctx, app);
         }
         self.composite.event(ctx);
+
+        // Clicking the progress bar jumps to that time
+        let bar = self.composite.rect_of("time bar").clone();
+        let cursor = ctx.canvas.get_cursor_in_screen_space()?;
+        if !bar.contains(cursor) || !ctx.normal_left_click() {
+            return None;
+        }
+        // The bar covers the whole day
+        let percent = ((cursor.x - bar.x1) / bar.width()).max(0.0).min(1.0);
+        let target = Time::END_OF_DAY.percent_of(percent);
+        let now = app.primary.sim.time();
+        if target > now {
+            Some(Transition::Push(Box::new(TimeWarpScreen::new(
+                ctx,
+                app,
+                target,
+                Vec::new(),
+            ))))
+        } else if target < now {
+            if app.primary.sim.rewind_to(&app.primary.map, target) {
+                app.recalculate_current_selection(ctx);
+                None
+            } else {
+                Some(Transition::Push(msg(
+                    "Error",
+                    vec!["Sorry, the simulation hasn't kept a snapshot from that far back."],
+                )))
+            }
+        } else {
+            None
+        }
     }
 
     pub fn draw(&self, g: &mut GfxCtx) {
         self.composite.draw(g);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_setting_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("abst_speed_setting_{}.json", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+        let _ = std::fs::remove_file(&path);
+
+        // Nothing saved yet, so start at real-time
+        assert_eq!(
+            SpeedSetting::load(path.clone()),
+            SpeedSetting { multiplier: 1.0 }
+        );
+
+        SpeedSetting { multiplier: 120.0 }.save(path.clone());
+        assert_eq!(
+            SpeedSetting::load(path.clone()),
+            SpeedSetting { multiplier: 120.0 }
+        );
+
+        // Something too slow on disk also falls back
+        SpeedSetting { multiplier: 0.0 }.save(path.clone());
+        assert_eq!(
+            SpeedSetting::load(path.clone()),
+            SpeedSetting { multiplier: 1.0 }
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parse_speed_clamps() {
+        assert_eq!(parse_speed("12.5x".to_string()), Some(12.5));
+        assert_eq!(parse_speed(" 120 ".to_string()), Some(120.0));
+        assert_eq!(parse_speed("0".to_string()), Some(MIN_SPEED));
+        assert_eq!(parse_speed("-3x".to_string()), Some(MIN_SPEED));
+        assert_eq!(parse_speed("fast".to_string()), None);
+        assert_eq!(parse_speed("infx".to_string()), None);
+    }
+}
@@ -74,6 +74,19 @@ where
     }
 }
 
+// Of the keyed points within max_dist_away of the query, returns the key of the closest one.
+pub fn nearest_within<K>(
+    pts: impl IntoIterator<Item = (K, Pt2D)>,
+    query_pt: Pt2D,
+    max_dist_away: Distance,
+) -> Option<K> {
+    pts.into_iter()
+        .map(|(key, pt)| (key, pt.dist_to(query_pt)))
+        .filter(|(_, dist)| *dist <= max_dist_away)
+        .min_by_key(|(_, dist)| *dist)
+        .map(|(key, _)| key)
+}
+
 fn pts_to_line_string(raw_pts: &Vec<Pt2D>) -> geo::LineString<f64> {
     let pts: Vec<geo::Point<f64>> = raw_pts
         .iter()
@@ -19,7 +19,7 @@ pub use crate::bounds::{Bounds, GPSBounds};
 pub use crate::circle::Circle;
 pub use crate::distance::Distance;
 pub use crate::duration::Duration;
-pub use crate::find_closest::FindClosest;
+pub use crate::find_closest::{nearest_within, FindClosest};
 pub use crate::gps::LonLat;
 pub use crate::line::{InfiniteLine, Line};
 pub use crate::polygon::{Polygon, Triangle};
@@ -27,7 +27,7 @@ pub use crate::polyline::PolyLine;
 pub use crate::pt::{HashablePt2D, Pt2D};
 pub use crate::ring::Ring;
 pub use crate::speed::Speed;
-pub use crate::stats::{DurationHistogram, PercentageHistogram, Statistic};
+pub use crate::stats::{exact_percentile, DurationHistogram, PercentageHistogram, Statistic};
 pub use crate::time::Time;
 
 // About 0.4 inches... which is quite tiny on the scale of things. :)
@@ -122,6 +122,21 @@ impl DurationHistogram {
         self.count
     }
 
+    pub fn merge(&mut self, other: &DurationHistogram) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            self.min = other.min;
+            self.max = other.max;
+        } else {
+            self.min = self.min.min(other.min);
+            self.max = self.max.max(other.max);
+        }
+        self.count += other.count;
+        self.histogram.merge(&other.histogram);
+    }
+
     // Could implement PartialEq, but be a bit more clear how approximate this is
     pub fn seems_eq(&self, other: &DurationHistogram) -> bool {
         self.describe() == other.describe()
@@ -219,3 +234,13 @@ impl PercentageHistogram {
 fn print_percentage(p: f64) -> String {
     format!("{:.1}%", p * 100.0)
 }
+
+// The histogram crate only handles unsigned values, so this finds the nearest-rank percentile of
+// a list of possibly negative durations directly. None if empty.
+pub fn exact_percentile(sorted: &[Duration], p: f64) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((p / 100.0) * (sorted.len() as f64)).ceil() as usize;
+    Some(sorted[rank.max(1).min(sorted.len()) - 1])
+}
@@ -9,9 +9,31 @@ use crate::{
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Error, Timer};
 use geom::{Bounds, Distance, GPSBounds, PolyLine, Polygon, Pt2D};
+use regex::Regex;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 
+// Something whose OSM metadata matched a search
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OsmMatch {
+    Road(RoadID),
+    Building(BuildingID),
+    Area(AreaID),
+}
+
+// Turns a search query into something to pass to search_osm. Things like
+// "highway = (primary|secondary)" are handy. If the query isn't a valid regex, fall back to a
+// substring search. Also returns true if the query was a regex.
+pub fn osm_matcher(query: &str) -> (Box<dyn Fn(&str) -> bool>, bool) {
+    match Regex::new(query) {
+        Ok(re) => (Box::new(move |x| re.is_match(x)), true),
+        Err(_) => {
+            let query = query.to_string();
+            (Box::new(move |x| x.contains(&query)), false)
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Map {
     roads: Vec<Road>,
@@ -237,6 +259,32 @@ impl Map {
         &self.areas
     }
 
+    // matches is called on every OSM tag, formatted like "key = value", and on building amenity
+    // names and types.
+    pub fn search_osm(&self, matches: &dyn Fn(&str) -> bool) -> Vec<OsmMatch> {
+        let tag_matches = |tags: &BTreeMap<String, String>| {
+            tags.iter().any(|(k, v)| matches(&format!("{} = {}", k, v)))
+        };
+        let mut results = Vec::new();
+        for r in &self.roads {
+            if tag_matches(&r.osm_tags) {
+                results.push(OsmMatch::Road(r.id));
+            }
+        }
+        for b in &self.buildings {
+            if tag_matches(&b.osm_tags) || b.amenities.iter().any(|(n, a)| matches(n) || matches(a))
+            {
+                results.push(OsmMatch::Building(b.id));
+            }
+        }
+        for a in &self.areas {
+            if tag_matches(&a.osm_tags) {
+                results.push(OsmMatch::Area(a.id));
+            }
+        }
+        results
+    }
+
     pub fn maybe_get_r(&self, id: RoadID) -> Option<&Road> {
         self.roads.get(id.0)
     }
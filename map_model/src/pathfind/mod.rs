@@ -172,6 +172,25 @@ impl Path {
         self.steps.push_back(step);
     }
 
+    // Keeps the first keep_steps, then continues along the other path, which has to start on the
+    // last kept step.
+    pub fn splice(&mut self, keep_steps: usize, other: Path, map: &Map) {
+        assert!(keep_steps > 0 && keep_steps <= self.steps.len());
+        while self.steps.len() > keep_steps {
+            let step = self.steps.pop_back().unwrap();
+            self.total_length -= step.as_traversable().length(map);
+        }
+        let mut rest = other.steps;
+        assert_eq!(
+            self.last_step().as_traversable(),
+            rest.pop_front().unwrap().as_traversable()
+        );
+        for step in rest {
+            self.add(step, map);
+        }
+        self.end_dist = other.end_dist;
+    }
+
     // Trusting the caller to do this in valid ways.
     pub fn modify_step(&mut self, idx: usize, step: PathStep, map: &Map) {
         assert!(idx != 0);
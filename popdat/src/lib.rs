@@ -3,7 +3,9 @@ mod trips;
 
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
-pub use trips::{clip_trips, trips_to_scenario, Trip, TripEndpt};
+pub use trips::{
+    clip_popdat, clip_trips, clipped_trips_to_scenario, trips_to_scenario, Trip, TripEndpt,
+};
 
 #[derive(Serialize, Deserialize)]
 pub struct PopDat {
@@ -2,10 +2,10 @@ use crate::psrc::{Endpoint, Mode, Parcel, Purpose};
 use crate::PopDat;
 use abstutil::{prettyprint_usize, MultiMap, Timer};
 use geom::{Distance, Duration, LonLat, Polygon, Pt2D, Time};
-use map_model::{BuildingID, IntersectionID, Map, PathConstraints, Position};
+use map_model::{BuildingID, IntersectionID, Map, PathConstraints};
 use sim::{
-    DrivingGoal, IndividTrip, Person, PersonID, Population, Scenario, SidewalkSpot, SpawnTrip,
-    TripSpec,
+    CarAppearingStart, DrivingGoal, IndividTrip, Person, PersonID, Population, Scenario,
+    SidewalkSpot, SpawnTrip,
 };
 use std::collections::{BTreeMap, HashMap};
 
@@ -16,7 +16,7 @@ pub struct Trip {
     pub depart_at: Time,
     pub purpose: (Purpose, Purpose),
     pub mode: Mode,
-    // These are an upper bound when TripEndpt::Border is involved.
+    // When TripEndpt::Border is involved, these only cover the estimated portion inside the map.
     pub trip_time: Duration,
     pub trip_dist: Distance,
     // (household, person within household)
@@ -38,28 +38,34 @@ impl Trip {
         self.depart_at + self.trip_time
     }
 
+    // Fix depart_at, trip_time, and trip_dist for border cases. Pathfinding for every trip is too
+    // slow, so assume constant speed along a straight line between the original endpoints, and
+    // keep only the portion inside the map.
+    fn clip_to_map(&mut self, map: &Map) {
+        let orig_from = self.from.orig_pt(map);
+        let orig_to = self.to.orig_pt(map);
+        let full_dist = orig_from.dist_to(orig_to);
+        if full_dist == Distance::ZERO {
+            return;
+        }
+        let in_from = self.from.in_map_pt(map);
+        let in_to = self.to.in_map_pt(map);
+
+        let before_ratio = (orig_from.dist_to(in_from) / full_dist).min(1.0);
+        let in_map_ratio = (in_from.dist_to(in_to) / full_dist).min(1.0);
+        self.depart_at = self.depart_at + before_ratio * self.trip_time;
+        self.trip_time = in_map_ratio * self.trip_time;
+        self.trip_dist = in_map_ratio * self.trip_dist;
+    }
+
     pub fn to_spawn_trip(&self, map: &Map) -> Option<SpawnTrip> {
         match self.mode {
             Mode::Drive => match self.from {
-                TripEndpt::Border(i, _) => {
-                    if let Some(start) = TripSpec::spawn_car_at(
-                        Position::new(
-                            map.get_i(i).get_outgoing_lanes(map, PathConstraints::Car)[0],
-                            Distance::ZERO,
-                        ),
-                        map,
-                    ) {
-                        Some(SpawnTrip::CarAppearing {
-                            start,
-                            goal: self.to.driving_goal(PathConstraints::Car, map),
-                            is_bike: false,
-                        })
-                    } else {
-                        // TODO need to be able to emit warnings from parallelize
-                        //timer.warn(format!("No room for car to appear at {:?}", self.from));
-                        None
-                    }
-                }
+                TripEndpt::Border(i, _) => Some(SpawnTrip::CarAppearing {
+                    start: CarAppearingStart::Border(i),
+                    goal: self.to.driving_goal(PathConstraints::Car, map),
+                    is_bike: false,
+                }),
                 TripEndpt::Building(b) => Some(SpawnTrip::MaybeUsingParkedCar(
                     b,
                     self.to.driving_goal(PathConstraints::Car, map),
@@ -70,24 +76,11 @@ impl Trip {
                     SidewalkSpot::building(b, map),
                     self.to.driving_goal(PathConstraints::Bike, map),
                 )),
-                TripEndpt::Border(i, _) => {
-                    if let Some(start) = TripSpec::spawn_car_at(
-                        Position::new(
-                            map.get_i(i).get_outgoing_lanes(map, PathConstraints::Bike)[0],
-                            Distance::ZERO,
-                        ),
-                        map,
-                    ) {
-                        Some(SpawnTrip::CarAppearing {
-                            start,
-                            goal: self.to.driving_goal(PathConstraints::Bike, map),
-                            is_bike: true,
-                        })
-                    } else {
-                        //timer.warn(format!("No room for bike to appear at {:?}", self.from));
-                        None
-                    }
-                }
+                TripEndpt::Border(i, _) => Some(SpawnTrip::CarAppearing {
+                    start: CarAppearingStart::Border(i),
+                    goal: self.to.driving_goal(PathConstraints::Bike, map),
+                    is_bike: true,
+                }),
             },
             Mode::Walk => Some(SpawnTrip::JustWalking(
                 self.from.start_sidewalk_spot(map),
@@ -131,6 +124,21 @@ impl TripEndpt {
             })
     }
 
+    // Where the trip really started or ended, possibly far outside the map
+    fn orig_pt(&self, map: &Map) -> Pt2D {
+        match self {
+            TripEndpt::Building(b) => map.get_b(*b).label_center,
+            TripEndpt::Border(_, pt) => *pt,
+        }
+    }
+
+    fn in_map_pt(&self, map: &Map) -> Pt2D {
+        match self {
+            TripEndpt::Building(b) => map.get_b(*b).label_center,
+            TripEndpt::Border(i, _) => map.get_i(*i).polygon.center(),
+        }
+    }
+
     fn start_sidewalk_spot(&self, map: &Map) -> SidewalkSpot {
         match self {
             TripEndpt::Building(b) => SidewalkSpot::building(*b, map),
@@ -165,7 +173,15 @@ impl TripEndpt {
 
 pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingID, Parcel>) {
     let popdat: PopDat = abstutil::read_binary(abstutil::path_popdat(), timer);
+    clip_popdat(popdat, map, timer)
+}
 
+// Separate from clip_trips so synthetic PopDat can be clipped without reading the real input.
+pub fn clip_popdat(
+    popdat: PopDat,
+    map: &Map,
+    timer: &mut Timer,
+) -> (Vec<Trip>, HashMap<BuildingID, Parcel>) {
     let mut osm_id_to_bldg = HashMap::new();
     for b in map.all_buildings() {
         osm_id_to_bldg.insert(b.osm_way_id, b.id);
@@ -238,7 +254,7 @@ pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingI
             },
         )?;
 
-        let trip = Trip {
+        let mut trip = Trip {
             from,
             to,
             depart_at: trip.depart_at,
@@ -251,26 +267,38 @@ pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingI
         };
 
         match (&trip.from, &trip.to) {
-            (TripEndpt::Border(_, _), TripEndpt::Border(_, _)) => {
-                // TODO Detect and handle pass-through trips
-                return None;
+            // Pass-through trips enter at the border nearest their origin and leave at the one
+            // nearest their destination. If those're the same, the trip never really uses the
+            // map.
+            (TripEndpt::Border(i1, _), TripEndpt::Border(i2, _)) => {
+                if i1 == i2 {
+                    return None;
+                }
+                trip.clip_to_map(map);
+            }
+            (TripEndpt::Border(_, _), TripEndpt::Building(_))
+            | (TripEndpt::Building(_), TripEndpt::Border(_, _)) => {
+                trip.clip_to_map(map);
             }
-            // Fix depart_at, trip_time, and trip_dist for border cases. Assume constant speed
-            // through the trip.
-            // TODO Disabled because slow and nonsensical distance ratios. :(
-            (TripEndpt::Border(_, _), TripEndpt::Building(_)) => {}
-            (TripEndpt::Building(_), TripEndpt::Border(_, _)) => {}
             (TripEndpt::Building(_), TripEndpt::Building(_)) => {}
         }
 
         Some(trip)
     });
     let trips: Vec<Trip> = maybe_results.into_iter().flatten().collect();
+    let pass_through = trips
+        .iter()
+        .filter(|t| match (&t.from, &t.to) {
+            (TripEndpt::Border(_, _), TripEndpt::Border(_, _)) => true,
+            _ => false,
+        })
+        .count();
 
     timer.note(format!(
-        "{} trips clipped down to just {}",
+        "{} trips clipped down to just {} ({} pass-through)",
         prettyprint_usize(total_trips),
-        prettyprint_usize(trips.len())
+        prettyprint_usize(trips.len()),
+        prettyprint_usize(pass_through)
     ));
 
     let mut bldgs = HashMap::new();
@@ -284,24 +312,35 @@ pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingI
 
 pub fn trips_to_scenario(map: &Map, timer: &mut Timer) -> Scenario {
     let (trips, _) = clip_trips(map, timer);
+    clipped_trips_to_scenario(trips, map, timer)
+}
+
+pub fn clipped_trips_to_scenario(trips: Vec<Trip>, map: &Map, timer: &mut Timer) -> Scenario {
     let orig_trips = trips.len();
 
     let individ_parked_cars = count_cars(&trips, map);
 
     let mut individ_trips: Vec<(Time, Option<PersonID>, SpawnTrip)> = Vec::new();
+    // Parallel to individ_trips
+    let mut origins: Vec<Option<BuildingID>> = Vec::new();
     // person -> (trip seq, index into individ_trips)
     let mut trips_per_person: MultiMap<(usize, usize), ((usize, bool, usize), usize)> =
         MultiMap::new();
-    for (trip, depart, person, seq) in timer
+    for (trip, depart, person, seq, origin) in timer
         .parallelize("turn PSRC trips into SpawnTrips", trips, |trip| {
+            let origin = match trip.from {
+                TripEndpt::Building(b) => Some(b),
+                TripEndpt::Border(_, _) => None,
+            };
             trip.to_spawn_trip(map)
-                .map(|spawn| (spawn, trip.depart_at, trip.person, trip.seq))
+                .map(|spawn| (spawn, trip.depart_at, trip.person, trip.seq, origin))
         })
         .into_iter()
         .flatten()
     {
         let idx = individ_trips.len();
         individ_trips.push((depart, None, trip));
+        origins.push(origin);
         trips_per_person.insert(person, (seq, idx));
     }
     timer.note(format!(
@@ -320,6 +359,12 @@ pub fn trips_to_scenario(map: &Map, timer: &mut Timer) -> Scenario {
     for (person, seq_trips) in trips_per_person.consume() {
         let id = PersonID(population.people.len());
         person_ids.insert(person, id);
+        // People are assumed to start the day at home. If their first trip begins outside the
+        // map, we don't know where that is.
+        let home = seq_trips
+            .iter()
+            .min_by_key(|(_, idx)| individ_trips[*idx].0)
+            .and_then(|(_, idx)| origins[*idx]);
         let mut trips = Vec::new();
         for (_, idx) in seq_trips {
             // TODO Track when there are gaps in the sequence, to explain the person warping.
@@ -327,12 +372,7 @@ pub fn trips_to_scenario(map: &Map, timer: &mut Timer) -> Scenario {
             assert!(individ_trips[idx].1.is_none());
             individ_trips[idx].1 = Some(id);
         }
-        population.people.push(Person {
-            id,
-            // TODO Do we have to scrape a new input file for this? :(
-            home: None,
-            trips,
-        });
+        population.people.push(Person { id, home, trips });
     }
     for (depart, person, trip) in individ_trips {
         population.individ_trips.push(IndividTrip {
@@ -346,6 +386,7 @@ pub fn trips_to_scenario(map: &Map, timer: &mut Timer) -> Scenario {
         scenario_name: "weekday".to_string(),
         map_name: map.get_name().to_string(),
         only_seed_buses: None,
+        only_seed_buses_matching: None,
         seed_parked_cars: Vec::new(),
         spawn_over_time: Vec::new(),
         border_spawn_over_time: Vec::new(),
@@ -1,12 +1,16 @@
-use crate::{CarID, Event, TripID, TripMode, TripPhaseType};
-use abstutil::Counter;
+use crate::{CarID, Event, PersonID, PersonState, TripID, TripMode, TripPhaseType};
+use abstutil::{Counter, Timer};
 use derivative::Derivative;
-use geom::{Distance, Duration, DurationHistogram, PercentageHistogram, Time};
+use geom::{
+    Distance, Duration, DurationHistogram, PercentageHistogram, Pt2D, Speed, Statistic, Time,
+};
 use map_model::{
-    BusRouteID, BusStopID, IntersectionID, Map, Path, PathRequest, RoadID, Traversable, TurnGroupID,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Map, Path, PathRequest, PathStep,
+    RoadID, Traversable, TurnGroupID,
 };
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::Mutex;
 
 #[derive(Clone, Serialize, Deserialize, Derivative)]
 pub struct Analytics {
@@ -15,14 +19,55 @@ pub struct Analytics {
     pub(crate) test_expectations: VecDeque<Event>,
     pub bus_arrivals: Vec<(Time, CarID, BusRouteID, BusStopID)>,
     pub bus_passengers_waiting: Vec<(Time, BusStopID, BusRouteID)>,
+    pub bus_boardings: Vec<(Time, BusRouteID, BusStopID)>,
+    pub bus_alightings: Vec<(Time, BusRouteID, BusStopID)>,
     // TODO Scraping TripMode from TripPhaseStarting is frustrating.
     pub started_trips: BTreeMap<TripID, (Time, TripMode)>,
+    // Where every trip started, recorded when its first leg begins, in order of time
+    pub trip_origins: Vec<(Time, TripMode, Pt2D)>,
     // TODO Hack: No TripMode means aborted
     // Finish time, ID, mode (or None as aborted), trip duration
     pub finished_trips: Vec<(Time, TripID, Option<TripMode>, Duration)>,
+    // For each mode (None is aborted), the cumulative number of finished trips, with one point
+    // per distinct finish time. Appended to as trips finish.
+    finished_trips_series: BTreeMap<Option<TripMode>, Vec<(Time, usize)>>,
+    // Aligned with finished_trips_series, the last trip to finish at each point
+    finished_trips_series_trips: BTreeMap<Option<TripMode>, Vec<TripID>>,
     // TODO This subsumes finished_trips
     pub trip_log: Vec<(Time, TripID, Option<PathRequest>, TripPhaseType)>,
     pub intersection_delays: BTreeMap<IntersectionID, Vec<(Time, Duration)>>,
+    // Summed as agents enter each lane and turn. TripMode::Transit is just buses, not their
+    // passengers.
+    dist_traveled_per_mode: BTreeMap<TripMode, Distance>,
+    // Summed the same way, but per trip. Moves to finished_trip_dists when the trip finishes.
+    dist_traveled_per_trip: BTreeMap<TripID, Distance>,
+    pub finished_trip_dists: BTreeMap<TripID, Distance>,
+    // For trips in progress, the sum of the free-flow time along the path of every leg started so
+    // far
+    free_flow_per_trip: BTreeMap<TripID, Duration>,
+    // Per person, summed over their finished trips, how much longer each trip took than its
+    // free-flow time
+    pub person_delay: BTreeMap<PersonID, Duration>,
+    // (time, occupied spots, total spots), sampled whenever the number of occupied spots changes
+    pub parking_occupancy: Vec<(Time, usize, usize)>,
+    // Per building, how many people entered (positive) or left (negative) at each time. Sum up to
+    // some time to get the occupancy then.
+    pub building_occupancy: BTreeMap<BuildingID, Vec<(Time, isize)>>,
+    // Every time a car parks near its destination, the straight-line distance between the spot
+    // and the building
+    pub parking_walk: Vec<(Time, CarID, BuildingID, Distance)>,
+    // The building each person is currently inside, to know where they left from
+    person_inside: BTreeMap<PersonID, BuildingID>,
+    // Per sidewalk, how many pedestrians are on it. Sampled every SIDEWALK_CROWDING_SAMPLE_RATE,
+    // only recording changes.
+    pub sidewalk_crowding: BTreeMap<LaneID, Vec<(Time, usize)>>,
+    last_crowding_sample: Option<Time>,
+    // The most commands the scheduler has held at once, to spot pathological scheduling
+    pub max_scheduler_queue_len: usize,
+
+    // The last result of summarize()
+    #[serde(skip_serializing, skip_deserializing)]
+    summary: SummaryCache,
 
     // After we restore from a savestate, don't record anything. This is only going to make sense
     // if savestates are only used for quickly previewing against prebaked results, where we have
@@ -44,6 +89,94 @@ pub struct ThruputStats {
     pub demand: BTreeMap<TurnGroupID, usize>,
 }
 
+// How a run compares to a baseline at some time. Positive deltas mean more or longer than the
+// baseline.
+#[derive(Clone, Debug)]
+pub struct AnalyticsDiff {
+    pub time: Time,
+    // Finished and ongoing trips, not counting aborted
+    pub trip_count_deltas: BTreeMap<TripMode, isize>,
+    // Only for modes with trips in both runs
+    pub median_trip_time_deltas: BTreeMap<TripMode, Duration>,
+    // Of the trips in both runs
+    pub num_faster: usize,
+    pub num_slower: usize,
+}
+
+// Of all completed trips involving parking, how much time was spent as "overhead" -- not the main
+// driving part of the trip? Only trips with both a driving and walking or parking portion count.
+pub struct ParkingOverhead {
+    pub num_trips: usize,
+    // What fraction of each trip was overhead
+    pub overhead: PercentageHistogram,
+    // Per trip, time spent looking for parking
+    pub parking_time: DurationHistogram,
+    // Per trip, time spent walking to the parked car and from the parking spot
+    pub walking_time: DurationHistogram,
+}
+
+impl ParkingOverhead {
+    pub fn median_overhead(&self) -> Option<f64> {
+        self.overhead.percentile(50.0)
+    }
+
+    pub fn median_parking_time(&self) -> Option<Duration> {
+        self.parking_time.percentile(50.0)
+    }
+
+    pub fn median_walking_time(&self) -> Option<Duration> {
+        self.walking_time.percentile(50.0)
+    }
+}
+
+const SUMMARY_SAMPLES: usize = 100;
+// Counting everybody on every sidewalk is too slow to do every step
+const SIDEWALK_CROWDING_SAMPLE_RATE: Duration = Duration::const_seconds(60.0);
+const SUMMARY_CHUNK_SIZE: usize = 10_000;
+
+#[derive(Clone)]
+pub struct Summary {
+    pub time: Time,
+    // All trips except aborted, for completed and ongoing trips as of time
+    pub all: DurationHistogram,
+    pub num_aborted: usize,
+    pub per_mode: BTreeMap<TripMode, DurationHistogram>,
+    // For each mode (None is aborted), the number of trips finished by evenly spaced times up to
+    // the summary's time
+    pub finished_over_time: BTreeMap<Option<TripMode>, Vec<(Time, usize)>>,
+}
+
+// Analytics gets shared across threads, so this can't be a RefCell.
+#[derive(Default)]
+struct SummaryCache(Mutex<Option<Summary>>);
+
+impl Clone for SummaryCache {
+    fn clone(&self) -> SummaryCache {
+        SummaryCache(Mutex::new(self.0.lock().unwrap().clone()))
+    }
+}
+
+impl Summary {
+    fn empty(time: Time) -> Summary {
+        let mut finished_over_time: BTreeMap<Option<TripMode>, Vec<(Time, usize)>> =
+            TripMode::all()
+                .into_iter()
+                .map(|m| (Some(m), Vec::new()))
+                .collect();
+        finished_over_time.insert(None, Vec::new());
+        Summary {
+            time,
+            all: DurationHistogram::new(),
+            num_aborted: 0,
+            per_mode: TripMode::all()
+                .into_iter()
+                .map(|m| (m, DurationHistogram::new()))
+                .collect(),
+            finished_over_time,
+        }
+    }
+}
+
 impl Analytics {
     pub fn new() -> Analytics {
         Analytics {
@@ -57,14 +190,37 @@ impl Analytics {
             test_expectations: VecDeque::new(),
             bus_arrivals: Vec::new(),
             bus_passengers_waiting: Vec::new(),
+            bus_boardings: Vec::new(),
+            bus_alightings: Vec::new(),
             started_trips: BTreeMap::new(),
+            trip_origins: Vec::new(),
             finished_trips: Vec::new(),
+            finished_trips_series: BTreeMap::new(),
+            finished_trips_series_trips: BTreeMap::new(),
             trip_log: Vec::new(),
             intersection_delays: BTreeMap::new(),
+            dist_traveled_per_mode: BTreeMap::new(),
+            dist_traveled_per_trip: BTreeMap::new(),
+            finished_trip_dists: BTreeMap::new(),
+            free_flow_per_trip: BTreeMap::new(),
+            person_delay: BTreeMap::new(),
+            parking_occupancy: Vec::new(),
+            building_occupancy: BTreeMap::new(),
+            parking_walk: Vec::new(),
+            person_inside: BTreeMap::new(),
+            sidewalk_crowding: BTreeMap::new(),
+            last_crowding_sample: None,
+            max_scheduler_queue_len: 0,
+            summary: SummaryCache::default(),
             record_anything: true,
         }
     }
 
+    // Keep what's been recorded, but ignore all future events
+    pub(crate) fn stop_recording(&mut self) {
+        self.record_anything = false;
+    }
+
     pub fn event(&mut self, ev: Event, time: Time, map: &Map) {
         if !self.record_anything {
             return;
@@ -74,8 +230,16 @@ impl Analytics {
         let raw_thruput = true;
 
         // Throughput
-        if let Event::AgentEntersTraversable(a, to) = ev {
+        if let Event::AgentEntersTraversable(a, trip, to) = ev {
             let mode = TripMode::from_agent(a);
+            *self
+                .dist_traveled_per_mode
+                .entry(mode)
+                .or_insert(Distance::ZERO) += to.length(map);
+            *self
+                .dist_traveled_per_trip
+                .entry(trip)
+                .or_insert(Distance::ZERO) += to.length(map);
             match to {
                 Traversable::Lane(l) => {
                     let r = map.get_l(l).parent;
@@ -115,8 +279,24 @@ impl Analytics {
             self.bus_passengers_waiting.push((time, stop, route));
         }
 
+        // Bus ridership. Passengers only get on and off while the bus is at a stop, so it's the
+        // one the bus most recently arrived at.
+        match ev {
+            Event::PedEntersBus(_, bus, route) => {
+                if let Some(stop) = self.current_bus_stop(bus) {
+                    self.bus_boardings.push((time, route, stop));
+                }
+            }
+            Event::PedLeavesBus(_, bus, route) => {
+                if let Some(stop) = self.current_bus_stop(bus) {
+                    self.bus_alightings.push((time, route, stop));
+                }
+            }
+            _ => {}
+        }
+
         // Started trips
-        if let Event::TripPhaseStarting(id, mode, _, _) = ev {
+        if let Event::TripPhaseStarting(id, mode, ref maybe_req, _) = ev {
             // TODO More efficiently
             if !self.started_trips.contains_key(&id)
                 && !self
@@ -125,14 +305,31 @@ impl Analytics {
                     .any(|(_, trip, _, _)| *trip == id)
             {
                 self.started_trips.insert(id, (time, mode));
+                // The first leg starts where the building or border connects to the map
+                if let Some(req) = maybe_req {
+                    self.trip_origins.push((time, mode, req.start.pt(map)));
+                }
             }
         }
 
         // Finished trips
+        if let Event::TripFinished(..) | Event::TripAborted(..) = ev {
+            *self.summary.0.lock().unwrap() = None;
+        }
         if let Event::TripFinished(id, mode, dt) = ev {
             self.finished_trips.push((time, id, Some(mode), dt));
+            self.extend_finished_trips_series(time, Some(mode), id);
+            let dist = self
+                .dist_traveled_per_trip
+                .remove(&id)
+                .unwrap_or(Distance::ZERO);
+            self.finished_trip_dists.insert(id, dist);
+            self.free_flow_per_trip.remove(&id);
         } else if let Event::TripAborted(id, mode) = ev {
+            self.dist_traveled_per_trip.remove(&id);
+            self.free_flow_per_trip.remove(&id);
             self.finished_trips.push((time, id, None, Duration::ZERO));
+            self.extend_finished_trips_series(time, None, id);
             if !self.started_trips.contains_key(&id) {
                 self.started_trips.insert(id, (time, mode));
             }
@@ -146,6 +343,21 @@ impl Analytics {
                 .push((time, delay));
         }
 
+        // Building occupancy
+        if let Event::CarParkedNearBuilding(car, b, dist) = ev {
+            self.parking_walk.push((time, car, b, dist));
+        }
+
+        if let Event::PersonStateChanged(person, state) = ev {
+            if let Some(b) = self.person_inside.remove(&person) {
+                self.change_building_occupancy(b, time, false);
+            }
+            if let PersonState::Inside(b) = state {
+                self.person_inside.insert(person, b);
+                self.change_building_occupancy(b, time, true);
+            }
+        }
+
         // TODO Kinda hacky, but these all consume the event, so kinda bundle em.
         match ev {
             Event::TripPhaseStarting(id, _, maybe_req, phase_type) => {
@@ -165,6 +377,152 @@ impl Analytics {
         }
     }
 
+    fn change_building_occupancy(&mut self, b: BuildingID, time: Time, entering: bool) {
+        let delta = if entering { 1 } else { -1 };
+        let deltas = self.building_occupancy.entry(b).or_insert_with(Vec::new);
+        // Collapse several changes at the same time into one point
+        match deltas.last_mut() {
+            Some((t, sum)) if *t == time => {
+                *sum += delta;
+            }
+            _ => {
+                deltas.push((time, delta));
+            }
+        }
+    }
+
+    // Counting all spots is slow, so only do it when something changed.
+    pub(crate) fn record_parking<F: Fn() -> usize>(
+        &mut self,
+        time: Time,
+        occupied: usize,
+        total_spots: F,
+    ) {
+        if !self.record_anything {
+            return;
+        }
+        match self.parking_occupancy.last() {
+            Some((_, last, total)) => {
+                if *last != occupied {
+                    let total = *total;
+                    self.parking_occupancy.push((time, occupied, total));
+                }
+            }
+            None => {
+                self.parking_occupancy.push((time, occupied, total_spots()));
+            }
+        }
+    }
+
+    // Counting pedestrians is slow, so only do it every so often.
+    pub(crate) fn record_sidewalk_crowding<F: Fn() -> Counter<LaneID>>(
+        &mut self,
+        time: Time,
+        peds_per_sidewalk: F,
+    ) {
+        if !self.record_anything {
+            return;
+        }
+        if let Some(t) = self.last_crowding_sample {
+            if time - t < SIDEWALK_CROWDING_SAMPLE_RATE {
+                return;
+            }
+        }
+        self.last_crowding_sample = Some(time);
+
+        let mut counts = peds_per_sidewalk().consume();
+        for (l, samples) in self.sidewalk_crowding.iter_mut() {
+            let cnt = counts.remove(l).unwrap_or(0);
+            if samples.last().map(|(_, last)| *last) != Some(cnt) {
+                samples.push((time, cnt));
+            }
+        }
+        for (l, cnt) in counts {
+            self.sidewalk_crowding.insert(l, vec![(time, cnt)]);
+        }
+    }
+
+    pub(crate) fn record_scheduler_queue_len(&mut self, len: usize) {
+        if self.record_anything {
+            self.max_scheduler_queue_len = self.max_scheduler_queue_len.max(len);
+        }
+    }
+
+    // Called whenever an agent starts a leg of a trip. The free-flow time assumes the agent moves
+    // at the speed limit (or their own max speed) the whole way, without stopping at
+    // intersections.
+    pub(crate) fn record_free_flow(
+        &mut self,
+        trip: TripID,
+        req: &PathRequest,
+        path: &Path,
+        max_speed: Option<Speed>,
+        map: &Map,
+    ) {
+        if !self.record_anything {
+            return;
+        }
+        let steps = path.get_steps();
+        let mut dt = Duration::ZERO;
+        for (idx, step) in steps.iter().enumerate() {
+            let t = step.as_traversable();
+            let mut len = t.length(map);
+            // The first and last lanes are only partly crossed.
+            if idx == 0 {
+                len -= match step {
+                    PathStep::Lane(_) => req.start.dist_along(),
+                    PathStep::ContraflowLane(_) => len - req.start.dist_along(),
+                    PathStep::Turn(_) => Distance::ZERO,
+                };
+            }
+            if idx == steps.len() - 1 {
+                len -= match step {
+                    PathStep::Lane(_) => t.length(map) - req.end.dist_along(),
+                    PathStep::ContraflowLane(_) => req.end.dist_along(),
+                    PathStep::Turn(_) => Distance::ZERO,
+                };
+            }
+            if len <= Distance::ZERO {
+                continue;
+            }
+            let speed = match max_speed {
+                Some(s) => t.speed_limit(map).min(s),
+                None => t.speed_limit(map),
+            };
+            dt += len / speed;
+        }
+        *self
+            .free_flow_per_trip
+            .entry(trip)
+            .or_insert(Duration::ZERO) += dt;
+    }
+
+    // Has to be called before the TripFinished event is processed.
+    pub(crate) fn record_person_delay(&mut self, person: PersonID, trip: TripID, actual: Duration) {
+        if !self.record_anything {
+            return;
+        }
+        let ideal = self
+            .free_flow_per_trip
+            .get(&trip)
+            .cloned()
+            .unwrap_or(Duration::ZERO);
+        // Agents start partway along their first lane, so they can beat the estimate slightly.
+        if actual > ideal {
+            *self.person_delay.entry(person).or_insert(Duration::ZERO) += actual - ideal;
+        }
+    }
+
+    // The n people whose trips were delayed the most, worst first
+    pub fn worst_delayed_people(&self, n: usize) -> Vec<(PersonID, Duration)> {
+        let mut people: Vec<(PersonID, Duration)> =
+            self.person_delay.iter().map(|(p, dt)| (*p, *dt)).collect();
+        people.sort_by_key(|(_, dt)| *dt);
+        people.reverse();
+        people.truncate(n);
+        people
+    }
+
     pub fn record_demand(&mut self, path: &Path, map: &Map) {
         for step in path.get_steps() {
             if let Traversable::Turn(t) = step.as_traversable() {
@@ -216,6 +574,154 @@ impl Analytics {
         (all, num_aborted, per_mode)
     }
 
+    // Just the number of trips per mode that finished by some time. Much cheaper than trip_times,
+    // and doesn't count ongoing or aborted trips.
+    pub fn mode_counts(&self, now: Time) -> BTreeMap<TripMode, usize> {
+        let mut counts = TripMode::all()
+            .into_iter()
+            .map(|m| (m, 0))
+            .collect::<BTreeMap<_, _>>();
+        for (t, _, m, _) in &self.finished_trips {
+            if *t > now {
+                break;
+            }
+            if let Some(mode) = m {
+                *counts.get_mut(mode).unwrap() += 1;
+            }
+        }
+        counts
+    }
+
+    fn extend_finished_trips_series(&mut self, time: Time, mode: Option<TripMode>, id: TripID) {
+        let series = self
+            .finished_trips_series
+            .entry(mode)
+            .or_insert_with(Vec::new);
+        let trips = self
+            .finished_trips_series_trips
+            .entry(mode)
+            .or_insert_with(Vec::new);
+        match series.last_mut() {
+            Some((t, cnt)) if *t == time => {
+                *cnt += 1;
+                *trips.last_mut().unwrap() = id;
+            }
+            Some((_, cnt)) => {
+                let cnt = *cnt + 1;
+                series.push((time, cnt));
+                trips.push(id);
+            }
+            None => {
+                series.push((time, 1));
+                trips.push(id);
+            }
+        }
+    }
+
+    // The cumulative number of trips of one mode (None is aborted) finished over time. Only
+    // changes are recorded, so the last point is often before now.
+    pub fn finished_trips_series(&self, mode: Option<TripMode>) -> &[(Time, usize)] {
+        self.finished_trips_series
+            .get(&mode)
+            .map(|series| series.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // Aligned with finished_trips_series, the last trip to finish at each point.
+    pub fn finished_trips_series_trips(&self, mode: Option<TripMode>) -> &[TripID] {
+        self.finished_trips_series_trips
+            .get(&mode)
+            .map(|trips| trips.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // The same as trip_times, plus the number of finished trips over time, computed in parallel.
+    // Cached until the next call with a different time or the next trip finishes.
+    pub fn summarize(&self, now: Time) -> Summary {
+        if let Some(ref summary) = *self.summary.0.lock().unwrap() {
+            if summary.time == now {
+                return summary.clone();
+            }
+        }
+        let summary = self.compute_summary(now);
+        *self.summary.0.lock().unwrap() = Some(summary.clone());
+        summary
+    }
+
+    fn compute_summary(&self, now: Time) -> Summary {
+        let num_finished = self
+            .finished_trips
+            .iter()
+            .take_while(|(t, _, _, _)| *t <= now)
+            .count();
+        let finished = &self.finished_trips[0..num_finished];
+        let sample_times: Vec<Time> = (0..SUMMARY_SAMPLES)
+            .map(|i| now.percent_of((i as f64) / ((SUMMARY_SAMPLES - 1) as f64)))
+            .collect();
+
+        let sample_times_ref = &sample_times;
+        let chunks: Vec<&[(Time, TripID, Option<TripMode>, Duration)]> =
+            finished.chunks(SUMMARY_CHUNK_SIZE).collect();
+        let partials = Timer::throwaway().parallelize("summarize trips", chunks, |chunk| {
+            let mut partial = Summary::empty(now);
+            // How many trips finished between each sample time and the previous one
+            let mut per_sample: BTreeMap<Option<TripMode>, Vec<usize>> = BTreeMap::new();
+            for (t, _, m, dt) in chunk {
+                if let Some(mode) = *m {
+                    partial.all.add(*dt);
+                    partial.per_mode.get_mut(&mode).unwrap().add(*dt);
+                } else {
+                    partial.num_aborted += 1;
+                }
+                let idx = sample_times_ref
+                    .iter()
+                    .position(|sample| *t <= *sample)
+                    .unwrap_or(SUMMARY_SAMPLES - 1);
+                per_sample
+                    .entry(*m)
+                    .or_insert_with(|| vec![0; SUMMARY_SAMPLES])[idx] += 1;
+            }
+            (partial, per_sample)
+        });
+
+        let mut summary = Summary::empty(now);
+        let mut per_sample: BTreeMap<Option<TripMode>, Vec<usize>> = BTreeMap::new();
+        for (partial, counts) in partials {
+            summary.all.merge(&partial.all);
+            summary.num_aborted += partial.num_aborted;
+            for (mode, hgram) in partial.per_mode {
+                summary.per_mode.get_mut(&mode).unwrap().merge(&hgram);
+            }
+            for (mode, list) in counts {
+                let total = per_sample
+                    .entry(mode)
+                    .or_insert_with(|| vec![0; SUMMARY_SAMPLES]);
+                for (idx, cnt) in list.into_iter().enumerate() {
+                    total[idx] += cnt;
+                }
+            }
+        }
+
+        // Ongoing trips count too
+        let finished_ids: BTreeSet<TripID> = finished.iter().map(|(_, id, _, _)| *id).collect();
+        for (id, (start, m)) in &self.started_trips {
+            if *start < now && !finished_ids.contains(id) {
+                summary.all.add(now - *start);
+                summary.per_mode.get_mut(m).unwrap().add(now - *start);
+            }
+        }
+
+        for (mode, pts) in summary.finished_over_time.iter_mut() {
+            let mut sum = 0;
+            for (idx, t) in sample_times.iter().enumerate() {
+                sum += per_sample.get(mode).map(|list| list[idx]).unwrap_or(0);
+                pts.push((*t, sum));
+            }
+        }
+
+        summary
+    }
+
     // Returns unsorted list of deltas, one for each trip finished or ongoing in both worlds.
     // Positive dt means faster.
     pub fn trip_time_deltas(&self, now: Time, baseline: &Analytics) -> Vec<Duration> {
@@ -248,6 +754,75 @@ impl Analytics {
             .collect()
     }
 
+    // (percentile, trip duration) at every 10%, for trips of one mode that've finished by now
+    pub fn trip_time_percentiles(&self, mode: TripMode, now: Time) -> Vec<(f64, Duration)> {
+        let mut times: Vec<Duration> = self
+            .finished_trips
+            .iter()
+            .take_while(|(t, _, _, _)| *t <= now)
+            .filter(|(_, _, m, _)| *m == Some(mode))
+            .map(|(_, _, _, dt)| *dt)
+            .collect();
+        if times.is_empty() {
+            return Vec::new();
+        }
+        times.sort();
+
+        let last_idx = (times.len() - 1) as f64;
+        (0..=10)
+            .map(|i| {
+                let pct = 10.0 * (i as f64);
+                let idx = (pct / 100.0 * last_idx).round() as usize;
+                (pct, times[idx])
+            })
+            .collect()
+    }
+
+    // For every trip successfully finished by now, returns (trip, mode, distance traveled,
+    // duration). The distance sums every lane and turn entered, so the first lane isn't counted.
+    pub fn distance_vs_time(&self, now: Time) -> Vec<(TripID, TripMode, Distance, Duration)> {
+        self.finished_trips
+            .iter()
+            .take_while(|(t, _, _, _)| *t <= now)
+            .filter_map(|(_, id, m, dt)| {
+                let mode = (*m)?;
+                let dist = self
+                    .finished_trip_dists
+                    .get(id)
+                    .cloned()
+                    .unwrap_or(Distance::ZERO);
+                Some((*id, mode, dist, *dt))
+            })
+            .collect()
+    }
+
+    pub fn diff(&self, baseline: &Analytics, now: Time) -> AnalyticsDiff {
+        let (_, _, per_mode) = self.trip_times(now);
+        let (_, _, baseline_per_mode) = baseline.trip_times(now);
+
+        let mut trip_count_deltas = BTreeMap::new();
+        let mut median_trip_time_deltas = BTreeMap::new();
+        for mode in TripMode::all() {
+            let a = &per_mode[&mode];
+            let b = &baseline_per_mode[&mode];
+            trip_count_deltas.insert(mode, (a.count() as isize) - (b.count() as isize));
+            if a.count() > 0 && b.count() > 0 {
+                median_trip_time_deltas
+                    .insert(mode, a.select(Statistic::P50) - b.select(Statistic::P50));
+            }
+        }
+
+        // trip_time_deltas is baseline minus this run, so positive is faster
+        let deltas = self.trip_time_deltas(now, baseline);
+        AnalyticsDiff {
+            time: now,
+            trip_count_deltas,
+            median_trip_time_deltas,
+            num_faster: deltas.iter().filter(|dt| **dt > Duration::ZERO).count(),
+            num_slower: deltas.iter().filter(|dt| **dt < Duration::ZERO).count(),
+        }
+    }
+
     pub fn bus_arrivals(&self, now: Time, r: BusRouteID) -> BTreeMap<BusStopID, DurationHistogram> {
         let mut per_bus: BTreeMap<CarID, Vec<(Time, BusStopID)>> = BTreeMap::new();
         for (t, car, route, stop) in &self.bus_arrivals {
@@ -273,6 +848,22 @@ impl Analytics {
         delay_to_stop
     }
 
+    // For each stop along the route, the number of (boardings, alightings) so far
+    pub fn bus_ridership(&self, r: BusRouteID) -> BTreeMap<BusStopID, (usize, usize)> {
+        let mut per_stop: BTreeMap<BusStopID, (usize, usize)> = BTreeMap::new();
+        for (_, route, stop) in &self.bus_boardings {
+            if *route == r {
+                per_stop.entry(*stop).or_insert((0, 0)).0 += 1;
+            }
+        }
+        for (_, route, stop) in &self.bus_alightings {
+            if *route == r {
+                per_stop.entry(*stop).or_insert((0, 0)).1 += 1;
+            }
+        }
+        per_stop
+    }
+
     // TODO Refactor!
     // For each stop, a list of (time, delay)
     pub fn bus_arrivals_over_time(
@@ -304,6 +895,15 @@ impl Analytics {
         delays_to_stop
     }
 
+    // The last stop this bus arrived at
+    fn current_bus_stop(&self, bus: CarID) -> Option<BusStopID> {
+        self.bus_arrivals
+            .iter()
+            .rev()
+            .find(|(_, car, _, _)| *car == bus)
+            .map(|(_, _, _, stop)| *stop)
+    }
+
     // At some moment in time, what's the distribution of passengers waiting for a route like?
     pub fn bus_passenger_delays(
         &self,
@@ -360,6 +960,24 @@ impl Analytics {
         self.throughput(now, road, window_size, &self.thruput_stats.raw_per_road)
     }
 
+    // Counts every agent entering the road, regardless of mode, in consecutive buckets starting
+    // at midnight. Each point is the start of the bucket.
+    pub fn throughput_over_time(&self, road: RoadID, bucket: Duration) -> Vec<(Time, usize)> {
+        assert!(bucket > Duration::ZERO);
+        let mut pts = vec![(Time::START_OF_DAY, 0)];
+        for (t, _, r) in &self.thruput_stats.raw_per_road {
+            if *r != road {
+                continue;
+            }
+            while *t >= pts.last().unwrap().0 + bucket {
+                let next = pts.last().unwrap().0 + bucket;
+                pts.push((next, 0));
+            }
+            pts.last_mut().unwrap().1 += 1;
+        }
+        pts
+    }
+
     pub fn throughput_intersection(
         &self,
         now: Time,
@@ -468,47 +1086,47 @@ impl Analytics {
         trips
     }
 
-    pub fn analyze_parking_phases(&self) -> Vec<String> {
-        // Of all completed trips involving parking, what percentage of total time was spent as
-        // "overhead" -- not the main driving part of the trip?
+    pub fn analyze_parking_phases(&self) -> ParkingOverhead {
         // TODO This is misleading for border trips -- the driving lasts longer.
-        let mut distrib = PercentageHistogram::new();
+        let mut result = ParkingOverhead {
+            num_trips: 0,
+            overhead: PercentageHistogram::new(),
+            parking_time: DurationHistogram::new(),
+            walking_time: DurationHistogram::new(),
+        };
         for (_, phases) in self.get_all_trip_phases() {
             if phases.last().as_ref().unwrap().end_time.is_none() {
                 continue;
             }
             let mut driving_time = Duration::ZERO;
-            let mut overhead = Duration::ZERO;
+            let mut parking_time = Duration::ZERO;
+            let mut walking_time = Duration::ZERO;
             for p in phases {
                 let dt = p.end_time.unwrap() - p.start_time;
                 match p.phase_type {
                     TripPhaseType::Driving => {
                         driving_time += dt;
                     }
-                    TripPhaseType::Parking | TripPhaseType::Walking => {
-                        overhead += dt;
+                    TripPhaseType::Parking => {
+                        parking_time += dt;
+                    }
+                    TripPhaseType::Walking => {
+                        walking_time += dt;
                     }
                     _ => {}
                 }
             }
+            let overhead = parking_time + walking_time;
             // Only interested in trips with both
             if driving_time == Duration::ZERO || overhead == Duration::ZERO {
                 continue;
             }
-            distrib.add(overhead / (driving_time + overhead));
+            result.num_trips += 1;
+            result.overhead.add(overhead / (driving_time + overhead));
+            result.parking_time.add(parking_time);
+            result.walking_time.add(walking_time);
         }
-        vec![
-            format!("Consider all trips with both a walking and driving portion"),
-            format!(
-                "The portion of the trip spent walking to the parked car, looking for parking, \
-                 and walking from the parking space to the final destination are all overhead."
-            ),
-            format!(
-                "So what's the distribution of overhead percentages look like? 0% is ideal -- the \
-                 entire trip is spent just driving between the original source and destination."
-            ),
-            distrib.describe(),
-        ]
+        result
     }
 
     pub fn intersection_delays(&self, i: IntersectionID, t1: Time, t2: Time) -> DurationHistogram {
@@ -528,6 +1146,23 @@ impl Analytics {
         delays
     }
 
+    // (min, median, max) of all delays measured at a traffic signal so far. None if nobody has
+    // waited there yet.
+    pub fn delay_stats(&self, i: IntersectionID) -> Option<(Duration, Duration, Duration)> {
+        let mut delays = DurationHistogram::new();
+        for (_, dt) in self.intersection_delays.get(&i)? {
+            delays.add(*dt);
+        }
+        if delays.count() == 0 {
+            return None;
+        }
+        Some((
+            delays.select(Statistic::Min),
+            delays.select(Statistic::P50),
+            delays.select(Statistic::Max),
+        ))
+    }
+
     pub fn intersection_delays_bucketized(
         &self,
         now: Time,
@@ -554,18 +1189,165 @@ impl Analytics {
         results
     }
 
+    // Vehicle miles traveled, except it's all modes and not in miles
+    pub fn vmt_by_mode(&self) -> BTreeMap<TripMode, Distance> {
+        TripMode::all()
+            .into_iter()
+            .map(|m| {
+                (
+                    m,
+                    self.dist_traveled_per_mode
+                        .get(&m)
+                        .cloned()
+                        .unwrap_or(Distance::ZERO),
+                )
+            })
+            .collect()
+    }
+
+    // How many people are inside the building at some time
+    pub fn occupancy_at(&self, b: BuildingID, time: Time) -> usize {
+        let sum: isize = self
+            .building_occupancy
+            .get(&b)
+            .map(|deltas| {
+                deltas
+                    .iter()
+                    .take_while(|(t, _)| *t <= time)
+                    .map(|(_, delta)| *delta)
+                    .sum()
+            })
+            .unwrap_or(0);
+        assert!(sum >= 0);
+        sum as usize
+    }
+
+    // How many pedestrians were on the sidewalk, as of the last sample at or before some time
+    pub fn peds_on_sidewalk_at(&self, l: LaneID, time: Time) -> usize {
+        self.sidewalk_crowding
+            .get(&l)
+            .and_then(|samples| samples.iter().take_while(|(t, _)| *t <= time).last())
+            .map(|(_, cnt)| *cnt)
+            .unwrap_or(0)
+    }
+
+    // Every building with somebody inside at some time
+    pub fn all_occupancy_at(&self, time: Time) -> Vec<(BuildingID, usize)> {
+        self.building_occupancy
+            .keys()
+            .map(|b| (*b, self.occupancy_at(*b, time)))
+            .filter(|(_, cnt)| *cnt > 0)
+            .collect()
+    }
+
+    // How far each driver parked from where they were going, in the order they parked
+    pub fn parking_walk_distances(&self) -> Vec<Distance> {
+        self.parking_walk.iter().map(|(_, _, _, d)| *d).collect()
+    }
+
+    // Where trips started in [t1, t2)
+    pub fn trip_origins_between(&self, t1: Time, t2: Time) -> Vec<(TripMode, Pt2D)> {
+        self.trip_origins
+            .iter()
+            .skip_while(|(t, _, _)| *t < t1)
+            .take_while(|(t, _, _)| *t < t2)
+            .map(|(_, mode, pt)| (*mode, *pt))
+            .collect()
+    }
+
+    // Percent of all parking spots that're occupied
+    pub fn parking_over_time(&self) -> Vec<(Time, f64)> {
+        self.parking_occupancy
+            .iter()
+            .map(|(t, occupied, total)| {
+                if *total == 0 {
+                    (*t, 0.0)
+                } else {
+                    (*t, 100.0 * (*occupied as f64) / (*total as f64))
+                }
+            })
+            .collect()
+    }
+
     pub fn active_agents(&self, now: Time) -> Vec<(Time, usize)> {
-        let mut starts_stops: Vec<(Time, bool)> = Vec::new();
-        for (_, (t, _)) in &self.started_trips {
-            if *t <= now {
-                starts_stops.push((*t, false));
+        self.active_agents_with_trips(now)
+            .into_iter()
+            .map(|(t, cnt, _)| (t, cnt))
+            .collect()
+    }
+
+    // Like active_agents, but also returns the last trip to start or finish at each point.
+    pub fn active_agents_with_trips(&self, now: Time) -> Vec<(Time, usize, Option<TripID>)> {
+        self.active_agents_filtered(now, None)
+    }
+
+    // Every mode is present, even if nobody used it.
+    pub fn active_agents_by_mode(&self, now: Time) -> BTreeMap<TripMode, Vec<(Time, usize)>> {
+        TripMode::all()
+            .into_iter()
+            .map(|mode| {
+                let pts = self
+                    .active_agents_filtered(now, Some(mode))
+                    .into_iter()
+                    .map(|(t, cnt, _)| (t, cnt))
+                    .collect();
+                (mode, pts)
+            })
+            .collect()
+    }
+
+    // What to plot for active agents over time, as (label, the mode if the series only covers one,
+    // points). Against a baseline, that's the baseline over the whole day and this simulation so
+    // far. Otherwise, it's one series per mode.
+    pub fn active_agents_series(
+        &self,
+        now: Time,
+        baseline: Option<&Analytics>,
+    ) -> Vec<(String, Option<TripMode>, Vec<(Time, usize)>)> {
+        if let Some(baseline) = baseline {
+            return vec![
+                (
+                    "Baseline".to_string(),
+                    None,
+                    baseline.active_agents(Time::END_OF_DAY),
+                ),
+                (
+                    "Current simulation".to_string(),
+                    None,
+                    self.active_agents(now),
+                ),
+            ];
+        }
+        self.active_agents_by_mode(now)
+            .into_iter()
+            .map(|(mode, pts)| (mode.to_string(), Some(mode), pts))
+            .collect()
+    }
+
+    // None means all modes
+    fn active_agents_filtered(
+        &self,
+        now: Time,
+        mode: Option<TripMode>,
+    ) -> Vec<(Time, usize, Option<TripID>)> {
+        let mut starts_stops: Vec<(Time, bool, TripID)> = Vec::new();
+        for (id, (t, m)) in &self.started_trips {
+            if *t <= now && mode.map(|x| x == *m).unwrap_or(true) {
+                starts_stops.push((*t, false, *id));
             }
         }
-        for (t, _, _, _) in &self.finished_trips {
+        for (t, id, _, _) in &self.finished_trips {
             if *t > now {
                 break;
             }
-            starts_stops.push((*t, true));
+            // The mode recorded when a trip finishes is missing for aborted trips, so use the
+            // one from when it started.
+            if let Some(m) = mode {
+                if self.started_trips.get(id).map(|(_, x)| *x) != Some(m) {
+                    continue;
+                }
+            }
+            starts_stops.push((*t, true, *id));
         }
         // Make sure the start events get sorted before the stops.
         starts_stops.sort();
@@ -573,12 +1355,14 @@ impl Analytics {
         let mut pts = Vec::new();
         let mut cnt = 0;
         let mut last_t = Time::START_OF_DAY;
-        for (t, ended) in starts_stops {
+        let mut last_trip = None;
+        for (t, ended, id) in starts_stops {
             if t != last_t {
                 // Step functions. Don't interpolate.
-                pts.push((last_t, cnt));
+                pts.push((last_t, cnt, last_trip));
             }
             last_t = t;
+            last_trip = Some(id);
             if ended {
                 // release mode disables this check, so...
                 if cnt == 0 {
@@ -589,9 +1373,9 @@ impl Analytics {
                 cnt += 1;
             }
         }
-        pts.push((last_t, cnt));
+        pts.push((last_t, cnt, last_trip));
         if last_t != now {
-            pts.push((now, cnt));
+            pts.push((now, cnt, None));
         }
         pts
     }
@@ -641,3 +1425,26 @@ impl Window {
         self.times.len()
     }
 }
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TripSortKey {
+    FinishTime,
+    Duration,
+    ID,
+}
+
+// Sorts entries from Analytics::finished_trips. With descending order, ties are reversed too.
+pub fn sort_finished_trips(
+    trips: &mut Vec<&(Time, TripID, Option<TripMode>, Duration)>,
+    key: TripSortKey,
+    descending: bool,
+) {
+    match key {
+        TripSortKey::FinishTime => trips.sort_by_key(|(t, _, _, _)| *t),
+        TripSortKey::Duration => trips.sort_by_key(|(_, _, _, dt)| *dt),
+        TripSortKey::ID => trips.sort_by_key(|(_, id, _, _)| *id),
+    }
+    if descending {
+        trips.reverse();
+    }
+}
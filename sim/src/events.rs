@@ -1,5 +1,5 @@
-use crate::{AgentID, CarID, ParkingSpot, PedestrianID, TripID, TripMode};
-use geom::Duration;
+use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, PersonState, TripID, TripMode};
+use geom::{Distance, Duration};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Map, Path, PathRequest, Traversable,
 };
@@ -8,6 +8,8 @@ use serde_derive::{Deserialize, Serialize};
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Event {
     CarReachedParkingSpot(CarID, ParkingSpot),
+    // How far the spot is from the building the driver was going to
+    CarParkedNearBuilding(CarID, BuildingID, Distance),
     CarOrBikeReachedBorder(CarID, IntersectionID),
 
     BusArrivedAtStop(CarID, BusRouteID, BusStopID),
@@ -22,18 +24,29 @@ pub enum Event {
 
     BikeStoppedAtSidewalk(CarID, LaneID),
 
-    AgentEntersTraversable(AgentID, Traversable),
+    AgentEntersTraversable(AgentID, TripID, Traversable),
     IntersectionDelayMeasured(IntersectionID, Duration),
 
     TripFinished(TripID, TripMode, Duration),
     TripAborted(TripID, TripMode),
     TripPhaseStarting(TripID, TripMode, Option<PathRequest>, TripPhaseType),
+    PersonStateChanged(PersonID, PersonState),
 
     // Just use for parking replanning. Not happy about copying the full path in here, but the way
     // to plumb info into Analytics is Event.
     PathAmended(Path),
 }
 
+impl Event {
+    // True if this event means the trip is over, successfully or not.
+    pub fn ends_trip(&self, trip: TripID) -> bool {
+        match self {
+            Event::TripFinished(id, _, _) | Event::TripAborted(id, _) => *id == trip,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum TripPhaseType {
     Driving,
@@ -9,31 +9,37 @@ mod sim;
 mod transit;
 mod trips;
 
-pub use self::analytics::{Analytics, TripPhase};
+pub use self::analytics::{
+    sort_finished_trips, Analytics, AnalyticsDiff, ParkingOverhead, Summary, TripPhase, TripSortKey,
+};
 pub use self::events::{Event, TripPhaseType};
 pub use self::make::{
-    ABTest, BorderSpawnOverTime, IndividTrip, OriginDestination, Person, Population, Scenario,
+    demand_arrow_width, demand_geojson, ABTest, BorderSpawnOverTime, BorderSpawnReport,
+    CarAppearingStart, IndividTrip, OriginDestination, Person, Population, Scenario,
     SeedParkedCars, SimFlags, SpawnOverTime, SpawnTrip, TripSpawner, TripSpec,
+    WeightedNeighborhoodChoice,
 };
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
 };
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
-pub use self::sim::{Sim, SimOptions};
+pub use self::sim::{
+    gridlock_component, replay_event_log, Sim, SimOptions, StopCondition, StopReason,
+};
 pub(crate) use self::transit::TransitSimState;
-pub use self::trips::{TripCount, TripResult};
-pub use self::trips::{TripEnd, TripMode, TripStart};
-pub(crate) use self::trips::{TripLeg, TripManager};
+pub(crate) use self::trips::TripManager;
+pub use self::trips::{validate_legs, TripCount, TripEndpointFilter, TripLeg, TripResult};
+pub use self::trips::{AbortReason, PersonState, TripEnd, TripEndpoint, TripMode, TripStart};
 pub use crate::render::{
     AgentMetadata, CarStatus, DontDrawAgents, DrawCarInput, DrawPedCrowdInput, DrawPedestrianInput,
-    GetDrawAgents, PedCrowdLocation, UnzoomedAgent,
+    GetDrawAgents, PedCrowdLocation, UnzoomedAgent, VisibleEntity,
 };
 use abstutil::Cloneable;
-use geom::{Distance, Pt2D, Speed, Time};
+use geom::{Distance, Line, Pt2D, Speed, Time};
 use map_model::{
-    BuildingID, BusStopID, DirectedRoadID, IntersectionID, LaneID, Map, Path, PathConstraints,
-    PathRequest, Position,
+    BuildingID, BusStopID, DirectedRoadID, IntersectionID, LaneID, LaneType, Map, Path,
+    PathConstraints, PathRequest, Position,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -51,6 +57,28 @@ pub const BUS_LENGTH: Distance = Distance::const_meters(12.5);
 // one car to the back of the other.
 pub const FOLLOWING_DISTANCE: Distance = Distance::const_meters(1.0);
 
+// Configurable through SimOptions, to study denser or sparser traffic. The defaults are the
+// constants above.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VehicleSizes {
+    pub following_distance: Distance,
+    // Car lengths are sampled uniformly from this range. Both must be < PARKING_SPOT_LENGTH.
+    pub min_car_length: Distance,
+    pub max_car_length: Distance,
+    pub bike_length: Distance,
+}
+
+impl Default for VehicleSizes {
+    fn default() -> VehicleSizes {
+        VehicleSizes {
+            following_distance: FOLLOWING_DISTANCE,
+            min_car_length: MIN_CAR_LENGTH,
+            max_car_length: MAX_CAR_LENGTH,
+            bike_length: BIKE_LENGTH,
+        }
+    }
+}
+
 // The numeric ID must be globally unique, without considering VehicleType. VehicleType is bundled
 // for convenient debugging.
 // TODO Implement Eq, Hash, Ord manually to guarantee this.
@@ -85,6 +113,20 @@ impl AgentID {
             _ => panic!("Not a CarID: {:?}", self),
         }
     }
+
+    pub fn car(self) -> Option<CarID> {
+        match self {
+            AgentID::Car(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn ped(self) -> Option<PedestrianID> {
+        match self {
+            AgentID::Pedestrian(id) => Some(id),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for AgentID {
@@ -173,7 +215,7 @@ impl VehicleSpec {
 pub enum ParkingSpot {
     // Lane and idx
     Onstreet(LaneID, usize),
-    // Building and idx (pretty meaningless)
+    // Building and idx, which is less than the building's number of stalls
     Offstreet(BuildingID, usize),
 }
 
@@ -199,6 +241,9 @@ pub struct ParkedCar {
 pub enum DrivingGoal {
     ParkNear(BuildingID),
     Border(IntersectionID, LaneID),
+    // End at the border on whichever of these lanes is reachable, trying them in order. Resolved
+    // when the path is calculated, since some lanes may be dead-ends from the start.
+    AnyLane(IntersectionID, Vec<LaneID>),
 }
 
 impl DrivingGoal {
@@ -207,12 +252,14 @@ impl DrivingGoal {
         constraints: PathConstraints,
         map: &Map,
     ) -> Option<DrivingGoal> {
-        let lanes = dr.lanes(constraints, map);
-        if lanes.is_empty() {
-            None
-        } else {
-            // TODO ideally could use any
-            Some(DrivingGoal::Border(dr.dst_i(map), lanes[0]))
+        DrivingGoal::border_lanes(dr.dst_i(map), dr.lanes(constraints, map))
+    }
+
+    pub(crate) fn border_lanes(i: IntersectionID, lanes: Vec<LaneID>) -> Option<DrivingGoal> {
+        match lanes.len() {
+            0 => None,
+            1 => Some(DrivingGoal::Border(i, lanes[0])),
+            _ => Some(DrivingGoal::AnyLane(i, lanes)),
         }
     }
 
@@ -224,21 +271,51 @@ impl DrivingGoal {
                 }
                 PathConstraints::Bike => {
                     let l = map.find_biking_lane_near_building(*b);
-                    Position::new(l, map.get_l(l).length() / 2.0)
+                    Position::new(l, bike_end_dist(l, *b, map))
                 }
                 PathConstraints::Bus | PathConstraints::Pedestrian => unreachable!(),
             },
             DrivingGoal::Border(_, l) => Position::new(*l, map.get_l(*l).length()),
+            // Without knowing where the path starts, just use the first
+            DrivingGoal::AnyLane(_, lanes) => Position::new(lanes[0], map.get_l(lanes[0]).length()),
         }
     }
 
+    // For AnyLane, the request is for the first lane that has a path, or the first lane if none
+    // do.
+    pub(crate) fn pathfind(
+        &self,
+        start: Position,
+        constraints: PathConstraints,
+        map: &Map,
+    ) -> (PathRequest, Option<Path>) {
+        if let DrivingGoal::AnyLane(_, lanes) = self {
+            for l in lanes {
+                let req = PathRequest {
+                    start,
+                    end: Position::new(*l, map.get_l(*l).length()),
+                    constraints,
+                };
+                if let Some(path) = map.pathfind(req.clone()) {
+                    return (req, Some(path));
+                }
+            }
+        }
+        let req = PathRequest {
+            start,
+            end: self.goal_pos(constraints, map),
+            constraints,
+        };
+        let path = map.pathfind(req.clone());
+        (req, path)
+    }
+
     pub(crate) fn make_router(&self, path: Path, map: &Map, vt: VehicleType) -> Router {
         match self {
             DrivingGoal::ParkNear(b) => {
                 if vt == VehicleType::Bike {
-                    // TODO Stop closer to the building?
                     let end = path.last_step().as_lane();
-                    Router::bike_then_stop(path, map.get_l(end).length() / 2.0)
+                    Router::bike_then_stop(path, bike_end_dist(end, *b, map), *b)
                 } else {
                     Router::park_near(path, *b)
                 }
@@ -246,13 +323,19 @@ impl DrivingGoal {
             DrivingGoal::Border(i, last_lane) => {
                 Router::end_at_border(path, map.get_l(*last_lane).length(), *i)
             }
+            DrivingGoal::AnyLane(i, _) => {
+                let last_lane = path.last_step().as_lane();
+                Router::end_at_border(path, map.get_l(last_lane).length(), *i)
+            }
         }
     }
 
     pub fn pt(&self, map: &Map) -> Pt2D {
         match self {
             DrivingGoal::ParkNear(b) => map.get_b(*b).polygon.center(),
-            DrivingGoal::Border(i, _) => map.get_i(*i).polygon.center(),
+            DrivingGoal::Border(i, _) | DrivingGoal::AnyLane(i, _) => {
+                map.get_i(*i).polygon.center()
+            }
         }
     }
 }
@@ -296,11 +379,23 @@ impl SidewalkSpot {
         }
     }
 
-    pub fn bike_rack(sidewalk: LaneID, map: &Map) -> Option<SidewalkSpot> {
+    // If the biker is headed somewhere, put the rack as close as possible to that building.
+    pub fn bike_rack(
+        sidewalk: LaneID,
+        near: Option<BuildingID>,
+        map: &Map,
+    ) -> Option<SidewalkSpot> {
         assert!(map.get_l(sidewalk).is_sidewalk());
         let driving_lane = map.get_parent(sidewalk).sidewalk_to_bike(sidewalk)?;
-        // TODO Arbitrary, but safe
-        let sidewalk_pos = Position::new(sidewalk, map.get_l(sidewalk).length() / 2.0);
+        let front_path = near.map(|b| map.get_b(b).front_path.sidewalk);
+        let sidewalk_pos = match front_path {
+            Some(pos) if pos.lane() == sidewalk => pos,
+            Some(pos) if map.get_l(pos.lane()).parent == map.get_l(sidewalk).parent => {
+                pos.equiv_pos(sidewalk, Distance::ZERO, map)
+            }
+            // TODO Arbitrary, but safe
+            _ => Position::new(sidewalk, map.get_l(sidewalk).length() / 2.0),
+        };
         let driving_pos = sidewalk_pos.equiv_pos(driving_lane, Distance::ZERO, map);
         Some(SidewalkSpot {
             connection: SidewalkPOI::BikeRack(driving_pos),
@@ -310,14 +405,25 @@ impl SidewalkSpot {
 
     pub fn bike_from_bike_rack(sidewalk: LaneID, map: &Map) -> Option<SidewalkSpot> {
         assert!(map.get_l(sidewalk).is_sidewalk());
+        if let Some(spot) = SidewalkSpot::bike_from_bike_rack_on_side(sidewalk, map) {
+            return Some(spot);
+        }
+        // On a one-way, the near side might not work out. Cross the street and start from there.
+        let other_side = map
+            .get_parent(sidewalk)
+            .any_on_other_side(sidewalk, LaneType::Sidewalk)?;
+        SidewalkSpot::bike_from_bike_rack_on_side(other_side, map)
+    }
+
+    fn bike_from_bike_rack_on_side(sidewalk: LaneID, map: &Map) -> Option<SidewalkSpot> {
         let driving_lane = map.get_parent(sidewalk).sidewalk_to_bike(sidewalk)?;
         // Don't start biking on a blackhole!
         // TODO Maybe compute a separate blackhole graph that includes bike lanes.
         if let Some(redirect) = map.get_l(driving_lane).parking_blackhole {
             let new_sidewalk = map.get_parent(redirect).bike_to_sidewalk(redirect)?;
-            SidewalkSpot::bike_rack(new_sidewalk, map)
+            SidewalkSpot::bike_rack(new_sidewalk, None, map)
         } else {
-            SidewalkSpot::bike_rack(sidewalk, map)
+            SidewalkSpot::bike_rack(sidewalk, None, map)
         }
     }
 
@@ -434,6 +540,30 @@ impl TimeInterval {
     }
 }
 
+// Where a dot moving at a constant speed along a trip's straight line would be at some time. None
+// before the trip departs or after it would've arrived.
+pub fn straight_line_dot(line: &Line, depart: Time, speed: Speed, now: Time) -> Option<Pt2D> {
+    let interval = TimeInterval::new(depart, depart + line.length() / speed);
+    if now < interval.start || now > interval.end {
+        return None;
+    }
+    Some(line.percent_along(interval.percent_clamp_end(now)))
+}
+
+// Where a bike heading to a building should stop along the last lane, lined up with the bike rack
+// closest to the building.
+fn bike_end_dist(bike_lane: LaneID, b: BuildingID, map: &Map) -> Distance {
+    map.get_parent(bike_lane)
+        .bike_to_sidewalk(bike_lane)
+        .and_then(|sidewalk| SidewalkSpot::bike_rack(sidewalk, Some(b), map))
+        .map(|spot| {
+            spot.sidewalk_pos
+                .equiv_pos(bike_lane, Distance::ZERO, map)
+                .dist_along()
+        })
+        .unwrap_or_else(|| map.get_l(bike_lane).length() / 2.0)
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct DistanceInterval {
     // TODO Private fields
@@ -1,6 +1,6 @@
-use crate::{Scenario, Sim, SimOptions};
+use crate::{Scenario, Sim, SimOptions, VehicleSizes};
 use abstutil::CmdArgs;
-use geom::Duration;
+use geom::{Distance, Duration};
 use map_model::{Map, MapEdits};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
@@ -15,6 +15,8 @@ pub struct SimFlags {
 
 impl SimFlags {
     pub fn from_args(args: &mut CmdArgs) -> SimFlags {
+        let defaults = VehicleSizes::default();
+        let parse_meters = |s: &str| s.parse::<f64>().map(Distance::meters);
         SimFlags {
             load: args
                 .optional_free()
@@ -30,6 +32,28 @@ impl SimFlags {
                 disable_block_the_box: args.enabled("--disable_block_the_box"),
                 recalc_lanechanging: !args.enabled("--dont_recalc_lc"),
                 clear_laggy_head_early: args.enabled("--clear_laggy_head_early"),
+                vehicle_sizes: VehicleSizes {
+                    following_distance: args
+                        .optional_parse("--following_distance", parse_meters)
+                        .unwrap_or(defaults.following_distance),
+                    min_car_length: args
+                        .optional_parse("--min_car_length", parse_meters)
+                        .unwrap_or(defaults.min_car_length),
+                    max_car_length: args
+                        .optional_parse("--max_car_length", parse_meters)
+                        .unwrap_or(defaults.max_car_length),
+                    bike_length: args
+                        .optional_parse("--bike_length", parse_meters)
+                        .unwrap_or(defaults.bike_length),
+                },
+                parking_skip_probability: args
+                    .optional_parse("--parking_skip_prob", |s| s.parse())
+                    .unwrap_or(0.0),
+                enable_walking: !args.enabled("--disable_walking"),
+                enable_transit: !args.enabled("--disable_transit"),
+                enable_parking: !args.enabled("--disable_parking"),
+                lazy_spawning: args.enabled("--lazy_spawning"),
+                snapshot_every: args.optional_parse("--snapshot_every", Duration::parse),
             },
         }
     }
@@ -6,7 +6,8 @@ mod spawner;
 pub use self::a_b_test::ABTest;
 pub use self::load::SimFlags;
 pub use self::scenario::{
-    BorderSpawnOverTime, IndividTrip, OriginDestination, Person, Population, Scenario,
-    SeedParkedCars, SpawnOverTime, SpawnTrip,
+    demand_arrow_width, demand_geojson, BorderSpawnOverTime, BorderSpawnReport, CarAppearingStart,
+    IndividTrip, OriginDestination, Person, Population, Scenario, SeedParkedCars, SpawnOverTime,
+    SpawnTrip, WeightedNeighborhoodChoice,
 };
 pub use self::spawner::{TripSpawner, TripSpec};
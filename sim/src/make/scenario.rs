@@ -1,18 +1,18 @@
 use crate::{
-    CarID, DrivingGoal, ParkingSpot, PersonID, SidewalkSpot, Sim, TripSpec, VehicleSpec,
-    VehicleType, BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    CarID, DrivingGoal, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot, Sim, TripEndpoint,
+    TripMode, TripSpec, VehicleSizes, VehicleSpec, VehicleType,
 };
 use abstutil::{fork_rng, Timer, WeightedUsizeChoice};
-use geom::{Distance, Duration, Speed, Time};
+use geom::{Distance, Duration, GPSBounds, Pt2D, Speed, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, DirectedRoadID, FullNeighborhoodInfo, LaneID, Map,
-    PathConstraints, Position, RoadID,
+    BuildingID, BusRouteID, BusStopID, DirectedRoadID, FullNeighborhoodInfo, IntersectionID,
+    LaneID, Map, PathConstraints, Position, RoadID,
 };
 use rand::seq::SliceRandom;
 use rand::Rng;
 use rand_xorshift::XorShiftRng;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Scenario {
@@ -22,6 +22,9 @@ pub struct Scenario {
     // Higher-level ways of specifying stuff
     // None means seed all buses. Otherwise the route name must be present here.
     pub only_seed_buses: Option<BTreeSet<String>>,
+    // Also seed routes whose name starts with this. When this is set, only_seed_buses = None no
+    // longer means all routes; a route is seeded if it's in only_seed_buses or matches this.
+    pub only_seed_buses_matching: Option<String>,
     pub seed_parked_cars: Vec<SeedParkedCars>,
     pub spawn_over_time: Vec<SpawnOverTime>,
     pub border_spawn_over_time: Vec<BorderSpawnOverTime>,
@@ -39,10 +42,15 @@ pub struct SpawnOverTime {
     // TODO use https://docs.rs/rand/0.5.5/rand/distributions/struct.Normal.html
     pub start_time: Time,
     pub stop_time: Time,
-    pub start_from_neighborhood: String,
+    pub start_from_neighborhoods: WeightedNeighborhoodChoice,
     pub goal: OriginDestination,
     pub percent_biking: f64,
     pub percent_use_transit: f64,
+    // If set, every bike spawned uses exactly this instead of a random one. Cars come from
+    // parked cars, so a car spec isn't used here.
+    pub vehicle_spec: Option<VehicleSpec>,
+    // If set, walking speeds are picked from this (min, max) range instead of the usual 2-3mph.
+    pub ped_speed: Option<(Speed, Speed)>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -56,6 +64,28 @@ pub struct BorderSpawnOverTime {
     pub stop_time: Time,
     pub start_from_border: DirectedRoadID,
     pub goal: OriginDestination,
+    // If set, every car or bike (matching the spec's type) spawned uses exactly this instead of a
+    // random one.
+    pub vehicle_spec: Option<VehicleSpec>,
+    // If set, walking speeds are picked from this (min, max) range instead of the usual 2-3mph.
+    pub ped_speed: Option<(Speed, Speed)>,
+}
+
+// How many agents one BorderSpawnOverTime asked for, versus how many actually got scheduled. Some
+// are dropped when the border's lanes are unusable or no goal can be picked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BorderSpawnReport {
+    pub start_from_border: DirectedRoadID,
+    // (requested, scheduled)
+    pub peds: (usize, usize),
+    pub cars: (usize, usize),
+    pub bikes: (usize, usize),
+}
+
+impl BorderSpawnReport {
+    pub fn is_short(&self) -> bool {
+        self.peds.1 < self.peds.0 || self.cars.1 < self.cars.0 || self.bikes.1 < self.bikes.0
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -64,22 +94,69 @@ pub struct SeedParkedCars {
     pub cars_per_building: WeightedUsizeChoice,
 }
 
+// Represents the probability of picking each neighborhood. The sum can be anything.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct WeightedNeighborhoodChoice {
+    pub weights: Vec<(String, usize)>,
+}
+
+impl WeightedNeighborhoodChoice {
+    pub fn single(neighborhood: &str) -> WeightedNeighborhoodChoice {
+        WeightedNeighborhoodChoice {
+            weights: vec![(neighborhood.to_string(), 1)],
+        }
+    }
+
+    // Like "north:3,south:1". A neighborhood without a weight gets 1.
+    pub fn parse(string: &str) -> Option<WeightedNeighborhoodChoice> {
+        let mut weights = Vec::new();
+        for part in string.split(',') {
+            let mut pieces = part.splitn(2, ':');
+            let name = pieces.next()?.trim();
+            if name.is_empty() {
+                return None;
+            }
+            let weight = match pieces.next() {
+                Some(x) => x.trim().parse::<usize>().ok()?,
+                None => 1,
+            };
+            weights.push((name.to_string(), weight));
+        }
+        if weights.iter().all(|(_, w)| *w == 0) {
+            return None;
+        }
+        Some(WeightedNeighborhoodChoice { weights })
+    }
+
+    pub fn sample(&self, rng: &mut XorShiftRng) -> &str {
+        // Don't touch the RNG, so scenarios with one neighborhood spawn the same as before
+        if self.weights.len() == 1 {
+            return &self.weights[0].0;
+        }
+        let idx = WeightedUsizeChoice {
+            weights: self.weights.iter().map(|(_, w)| *w).collect(),
+        }
+        .sample(rng);
+        &self.weights[idx].0
+    }
+}
+
 impl Scenario {
     // TODO may need to fork the RNG a bit more
-    pub fn instantiate(&self, sim: &mut Sim, map: &Map, rng: &mut XorShiftRng, timer: &mut Timer) {
+    // Returns a report for every BorderSpawnOverTime entry, in order.
+    pub fn instantiate(
+        &self,
+        sim: &mut Sim,
+        map: &Map,
+        rng: &mut XorShiftRng,
+        timer: &mut Timer,
+    ) -> Vec<BorderSpawnReport> {
         sim.set_name(self.scenario_name.clone());
 
         timer.start(format!("Instantiating {}", self.scenario_name));
 
-        if let Some(ref routes) = self.only_seed_buses {
-            for route in map.get_all_bus_routes() {
-                if routes.contains(&route.name) {
-                    sim.seed_bus_route(route, map, timer);
-                }
-            }
-        } else {
-            // All of them
-            for route in map.get_all_bus_routes() {
+        for route in map.get_all_bus_routes() {
+            if self.should_seed_bus_route(&route.name) {
                 sim.seed_bus_route(route, map, timer);
             }
         }
@@ -88,7 +165,16 @@ impl Scenario {
         let neighborhoods = FullNeighborhoodInfo::load_all(map);
         timer.stop("load full neighborhood info");
 
+        if !sim.parking_enabled()
+            && (!self.seed_parked_cars.is_empty()
+                || !self.population.individ_parked_cars.is_empty())
+        {
+            timer.warn("Not seeding any parked cars, because parking is disabled".to_string());
+        }
         for s in &self.seed_parked_cars {
+            if !sim.parking_enabled() {
+                break;
+            }
             if !neighborhoods.contains_key(&s.neighborhood) {
                 panic!("Neighborhood {} isn't defined", s.neighborhood);
             }
@@ -108,8 +194,10 @@ impl Scenario {
         let mut reserved_cars: HashSet<CarID> = HashSet::new();
 
         for s in &self.spawn_over_time {
-            if !neighborhoods.contains_key(&s.start_from_neighborhood) {
-                panic!("Neighborhood {} isn't defined", s.start_from_neighborhood);
+            for (name, _) in &s.start_from_neighborhoods.weights {
+                if !neighborhoods.contains_key(name) {
+                    panic!("Neighborhood {} isn't defined", name);
+                }
             }
 
             timer.start_iter("SpawnOverTime each agent", s.num_agents);
@@ -119,12 +207,40 @@ impl Scenario {
             }
         }
 
+        let mut border_reports = Vec::new();
         timer.start_iter("BorderSpawnOverTime", self.border_spawn_over_time.len());
         for s in &self.border_spawn_over_time {
             timer.next();
-            s.spawn_peds(rng, sim, &neighborhoods, map, timer);
-            s.spawn_cars(rng, sim, &neighborhoods, map, timer);
-            s.spawn_bikes(rng, sim, &neighborhoods, map, timer);
+            border_reports.push(BorderSpawnReport {
+                start_from_border: s.start_from_border,
+                peds: (
+                    s.num_peds,
+                    s.spawn_peds(rng, sim, &neighborhoods, map, timer),
+                ),
+                cars: (
+                    s.num_cars,
+                    s.spawn_cars(rng, sim, &neighborhoods, map, timer),
+                ),
+                bikes: (
+                    s.num_bikes,
+                    s.spawn_bikes(rng, sim, &neighborhoods, map, timer),
+                ),
+            });
+        }
+        for r in &border_reports {
+            if r.is_short() {
+                timer.note(format!(
+                    "BorderSpawnOverTime from {} only scheduled {}/{} peds, {}/{} cars, {}/{} \
+                     bikes",
+                    r.start_from_border,
+                    r.peds.1,
+                    r.peds.0,
+                    r.cars.1,
+                    r.cars.0,
+                    r.bikes.1,
+                    r.bikes.0
+                ));
+            }
         }
 
         let mut individ_parked_cars: Vec<(BuildingID, usize)> = Vec::new();
@@ -134,17 +250,27 @@ impl Scenario {
             }
         }
         individ_parked_cars.shuffle(rng);
-        seed_individ_parked_cars(individ_parked_cars, sim, map, rng, timer);
+        if sim.parking_enabled() {
+            seed_individ_parked_cars(individ_parked_cars, sim, map, rng, timer);
+        }
+
+        for p in &self.population.people {
+            sim.new_person(p.id, p.home);
+        }
 
         timer.start_iter("IndividTrip", self.population.individ_trips.len());
         for t in &self.population.individ_trips {
             timer.next();
-            let spec = t.trip.clone().to_trip_spec(rng);
-            sim.schedule_trip(t.depart, spec, map);
+            if let Some(spec) = t.trip.clone().to_trip_spec(rng, map, sim.vehicle_sizes()) {
+                sim.schedule_person_trip(Some(t.person), t.depart, spec, map);
+            } else {
+                timer.warn(format!("Couldn't start {:?}", t.trip));
+            }
         }
 
         sim.spawn_all_trips(map, timer, true);
         timer.stop(format!("Instantiating {}", self.scenario_name));
+        border_reports
     }
 
     pub fn save(&self) {
@@ -154,10 +280,105 @@ impl Scenario {
         );
     }
 
+    pub fn should_seed_bus_route(&self, name: &str) -> bool {
+        let exact = self
+            .only_seed_buses
+            .as_ref()
+            .map(|routes| routes.contains(name));
+        match self.only_seed_buses_matching {
+            Some(ref prefix) => exact == Some(true) || name.starts_with(prefix),
+            // All of them
+            None => exact.unwrap_or(true),
+        }
+    }
+
+    // Before instantiating, make sure there's room for every parked car requested, counting cars
+    // already seeded in the sim. Returns a warning if not.
+    pub fn check_capacity(&self, sim: &Sim) -> Option<String> {
+        let (filled, free) = sim.get_all_parking_spots();
+        let requested: usize = self.population.individ_parked_cars.values().sum();
+        let demand = requested + filled.len();
+        let supply = filled.len() + free.len();
+        if demand > supply {
+            Some(format!(
+                "{} parked cars requested and {} already seeded, but the map only has {} parking \
+                 spots",
+                requested,
+                filled.len(),
+                supply
+            ))
+        } else {
+            None
+        }
+    }
+
+    // individ_parked_cars assumes trips are instantaneous, so a car used to leave a building
+    // might not have arrived there yet. Replays every driving trip, estimating arrival from the
+    // straight-line distance, and compares the peak number of cars simultaneously needed at each
+    // building against the number seeded. Negative means a shortfall, positive a surplus.
+    // Buildings that no driving trip touches and that seed no cars are omitted.
+    pub fn audit_parking(&self, map: &Map) -> Vec<(BuildingID, isize)> {
+        // Optimistic, so this never overstates overlap
+        let speed = Speed::miles_per_hour(30.0);
+
+        // (time, building, +1 for a car arriving or -1 for one leaving)
+        let mut events: Vec<(Time, BuildingID, isize)> = Vec::new();
+        for trip in &self.population.individ_trips {
+            let (from, goal) = match trip.trip {
+                SpawnTrip::MaybeUsingParkedCar(b, ref goal) => {
+                    events.push((trip.depart, b, -1));
+                    (map.get_b(b).polygon.center(), goal)
+                }
+                SpawnTrip::CarAppearing {
+                    start,
+                    ref goal,
+                    is_bike: false,
+                } => (start.pt(map), goal),
+                _ => {
+                    continue;
+                }
+            };
+            if let DrivingGoal::ParkNear(b) = goal {
+                let dist = from.dist_to(map.get_b(*b).polygon.center());
+                events.push((trip.depart + dist / speed, *b, 1));
+            }
+        }
+        // Arrivals happen before departures at the same time
+        events.sort_by_key(|(t, b, delta)| (*t, -*delta, *b));
+
+        let mut balance: BTreeMap<BuildingID, isize> = BTreeMap::new();
+        let mut lowest: BTreeMap<BuildingID, isize> = BTreeMap::new();
+        for (_, b, delta) in events {
+            let cnt = balance.entry(b).or_insert(0);
+            *cnt += delta;
+            let low = lowest.entry(b).or_insert(0);
+            *low = (*low).min(*cnt);
+        }
+        for (b, cnt) in &self.population.individ_parked_cars {
+            if *cnt > 0 {
+                lowest.entry(*b).or_insert(0);
+            }
+        }
+
+        lowest
+            .into_iter()
+            .map(|(b, low)| {
+                let seeded = self
+                    .population
+                    .individ_parked_cars
+                    .get(&b)
+                    .cloned()
+                    .unwrap_or(0) as isize;
+                (b, seeded + low)
+            })
+            .collect()
+    }
+
     pub fn small_run(map: &Map) -> Scenario {
         let mut s = Scenario {
             scenario_name: "small_run".to_string(),
             only_seed_buses: None,
+            only_seed_buses_matching: None,
             map_name: map.get_name().to_string(),
             seed_parked_cars: vec![SeedParkedCars {
                 neighborhood: "_everywhere_".to_string(),
@@ -169,10 +390,12 @@ impl Scenario {
                 num_agents: 100,
                 start_time: Time::START_OF_DAY,
                 stop_time: Time::START_OF_DAY + Duration::seconds(5.0),
-                start_from_neighborhood: "_everywhere_".to_string(),
+                start_from_neighborhoods: WeightedNeighborhoodChoice::single("_everywhere_"),
                 goal: OriginDestination::Neighborhood("_everywhere_".to_string()),
                 percent_biking: 0.5,
                 percent_use_transit: 0.5,
+                vehicle_spec: None,
+                ped_speed: None,
             }],
             // If there are no sidewalks/driving lanes at a border, scenario instantiation will
             // just warn and skip them.
@@ -188,6 +411,8 @@ impl Scenario {
                     start_from_border: i.some_outgoing_road(map),
                     goal: OriginDestination::Neighborhood("_everywhere_".to_string()),
                     percent_use_transit: 0.5,
+                    vehicle_spec: None,
+                    ped_speed: None,
                 })
                 .collect(),
             population: Population {
@@ -201,10 +426,12 @@ impl Scenario {
                 num_agents: 10,
                 start_time: Time::START_OF_DAY,
                 stop_time: Time::START_OF_DAY + Duration::seconds(5.0),
-                start_from_neighborhood: "_everywhere_".to_string(),
+                start_from_neighborhoods: WeightedNeighborhoodChoice::single("_everywhere_"),
                 goal: OriginDestination::EndOfRoad(i.some_incoming_road(map)),
                 percent_biking: 0.5,
                 percent_use_transit: 0.5,
+                vehicle_spec: None,
+                ped_speed: None,
             });
         }
         s
@@ -215,6 +442,7 @@ impl Scenario {
             scenario_name: name.to_string(),
             map_name: map.get_name().to_string(),
             only_seed_buses: Some(BTreeSet::new()),
+            only_seed_buses_matching: None,
             seed_parked_cars: Vec::new(),
             spawn_over_time: Vec::new(),
             border_spawn_over_time: Vec::new(),
@@ -232,6 +460,7 @@ impl Scenario {
             scenario_name: "scaled_run".to_string(),
             map_name: map.get_name().to_string(),
             only_seed_buses: Some(BTreeSet::new()),
+            only_seed_buses_matching: None,
             seed_parked_cars: vec![SeedParkedCars {
                 neighborhood: "_everywhere_".to_string(),
                 cars_per_building: WeightedUsizeChoice {
@@ -242,10 +471,12 @@ impl Scenario {
                 num_agents: num_agents,
                 start_time: Time::START_OF_DAY,
                 stop_time: Time::START_OF_DAY + Duration::seconds(5.0),
-                start_from_neighborhood: "_everywhere_".to_string(),
+                start_from_neighborhoods: WeightedNeighborhoodChoice::single("_everywhere_"),
                 goal: OriginDestination::Neighborhood("_everywhere_".to_string()),
                 percent_biking: 0.5,
                 percent_use_transit: 0.5,
+                vehicle_spec: None,
+                ped_speed: None,
             }],
             border_spawn_over_time: Vec::new(),
             population: Population {
@@ -256,26 +487,14 @@ impl Scenario {
         }
     }
 
+    // Uses the default VehicleSizes
     pub fn rand_car(rng: &mut XorShiftRng) -> VehicleSpec {
-        let length = Scenario::rand_dist(rng, MIN_CAR_LENGTH, MAX_CAR_LENGTH);
-        VehicleSpec {
-            vehicle_type: VehicleType::Car,
-            length,
-            max_speed: None,
-        }
+        VehicleSizes::default().rand_car(rng)
     }
 
+    // Uses the default VehicleSizes
     pub fn rand_bike(rng: &mut XorShiftRng) -> VehicleSpec {
-        let max_speed = Some(Scenario::rand_speed(
-            rng,
-            Speed::miles_per_hour(8.0),
-            Speed::miles_per_hour(10.0),
-        ));
-        VehicleSpec {
-            vehicle_type: VehicleType::Bike,
-            length: BIKE_LENGTH,
-            max_speed,
-        }
+        VehicleSizes::default().rand_bike(rng)
     }
 
     pub fn rand_dist(rng: &mut XorShiftRng, low: Distance, high: Distance) -> Distance {
@@ -301,6 +520,30 @@ impl Scenario {
     }
 }
 
+impl VehicleSizes {
+    pub fn rand_car(&self, rng: &mut XorShiftRng) -> VehicleSpec {
+        let length = Scenario::rand_dist(rng, self.min_car_length, self.max_car_length);
+        VehicleSpec {
+            vehicle_type: VehicleType::Car,
+            length,
+            max_speed: None,
+        }
+    }
+
+    pub fn rand_bike(&self, rng: &mut XorShiftRng) -> VehicleSpec {
+        let max_speed = Some(Scenario::rand_speed(
+            rng,
+            Speed::miles_per_hour(8.0),
+            Speed::miles_per_hour(10.0),
+        ));
+        VehicleSpec {
+            vehicle_type: VehicleType::Bike,
+            length: self.bike_length,
+            max_speed,
+        }
+    }
+}
+
 impl SpawnOverTime {
     fn spawn_agent(
         &self,
@@ -314,7 +557,7 @@ impl SpawnOverTime {
         let spawn_time = rand_time(rng, self.start_time, self.stop_time);
         // Note that it's fine for agents to start/end at the same building. Later we might
         // want a better assignment of people per household, or workers per office building.
-        let from_bldg = *neighborhoods[&self.start_from_neighborhood]
+        let from_bldg = *neighborhoods[self.start_from_neighborhoods.sample(rng)]
             .buildings
             .choose(rng)
             .unwrap();
@@ -337,7 +580,7 @@ impl SpawnOverTime {
                         start: SidewalkSpot::building(from_bldg, map),
                         spot,
                         goal,
-                        ped_speed: Scenario::rand_ped_speed(rng),
+                        ped_speed: rand_ped_speed_in(self.ped_speed, rng),
                     },
                     map,
                 );
@@ -351,13 +594,9 @@ impl SpawnOverTime {
                     .pick_driving_goal(PathConstraints::Bike, map, &neighborhoods, rng, timer)
             {
                 let start_at = map.get_b(from_bldg).sidewalk();
-                // TODO Just start biking on the other side of the street if the sidewalk
-                // is on a one-way. Or at least warn.
-                if map
-                    .get_parent(start_at)
-                    .sidewalk_to_bike(start_at)
-                    .is_some()
-                {
+                // This falls back to the other side of the street, if the building's sidewalk is
+                // on a one-way.
+                if SidewalkSpot::bike_from_bike_rack(start_at, map).is_some() {
                     let ok = if let DrivingGoal::ParkNear(to_bldg) = goal {
                         let end_at = map.get_b(to_bldg).sidewalk();
                         map.get_parent(end_at).sidewalk_to_bike(end_at).is_some()
@@ -370,9 +609,14 @@ impl SpawnOverTime {
                             spawn_time,
                             TripSpec::UsingBike {
                                 start: SidewalkSpot::building(from_bldg, map),
-                                vehicle: Scenario::rand_bike(rng),
+                                vehicle: exact_or_rand(
+                                    &self.vehicle_spec,
+                                    VehicleType::Bike,
+                                    sim.vehicle_sizes(),
+                                    rng,
+                                ),
                                 goal,
-                                ped_speed: Scenario::rand_ped_speed(rng),
+                                ped_speed: rand_ped_speed_in(self.ped_speed, rng),
                             },
                             map,
                         );
@@ -403,7 +647,7 @@ impl SpawnOverTime {
                             stop1,
                             stop2,
                             goal,
-                            ped_speed: Scenario::rand_ped_speed(rng),
+                            ped_speed: rand_ped_speed_in(self.ped_speed, rng),
                         },
                         map,
                     );
@@ -416,7 +660,7 @@ impl SpawnOverTime {
                 TripSpec::JustWalking {
                     start: start_spot,
                     goal,
-                    ped_speed: Scenario::rand_ped_speed(rng),
+                    ped_speed: rand_ped_speed_in(self.ped_speed, rng),
                 },
                 map,
             );
@@ -435,9 +679,9 @@ impl BorderSpawnOverTime {
         neighborhoods: &HashMap<String, FullNeighborhoodInfo>,
         map: &Map,
         timer: &mut Timer,
-    ) {
+    ) -> usize {
         if self.num_peds == 0 {
-            return;
+            return 0;
         }
 
         let start = if let Some(s) =
@@ -449,9 +693,10 @@ impl BorderSpawnOverTime {
                 "Can't start_at_border for {} without sidewalk",
                 self.start_from_border
             ));
-            return;
+            return 0;
         };
 
+        let mut scheduled = 0;
         for _ in 0..self.num_peds {
             let spawn_time = rand_time(rng, self.start_time, self.stop_time);
             if let Some(goal) = self.goal.pick_walking_goal(map, &neighborhoods, rng, timer) {
@@ -469,10 +714,11 @@ impl BorderSpawnOverTime {
                                 stop1,
                                 stop2,
                                 goal,
-                                ped_speed: Scenario::rand_ped_speed(rng),
+                                ped_speed: rand_ped_speed_in(self.ped_speed, rng),
                             },
                             map,
                         );
+                        scheduled += 1;
                         continue;
                     }
                 }
@@ -482,12 +728,14 @@ impl BorderSpawnOverTime {
                     TripSpec::JustWalking {
                         start: start.clone(),
                         goal,
-                        ped_speed: Scenario::rand_ped_speed(rng),
+                        ped_speed: rand_ped_speed_in(self.ped_speed, rng),
                     },
                     map,
                 );
+                scheduled += 1;
             }
         }
+        scheduled
     }
 
     fn spawn_cars(
@@ -497,13 +745,14 @@ impl BorderSpawnOverTime {
         neighborhoods: &HashMap<String, FullNeighborhoodInfo>,
         map: &Map,
         timer: &mut Timer,
-    ) {
+    ) -> usize {
         if self.num_cars == 0 {
-            return;
+            return 0;
         }
         let lanes = pick_starting_lanes(
             self.start_from_border.lanes(PathConstraints::Car, map),
             false,
+            sim.vehicle_sizes(),
             map,
         );
         if lanes.is_empty() {
@@ -511,28 +760,36 @@ impl BorderSpawnOverTime {
                 "Can't start {} cars at border for {}",
                 self.num_cars, self.start_from_border
             ));
-            return;
+            return 0;
         };
 
+        let mut scheduled = 0;
         for _ in 0..self.num_cars {
             let spawn_time = rand_time(rng, self.start_time, self.stop_time);
             if let Some(goal) =
                 self.goal
                     .pick_driving_goal(PathConstraints::Car, map, &neighborhoods, rng, timer)
             {
-                let vehicle = Scenario::rand_car(rng);
+                let vehicle = exact_or_rand(
+                    &self.vehicle_spec,
+                    VehicleType::Car,
+                    sim.vehicle_sizes(),
+                    rng,
+                );
                 sim.schedule_trip(
                     spawn_time,
                     TripSpec::CarAppearing {
                         start_pos: Position::new(*lanes.choose(rng).unwrap(), vehicle.length),
                         vehicle_spec: vehicle,
                         goal,
-                        ped_speed: Scenario::rand_ped_speed(rng),
+                        ped_speed: rand_ped_speed_in(self.ped_speed, rng),
                     },
                     map,
                 );
+                scheduled += 1;
             }
         }
+        scheduled
     }
 
     fn spawn_bikes(
@@ -542,13 +799,14 @@ impl BorderSpawnOverTime {
         neighborhoods: &HashMap<String, FullNeighborhoodInfo>,
         map: &Map,
         timer: &mut Timer,
-    ) {
+    ) -> usize {
         if self.num_bikes == 0 {
-            return;
+            return 0;
         }
         let lanes = pick_starting_lanes(
             self.start_from_border.lanes(PathConstraints::Bike, map),
             true,
+            sim.vehicle_sizes(),
             map,
         );
         if lanes.is_empty() {
@@ -556,28 +814,36 @@ impl BorderSpawnOverTime {
                 "Can't start {} bikes at border for {}",
                 self.num_bikes, self.start_from_border
             ));
-            return;
+            return 0;
         };
 
+        let mut scheduled = 0;
         for _ in 0..self.num_bikes {
             let spawn_time = rand_time(rng, self.start_time, self.stop_time);
             if let Some(goal) =
                 self.goal
                     .pick_driving_goal(PathConstraints::Bike, map, &neighborhoods, rng, timer)
             {
-                let bike = Scenario::rand_bike(rng);
+                let bike = exact_or_rand(
+                    &self.vehicle_spec,
+                    VehicleType::Bike,
+                    sim.vehicle_sizes(),
+                    rng,
+                );
                 sim.schedule_trip(
                     spawn_time,
                     TripSpec::CarAppearing {
                         start_pos: Position::new(*lanes.choose(rng).unwrap(), bike.length),
                         vehicle_spec: bike,
                         goal,
-                        ped_speed: Scenario::rand_ped_speed(rng),
+                        ped_speed: rand_ped_speed_in(self.ped_speed, rng),
                     },
                     map,
                 );
+                scheduled += 1;
             }
         }
+        scheduled
     }
 }
 
@@ -681,10 +947,13 @@ fn seed_parked_cars(
                 *b,
                 &mut open_spots_per_road,
                 neighborhoods_roads,
+                sim.parking_skip_probability(),
+                &mut forked_rng,
                 map,
                 timer,
             ) {
-                sim.seed_parked_car(Scenario::rand_car(&mut forked_rng), spot, Some(*b));
+                let vehicle = sim.vehicle_sizes().rand_car(&mut forked_rng);
+                sim.seed_parked_car(vehicle, spot, Some(*b));
                 new_cars += 1;
             } else {
                 // TODO This should be more critical, but neighborhoods can currently contain a
@@ -748,10 +1017,17 @@ fn seed_individ_parked_cars(
         }
         for _ in 0..cnt {
             // TODO Fork?
-            if let Some(spot) =
-                find_spot_near_building(b, &mut open_spots_per_road, &all_roads, map, timer)
-            {
-                sim.seed_parked_car(Scenario::rand_car(base_rng), spot, Some(b));
+            if let Some(spot) = find_spot_near_building(
+                b,
+                &mut open_spots_per_road,
+                &all_roads,
+                sim.parking_skip_probability(),
+                base_rng,
+                map,
+                timer,
+            ) {
+                let vehicle = sim.vehicle_sizes().rand_car(base_rng);
+                sim.seed_parked_car(vehicle, spot, Some(b));
             } else {
                 timer.warn("Not enough room to seed individual parked cars.".to_string());
                 ok = false;
@@ -763,47 +1039,106 @@ fn seed_individ_parked_cars(
 
 // Pick a parking spot for this building. If the building's road has a free spot, use it. If not,
 // start BFSing out from the road in a deterministic way until finding a nearby road with an open
-// spot.
+// spot. With skip_probability, a road with free spots is sometimes passed up to keep looking
+// farther away. If every road gets skipped, fall back to the last one passed up.
 fn find_spot_near_building(
     b: BuildingID,
     open_spots_per_road: &mut BTreeMap<RoadID, Vec<ParkingSpot>>,
     neighborhoods_roads: &BTreeSet<RoadID>,
+    skip_probability: f64,
+    rng: &mut XorShiftRng,
     map: &Map,
     timer: &mut Timer,
 ) -> Option<ParkingSpot> {
-    let mut roads_queue: VecDeque<RoadID> = VecDeque::new();
-    let mut visited: HashSet<RoadID> = HashSet::new();
-    {
-        let start = map.building_to_road(b).id;
-        roads_queue.push_back(start);
-        visited.insert(start);
+    let mut visited: Vec<RoadID> = Vec::new();
+    let mut last_skipped: Option<RoadID> = None;
+    let result = ParkingSimState::search_roads_near_building(
+        b,
+        map,
+        std::usize::MAX,
+        // Don't floodfill out of the neighborhood
+        |r| neighborhoods_roads.contains(&r),
+        |r| {
+            visited.push(r);
+            let spots = open_spots_per_road.get_mut(&r)?;
+            if spots.is_empty() {
+                return None;
+            }
+            // Only touch the RNG when skipping is possible, so a probability of 0 doesn't change
+            // anything downstream.
+            if skip_probability > 0.0 && rng.gen_bool(skip_probability) {
+                last_skipped = Some(r);
+                return None;
+            }
+            spots.pop()
+        },
+    )
+    .or_else(|| {
+        last_skipped.and_then(|r| {
+            open_spots_per_road
+                .get_mut(&r)
+                .and_then(|spots| spots.pop())
+        })
+    });
+    if result.is_none() {
+        timer.warn(format!(
+            "Giving up looking for a free parking spot, searched {} roads of {}: {:?}",
+            visited.len(),
+            open_spots_per_road.len(),
+            visited
+        ));
     }
+    result
+}
 
-    loop {
-        if roads_queue.is_empty() {
-            timer.warn(format!(
-                "Giving up looking for a free parking spot, searched {} roads of {}: {:?}",
-                visited.len(),
-                open_spots_per_road.len(),
-                visited
-            ));
-        }
-        let r = roads_queue.pop_front()?;
-        if let Some(spots) = open_spots_per_road.get_mut(&r) {
-            // TODO With some probability, skip this available spot and park farther away
-            if !spots.is_empty() {
-                return spots.pop();
-            }
-        }
+// Maps the number of trips along a demand arrow to its width, between 1 and 3 meters. The square
+// root keeps one popular destination from shrinking every other arrow to the minimum.
+pub fn demand_arrow_width(count: usize, max_count: usize) -> Distance {
+    if max_count == 0 {
+        return Distance::meters(1.0);
+    }
+    let pct = ((count as f64) / (max_count as f64)).sqrt().min(1.0);
+    Distance::meters(1.0) + pct * Distance::meters(2.0)
+}
 
-        for next_r in map.get_next_roads(r).into_iter() {
-            // Don't floodfill out of the neighborhood
-            if !visited.contains(&next_r) && neighborhoods_roads.contains(&next_r) {
-                roads_queue.push_back(next_r);
-                visited.insert(next_r);
+// The trips to and from one place as GeoJSON, for looking at demand in other GIS tools. Each
+// other endpoint is given as (point, outbound trips, inbound trips) and becomes one line, leading
+// away from home if there are any outbound trips.
+pub fn demand_geojson(
+    home: Pt2D,
+    endpoints: Vec<(Pt2D, usize, usize)>,
+    gps_bounds: &GPSBounds,
+) -> geojson::GeoJson {
+    let to_gps = |pt: Pt2D| {
+        let gps = pt.forcibly_to_gps(gps_bounds);
+        vec![gps.longitude, gps.latitude]
+    };
+    let features = endpoints
+        .into_iter()
+        .map(|(pt, outbound, inbound)| {
+            let line = if outbound > 0 {
+                vec![to_gps(home), to_gps(pt)]
+            } else {
+                vec![to_gps(pt), to_gps(home)]
+            };
+            let mut properties = serde_json::Map::new();
+            properties.insert("count".to_string(), (outbound + inbound).into());
+            properties.insert("outbound".to_string(), outbound.into());
+            properties.insert("inbound".to_string(), inbound.into());
+            geojson::Feature {
+                bbox: None,
+                geometry: Some(geojson::Geometry::new(geojson::Value::LineString(line))),
+                id: None,
+                properties: Some(properties),
+                foreign_members: None,
             }
-        }
-    }
+        })
+        .collect();
+    geojson::GeoJson::FeatureCollection(geojson::FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    })
 }
 
 fn rand_time(rng: &mut XorShiftRng, low: Time, high: Time) -> Time {
@@ -821,8 +1156,7 @@ pub struct IndividTrip {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum SpawnTrip {
     CarAppearing {
-        // TODO Replace start with building|border
-        start: Position,
+        start: CarAppearingStart,
         goal: DrivingGoal,
         // For bikes starting at a border, use CarAppearing. UsingBike implies a walk->bike trip.
         is_bike: bool,
@@ -830,24 +1164,83 @@ pub enum SpawnTrip {
     MaybeUsingParkedCar(BuildingID, DrivingGoal),
     UsingBike(SidewalkSpot, DrivingGoal),
     JustWalking(SidewalkSpot, SidewalkSpot),
+    // Suddenly appear somewhere along a sidewalk, then walk
+    WalkFromLane(LaneID, Distance, SidewalkSpot),
     UsingTransit(SidewalkSpot, SidewalkSpot, BusRouteID, BusStopID, BusStopID),
+    // Run errands along the way. Only walking and driving (starting from a border) work.
+    MultiStop(Vec<TripEndpoint>, TripMode),
 }
 
-impl SpawnTrip {
-    pub fn to_trip_spec(self, rng: &mut XorShiftRng) -> TripSpec {
+// Where a vehicle appears. Buildings and borders are resolved to a lane only when spawning.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum CarAppearingStart {
+    // Used as-is, for interactively placed vehicles
+    Lane(Position),
+    // Somewhere on the closest lane in front of the building
+    Bldg(BuildingID),
+    // The start of the first lane leaving the border
+    Border(IntersectionID),
+}
+
+impl CarAppearingStart {
+    // None if there's nowhere for the vehicle to fit
+    pub fn resolve(&self, is_bike: bool, map: &Map) -> Option<Position> {
         match self {
+            CarAppearingStart::Lane(pos) => Some(*pos),
+            CarAppearingStart::Bldg(b) => {
+                let pos = if is_bike {
+                    Position::bldg_via_biking(*b, map)
+                } else {
+                    Position::bldg_via_driving(*b, map)
+                }
+                .unwrap_or_else(|| {
+                    let l = map.find_driving_lane_near_building(*b);
+                    Position::new(l, map.get_l(l).length() / 2.0)
+                });
+                TripSpec::spawn_car_at(pos, map)
+            }
+            CarAppearingStart::Border(i) => {
+                let constraints = if is_bike {
+                    PathConstraints::Bike
+                } else {
+                    PathConstraints::Car
+                };
+                let lane = *map.get_i(*i).get_outgoing_lanes(map, constraints).get(0)?;
+                TripSpec::spawn_car_at(Position::new(lane, Distance::ZERO), map)
+            }
+        }
+    }
+
+    pub fn pt(&self, map: &Map) -> Pt2D {
+        match self {
+            CarAppearingStart::Lane(pos) => pos.pt(map),
+            CarAppearingStart::Bldg(b) => map.get_b(*b).polygon.center(),
+            CarAppearingStart::Border(i) => map.get_i(*i).polygon.center(),
+        }
+    }
+}
+
+impl SpawnTrip {
+    // None if a vehicle's start can't be resolved, or a pedestrian's start isn't on the sidewalk
+    pub fn to_trip_spec(
+        self,
+        rng: &mut XorShiftRng,
+        map: &Map,
+        sizes: VehicleSizes,
+    ) -> Option<TripSpec> {
+        Some(match self {
             SpawnTrip::CarAppearing {
                 start,
                 goal,
                 is_bike,
                 ..
             } => TripSpec::CarAppearing {
-                start_pos: start,
+                start_pos: start.resolve(is_bike, map)?,
                 goal,
                 vehicle_spec: if is_bike {
-                    Scenario::rand_bike(rng)
+                    sizes.rand_bike(rng)
                 } else {
-                    Scenario::rand_car(rng)
+                    sizes.rand_car(rng)
                 },
                 ped_speed: Scenario::rand_ped_speed(rng),
             },
@@ -859,7 +1252,7 @@ impl SpawnTrip {
             SpawnTrip::UsingBike(start, goal) => TripSpec::UsingBike {
                 start,
                 goal,
-                vehicle: Scenario::rand_bike(rng),
+                vehicle: sizes.rand_bike(rng),
                 ped_speed: Scenario::rand_ped_speed(rng),
             },
             SpawnTrip::JustWalking(start, goal) => TripSpec::JustWalking {
@@ -867,6 +1260,17 @@ impl SpawnTrip {
                 goal,
                 ped_speed: Scenario::rand_ped_speed(rng),
             },
+            SpawnTrip::WalkFromLane(l, dist, goal) => {
+                let lane = map.get_l(l);
+                if !lane.is_sidewalk() || dist > lane.length() {
+                    return None;
+                }
+                TripSpec::JustWalking {
+                    start: SidewalkSpot::suddenly_appear(l, dist, map),
+                    goal,
+                    ped_speed: Scenario::rand_ped_speed(rng),
+                }
+            }
             SpawnTrip::UsingTransit(start, goal, route, stop1, stop2) => TripSpec::UsingTransit {
                 start,
                 goal,
@@ -875,12 +1279,55 @@ impl SpawnTrip {
                 stop2,
                 ped_speed: Scenario::rand_ped_speed(rng),
             },
-        }
+            SpawnTrip::MultiStop(stops, mode) => TripSpec::MultiStop {
+                stops,
+                mode,
+                vehicle_spec: if mode == TripMode::Drive {
+                    Some(sizes.rand_car(rng))
+                } else {
+                    None
+                },
+                ped_speed: Scenario::rand_ped_speed(rng),
+            },
+        })
+    }
+}
+
+// Use the exact spec if it's for this type of vehicle
+fn rand_ped_speed_in(range: Option<(Speed, Speed)>, rng: &mut XorShiftRng) -> Speed {
+    match range {
+        Some((low, high)) => Scenario::rand_speed(rng, low, high),
+        None => Scenario::rand_ped_speed(rng),
     }
 }
 
-fn pick_starting_lanes(mut lanes: Vec<LaneID>, is_bike: bool, map: &Map) -> Vec<LaneID> {
-    let min_len = if is_bike { BIKE_LENGTH } else { MAX_CAR_LENGTH };
+fn exact_or_rand(
+    exact: &Option<VehicleSpec>,
+    vehicle_type: VehicleType,
+    sizes: VehicleSizes,
+    rng: &mut XorShiftRng,
+) -> VehicleSpec {
+    match exact {
+        Some(spec) if spec.vehicle_type == vehicle_type => spec.clone(),
+        _ => match vehicle_type {
+            VehicleType::Car => sizes.rand_car(rng),
+            VehicleType::Bike => sizes.rand_bike(rng),
+            VehicleType::Bus => unreachable!(),
+        },
+    }
+}
+
+fn pick_starting_lanes(
+    mut lanes: Vec<LaneID>,
+    is_bike: bool,
+    sizes: VehicleSizes,
+    map: &Map,
+) -> Vec<LaneID> {
+    let min_len = if is_bike {
+        sizes.bike_length
+    } else {
+        sizes.max_car_length
+    };
     lanes.retain(|l| map.get_l(*l).length() > min_len);
 
     if is_bike {
@@ -1,13 +1,21 @@
 use crate::{
-    CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, ParkingSimState, ParkingSpot,
-    PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TripLeg, TripManager, TripStart,
-    VehicleSpec, MAX_CAR_LENGTH,
+    AbortReason, Analytics, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal,
+    ParkingSimState, ParkingSpot, PedestrianID, PersonID, Scenario, Scheduler, SidewalkPOI,
+    SidewalkSpot, TripEndpoint, TripLeg, TripManager, TripMode, TripStart, VehicleSizes,
+    VehicleSpec, VehicleType, MAX_CAR_LENGTH,
 };
 use abstutil::Timer;
-use geom::{Speed, Time, EPSILON_DIST};
-use map_model::{BuildingID, BusRouteID, BusStopID, Map, PathConstraints, PathRequest, Position};
+use geom::{Distance, Duration, Speed, Time, EPSILON_DIST};
+use map_model::{
+    BuildingID, BusRouteID, BusStopID, Map, Path, PathConstraints, PathRequest, Position,
+    Traversable,
+};
+use rand_xorshift::XorShiftRng;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Bikes spawned by ModeChoice don't have a random speed.
+const MODE_CHOICE_BIKE_SPEED: Speed = Speed::const_meters_per_second(4.0);
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub enum TripSpec {
@@ -29,6 +37,14 @@ pub enum TripSpec {
         goal: DrivingGoal,
         ped_speed: Speed,
     },
+    // Borrow whatever unclaimed car is parked closest to the building, no matter who owns it. Gets
+    // resolved into UsingParkedCar when scheduled.
+    UsingNearbyParkedCar {
+        start_bldg: BuildingID,
+        radius: Distance,
+        goal: DrivingGoal,
+        ped_speed: Speed,
+    },
     JustWalking {
         start: SidewalkSpot,
         goal: SidewalkSpot,
@@ -48,12 +64,35 @@ pub enum TripSpec {
         stop2: BusStopID,
         ped_speed: Speed,
     },
+    // Visit each stop in order, pausing at the intermediate ones. Only walking and driving are
+    // supported. Driving trips have to start at a border.
+    MultiStop {
+        stops: Vec<TripEndpoint>,
+        mode: TripMode,
+        vehicle_spec: Option<VehicleSpec>,
+        ped_speed: Speed,
+    },
+    // Pick whichever of the options is estimated to be fastest when the trip is spawned. Gets
+    // resolved into one of the other specs in spawn_all. Transit isn't supported yet.
+    ModeChoice {
+        start_bldg: BuildingID,
+        goal: TripEndpoint,
+        ped_speed: Speed,
+        options: Vec<TripMode>,
+    },
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct TripSpawner {
     parked_cars_claimed: BTreeSet<CarID>,
-    trips: Vec<(Time, Option<PedestrianID>, Option<CarID>, TripSpec)>,
+    trips: Vec<TripToSpawn>,
+    // With lazy spawning, trips wait here until they depart, keyed by the Command::SpawnTrip that
+    // will spawn them. Also remembers retry_if_no_room.
+    deferred: BTreeMap<usize, (TripToSpawn, bool)>,
+    next_deferred: usize,
+    // Problems with deferred trips, until the next Timer collects them
+    #[serde(skip_serializing, skip_deserializing)]
+    deferred_warnings: Vec<String>,
 }
 
 impl TripSpawner {
@@ -61,18 +100,51 @@ impl TripSpawner {
         TripSpawner {
             parked_cars_claimed: BTreeSet::new(),
             trips: Vec::new(),
+            deferred: BTreeMap::new(),
+            next_deferred: 0,
+            deferred_warnings: Vec::new(),
         }
     }
 
     pub fn schedule_trip(
         &mut self,
+        person: Option<PersonID>,
         start_time: Time,
         ped_id: Option<PedestrianID>,
         car_id: Option<CarID>,
         spec: TripSpec,
         map: &Map,
-        parking: &ParkingSimState,
+        parking: &mut ParkingSimState,
     ) {
+        let spec = match spec {
+            TripSpec::UsingNearbyParkedCar {
+                start_bldg,
+                radius,
+                goal,
+                ped_speed,
+            } => {
+                if let Some(spot) =
+                    parking.find_car_near(start_bldg, radius, &self.parked_cars_claimed, map)
+                {
+                    TripSpec::UsingParkedCar {
+                        start: SidewalkSpot::building(start_bldg, map),
+                        spot,
+                        goal,
+                        ped_speed,
+                    }
+                } else {
+                    // spawn_all will abort the trip.
+                    TripSpec::UsingNearbyParkedCar {
+                        start_bldg,
+                        radius,
+                        goal,
+                        ped_speed,
+                    }
+                }
+            }
+            x => x,
+        };
+
         // TODO We'll want to repeat this validation when we spawn stuff later for a second leg...
         match &spec {
             TripSpec::CarAppearing {
@@ -102,11 +174,23 @@ impl TripSpawner {
                             panic!("Can't start a car at the edge of a border already");
                         }
                     }
+                    DrivingGoal::AnyLane(_, lanes) => {
+                        if lanes.is_empty() {
+                            panic!("A car wants to end at a border, but has no lanes to try");
+                        }
+                        if lanes.iter().all(|l| {
+                            start_pos.lane() == *l
+                                && start_pos.dist_along() == map.get_l(*l).length()
+                        }) {
+                            panic!("Can't start a car at the edge of a border already");
+                        }
+                    }
                     DrivingGoal::ParkNear(_) => {}
                 }
             }
-            TripSpec::UsingParkedCar { spot, .. } => {
-                let car_id = parking.get_car_at_spot(*spot).unwrap().vehicle.id;
+            TripSpec::UsingParkedCar { start, spot, .. } => {
+                let vehicle = &parking.get_car_at_spot(*spot).unwrap().vehicle;
+                let car_id = vehicle.id;
                 if self.parked_cars_claimed.contains(&car_id) {
                     panic!(
                         "A TripSpec wants to use {}, which is already claimed",
@@ -114,8 +198,14 @@ impl TripSpawner {
                     );
                 }
                 self.parked_cars_claimed.insert(car_id);
+                // Somebody's borrowing the car, so the owner can't take it later.
+                if vehicle.owner.is_some()
+                    && start.connection != SidewalkPOI::Building(vehicle.owner.unwrap())
+                {
+                    parking.dynamically_reserve_specific_car(car_id);
+                }
             }
-            TripSpec::MaybeUsingParkedCar { .. } => {}
+            TripSpec::MaybeUsingParkedCar { .. } | TripSpec::UsingNearbyParkedCar { .. } => {}
             TripSpec::JustWalking { start, goal, .. } => {
                 if start == goal {
                     panic!(
@@ -168,310 +258,631 @@ impl TripSpawner {
                                 goal: SidewalkSpot::building(*b, map),
                                 ped_speed: *ped_speed,
                             },
+                            person,
                         ));
                         return;
                     }
                 }
             }
             TripSpec::UsingTransit { .. } => {}
+            TripSpec::ModeChoice { options, .. } => {
+                if options.is_empty() || options.contains(&TripMode::Transit) {
+                    panic!("ModeChoice can't pick from {:?}", options);
+                }
+            }
+            TripSpec::MultiStop {
+                stops,
+                mode,
+                vehicle_spec,
+                ..
+            } => {
+                if stops.len() < 2 {
+                    panic!("A MultiStop trip needs at least two stops, not {:?}", stops);
+                }
+                for stop in &stops[1..stops.len() - 1] {
+                    if let TripEndpoint::Border(_) = stop {
+                        panic!(
+                            "A MultiStop trip can't pass through {:?} along the way",
+                            stop
+                        );
+                    }
+                }
+                match mode {
+                    TripMode::Walk => {
+                        if stops[0].start_sidewalk_spot(map).is_none()
+                            || stops.last().unwrap().end_sidewalk_spot(map).is_none()
+                        {
+                            println!("Can't walk between {:?}; no sidewalks at a border", stops);
+                            return;
+                        }
+                    }
+                    TripMode::Drive => {
+                        if vehicle_spec.is_none() {
+                            panic!("A MultiStop driving trip needs a vehicle");
+                        }
+                        if TripSpec::multi_stop_start_pos(&stops[0], map).is_none()
+                            || stops
+                                .last()
+                                .unwrap()
+                                .driving_goal(PathConstraints::Car, map)
+                                .is_none()
+                        {
+                            println!("Can't drive between {:?}; no room at a border", stops);
+                            return;
+                        }
+                    }
+                    TripMode::Bike | TripMode::Transit => {
+                        panic!("MultiStop trips can't use {}", mode);
+                    }
+                }
+            }
         };
 
-        self.trips.push((start_time, ped_id, car_id, spec));
+        self.trips.push((start_time, ped_id, car_id, spec, person));
     }
 
     pub fn spawn_all(
         &mut self,
         map: &Map,
         parking: &ParkingSimState,
+        analytics: &Analytics,
         trips: &mut TripManager,
         scheduler: &mut Scheduler,
+        vehicle_sizes: VehicleSizes,
         timer: &mut Timer,
         retry_if_no_room: bool,
     ) {
-        let paths = timer.parallelize(
-            "calculate paths",
-            std::mem::replace(&mut self.trips, Vec::new()),
-            |tuple| {
-                let req = tuple.3.get_pathfinding_request(map, parking);
-                (tuple, req.clone(), map.pathfind(req))
-            },
-        );
+        let to_spawn = std::mem::replace(&mut self.trips, Vec::new())
+            .into_iter()
+            .map(|tuple| resolve_mode_choice(tuple, map, parking, analytics, vehicle_sizes))
+            .collect();
+
+        let paths = timer.parallelize("calculate paths", to_spawn, |tuple| {
+            let (req, path) = tuple.3.pathfind(map, parking);
+            (tuple, req, path)
+        });
 
         timer.start_iter("spawn trips", paths.len());
-        for ((start_time, ped_id, car_id, spec), req, maybe_path) in paths {
+        let mut warnings = Vec::new();
+        for (tuple, req, maybe_path) in paths {
             timer.next();
-            match spec {
-                TripSpec::CarAppearing {
-                    start_pos,
-                    vehicle_spec,
+            spawn_trip(
+                tuple,
+                req,
+                maybe_path,
+                map,
+                parking,
+                trips,
+                scheduler,
+                &mut warnings,
+                retry_if_no_room,
+            );
+        }
+        for line in warnings {
+            timer.warn(line);
+        }
+
+        timer.start("finalize spawned trips");
+        scheduler.finalize_batch();
+        timer.stop("finalize spawned trips");
+    }
+
+    // Instead of pathfinding everything now, wait until each trip departs. Problems with a trip
+    // aren't noticed until then.
+    pub fn defer_all(&mut self, scheduler: &mut Scheduler, retry_if_no_room: bool) {
+        for tuple in std::mem::replace(&mut self.trips, Vec::new()) {
+            let id = self.next_deferred;
+            self.next_deferred += 1;
+            scheduler.push(tuple.0, Command::SpawnTrip(id));
+            self.deferred.insert(id, (tuple, retry_if_no_room));
+        }
+    }
+
+    // Called when a deferred trip departs. Mode choice and pathfinding happen now.
+    pub fn spawn_deferred(
+        &mut self,
+        id: usize,
+        map: &Map,
+        parking: &ParkingSimState,
+        analytics: &Analytics,
+        trips: &mut TripManager,
+        scheduler: &mut Scheduler,
+        vehicle_sizes: VehicleSizes,
+    ) {
+        let (tuple, retry_if_no_room) = self.deferred.remove(&id).unwrap();
+        let tuple = resolve_mode_choice(tuple, map, parking, analytics, vehicle_sizes);
+        let (req, maybe_path) = tuple.3.pathfind(map, parking);
+        spawn_trip(
+            tuple,
+            req,
+            maybe_path,
+            map,
+            parking,
+            trips,
+            scheduler,
+            &mut self.deferred_warnings,
+            retry_if_no_room,
+        );
+    }
+
+    pub fn collect_warnings(&mut self) -> Vec<String> {
+        std::mem::replace(&mut self.deferred_warnings, Vec::new())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.trips.is_empty()
+    }
+
+    // Deferred trips that haven't departed yet
+    pub fn num_deferred(&self) -> usize {
+        self.deferred.len()
+    }
+}
+
+type TripToSpawn = (
+    Time,
+    Option<PedestrianID>,
+    Option<CarID>,
+    TripSpec,
+    Option<PersonID>,
+);
+
+// ModeChoice trips become one of the other specs, based on what's estimated to be fastest right
+// now.
+fn resolve_mode_choice(
+    (start_time, ped_id, car_id, spec, person): TripToSpawn,
+    map: &Map,
+    parking: &ParkingSimState,
+    analytics: &Analytics,
+    vehicle_sizes: VehicleSizes,
+) -> TripToSpawn {
+    let spec = match spec {
+        TripSpec::ModeChoice {
+            start_bldg,
+            goal,
+            ped_speed,
+            mut options,
+        } => {
+            // Can't drive without a car parked at home
+            if parking.get_parked_cars_by_owner(start_bldg).is_empty() {
+                options.retain(|m| *m != TripMode::Drive);
+            }
+            match TripSpec::choose_mode(start_bldg, &goal, ped_speed, &options, map, analytics) {
+                Some(TripMode::Walk) => TripSpec::JustWalking {
+                    start: SidewalkSpot::building(start_bldg, map),
+                    goal: goal.end_sidewalk_spot(map).unwrap(),
+                    ped_speed,
+                },
+                Some(TripMode::Drive) => TripSpec::MaybeUsingParkedCar {
+                    start_bldg,
+                    goal: goal.driving_goal(PathConstraints::Car, map).unwrap(),
+                    ped_speed,
+                },
+                Some(TripMode::Bike) => TripSpec::UsingBike {
+                    start: SidewalkSpot::building(start_bldg, map),
+                    goal: goal.driving_goal(PathConstraints::Bike, map).unwrap(),
+                    vehicle: VehicleSpec {
+                        vehicle_type: VehicleType::Bike,
+                        length: vehicle_sizes.bike_length,
+                        max_speed: Some(MODE_CHOICE_BIKE_SPEED),
+                    },
+                    ped_speed,
+                },
+                // Below, the trip is aborted.
+                Some(TripMode::Transit) | None => TripSpec::ModeChoice {
+                    start_bldg,
                     goal,
                     ped_speed,
-                } => {
-                    // Assumption: If a car is appearing at a border and driving to a building,
-                    // then it's owned by that building. Otherwise we wind up with endless waves of
-                    // parked cars that're never reused.
-                    let owner = if let DrivingGoal::ParkNear(b) = goal {
-                        Some(b)
-                    } else {
-                        None
-                    };
-                    let vehicle = vehicle_spec.make(car_id.unwrap(), owner);
-                    let mut legs = vec![TripLeg::Drive(vehicle.clone(), goal.clone())];
-                    if let DrivingGoal::ParkNear(b) = goal {
-                        legs.push(TripLeg::Walk(
-                            ped_id.unwrap(),
-                            ped_speed,
-                            SidewalkSpot::building(b, map),
-                        ));
-                    }
-                    let trip_start = TripStart::Border(map.get_l(start_pos.lane()).src_i);
-                    let trip = trips.new_trip(start_time, trip_start, legs);
-                    if let Some(path) = maybe_path {
-                        let router = goal.make_router(path, map, vehicle.vehicle_type);
-                        scheduler.quick_push(
-                            start_time,
-                            Command::SpawnCar(
-                                CreateCar::for_appearing(vehicle, start_pos, router, req, trip),
-                                retry_if_no_room,
-                            ),
-                        );
-                    } else {
-                        timer.warn(format!(
-                            "CarAppearing trip couldn't find the first path {}",
-                            req
-                        ));
-                        trips.abort_trip_failed_start(trip);
+                    options,
+                },
+            }
+        }
+        x => x,
+    };
+    (start_time, ped_id, car_id, spec, person)
+}
+
+// Creates the trip and schedules its first agent, given the path found for the first leg. Any
+// problems are added to warnings, and the trip is aborted.
+fn spawn_trip(
+    (start_time, ped_id, car_id, spec, person): TripToSpawn,
+    req: PathRequest,
+    maybe_path: Option<Path>,
+    map: &Map,
+    parking: &ParkingSimState,
+    trips: &mut TripManager,
+    scheduler: &mut Scheduler,
+    warnings: &mut Vec<String>,
+    retry_if_no_room: bool,
+) {
+    match spec {
+        TripSpec::CarAppearing {
+            start_pos,
+            vehicle_spec,
+            goal,
+            ped_speed,
+        } => {
+            // Assumption: If a car is appearing at a border and driving to a building,
+            // then it's owned by that building. Otherwise we wind up with endless waves of
+            // parked cars that're never reused.
+            let owner = if let DrivingGoal::ParkNear(b) = goal {
+                Some(b)
+            } else {
+                None
+            };
+            let vehicle = vehicle_spec.make(car_id.unwrap(), owner);
+            let mut legs = vec![TripLeg::Drive(vehicle.clone(), goal.clone())];
+            if let DrivingGoal::ParkNear(b) = goal {
+                legs.push(TripLeg::Walk(
+                    ped_id.unwrap(),
+                    ped_speed,
+                    SidewalkSpot::building(b, map),
+                ));
+            }
+            let trip_start = TripStart::Border(map.get_l(start_pos.lane()).src_i);
+            let trip = trips.new_trip(person, start_time, trip_start, legs);
+            if let Some(path) = maybe_path {
+                let router = goal.make_router(path, map, vehicle.vehicle_type);
+                scheduler.quick_push(
+                    start_time,
+                    Command::SpawnCar(
+                        CreateCar::for_appearing(vehicle, start_pos, router, req, trip),
+                        retry_if_no_room,
+                    ),
+                );
+            } else {
+                warnings.push(format!(
+                    "CarAppearing trip couldn't find the first path {}",
+                    req
+                ));
+                trips.abort_trip_failed_start(trip, AbortReason::NoPath);
+            }
+        }
+        TripSpec::UsingParkedCar {
+            start,
+            spot,
+            goal,
+            ped_speed,
+        } => {
+            let vehicle = &parking.get_car_at_spot(spot).unwrap().vehicle;
+            // The car might belong to somebody else, if it was borrowed.
+            let start_bldg = match start.connection {
+                SidewalkPOI::Building(b) => b,
+                _ => unreachable!(),
+            };
+
+            let parking_spot = SidewalkSpot::parking_spot(spot, map, parking);
+
+            let mut legs = vec![
+                TripLeg::Walk(ped_id.unwrap(), ped_speed, parking_spot.clone()),
+                TripLeg::Drive(vehicle.clone(), goal.clone()),
+            ];
+            match goal {
+                DrivingGoal::ParkNear(b) => {
+                    legs.push(TripLeg::Walk(
+                        ped_id.unwrap(),
+                        ped_speed,
+                        SidewalkSpot::building(b, map),
+                    ));
+                }
+                DrivingGoal::Border(_, _) | DrivingGoal::AnyLane(_, _) => {}
+            }
+            let trip = trips.new_trip(person, start_time, TripStart::Bldg(start_bldg), legs);
+
+            if let Some(path) = maybe_path {
+                scheduler.quick_push(
+                    start_time,
+                    Command::SpawnPed(CreatePedestrian {
+                        id: ped_id.unwrap(),
+                        speed: ped_speed,
+                        start,
+                        goal: parking_spot,
+                        path,
+                        req,
+                        trip,
+                    }),
+                );
+            } else {
+                warnings.push(format!(
+                    "UsingParkedCar trip couldn't find the first path {}",
+                    req
+                ));
+                trips.abort_trip_failed_start(trip, AbortReason::NoPath);
+            }
+        }
+        TripSpec::MaybeUsingParkedCar {
+            start_bldg,
+            goal,
+            ped_speed,
+        } => {
+            let walk_to = SidewalkSpot::deferred_parking_spot(start_bldg, goal, map);
+            // Can't add TripLeg::Drive, because we don't know the vehicle yet! Plumb along
+            // the DrivingGoal, so we can expand the trip later.
+            let legs = vec![TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone())];
+            let trip = trips.new_trip(person, start_time, TripStart::Bldg(start_bldg), legs);
+
+            scheduler.quick_push(
+                start_time,
+                Command::SpawnPed(CreatePedestrian {
+                    id: ped_id.unwrap(),
+                    speed: ped_speed,
+                    start: SidewalkSpot::building(start_bldg, map),
+                    goal: walk_to,
+                    // This is guaranteed to work, and is junk anyway.
+                    path: maybe_path.unwrap(),
+                    req,
+                    trip,
+                }),
+            );
+        }
+        // Only still around if there was no car to borrow
+        TripSpec::UsingNearbyParkedCar {
+            start_bldg,
+            goal,
+            ped_speed,
+            ..
+        } => {
+            let legs = vec![TripLeg::Walk(
+                ped_id.unwrap(),
+                ped_speed,
+                SidewalkSpot::deferred_parking_spot(start_bldg, goal, map),
+            )];
+            let trip = trips.new_trip(person, start_time, TripStart::Bldg(start_bldg), legs);
+            warnings.push(format!(
+                "UsingNearbyParkedCar trip from {} couldn't find a car to borrow",
+                start_bldg
+            ));
+            trips.abort_trip_failed_start(trip, AbortReason::FailedToSpawn);
+        }
+        // Only still around if no mode worked
+        TripSpec::ModeChoice {
+            start_bldg,
+            goal,
+            ped_speed,
+            options,
+        } => {
+            let legs = vec![TripLeg::Walk(
+                ped_id.unwrap(),
+                ped_speed,
+                goal.end_sidewalk_spot(map)
+                    .unwrap_or_else(|| SidewalkSpot::building(start_bldg, map)),
+            )];
+            let trip = trips.new_trip(person, start_time, TripStart::Bldg(start_bldg), legs);
+            warnings.push(format!(
+                "ModeChoice trip from {} to {:?} can't use any of {:?}",
+                start_bldg, goal, options
+            ));
+            trips.abort_trip_failed_start(trip, AbortReason::NoPath);
+        }
+        TripSpec::JustWalking {
+            start,
+            goal,
+            ped_speed,
+        } => {
+            let trip = trips.new_trip(
+                person,
+                start_time,
+                match start.connection {
+                    SidewalkPOI::Building(b) => TripStart::Bldg(b),
+                    SidewalkPOI::SuddenlyAppear => {
+                        TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
                     }
+                    SidewalkPOI::Border(i) => TripStart::Border(i),
+                    _ => unreachable!(),
+                },
+                vec![TripLeg::Walk(ped_id.unwrap(), ped_speed, goal.clone())],
+            );
+
+            if let Some(path) = maybe_path {
+                scheduler.quick_push(
+                    start_time,
+                    Command::SpawnPed(CreatePedestrian {
+                        id: ped_id.unwrap(),
+                        speed: ped_speed,
+                        start,
+                        goal,
+                        path,
+                        req,
+                        trip,
+                    }),
+                );
+            } else {
+                warnings.push(format!(
+                    "JustWalking trip couldn't find the first path {}",
+                    req
+                ));
+                trips.abort_trip_failed_start(trip, AbortReason::NoPath);
+            }
+        }
+        TripSpec::UsingBike {
+            start,
+            vehicle,
+            goal,
+            ped_speed,
+        } => {
+            let walk_to =
+                SidewalkSpot::bike_from_bike_rack(start.sidewalk_pos.lane(), map).unwrap();
+            let mut legs = vec![
+                TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone()),
+                TripLeg::Drive(vehicle.make(car_id.unwrap(), None), goal.clone()),
+            ];
+            match goal {
+                DrivingGoal::ParkNear(b) => {
+                    legs.push(TripLeg::Walk(
+                        ped_id.unwrap(),
+                        ped_speed,
+                        SidewalkSpot::building(b, map),
+                    ));
                 }
-                TripSpec::UsingParkedCar {
-                    start,
-                    spot,
-                    goal,
-                    ped_speed,
-                } => {
-                    let vehicle = &parking.get_car_at_spot(spot).unwrap().vehicle;
-                    match start.connection {
-                        SidewalkPOI::Building(b) => assert_eq!(vehicle.owner, Some(b)),
-                        _ => unreachable!(),
-                    };
-
-                    let parking_spot = SidewalkSpot::parking_spot(spot, map, parking);
-
-                    let mut legs = vec![
-                        TripLeg::Walk(ped_id.unwrap(), ped_speed, parking_spot.clone()),
-                        TripLeg::Drive(vehicle.clone(), goal.clone()),
-                    ];
-                    match goal {
-                        DrivingGoal::ParkNear(b) => {
-                            legs.push(TripLeg::Walk(
-                                ped_id.unwrap(),
-                                ped_speed,
-                                SidewalkSpot::building(b, map),
-                            ));
-                        }
-                        DrivingGoal::Border(_, _) => {}
+                DrivingGoal::Border(_, _) | DrivingGoal::AnyLane(_, _) => {}
+            };
+            let trip = trips.new_trip(
+                person,
+                start_time,
+                match start.connection {
+                    SidewalkPOI::Building(b) => TripStart::Bldg(b),
+                    SidewalkPOI::SuddenlyAppear => {
+                        TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
                     }
-                    let trip =
-                        trips.new_trip(start_time, TripStart::Bldg(vehicle.owner.unwrap()), legs);
+                    SidewalkPOI::Border(i) => TripStart::Border(i),
+                    _ => unreachable!(),
+                },
+                legs,
+            );
 
-                    if let Some(path) = maybe_path {
-                        scheduler.quick_push(
-                            start_time,
-                            Command::SpawnPed(CreatePedestrian {
-                                id: ped_id.unwrap(),
-                                speed: ped_speed,
-                                start,
-                                goal: parking_spot,
-                                path,
-                                req,
-                                trip,
-                            }),
-                        );
-                    } else {
-                        timer.warn(format!(
-                            "UsingParkedCar trip couldn't find the first path {}",
-                            req
+            if let Some(path) = maybe_path {
+                scheduler.quick_push(
+                    start_time,
+                    Command::SpawnPed(CreatePedestrian {
+                        id: ped_id.unwrap(),
+                        speed: ped_speed,
+                        start,
+                        goal: walk_to,
+                        path,
+                        req,
+                        trip,
+                    }),
+                );
+            } else {
+                warnings.push(format!(
+                    "UsingBike trip couldn't find the first path {}",
+                    req
+                ));
+                trips.abort_trip_failed_start(trip, AbortReason::NoPath);
+            }
+        }
+        TripSpec::UsingTransit {
+            start,
+            route,
+            stop1,
+            stop2,
+            goal,
+            ped_speed,
+        } => {
+            let walk_to = SidewalkSpot::bus_stop(stop1, map);
+            let trip = trips.new_trip(
+                person,
+                start_time,
+                match start.connection {
+                    SidewalkPOI::Building(b) => TripStart::Bldg(b),
+                    SidewalkPOI::SuddenlyAppear => {
+                        TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
+                    }
+                    SidewalkPOI::Border(i) => TripStart::Border(i),
+                    _ => unreachable!(),
+                },
+                vec![
+                    TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone()),
+                    TripLeg::RideBus(ped_id.unwrap(), route, stop2),
+                    TripLeg::Walk(ped_id.unwrap(), ped_speed, goal),
+                ],
+            );
+
+            if let Some(path) = maybe_path {
+                scheduler.quick_push(
+                    start_time,
+                    Command::SpawnPed(CreatePedestrian {
+                        id: ped_id.unwrap(),
+                        speed: ped_speed,
+                        start,
+                        goal: walk_to,
+                        path,
+                        req,
+                        trip,
+                    }),
+                );
+            } else {
+                warnings.push(format!(
+                    "UsingTransit trip couldn't find the first path {}",
+                    req
+                ));
+                trips.abort_trip_failed_start(trip, AbortReason::NoPath);
+            }
+        }
+        TripSpec::MultiStop {
+            stops,
+            mode,
+            vehicle_spec,
+            ped_speed,
+        } => {
+            let ped = ped_id.unwrap();
+            let mut legs = Vec::new();
+            let trip_start = stops[0].to_trip_start();
+            if mode == TripMode::Drive {
+                // Same assumption as CarAppearing: the car belongs to wherever it winds
+                // up.
+                let owner = match stops.last().unwrap() {
+                    TripEndpoint::Bldg(b) => Some(*b),
+                    TripEndpoint::Border(_) => None,
+                };
+                let vehicle = vehicle_spec.unwrap().make(car_id.unwrap(), owner);
+                for stop in &stops[1..] {
+                    let goal = stop.driving_goal(PathConstraints::Car, map).unwrap();
+                    legs.push(TripLeg::Drive(vehicle.clone(), goal));
+                    if let TripEndpoint::Bldg(b) = stop {
+                        legs.push(TripLeg::Walk(
+                            ped,
+                            ped_speed,
+                            SidewalkSpot::building(*b, map),
                         ));
-                        trips.abort_trip_failed_start(trip);
                     }
                 }
-                TripSpec::MaybeUsingParkedCar {
-                    start_bldg,
-                    goal,
-                    ped_speed,
-                } => {
-                    let walk_to = SidewalkSpot::deferred_parking_spot(start_bldg, goal, map);
-                    // Can't add TripLeg::Drive, because we don't know the vehicle yet! Plumb along
-                    // the DrivingGoal, so we can expand the trip later.
-                    let legs = vec![TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone())];
-                    let trip = trips.new_trip(start_time, TripStart::Bldg(start_bldg), legs);
+                let trip = trips.new_trip(person, start_time, trip_start, legs);
 
+                if let Some(path) = maybe_path {
+                    let start_pos = req.start;
+                    let goal = stops[1].driving_goal(PathConstraints::Car, map).unwrap();
+                    let router = goal.make_router(path, map, vehicle.vehicle_type);
                     scheduler.quick_push(
                         start_time,
-                        Command::SpawnPed(CreatePedestrian {
-                            id: ped_id.unwrap(),
-                            speed: ped_speed,
-                            start: SidewalkSpot::building(start_bldg, map),
-                            goal: walk_to,
-                            // This is guaranteed to work, and is junk anyway.
-                            path: maybe_path.unwrap(),
-                            req,
-                            trip,
-                        }),
+                        Command::SpawnCar(
+                            CreateCar::for_appearing(vehicle, start_pos, router, req, trip),
+                            retry_if_no_room,
+                        ),
                     );
+                } else {
+                    warnings.push(format!(
+                        "MultiStop trip couldn't find the first path {}",
+                        req
+                    ));
+                    trips.abort_trip_failed_start(trip, AbortReason::NoPath);
                 }
-                TripSpec::JustWalking {
-                    start,
-                    goal,
-                    ped_speed,
-                } => {
-                    let trip = trips.new_trip(
-                        start_time,
-                        match start.connection {
-                            SidewalkPOI::Building(b) => TripStart::Bldg(b),
-                            SidewalkPOI::SuddenlyAppear => {
-                                TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
-                            }
-                            SidewalkPOI::Border(i) => TripStart::Border(i),
-                            _ => unreachable!(),
-                        },
-                        vec![TripLeg::Walk(ped_id.unwrap(), ped_speed, goal.clone())],
-                    );
-
-                    if let Some(path) = maybe_path {
-                        scheduler.quick_push(
-                            start_time,
-                            Command::SpawnPed(CreatePedestrian {
-                                id: ped_id.unwrap(),
-                                speed: ped_speed,
-                                start,
-                                goal,
-                                path,
-                                req,
-                                trip,
-                            }),
-                        );
-                    } else {
-                        timer.warn(format!(
-                            "JustWalking trip couldn't find the first path {}",
-                            req
-                        ));
-                        trips.abort_trip_failed_start(trip);
-                    }
+            } else {
+                for stop in &stops[1..] {
+                    legs.push(TripLeg::Walk(
+                        ped,
+                        ped_speed,
+                        stop.end_sidewalk_spot(map).unwrap(),
+                    ));
                 }
-                TripSpec::UsingBike {
-                    start,
-                    vehicle,
-                    goal,
-                    ped_speed,
-                } => {
-                    let walk_to =
-                        SidewalkSpot::bike_from_bike_rack(start.sidewalk_pos.lane(), map).unwrap();
-                    let mut legs = vec![
-                        TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone()),
-                        TripLeg::Drive(vehicle.make(car_id.unwrap(), None), goal.clone()),
-                    ];
-                    match goal {
-                        DrivingGoal::ParkNear(b) => {
-                            legs.push(TripLeg::Walk(
-                                ped_id.unwrap(),
-                                ped_speed,
-                                SidewalkSpot::building(b, map),
-                            ));
-                        }
-                        DrivingGoal::Border(_, _) => {}
-                    };
-                    let trip = trips.new_trip(
-                        start_time,
-                        match start.connection {
-                            SidewalkPOI::Building(b) => TripStart::Bldg(b),
-                            SidewalkPOI::SuddenlyAppear => {
-                                TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
-                            }
-                            SidewalkPOI::Border(i) => TripStart::Border(i),
-                            _ => unreachable!(),
-                        },
-                        legs,
-                    );
+                let goal = match legs[0] {
+                    TripLeg::Walk(_, _, ref spot) => spot.clone(),
+                    _ => unreachable!(),
+                };
+                let trip = trips.new_trip(person, start_time, trip_start, legs);
 
-                    if let Some(path) = maybe_path {
-                        scheduler.quick_push(
-                            start_time,
-                            Command::SpawnPed(CreatePedestrian {
-                                id: ped_id.unwrap(),
-                                speed: ped_speed,
-                                start,
-                                goal: walk_to,
-                                path,
-                                req,
-                                trip,
-                            }),
-                        );
-                    } else {
-                        timer.warn(format!(
-                            "UsingBike trip couldn't find the first path {}",
-                            req
-                        ));
-                        trips.abort_trip_failed_start(trip);
-                    }
-                }
-                TripSpec::UsingTransit {
-                    start,
-                    route,
-                    stop1,
-                    stop2,
-                    goal,
-                    ped_speed,
-                } => {
-                    let walk_to = SidewalkSpot::bus_stop(stop1, map);
-                    let trip = trips.new_trip(
+                if let Some(path) = maybe_path {
+                    scheduler.quick_push(
                         start_time,
-                        match start.connection {
-                            SidewalkPOI::Building(b) => TripStart::Bldg(b),
-                            SidewalkPOI::SuddenlyAppear => {
-                                TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
-                            }
-                            SidewalkPOI::Border(i) => TripStart::Border(i),
-                            _ => unreachable!(),
-                        },
-                        vec![
-                            TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone()),
-                            TripLeg::RideBus(ped_id.unwrap(), route, stop2),
-                            TripLeg::Walk(ped_id.unwrap(), ped_speed, goal),
-                        ],
+                        Command::SpawnPed(CreatePedestrian {
+                            id: ped,
+                            speed: ped_speed,
+                            start: stops[0].start_sidewalk_spot(map).unwrap(),
+                            goal,
+                            path,
+                            req,
+                            trip,
+                        }),
                     );
-
-                    if let Some(path) = maybe_path {
-                        scheduler.quick_push(
-                            start_time,
-                            Command::SpawnPed(CreatePedestrian {
-                                id: ped_id.unwrap(),
-                                speed: ped_speed,
-                                start,
-                                goal: walk_to,
-                                path,
-                                req,
-                                trip,
-                            }),
-                        );
-                    } else {
-                        timer.warn(format!(
-                            "UsingTransit trip couldn't find the first path {}",
-                            req
-                        ));
-                        trips.abort_trip_failed_start(trip);
-                    }
+                } else {
+                    warnings.push(format!(
+                        "MultiStop trip couldn't find the first path {}",
+                        req
+                    ));
+                    trips.abort_trip_failed_start(trip, AbortReason::NoPath);
                 }
             }
         }
-
-        timer.start("finalize spawned trips");
-        scheduler.finalize_batch();
-        timer.stop("finalize spawned trips");
-    }
-
-    pub fn is_done(&self) -> bool {
-        self.trips.is_empty()
     }
 }
 
@@ -493,6 +904,191 @@ impl TripSpec {
         }
     }
 
+    // For interactive debugging: somebody suddenly appears at start and heads to the goal.
+    // Pedestrians appear on a sidewalk; cars and bikes appear on a lane they're allowed to use.
+    pub fn appearing_on_lane(
+        start: Position,
+        goal: &TripEndpoint,
+        mode: TripMode,
+        sizes: VehicleSizes,
+        map: &Map,
+        rng: &mut XorShiftRng,
+    ) -> Result<TripSpec, String> {
+        let lane = map.get_l(start.lane());
+        let ped_speed = Scenario::rand_ped_speed(rng);
+        let (vehicle_spec, constraints) = match mode {
+            TripMode::Walk => {
+                if !lane.is_sidewalk() {
+                    return Err(format!("Pedestrians can't appear on {}", lane.id));
+                }
+                let goal = goal
+                    .end_sidewalk_spot(map)
+                    .ok_or_else(|| format!("Can't end a walking trip at {:?}", goal))?;
+                return Ok(TripSpec::JustWalking {
+                    start: SidewalkSpot::suddenly_appear(lane.id, start.dist_along(), map),
+                    goal,
+                    ped_speed,
+                });
+            }
+            TripMode::Drive => (sizes.rand_car(rng), PathConstraints::Car),
+            TripMode::Bike => (sizes.rand_bike(rng), PathConstraints::Bike),
+            TripMode::Transit => {
+                return Err("Buses can't suddenly appear".to_string());
+            }
+        };
+        if !constraints.can_use(lane, map) {
+            return Err(format!("A {} can't appear on {}", mode, lane.id));
+        }
+        let start_pos = TripSpec::spawn_car_at(start, map)
+            .ok_or_else(|| format!("{} is too short to appear on", lane.id))?;
+        let goal = goal
+            .driving_goal(constraints, map)
+            .ok_or_else(|| format!("Can't end a {} trip at {:?}", mode, goal))?;
+        Ok(TripSpec::CarAppearing {
+            start_pos,
+            goal,
+            vehicle_spec,
+            ped_speed,
+        })
+    }
+
+    // Picks whichever option is estimated to be fastest right now, using speed limits along the
+    // path plus the median delay measured so far at each intersection crossed. Ties go to the
+    // earliest option.
+    pub fn choose_mode(
+        start_bldg: BuildingID,
+        goal: &TripEndpoint,
+        ped_speed: Speed,
+        options: &Vec<TripMode>,
+        map: &Map,
+        analytics: &Analytics,
+    ) -> Option<TripMode> {
+        options
+            .iter()
+            .filter_map(|mode| {
+                let dt = TripSpec::estimate_trip_time(
+                    *mode, start_bldg, goal, ped_speed, map, analytics,
+                )?;
+                Some((*mode, dt))
+            })
+            .min_by_key(|(_, dt)| *dt)
+            .map(|(mode, _)| mode)
+    }
+
+    // None if the mode can't be used for this trip
+    fn estimate_trip_time(
+        mode: TripMode,
+        start_bldg: BuildingID,
+        goal: &TripEndpoint,
+        ped_speed: Speed,
+        map: &Map,
+        analytics: &Analytics,
+    ) -> Option<Duration> {
+        let start_sidewalk = map.get_b(start_bldg).front_path.sidewalk;
+        let (constraints, start, max_speed) = match mode {
+            TripMode::Walk => {
+                let path = map.pathfind(PathRequest {
+                    start: start_sidewalk,
+                    end: goal.end_sidewalk_spot(map)?.sidewalk_pos,
+                    constraints: PathConstraints::Pedestrian,
+                })?;
+                return Some(path.total_length() / ped_speed);
+            }
+            TripMode::Drive => {
+                let lane = map.find_driving_lane_near_building(start_bldg);
+                (
+                    PathConstraints::Car,
+                    start_sidewalk.equiv_pos(lane, Distance::ZERO, map),
+                    None,
+                )
+            }
+            TripMode::Bike => {
+                let rack = SidewalkSpot::bike_from_bike_rack(start_sidewalk.lane(), map)?;
+                let pos = match rack.connection {
+                    SidewalkPOI::BikeRack(pos) => pos,
+                    _ => unreachable!(),
+                };
+                (PathConstraints::Bike, pos, Some(MODE_CHOICE_BIKE_SPEED))
+            }
+            TripMode::Transit => {
+                return None;
+            }
+        };
+
+        let driving_goal = goal.driving_goal(constraints, map)?;
+        let end = driving_goal.goal_pos(constraints, map);
+        if mode == TripMode::Bike {
+            if let DrivingGoal::ParkNear(_) = driving_goal {
+                // Same check as schedule_trip
+                map.get_parent(end.lane()).bike_to_sidewalk(end.lane())?;
+            }
+        }
+        let path = map.pathfind(PathRequest {
+            start,
+            end,
+            constraints,
+        })?;
+        let mut total = Duration::ZERO;
+        for step in path.get_steps() {
+            let t = step.as_traversable();
+            let speed = match max_speed {
+                Some(s) => t.speed_limit(map).min(s),
+                None => t.speed_limit(map),
+            };
+            total += t.length(map) / speed;
+            if let Traversable::Turn(turn) = t {
+                if let Some((_, median, _)) = analytics.delay_stats(turn.parent) {
+                    total += median;
+                }
+            }
+        }
+        Some(total)
+    }
+
+    // Where a car starting a MultiStop trip appears
+    fn multi_stop_start_pos(start: &TripEndpoint, map: &Map) -> Option<Position> {
+        match start {
+            TripEndpoint::Border(i) => {
+                let lane = *map
+                    .get_i(*i)
+                    .get_outgoing_lanes(map, PathConstraints::Car)
+                    .get(0)?;
+                TripSpec::spawn_car_at(Position::new(lane, Distance::ZERO), map)
+            }
+            TripEndpoint::Bldg(_) => None,
+        }
+    }
+
+    // Like get_pathfinding_request, but for goals with a choice of lanes, picks one that has a
+    // path.
+    pub(crate) fn pathfind(
+        &self,
+        map: &Map,
+        parking: &ParkingSimState,
+    ) -> (PathRequest, Option<Path>) {
+        match self {
+            TripSpec::CarAppearing {
+                start_pos,
+                vehicle_spec,
+                goal,
+                ..
+            } => goal.pathfind(*start_pos, vehicle_spec.vehicle_type.to_constraints(), map),
+            TripSpec::MultiStop { stops, mode, .. } if *mode == TripMode::Drive => stops[1]
+                .driving_goal(PathConstraints::Car, map)
+                .unwrap()
+                .pathfind(
+                    TripSpec::multi_stop_start_pos(&stops[0], map).unwrap(),
+                    PathConstraints::Car,
+                    map,
+                ),
+            _ => {
+                let req = self.get_pathfinding_request(map, parking);
+                let path = map.pathfind(req.clone());
+                (req, path)
+            }
+        }
+    }
+
     pub(crate) fn get_pathfinding_request(
         &self,
         map: &Map,
@@ -519,7 +1115,9 @@ impl TripSpec {
             },
             // Don't know where the parked car will be, so just make a dummy path that'll never
             // fail.
-            TripSpec::MaybeUsingParkedCar { start_bldg, .. } => {
+            TripSpec::MaybeUsingParkedCar { start_bldg, .. }
+            | TripSpec::UsingNearbyParkedCar { start_bldg, .. }
+            | TripSpec::ModeChoice { start_bldg, .. } => {
                 let pos = map.get_b(*start_bldg).front_path.sidewalk;
                 PathRequest {
                     start: pos,
@@ -544,6 +1142,24 @@ impl TripSpec {
                 end: SidewalkSpot::bus_stop(*stop1, map).sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
             },
+            TripSpec::MultiStop { stops, mode, .. } => {
+                if *mode == TripMode::Drive {
+                    PathRequest {
+                        start: TripSpec::multi_stop_start_pos(&stops[0], map).unwrap(),
+                        end: stops[1]
+                            .driving_goal(PathConstraints::Car, map)
+                            .unwrap()
+                            .goal_pos(PathConstraints::Car, map),
+                        constraints: PathConstraints::Car,
+                    }
+                } else {
+                    PathRequest {
+                        start: stops[0].start_sidewalk_spot(map).unwrap().sidewalk_pos,
+                        end: stops[1].end_sidewalk_spot(map).unwrap().sidewalk_pos,
+                        constraints: PathConstraints::Pedestrian,
+                    }
+                }
+            }
         }
     }
 }
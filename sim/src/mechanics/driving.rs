@@ -2,13 +2,13 @@ use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
 use crate::{
     ActionAtEnd, AgentID, AgentMetadata, CarID, Command, CreateCar, DistanceInterval, DrawCarInput,
-    Event, IntersectionSimState, ParkedCar, ParkingSimState, Scheduler, TimeInterval,
-    TransitSimState, TripManager, TripPositions, UnzoomedAgent, VehicleType, WalkingSimState,
-    FOLLOWING_DISTANCE,
+    DrivingGoal, Event, IntersectionSimState, ParkedCar, ParkingSimState, Scheduler, TimeInterval,
+    TransitSimState, TripManager, TripPositions, UnzoomedAgent, Vehicle, VehicleType,
+    WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{BuildingID, LaneID, Map, Path, PathStep, Traversable};
+use map_model::{BuildingID, IntersectionID, LaneID, Map, Path, PathStep, Position, Traversable};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet, VecDeque};
 
@@ -36,6 +36,7 @@ pub struct DrivingSimState {
 
     recalc_lanechanging: bool,
     clear_laggy_head_early: bool,
+    following_distance: Distance,
 }
 
 impl DrivingSimState {
@@ -43,6 +44,7 @@ impl DrivingSimState {
         map: &Map,
         recalc_lanechanging: bool,
         clear_laggy_head_early: bool,
+        following_distance: Distance,
     ) -> DrivingSimState {
         let mut sim = DrivingSimState {
             cars: BTreeMap::new(),
@@ -50,17 +52,18 @@ impl DrivingSimState {
             events: Vec::new(),
             recalc_lanechanging,
             clear_laggy_head_early,
+            following_distance,
         };
 
         for l in map.all_lanes() {
             if l.lane_type.is_for_moving_vehicles() {
-                let q = Queue::new(Traversable::Lane(l.id), map);
+                let q = Queue::new(Traversable::Lane(l.id), following_distance, map);
                 sim.queues.insert(q.id, q);
             }
         }
         for t in map.all_turns().values() {
             if !t.between_sidewalks() {
-                let q = Queue::new(Traversable::Turn(t.id), map);
+                let q = Queue::new(Traversable::Turn(t.id), following_distance, map);
                 sim.queues.insert(q.id, q);
             }
         }
@@ -145,7 +148,7 @@ impl DrivingSimState {
                 queue.cars.insert(idx, car.vehicle.id);
                 // Don't use try_to_reserve_entry -- it's overly conservative.
                 // get_idx_to_insert_car does a more detailed check of the current space usage.
-                queue.reserved_length += car.vehicle.length + FOLLOWING_DISTANCE;
+                queue.reserved_length += car.vehicle.length + self.following_distance;
             }
             self.cars.insert(car.vehicle.id, car);
             return true;
@@ -306,7 +309,7 @@ impl DrivingSimState {
                                 follower.total_blocked_time += now - blocked_since;
                                 follower.state = follower.crossing_state(
                                     // Since the follower was Queued, this must be where they are.
-                                    dist - car.vehicle.length - FOLLOWING_DISTANCE,
+                                    dist - car.vehicle.length - self.following_distance,
                                     now,
                                     map,
                                 );
@@ -373,6 +376,7 @@ impl DrivingSimState {
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                 self.events.push(Event::AgentEntersTraversable(
                     AgentID::Car(car.vehicle.id),
+                    car.trip,
                     goto,
                 ));
 
@@ -383,7 +387,7 @@ impl DrivingSimState {
                 // TODO Don't even bother updating laggy head (which will unblock intermediate
                 // steps and call turn_finished and such) if we're bound for a tiny lane. Unless
                 // we're trying the experimental clear_laggy_head_early strategy.
-                if goto.length(map) >= car.vehicle.length + FOLLOWING_DISTANCE
+                if goto.length(map) >= car.vehicle.length + self.following_distance
                     || self.clear_laggy_head_early
                 {
                     // Optimistically assume we'll be out of the way ASAP.
@@ -394,7 +398,7 @@ impl DrivingSimState {
                         car.crossing_state_with_end_dist(
                             DistanceInterval::new_driving(
                                 Distance::ZERO,
-                                car.vehicle.length + FOLLOWING_DISTANCE,
+                                car.vehicle.length + self.following_distance,
                             ),
                             now,
                             map,
@@ -540,6 +544,49 @@ impl DrivingSimState {
         }
     }
 
+    // Replaces the rest of the car's route with a path to a new goal. The car keeps the step it's
+    // on, plus the next turn and lane if it's on a lane, since it may have already asked to do
+    // that turn. Err if the car is on its last step or parking, where changing course isn't safe.
+    // Ok(None) if there's no path to the new goal, otherwise the length of the whole route, before
+    // and after the change.
+    pub fn change_goal(
+        &mut self,
+        id: CarID,
+        goal: &DrivingGoal,
+        map: &Map,
+    ) -> Result<Option<(Distance, Distance)>, String> {
+        let car = self.cars.get_mut(&id).unwrap();
+        match car.state {
+            CarState::Parking(_, _, _) | CarState::Idling(_, _) => {
+                return Err(format!("{} is stopped", id));
+            }
+            _ => {}
+        }
+        if car.router.last_step() {
+            return Err(format!("{} is already on the last step of its route", id));
+        }
+
+        let (keep_steps, from) = match car.router.head() {
+            Traversable::Lane(_) => (3, car.router.get_path().get_steps()[2]),
+            Traversable::Turn(_) => (2, car.router.get_path().get_steps()[1]),
+        };
+        let start = Position::new(from.as_lane(), Distance::ZERO);
+        let rest = match goal.pathfind(start, car.vehicle.vehicle_type.to_constraints(), map) {
+            (_, Some(path)) => path,
+            (_, None) => {
+                return Ok(None);
+            }
+        };
+        let mut path = car.router.get_path().clone();
+        let old_length = path.total_length();
+        path.splice(keep_steps, rest, map);
+        let new_length = path.total_length();
+        car.router = goal.make_router(path, map, car.vehicle.vehicle_type);
+        self.events
+            .push(Event::PathAmended(car.router.get_path().clone()));
+        Ok(Some((old_length, new_length)))
+    }
+
     pub fn kill_stuck_car(
         &mut self,
         c: CarID,
@@ -559,7 +606,7 @@ impl DrivingSimState {
         // Hacks to delete cars that're mid-turn
         if let Traversable::Turn(_) = car.router.head() {
             let queue = self.queues.get_mut(&car.router.head()).unwrap();
-            queue.reserved_length += car.vehicle.length + FOLLOWING_DISTANCE;
+            queue.reserved_length += car.vehicle.length + self.following_distance;
         }
         if let Some(Traversable::Turn(t)) = car.router.maybe_next() {
             intersections.cancel_request(AgentID::Car(c), t);
@@ -655,7 +702,7 @@ impl DrivingSimState {
                 );
             }
         }
-        let our_len = self.cars[&id].vehicle.length + FOLLOWING_DISTANCE;
+        let our_len = self.cars[&id].vehicle.length + self.following_distance;
 
         // Have we made it far enough yet? Unfortunately, we have some math imprecision issues...
         {
@@ -795,6 +842,25 @@ impl DrivingSimState {
         self.cars.values().map(|car| car.metadata(now)).collect()
     }
 
+    // Cars that've been waiting longer than the threshold, and how long
+    pub fn find_stuck_cars(&self, now: Time, threshold: Duration) -> Vec<(CarID, Duration)> {
+        self.cars
+            .values()
+            .filter_map(|car| match car.state {
+                CarState::Queued { blocked_since }
+                | CarState::WaitingToAdvance { blocked_since } => {
+                    let dt = now - blocked_since;
+                    if dt > threshold {
+                        Some((car.vehicle.id, dt))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn does_car_exist(&self, id: CarID) -> bool {
         self.cars.contains_key(&id)
     }
@@ -972,20 +1038,115 @@ impl DrivingSimState {
         car.vehicle.owner
     }
 
-    // TODO Clean this up
+    pub fn get_vehicle(&self, id: CarID) -> Option<&Vehicle> {
+        Some(&self.cars.get(&id)?.vehicle)
+    }
+
     pub fn find_blockage_front(
         &self,
         start: CarID,
         map: &Map,
         intersections: &IntersectionSimState,
     ) -> String {
+        match self.trace_blockage(start, map, intersections) {
+            Blockage::Gridlock(i, chain) => format!(
+                "Gridlock near {}! {:?}",
+                i,
+                chain.into_iter().map(|(i, _)| i).collect::<Vec<_>>()
+            ),
+            Blockage::Other(reason) => reason,
+        }
+    }
+
+    // If the car is part of gridlock, returns every intersection along the chain of blockage
+    // starting from it, and the car stuck at the front of the queue waiting for each one.
+    pub fn gridlock_chain(
+        &self,
+        start: CarID,
+        map: &Map,
+        intersections: &IntersectionSimState,
+    ) -> Option<Vec<(IntersectionID, CarID)>> {
+        match self.trace_blockage(start, map, intersections) {
+            Blockage::Gridlock(_, chain) => Some(chain),
+            Blockage::Other(_) => None,
+        }
+    }
+
+    // Every car from start to the front of whatever's holding it up, in order, so each car is
+    // waiting on the next one. A car at the front of its lane is waiting on the last car in the
+    // next lane, if that lane is full. Stops when the chain loops back around in gridlock.
+    pub fn blockage_chain(&self, start: CarID) -> Vec<CarID> {
+        let mut chain = vec![start];
+        let mut current = start;
+        let mut on = self.cars[&start].router.head();
+        loop {
+            let queue = &self.queues[&on];
+            let idx = queue.cars.iter().position(|c| *c == current).unwrap();
+            for c in queue.cars.iter().take(idx).rev() {
+                if chain.contains(c) {
+                    return chain;
+                }
+                chain.push(*c);
+            }
+            let front = *chain.last().unwrap();
+
+            // Is the front car stuck waiting for room on its next lane?
+            let steps = self.cars[&front].router.get_path().get_steps();
+            let next_step = match on {
+                Traversable::Lane(_) => steps.get(2),
+                Traversable::Turn(_) => steps.get(1),
+            };
+            let next_lane = if let Some(PathStep::Lane(l)) = next_step {
+                Traversable::Lane(*l)
+            } else {
+                return chain;
+            };
+            let next_queue = &self.queues[&next_lane];
+            if next_queue.room_for_car(&self.cars[&front]) {
+                return chain;
+            }
+            current = if let Some(c) = next_queue.cars.back() {
+                *c
+            } else {
+                return chain;
+            };
+            if chain.contains(&current) {
+                return chain;
+            }
+            chain.push(current);
+            on = next_lane;
+        }
+    }
+
+    pub fn is_gridlocked(
+        &self,
+        start: CarID,
+        map: &Map,
+        intersections: &IntersectionSimState,
+    ) -> bool {
+        match self.trace_blockage(start, map, intersections) {
+            Blockage::Gridlock(_, _) => true,
+            Blockage::Other(_) => false,
+        }
+    }
+
+    // TODO Clean this up
+    fn trace_blockage(
+        &self,
+        start: CarID,
+        map: &Map,
+        intersections: &IntersectionSimState,
+    ) -> Blockage {
         let mut seen_intersections = HashSet::new();
+        let mut chain = Vec::new();
 
         let mut current_head = start;
         let mut current_lane = match self.cars[&start].router.head() {
             Traversable::Lane(l) => l,
             Traversable::Turn(_) => {
-                return "TODO Doesn't support starting from a turn yet".to_string();
+                return Blockage::Other(
+                    "TODO Doesn't support starting from a turn yet".to_string(),
+                );
             }
         };
         loop {
@@ -993,14 +1154,15 @@ impl DrivingSimState {
                 if let Some(c) = self.queues[&Traversable::Lane(current_lane)].cars.get(0) {
                     *c
                 } else {
-                    return format!("no gridlock, {}", current_head);
+                    return Blockage::Other(format!("no gridlock, {}", current_head));
                 };
 
             let i = map.get_l(current_lane).dst_i;
             if seen_intersections.contains(&i) {
-                return format!("Gridlock near {}! {:?}", i, seen_intersections);
+                return Blockage::Gridlock(i, chain);
             }
             seen_intersections.insert(i);
+            chain.push((i, current_head));
 
             // Why isn't current_head proceeding? Pedestrians can never get stuck in an
             // intersection.
@@ -1012,7 +1174,7 @@ impl DrivingSimState {
                     _ => false,
                 })
             {
-                return format!("someone's turning in {} still", i);
+                return Blockage::Other(format!("someone's turning in {} still", i));
             }
 
             current_lane = if let Some(PathStep::Lane(l)) = self.cars[&current_head]
@@ -1023,16 +1185,19 @@ impl DrivingSimState {
             {
                 *l
             } else {
-                return format!(
+                return Blockage::Other(format!(
                     "{} is near end of path, probably tmp blockage",
                     current_head
-                );
+                ));
             };
 
             // Lack of capacity?
             if self.queues[&Traversable::Lane(current_lane)].room_for_car(&self.cars[&current_head])
             {
-                return format!("{} is about to proceed, tmp blockage", current_head);
+                return Blockage::Other(format!(
+                    "{} is about to proceed, tmp blockage",
+                    current_head
+                ));
             }
         }
     }
@@ -1041,3 +1206,11 @@ impl DrivingSimState {
         std::mem::replace(&mut self.events, Vec::new())
     }
 }
+
+enum Blockage {
+    // Where the cycle was detected, and every intersection along the way, with the car waiting
+    // for it
+    Gridlock(IntersectionID, Vec<(IntersectionID, CarID)>),
+    // A description of why there's no gridlock
+    Other(String),
+}
@@ -5,9 +5,9 @@ use abstutil::{
 };
 use geom::{Distance, Duration, Pt2D};
 use map_model;
-use map_model::{BuildingID, Lane, LaneID, LaneType, Map, Position, Traversable};
+use map_model::{BuildingID, Lane, LaneID, LaneType, Map, Position, RoadID, Traversable};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct ParkingSimState {
@@ -98,7 +98,7 @@ impl ParkingSimState {
 
     pub fn get_free_offstreet_spots(&self, b: BuildingID) -> Vec<ParkingSpot> {
         let mut spots: Vec<ParkingSpot> = Vec::new();
-        for idx in 0..self.num_spots_per_offstreet.get(&b).cloned().unwrap_or(0) {
+        for idx in 0..self.get_offstreet_capacity(b) {
             let spot = ParkingSpot::offstreet(b, idx);
             if self.is_free(spot) {
                 spots.push(spot);
@@ -107,6 +107,89 @@ impl ParkingSimState {
         spots
     }
 
+    // Free spots on the building's road first, then spreading out to nearby roads in a
+    // deterministic order. Searches at most max_roads roads.
+    pub fn free_spots_near_building(
+        &self,
+        b: BuildingID,
+        map: &Map,
+        max_roads: usize,
+    ) -> Vec<ParkingSpot> {
+        let mut spots = Vec::new();
+        ParkingSimState::search_roads_near_building(
+            b,
+            map,
+            max_roads,
+            |_| true,
+            |r| -> Option<()> {
+                let road = map.get_r(r);
+                for (l, _) in road
+                    .children_forwards
+                    .iter()
+                    .chain(road.children_backwards.iter())
+                {
+                    spots.extend(self.get_free_spots(*l));
+                }
+                None
+            },
+        );
+        spots
+    }
+
+    // Breadth-first search over roads, starting from the building's road and only expanding to
+    // allowed roads. Stops at the first road where visit returns something, or after visiting
+    // max_roads.
+    pub(crate) fn search_roads_near_building<T>(
+        b: BuildingID,
+        map: &Map,
+        max_roads: usize,
+        allowed: impl Fn(RoadID) -> bool,
+        mut visit: impl FnMut(RoadID) -> Option<T>,
+    ) -> Option<T> {
+        let mut roads_queue: VecDeque<RoadID> = VecDeque::new();
+        let mut visited: HashSet<RoadID> = HashSet::new();
+        {
+            let start = map.building_to_road(b).id;
+            roads_queue.push_back(start);
+            visited.insert(start);
+        }
+
+        let mut num_visited = 0;
+        while let Some(r) = roads_queue.pop_front() {
+            if num_visited == max_roads {
+                return None;
+            }
+            num_visited += 1;
+            if let Some(result) = visit(r) {
+                return Some(result);
+            }
+
+            for next_r in map.get_next_roads(r).into_iter() {
+                if !visited.contains(&next_r) && allowed(next_r) {
+                    roads_queue.push_back(next_r);
+                    visited.insert(next_r);
+                }
+            }
+        }
+        None
+    }
+
+    pub fn num_occupied_spots(&self) -> usize {
+        self.occupants.len()
+    }
+
+    pub fn num_spots(&self) -> usize {
+        self.onstreet_lanes
+            .values()
+            .map(|lane| lane.spot_dist_along.len())
+            .sum::<usize>()
+            + self.num_spots_per_offstreet.values().sum::<usize>()
+    }
+
+    pub fn get_offstreet_capacity(&self, b: BuildingID) -> usize {
+        self.num_spots_per_offstreet.get(&b).cloned().unwrap_or(0)
+    }
+
     pub fn reserve_spot(&mut self, spot: ParkingSpot) {
         assert!(self.is_free(spot));
         self.reserved_spots.insert(spot);
@@ -145,6 +228,40 @@ impl ParkingSimState {
         None
     }
 
+    // Keep a car from being dynamically reserved by its owner, because somebody else already
+    // claimed it.
+    pub fn dynamically_reserve_specific_car(&mut self, id: CarID) {
+        assert!(self.parked_cars.contains_key(&id));
+        assert!(self.dynamically_reserved_cars.insert(id));
+    }
+
+    // The closest parked car to a building that nobody has reserved yet, regardless of owner
+    pub fn find_car_near(
+        &self,
+        b: BuildingID,
+        radius: Distance,
+        claimed: &BTreeSet<CarID>,
+        map: &Map,
+    ) -> Option<ParkingSpot> {
+        let pt = map.get_b(b).label_center;
+        self.parked_cars
+            .values()
+            .filter(|p| {
+                !claimed.contains(&p.vehicle.id)
+                    && !self.dynamically_reserved_cars.contains(&p.vehicle.id)
+            })
+            .filter_map(|p| {
+                let dist = self.canonical_pt(p.vehicle.id, map)?.dist_to(pt);
+                if dist <= radius {
+                    Some((p.spot, dist))
+                } else {
+                    None
+                }
+            })
+            .min_by_key(|(_, dist)| *dist)
+            .map(|(spot, _)| spot)
+    }
+
     pub fn dynamically_return_car(&mut self, p: ParkedCar) {
         self.dynamically_reserved_cars.remove(&p.vehicle.id);
     }
@@ -209,6 +326,12 @@ impl ParkingSimState {
     }
 
     pub fn is_free(&self, spot: ParkingSpot) -> bool {
+        // Garages can't swallow more cars than they have stalls.
+        if let ParkingSpot::Offstreet(b, idx) = spot {
+            if idx >= self.get_offstreet_capacity(b) {
+                return false;
+            }
+        }
         !self.occupants.contains_key(&spot) && !self.reserved_spots.contains(&spot)
     }
 
@@ -312,7 +435,7 @@ impl ParkingSimState {
 
     pub fn get_offstreet_parked_cars(&self, b: BuildingID) -> Vec<&ParkedCar> {
         let mut results = Vec::new();
-        for idx in 0..self.num_spots_per_offstreet.get(&b).cloned().unwrap_or(0) {
+        for idx in 0..self.get_offstreet_capacity(b) {
             if let Some(car) = self.occupants.get(&ParkingSpot::offstreet(b, idx)) {
                 results.push(&self.parked_cars[&car]);
             }
@@ -338,12 +461,14 @@ impl ParkingSimState {
                 }
             }
         }
-        for (b, idx) in &self.num_spots_per_offstreet {
-            let spot = ParkingSpot::Offstreet(*b, *idx);
-            if self.is_free(spot) {
-                available.push(spot);
-            } else {
-                filled.push(spot);
+        for (b, num_stalls) in &self.num_spots_per_offstreet {
+            for idx in 0..*num_stalls {
+                let spot = ParkingSpot::Offstreet(*b, idx);
+                if self.is_free(spot) {
+                    available.push(spot);
+                } else {
+                    filled.push(spot);
+                }
             }
         }
 
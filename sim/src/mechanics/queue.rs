@@ -1,5 +1,5 @@
 use crate::mechanics::car::{Car, CarState};
-use crate::{CarID, FOLLOWING_DISTANCE};
+use crate::CarID;
 use geom::{Distance, Time};
 use map_model::{Map, Traversable};
 use serde_derive::{Deserialize, Serialize};
@@ -13,7 +13,9 @@ pub struct Queue {
     pub laggy_head: Option<CarID>,
 
     pub geom_len: Distance,
-    // When a car's turn is accepted, reserve the vehicle length + FOLLOWING_DISTANCE for the
+    // The gap kept between a car's back and its follower's front
+    pub following_distance: Distance,
+    // When a car's turn is accepted, reserve the vehicle length + following_distance for the
     // target lane. When the car completely leaves (stops being the laggy_head), free up that
     // space. To prevent blocking the box for possibly scary amounts of time, allocate some of this
     // length first. This is unused for turns themselves. This value can exceed geom_len (for the
@@ -22,12 +24,13 @@ pub struct Queue {
 }
 
 impl Queue {
-    pub fn new(id: Traversable, map: &Map) -> Queue {
+    pub fn new(id: Traversable, following_distance: Distance, map: &Map) -> Queue {
         Queue {
             id,
             cars: VecDeque::new(),
             laggy_head: None,
             geom_len: id.length(map),
+            following_distance,
             reserved_length: Distance::ZERO,
         }
     }
@@ -48,12 +51,12 @@ impl Queue {
         for id in &self.cars {
             let bound = match result.last() {
                 Some((leader, last_dist)) => {
-                    *last_dist - cars[leader].vehicle.length - FOLLOWING_DISTANCE
+                    *last_dist - cars[leader].vehicle.length - self.following_distance
                 }
                 None => match self.laggy_head {
                     Some(id) => {
                         // The simple but broken version:
-                        //self.geom_len - cars[&id].vehicle.length - FOLLOWING_DISTANCE
+                        //self.geom_len - cars[&id].vehicle.length - self.following_distance
 
                         // The expensive case. We need to figure out exactly where the laggy head
                         // is on their queue. No protection against gridlock here!
@@ -73,10 +76,12 @@ impl Queue {
                         }
                         // They might actually be out of the way, but laggy_head hasn't been
                         // updated yet.
-                        if dist_away_from_this_queue < leader.vehicle.length + FOLLOWING_DISTANCE {
+                        if dist_away_from_this_queue
+                            < leader.vehicle.length + self.following_distance
+                        {
                             self.geom_len
                                 - (cars[&id].vehicle.length - dist_away_from_this_queue)
-                                - FOLLOWING_DISTANCE
+                                - self.following_distance
                         } else {
                             self.geom_len
                         }
@@ -120,7 +125,7 @@ impl Queue {
 
             result.push((*id, front));
         }
-        validate_positions(result, cars, now, self.id)
+        validate_positions(result, cars, now, self.id, self.following_distance)
     }
 
     pub fn get_idx_to_insert_car(
@@ -149,13 +154,13 @@ impl Queue {
 
         // Are we too close to the leader?
         if idx != 0
-            && dists[idx - 1].1 - cars[&dists[idx - 1].0].vehicle.length - FOLLOWING_DISTANCE
+            && dists[idx - 1].1 - cars[&dists[idx - 1].0].vehicle.length - self.following_distance
                 < start_dist
         {
             return None;
         }
         // Or the follower?
-        if idx != dists.len() && start_dist - vehicle_len - FOLLOWING_DISTANCE < dists[idx].1 {
+        if idx != dists.len() && start_dist - vehicle_len - self.following_distance < dists[idx].1 {
             return None;
         }
 
@@ -165,11 +170,11 @@ impl Queue {
     // If true, there's room and the car must actually start the turn (because the space is
     // reserved).
     pub fn try_to_reserve_entry(&mut self, car: &Car, force_entry: bool) -> bool {
-        // Sometimes a car + FOLLOWING_DISTANCE might be longer than the geom_len entirely. In that
+        // Sometimes a car + following_distance might be longer than the geom_len entirely. In that
         // case, it just means the car won't totally fit on the queue at once, which is fine.
         // Reserve the normal amount of space; the next car trying to enter will get rejected.
         // Also allow this don't-block-the-box prevention to be disabled.
-        let dist = car.vehicle.length + FOLLOWING_DISTANCE;
+        let dist = car.vehicle.length + self.following_distance;
         if self.reserved_length + dist < self.geom_len
             || self.reserved_length == Distance::ZERO
             || force_entry
@@ -183,11 +188,11 @@ impl Queue {
     // TODO Refactor
     pub fn room_for_car(&self, car: &Car) -> bool {
         self.reserved_length == Distance::ZERO
-            || self.reserved_length + car.vehicle.length + FOLLOWING_DISTANCE < self.geom_len
+            || self.reserved_length + car.vehicle.length + self.following_distance < self.geom_len
     }
 
     pub fn free_reserved_space(&mut self, car: &Car) {
-        self.reserved_length -= car.vehicle.length + FOLLOWING_DISTANCE;
+        self.reserved_length -= car.vehicle.length + self.following_distance;
         assert!(self.reserved_length >= Distance::ZERO);
     }
 }
@@ -197,9 +202,10 @@ fn validate_positions(
     cars: &BTreeMap<CarID, Car>,
     now: Time,
     id: Traversable,
+    following_distance: Distance,
 ) -> Vec<(CarID, Distance)> {
     for pair in dists.windows(2) {
-        if pair[0].1 - cars[&pair[0].0].vehicle.length - FOLLOWING_DISTANCE < pair[1].1 {
+        if pair[0].1 - cars[&pair[0].0].vehicle.length - following_distance < pair[1].1 {
             dump_cars(&dists, cars, id, now);
             panic!(
                 "get_car_positions wound up with bad positioning: {} then {}\n{:?}",
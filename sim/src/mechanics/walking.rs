@@ -4,9 +4,11 @@ use crate::{
     PedCrowdLocation, PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TimeInterval,
     TransitSimState, TripID, TripManager, TripPositions, UnzoomedAgent,
 };
-use abstutil::{deserialize_multimap, serialize_multimap, MultiMap};
+use abstutil::{deserialize_multimap, serialize_multimap, Counter, MultiMap};
 use geom::{Distance, Duration, Line, PolyLine, Speed, Time};
-use map_model::{BuildingID, BusRouteID, Map, Path, PathStep, Traversable, SIDEWALK_THICKNESS};
+use map_model::{
+    BuildingID, BusRouteID, LaneID, Map, Path, PathStep, Traversable, SIDEWALK_THICKNESS,
+};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
@@ -216,7 +218,7 @@ impl WalkingSimState {
             PedState::EnteringBuilding(bldg, _) => {
                 self.peds_per_traversable
                     .remove(ped.path.current_step().as_traversable(), ped.id);
-                trips.ped_reached_building(now, ped.id, bldg, map);
+                trips.ped_reached_building(now, ped.id, bldg, map, scheduler);
                 self.peds.remove(&id);
             }
             PedState::StartingToBike(ref spot, _, _) => {
@@ -318,6 +320,10 @@ impl WalkingSimState {
         p.path.trace(map, dist, dist_ahead)
     }
 
+    pub fn get_ped_speed(&self, id: PedestrianID) -> Option<Speed> {
+        Some(self.peds.get(&id)?.speed)
+    }
+
     pub fn get_path(&self, id: PedestrianID) -> Option<&Path> {
         let p = self.peds.get(&id)?;
         Some(&p.path)
@@ -341,10 +347,39 @@ impl WalkingSimState {
         self.peds.values().map(|ped| ped.metadata(now)).collect()
     }
 
+    // Waiting for a bus doesn't count as being stuck.
+    pub fn find_stuck_peds(&self, now: Time, threshold: Duration) -> Vec<(PedestrianID, Duration)> {
+        self.peds
+            .values()
+            .filter_map(|ped| match ped.state {
+                PedState::WaitingToTurn(_, blocked_since) => {
+                    let dt = now - blocked_since;
+                    if dt > threshold {
+                        Some((ped.id, dt))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn does_ped_exist(&self, id: PedestrianID) -> bool {
         self.peds.contains_key(&id)
     }
 
+    // How many pedestrians are on each sidewalk right now, not counting anybody crossing a turn
+    pub fn peds_per_sidewalk(&self) -> Counter<LaneID> {
+        let mut cnt = Counter::new();
+        for ped in self.peds.values() {
+            if let Traversable::Lane(l) = ped.path.current_step().as_traversable() {
+                cnt.inc(l);
+            }
+        }
+        cnt
+    }
+
     pub fn populate_trip_positions(&self, trip_positions: &mut TripPositions, map: &Map) {
         for ped in self.peds.values() {
             trip_positions
@@ -630,6 +665,7 @@ impl Pedestrian {
         peds_per_traversable.insert(self.path.current_step().as_traversable(), self.id);
         events.push(Event::AgentEntersTraversable(
             AgentID::Pedestrian(self.id),
+            self.trip,
             self.path.current_step().as_traversable(),
         ));
         true
@@ -1,6 +1,7 @@
 use crate::{CarID, PedestrianID, VehicleType};
 use geom::{Angle, Distance, Duration, PolyLine, Pt2D, Time};
 use map_model::{BuildingID, Map, Traversable, TurnID};
+use serde_derive::{Deserialize, Serialize};
 
 // Intermediate structures so that sim and game crates don't have a cyclic dependency.
 #[derive(Clone)]
@@ -62,6 +63,15 @@ pub struct UnzoomedAgent {
     pub metadata: AgentMetadata,
 }
 
+// Something drawn on screen, dumped for bug reports.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct VisibleEntity {
+    // Like "Lane" or "Car"
+    pub kind: String,
+    pub id: String,
+    pub center: Pt2D,
+}
+
 // TODO Can we return borrows instead? Nice for time travel, not for main sim?
 // actually good for main sim too; we're constantly calculating stuff while sim is paused
 // otherwise? except we don't know what to calculate. maybe cache it?
@@ -43,6 +43,7 @@ enum Goal {
     },
     BikeThenStop {
         end_dist: Distance,
+        target: BuildingID,
     },
     FollowBusRoute {
         end_dist: Distance,
@@ -68,10 +69,10 @@ impl Router {
         }
     }
 
-    pub fn bike_then_stop(path: Path, end_dist: Distance) -> Router {
+    pub fn bike_then_stop(path: Path, end_dist: Distance, target: BuildingID) -> Router {
         Router {
             path,
-            goal: Goal::BikeThenStop { end_dist },
+            goal: Goal::BikeThenStop { end_dist, target },
         }
     }
 
@@ -112,7 +113,7 @@ impl Router {
                 stuck_end_dist,
                 ..
             } => stuck_end_dist.unwrap_or_else(|| spot.unwrap().1),
-            Goal::BikeThenStop { end_dist } => end_dist,
+            Goal::BikeThenStop { end_dist, .. } => end_dist,
             Goal::FollowBusRoute { end_dist } => end_dist,
         }
     }
@@ -243,12 +244,12 @@ impl Router {
                     None
                 }
             }
-            Goal::BikeThenStop { end_dist } => {
+            Goal::BikeThenStop { end_dist, target } => {
                 if end_dist == front {
                     let last_lane = self.head().as_lane();
                     if let Some(sidewalk) = map.get_parent(last_lane).bike_to_sidewalk(last_lane) {
                         Some(ActionAtEnd::StopBiking(
-                            SidewalkSpot::bike_rack(sidewalk, map).unwrap(),
+                            SidewalkSpot::bike_rack(sidewalk, Some(target), map).unwrap(),
                         ))
                     } else {
                         println!(
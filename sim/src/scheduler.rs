@@ -1,4 +1,4 @@
-use crate::{AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID};
+use crate::{AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID, TripID};
 use derivative::Derivative;
 use geom::{Duration, DurationHistogram, Time};
 use map_model::{IntersectionID, Path, PathRequest};
@@ -11,6 +11,8 @@ pub enum Command {
     // If true, retry when there's no room to spawn somewhere
     SpawnCar(CreateCar, bool),
     SpawnPed(CreatePedestrian),
+    // With lazy spawning, pathfind and spawn the trip TripSpawner has deferred under this ID
+    SpawnTrip(usize),
     UpdateCar(CarID),
     // Distinguish this from UpdateCar to avoid confusing things
     UpdateLaggyHead(CarID),
@@ -31,6 +33,7 @@ impl Command {
         match self {
             Command::SpawnCar(ref create, _) => CommandType::Car(create.vehicle.id),
             Command::SpawnPed(ref create) => CommandType::Ped(create.id),
+            Command::SpawnTrip(id) => CommandType::Trip(*id),
             Command::UpdateCar(id) => CommandType::Car(*id),
             Command::UpdateLaggyHead(id) => CommandType::CarLaggyHead(*id),
             Command::UpdatePed(id) => CommandType::Ped(*id),
@@ -47,6 +50,7 @@ pub enum CommandType {
     Car(CarID),
     CarLaggyHead(CarID),
     Ped(PedestrianID),
+    Trip(usize),
     Intersection(IntersectionID),
     Savestate,
 }
@@ -157,6 +161,27 @@ impl Scheduler {
         self.queued_commands.remove(&cmd.to_type());
     }
 
+    // Cancels the command that'll spawn the first agent of a trip. Returns false if there's no such
+    // command queued, because the trip already started.
+    pub fn cancel_spawn(&mut self, trip: TripID) -> bool {
+        let cmd_type = self
+            .queued_commands
+            .iter()
+            .find(|(_, (cmd, _))| match cmd {
+                Command::SpawnCar(ref create, _) => create.trip == trip,
+                Command::SpawnPed(ref create) => create.trip == trip,
+                _ => false,
+            })
+            .map(|(cmd_type, _)| cmd_type.clone());
+        if let Some(cmd_type) = cmd_type {
+            // The item stays in the heap, but get_next will skip it.
+            self.queued_commands.remove(&cmd_type);
+            true
+        } else {
+            false
+        }
+    }
+
     // This next command might've actually been rescheduled to a later time; the caller won't know
     // that here.
     pub fn peek_next_time(&self) -> Option<Time> {
@@ -181,6 +206,12 @@ impl Scheduler {
         Some(cmd)
     }
 
+    // Includes stale entries for commands that were since updated or cancelled, since those still
+    // take up space until they're popped.
+    pub fn queue_len(&self) -> usize {
+        self.items.len()
+    }
+
     pub fn describe_stats(&self) -> String {
         format!("delta times for events: {}", self.delta_times.describe())
     }
@@ -1,25 +1,29 @@
 use crate::{
-    AgentID, AgentMetadata, Analytics, CarID, Command, CreateCar, DrawCarInput, DrawPedCrowdInput,
-    DrawPedestrianInput, DrivingGoal, DrivingSimState, Event, GetDrawAgents, IntersectionSimState,
-    ParkedCar, ParkingSimState, ParkingSpot, PedestrianID, Router, Scheduler, SidewalkPOI,
-    SidewalkSpot, TransitSimState, TripCount, TripEnd, TripID, TripLeg, TripManager, TripMode,
-    TripPhaseType, TripPositions, TripResult, TripSpawner, TripSpec, TripStart, UnzoomedAgent,
-    VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH,
+    AbortReason, AgentID, AgentMetadata, Analytics, CarID, Command, CreateCar, DrawCarInput,
+    DrawPedCrowdInput, DrawPedestrianInput, DrivingGoal, DrivingSimState, Event, GetDrawAgents,
+    IntersectionSimState, ParkedCar, ParkingSimState, ParkingSpot, PedestrianID, PersonID,
+    PersonState, Router, Scenario, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState,
+    TripCount, TripEnd, TripEndpoint, TripID, TripLeg, TripManager, TripMode, TripPhaseType,
+    TripPositions, TripResult, TripSpawner, TripSpec, TripStart, UnzoomedAgent, Vehicle,
+    VehicleSizes, VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH,
 };
 use abstutil::Timer;
 use derivative::Derivative;
-use geom::{Distance, Duration, PolyLine, Pt2D, Time};
+use geom::{Distance, Duration, PolyLine, Pt2D, Speed, Time};
 use instant::Instant;
 use map_model::{
     BuildingID, BusRoute, BusRouteID, IntersectionID, LaneID, Map, Path, PathConstraints,
-    PathRequest, PathStep, Traversable,
+    PathRequest, PathStep, RoadID, Traversable,
 };
+use rand_xorshift::XorShiftRng;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 use std::panic;
 
 // TODO Do something else.
 const BLIND_RETRY_TO_SPAWN: Duration = Duration::const_seconds(5.0);
+// How far back rewinding can go is limited by this and the snapshot frequency.
+const MAX_SNAPSHOTS: usize = 10;
 
 #[derive(Serialize, Deserialize, Clone, Derivative)]
 #[derivative(PartialEq)]
@@ -35,6 +39,12 @@ pub struct Sim {
     time: Time,
     car_id_counter: usize,
     ped_id_counter: usize,
+    vehicle_sizes: VehicleSizes,
+    parking_skip_probability: f64,
+    enable_walking: bool,
+    enable_transit: bool,
+    enable_parking: bool,
+    lazy_spawning: bool,
 
     // TODO Reconsider these
     pub(crate) map_name: String,
@@ -56,9 +66,40 @@ pub struct Sim {
     #[serde(skip_serializing, skip_deserializing)]
     analytics: Analytics,
 
+    // Checked by time_limited_step, along with the next time to look for gridlock
     #[derivative(PartialEq = "ignore")]
     #[serde(skip_serializing, skip_deserializing)]
-    check_for_gridlock: Option<(Time, Duration)>,
+    stop_conditions: Vec<StopCondition>,
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    next_gridlock_check: Option<Time>,
+
+    // For rewinding. Serialized savestates, without analytics, and the time of each. Oldest first.
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    snapshots: VecDeque<(Time, Vec<u8>)>,
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    snapshot_every: Option<(Time, Duration)>,
+    // Named full copies to jump between
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    bookmarks: BTreeMap<String, Sim>,
+
+    // The trip, and whether it's ended yet
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    watched_trip: Option<(TripID, bool)>,
+
+    // Every event and when it happened, if recording
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    event_log: Option<Vec<(Time, Event)>>,
+
+    // Trips not scheduled because a subsystem is disabled. Reported once by spawn_all_trips.
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    dropped_trips: BTreeMap<&'static str, usize>,
 }
 
 #[derive(Clone)]
@@ -69,6 +110,20 @@ pub struct SimOptions {
     pub disable_block_the_box: bool,
     pub recalc_lanechanging: bool,
     pub clear_laggy_head_early: bool,
+    pub vehicle_sizes: VehicleSizes,
+    // When seeding parked cars, the chance of passing up a road with free spots and looking
+    // farther away.
+    pub parking_skip_probability: f64,
+    // For profiling, entire subsystems can be turned off. Trips that need them are dropped when
+    // they're scheduled.
+    pub enable_walking: bool,
+    pub enable_transit: bool,
+    pub enable_parking: bool,
+    // Don't pathfind every trip in spawn_all_trips; wait until each one departs. Good for huge
+    // scenarios, but problems with a trip are only warned about when it's due to start.
+    pub lazy_spawning: bool,
+    // Keep a snapshot this often, so rewind_to can work. Each one costs a savestate.
+    pub snapshot_every: Option<Duration>,
 }
 
 impl SimOptions {
@@ -80,6 +135,13 @@ impl SimOptions {
             disable_block_the_box: false,
             recalc_lanechanging: true,
             clear_laggy_head_early: false,
+            vehicle_sizes: VehicleSizes::default(),
+            parking_skip_probability: 0.0,
+            enable_walking: true,
+            enable_transit: true,
+            enable_parking: true,
+            lazy_spawning: false,
+            snapshot_every: None,
         }
     }
 }
@@ -87,6 +149,9 @@ impl SimOptions {
 // Setup
 impl Sim {
     pub fn new(map: &Map, opts: SimOptions, timer: &mut Timer) -> Sim {
+        assert!(opts.vehicle_sizes.max_car_length < map_model::PARKING_SPOT_LENGTH);
+        assert!(opts.vehicle_sizes.min_car_length <= opts.vehicle_sizes.max_car_length);
+        assert!(opts.parking_skip_probability >= 0.0 && opts.parking_skip_probability <= 1.0);
         let mut scheduler = Scheduler::new();
         if let Some(d) = opts.savestate_every {
             scheduler.push(Time::START_OF_DAY + d, Command::Savestate(d));
@@ -96,6 +161,7 @@ impl Sim {
                 map,
                 opts.recalc_lanechanging,
                 opts.clear_laggy_head_early,
+                opts.vehicle_sizes.following_distance,
             ),
             parking: ParkingSimState::new(map, timer),
             walking: WalkingSimState::new(),
@@ -112,6 +178,12 @@ impl Sim {
             time: Time::START_OF_DAY,
             car_id_counter: 0,
             ped_id_counter: 0,
+            vehicle_sizes: opts.vehicle_sizes,
+            parking_skip_probability: opts.parking_skip_probability,
+            enable_walking: opts.enable_walking,
+            enable_transit: opts.enable_transit,
+            enable_parking: opts.enable_parking,
+            lazy_spawning: opts.lazy_spawning,
 
             map_name: map.get_name().to_string(),
             // TODO
@@ -119,7 +191,14 @@ impl Sim {
             run_name: opts.run_name,
             step_count: 0,
             trip_positions: None,
-            check_for_gridlock: None,
+            stop_conditions: Vec::new(),
+            next_gridlock_check: None,
+            snapshots: VecDeque::new(),
+            snapshot_every: opts.snapshot_every.map(|dt| (Time::START_OF_DAY + dt, dt)),
+            bookmarks: BTreeMap::new(),
+            watched_trip: None,
+            event_log: None,
+            dropped_trips: BTreeMap::new(),
 
             analytics: Analytics::new(),
         }
@@ -131,6 +210,21 @@ impl Sim {
         spec: TripSpec,
         map: &Map,
     ) -> (Option<PedestrianID>, Option<CarID>) {
+        self.schedule_person_trip(None, start_time, spec, map)
+    }
+
+    pub fn schedule_person_trip(
+        &mut self,
+        person: Option<PersonID>,
+        start_time: Time,
+        spec: TripSpec,
+        map: &Map,
+    ) -> (Option<PedestrianID>, Option<CarID>) {
+        if let Some(subsystem) = self.missing_subsystem(&spec) {
+            *self.dropped_trips.entry(subsystem).or_insert(0) += 1;
+            return (None, None);
+        }
+
         let (ped_id, car_id) = match spec {
             TripSpec::CarAppearing {
                 ref vehicle_spec,
@@ -151,6 +245,7 @@ impl Sim {
             }
             TripSpec::UsingParkedCar { .. }
             | TripSpec::MaybeUsingParkedCar { .. }
+            | TripSpec::UsingNearbyParkedCar { .. }
             | TripSpec::JustWalking { .. }
             | TripSpec::UsingTransit { .. } => {
                 let id = PedestrianID(self.ped_id_counter);
@@ -164,24 +259,143 @@ impl Sim {
                 self.car_id_counter += 1;
                 (Some(ped), Some(car))
             }
+            TripSpec::ModeChoice { ref options, .. } => {
+                let ped = PedestrianID(self.ped_id_counter);
+                self.ped_id_counter += 1;
+                // In case biking is picked
+                let car = if options.contains(&TripMode::Bike) {
+                    let id = CarID(self.car_id_counter, VehicleType::Bike);
+                    self.car_id_counter += 1;
+                    Some(id)
+                } else {
+                    None
+                };
+                (Some(ped), car)
+            }
+            TripSpec::MultiStop {
+                ref vehicle_spec, ..
+            } => {
+                let ped = PedestrianID(self.ped_id_counter);
+                self.ped_id_counter += 1;
+                let car = vehicle_spec.as_ref().map(|spec| {
+                    let id = CarID(self.car_id_counter, spec.vehicle_type);
+                    self.car_id_counter += 1;
+                    id
+                });
+                (Some(ped), car)
+            }
         };
 
-        self.spawner
-            .schedule_trip(start_time, ped_id, car_id, spec, map, &self.parking);
+        self.spawner.schedule_trip(
+            person,
+            start_time,
+            ped_id,
+            car_id,
+            spec,
+            map,
+            &mut self.parking,
+        );
         (ped_id, car_id)
     }
 
+    // If the trip needs some subsystem that's been disabled, which one
+    fn missing_subsystem(&self, spec: &TripSpec) -> Option<&'static str> {
+        let (walking, transit, parking) = match spec {
+            TripSpec::CarAppearing { goal, .. } => {
+                let parks = match goal {
+                    DrivingGoal::ParkNear(_) => true,
+                    DrivingGoal::Border(_, _) | DrivingGoal::AnyLane(_, _) => false,
+                };
+                (parks, false, parks)
+            }
+            TripSpec::UsingParkedCar { .. }
+            | TripSpec::MaybeUsingParkedCar { .. }
+            | TripSpec::UsingNearbyParkedCar { .. } => (true, false, true),
+            TripSpec::JustWalking { .. } | TripSpec::UsingBike { .. } => (true, false, false),
+            TripSpec::UsingTransit { .. } => (true, true, false),
+            TripSpec::ModeChoice { options, .. } => (
+                true,
+                options.contains(&TripMode::Transit),
+                options.contains(&TripMode::Drive),
+            ),
+            TripSpec::MultiStop { mode, .. } => (true, false, *mode == TripMode::Drive),
+        };
+        if walking && !self.enable_walking {
+            Some("walking")
+        } else if transit && !self.enable_transit {
+            Some("transit")
+        } else if parking && !self.enable_parking {
+            Some("parking")
+        } else {
+            None
+        }
+    }
+
     pub fn spawn_all_trips(&mut self, map: &Map, timer: &mut Timer, retry_if_no_room: bool) {
+        for (subsystem, count) in std::mem::replace(&mut self.dropped_trips, BTreeMap::new()) {
+            timer.note(format!(
+                "Didn't schedule {} trips, because {} is disabled",
+                abstutil::prettyprint_usize(count),
+                subsystem
+            ));
+        }
+        if self.lazy_spawning {
+            self.spawner
+                .defer_all(&mut self.scheduler, retry_if_no_room);
+            return;
+        }
         self.spawner.spawn_all(
             map,
             &self.parking,
+            &self.analytics,
             &mut self.trips,
             &mut self.scheduler,
+            self.vehicle_sizes,
             timer,
             retry_if_no_room,
         );
     }
 
+    // Retracts a trip that's been spawned but hasn't started yet, counting it as aborted. Returns
+    // false if the trip already started.
+    pub fn cancel_future_trip(&mut self, id: TripID) -> bool {
+        if !self.scheduler.cancel_spawn(id) {
+            return false;
+        }
+        self.trips
+            .abort_trip_failed_start(id, AbortReason::Cancelled);
+        true
+    }
+
+    // Sends a trip that's currently driving somewhere else. If there's no path to the new goal, the
+    // trip is aborted.
+    pub fn change_trip_goal(
+        &mut self,
+        id: TripID,
+        new_goal: TripEndpoint,
+        map: &Map,
+    ) -> Result<(), String> {
+        let (car, driving_goal) = self.trips.prepare_goal_change(id, &new_goal, map)?;
+        if let Some((old_length, new_length)) = self.driving.change_goal(car, &driving_goal, map)? {
+            self.trips
+                .change_goal(id, new_goal, driving_goal, old_length, new_length, map);
+            Ok(())
+        } else {
+            self.trips.abort_car_trip(car, AbortReason::NoPath);
+            self.driving.kill_stuck_car(
+                car,
+                self.time,
+                map,
+                &mut self.scheduler,
+                &mut self.intersections,
+            );
+            Err(format!(
+                "No path for {} to {:?}, so {} was aborted",
+                car, new_goal, id
+            ))
+        }
+    }
+
     pub fn get_free_spots(&self, l: LaneID) -> Vec<ParkingSpot> {
         self.parking.get_free_spots(l)
     }
@@ -190,6 +404,20 @@ impl Sim {
         self.parking.get_free_offstreet_spots(b)
     }
 
+    pub fn free_spots_near_building(
+        &self,
+        b: BuildingID,
+        map: &Map,
+        max_roads: usize,
+    ) -> Vec<ParkingSpot> {
+        self.parking.free_spots_near_building(b, map, max_roads)
+    }
+
+    // 0 if the building has no offstreet parking
+    pub fn get_offstreet_capacity(&self, b: BuildingID) -> usize {
+        self.parking.get_offstreet_capacity(b)
+    }
+
     // (Filled, available)
     pub fn get_all_parking_spots(&self) -> (Vec<ParkingSpot>, Vec<ParkingSpot>) {
         self.parking.get_all_parking_spots()
@@ -212,6 +440,27 @@ impl Sim {
         id
     }
 
+    // Places exactly these cars, keeping their IDs and vehicles, instead of randomizing anything.
+    // Panics if a spot is already taken.
+    pub fn seed_parked_cars_exact(&mut self, cars: Vec<ParkedCar>) {
+        for p in cars {
+            if !self.parking.is_free(p.spot) {
+                panic!(
+                    "Can't seed {} at {:?}, because the spot isn't free",
+                    p.vehicle.id, p.spot
+                );
+            }
+            // Don't hand out these IDs again later
+            self.car_id_counter = self.car_id_counter.max(p.vehicle.id.0 + 1);
+            self.parking.reserve_spot(p.spot);
+            self.parking.add_parked_car(p);
+        }
+    }
+
+    pub fn get_car_at_spot(&self, spot: ParkingSpot) -> Option<&ParkedCar> {
+        self.parking.get_car_at_spot(spot)
+    }
+
     pub fn get_parked_cars_by_owner(&self, bldg: BuildingID) -> Vec<&ParkedCar> {
         self.parking.get_parked_cars_by_owner(bldg)
     }
@@ -222,6 +471,13 @@ impl Sim {
 
     pub fn seed_bus_route(&mut self, route: &BusRoute, map: &Map, timer: &mut Timer) -> Vec<CarID> {
         let mut results: Vec<CarID> = Vec::new();
+        if !self.enable_transit {
+            timer.warn(format!(
+                "Not seeding {}, because transit is disabled",
+                route.name
+            ));
+            return results;
+        }
 
         // Try to spawn just ONE bus anywhere.
         // TODO Be more realistic. One bus per stop is too much, one is too little.
@@ -244,6 +500,7 @@ impl Sim {
             // first round of buses.
             // Same for this TripStart, though it doesn't matter too much.
             let trip = self.trips.new_trip(
+                None,
                 self.time,
                 TripStart::Border(map.get_l(path.current_step().as_lane()).src_i),
                 vec![TripLeg::ServeBusRoute(id, route.id)],
@@ -255,7 +512,8 @@ impl Sim {
                         "Giving up on seeding a bus headed towards stop {} of {} ({})",
                         next_stop_idx, route.name, route.id
                     ));
-                    self.trips.abort_trip_failed_start(trip);
+                    self.trips
+                        .abort_trip_failed_start(trip, AbortReason::NoPath);
                     break;
                 }
                 let start_lane = if let PathStep::Lane(l) = path.current_step() {
@@ -287,7 +545,8 @@ impl Sim {
                     &self.parking,
                     &mut self.scheduler,
                 ) {
-                    self.trips.agent_starting_trip_leg(AgentID::Car(id), trip);
+                    self.trips
+                        .agent_starting_trip_leg(AgentID::Car(id), trip, path.total_length());
                     self.transit.bus_created(id, route.id, next_stop_idx);
                     self.analytics.record_demand(&path, map);
                     results.push(id);
@@ -394,6 +653,8 @@ impl Sim {
             if time > max_time {
                 return;
             }
+            self.analytics
+                .record_scheduler_queue_len(self.scheduler.queue_len());
             if let Some(cmd) = self.scheduler.get_next() {
                 if self.do_step(map, time, cmd) {
                     savestate = true;
@@ -426,6 +687,7 @@ impl Sim {
                     self.trips.agent_starting_trip_leg(
                         AgentID::Car(create_car.vehicle.id),
                         create_car.trip,
+                        create_car.router.get_path().total_length(),
                     );
                     if let Some(parked_car) = create_car.maybe_parked_car {
                         self.parking.remove_parked_car(parked_car);
@@ -447,6 +709,13 @@ impl Sim {
                     ));
                     self.analytics
                         .record_demand(create_car.router.get_path(), map);
+                    self.analytics.record_free_flow(
+                        create_car.trip,
+                        &create_car.req,
+                        create_car.router.get_path(),
+                        create_car.vehicle.max_speed,
+                        map,
+                    );
                 } else if retry_if_no_room {
                     // TODO Record this in the trip log
                     self.scheduler.push(
@@ -458,9 +727,21 @@ impl Sim {
                         "No room to spawn car for {}. Not retrying!",
                         create_car.trip
                     );
-                    self.trips.abort_trip_failed_start(create_car.trip);
+                    self.trips
+                        .abort_trip_failed_start(create_car.trip, AbortReason::FailedToSpawn);
                 }
             }
+            Command::SpawnTrip(id) => {
+                self.spawner.spawn_deferred(
+                    id,
+                    map,
+                    &self.parking,
+                    &self.analytics,
+                    &mut self.trips,
+                    &mut self.scheduler,
+                    self.vehicle_sizes,
+                );
+            }
             Command::SpawnPed(mut create_ped) => {
                 let ok = if let SidewalkPOI::DeferredParkingSpot(b, driving_goal) =
                     create_ped.goal.connection.clone()
@@ -491,7 +772,7 @@ impl Sim {
                                         SidewalkSpot::building(b, map),
                                     ));
                                 }
-                                DrivingGoal::Border(_, _) => {}
+                                DrivingGoal::Border(_, _) | DrivingGoal::AnyLane(_, _) => {}
                             }
                             self.trips.dynamically_override_legs(create_ped.trip, legs);
                             true
@@ -519,6 +800,7 @@ impl Sim {
                     self.trips.agent_starting_trip_leg(
                         AgentID::Pedestrian(create_ped.id),
                         create_ped.trip,
+                        create_ped.path.total_length(),
                     );
                     events.push(Event::TripPhaseStarting(
                         create_ped.trip,
@@ -527,6 +809,13 @@ impl Sim {
                         TripPhaseType::Walking,
                     ));
                     self.analytics.record_demand(&create_ped.path, map);
+                    self.analytics.record_free_flow(
+                        create_ped.trip,
+                        &create_ped.req,
+                        &create_ped.path,
+                        Some(create_ped.speed),
+                        map,
+                    );
 
                     // Maybe there's actually no work to do!
                     match (&create_ped.start.connection, &create_ped.goal.connection) {
@@ -549,7 +838,8 @@ impl Sim {
                         }
                     }
                 } else {
-                    self.trips.abort_trip_failed_start(create_ped.trip);
+                    self.trips
+                        .abort_trip_failed_start(create_ped.trip, AbortReason::FailedToSpawn);
                 }
             }
             Command::UpdateCar(car) => {
@@ -603,7 +893,26 @@ impl Sim {
         events.extend(self.driving.collect_events());
         events.extend(self.walking.collect_events());
         events.extend(self.intersections.collect_events());
+        if let Some((trip, ref mut done)) = self.watched_trip {
+            if events.iter().any(|ev| ev.ends_trip(trip)) {
+                *done = true;
+            }
+        }
+        let parking = &self.parking;
+        self.analytics
+            .record_parking(time, parking.num_occupied_spots(), || parking.num_spots());
+        let walking = &self.walking;
+        self.analytics
+            .record_sidewalk_crowding(time, || walking.peds_per_sidewalk());
+        if let Some(ref mut log) = self.event_log {
+            log.extend(events.iter().map(|ev| (time, ev.clone())));
+        }
         for ev in events {
+            if let Event::TripFinished(trip, _, dt) = ev {
+                if let Some(person) = self.trips.trip_to_person(trip) {
+                    self.analytics.record_person_delay(person, trip, dt);
+                }
+            }
             self.analytics.event(ev, self.time, map);
         }
 
@@ -618,6 +927,7 @@ impl Sim {
         timer.start(format!("Advance sim to {}", end_time));
         while self.time < end_time {
             self.minimal_step(map, end_time - self.time);
+            self.maybe_snapshot();
             if Duration::realtime_elapsed(last_update) >= Duration::seconds(1.0) {
                 // TODO Not timer?
                 println!(
@@ -628,41 +938,51 @@ impl Sim {
                 last_update = Instant::now();
             }
         }
+        for line in self.spawner.collect_warnings() {
+            timer.warn(line);
+        }
         timer.stop(format!("Advance sim to {}", end_time));
     }
     pub fn normal_step(&mut self, map: &Map, dt: Duration) {
         self.timed_step(map, dt, &mut Timer::throwaway());
     }
 
-    // TODO Do this like periodic savestating instead?
-    pub fn set_gridlock_checker(&mut self, freq: Option<Duration>) {
-        if let Some(dt) = freq {
-            assert!(self.check_for_gridlock.is_none());
-            self.check_for_gridlock = Some((self.time + dt, dt));
-        } else {
-            assert!(self.check_for_gridlock.is_some());
-            self.check_for_gridlock = None;
+    // Replaces whatever time_limited_step checked before. Pass an empty list to stop checking.
+    pub fn set_stop_conditions(&mut self, conditions: Vec<StopCondition>) {
+        self.next_gridlock_check = None;
+        for c in &conditions {
+            if let StopCondition::Gridlock(dt) = c {
+                self.next_gridlock_check = Some(self.time + *dt);
+            }
         }
+        self.stop_conditions = conditions;
     }
-    // This will return delayed intersections if that's why it stops early.
+
+    // Returns the reason if any stop condition (see set_stop_conditions) makes this stop early.
     pub fn time_limited_step(
         &mut self,
         map: &Map,
         dt: Duration,
         real_time_limit: Duration,
-    ) -> Option<Vec<(IntersectionID, Time)>> {
+    ) -> Option<StopReason> {
         let started_at = Instant::now();
         let end_time = self.time + dt;
 
         while self.time < end_time && Duration::realtime_elapsed(started_at) < real_time_limit {
             self.minimal_step(map, end_time - self.time);
-            if let Some((ref mut t, dt)) = self.check_for_gridlock {
-                if self.time >= *t {
-                    *t += dt;
-                    let gridlock = self.delayed_intersections(dt);
-                    if !gridlock.is_empty() {
-                        return Some(gridlock);
+            self.maybe_snapshot();
+            for idx in 0..self.stop_conditions.len() {
+                if let StopCondition::Gridlock(dt) = self.stop_conditions[idx] {
+                    // Looking for gridlock is expensive, so don't do it every step.
+                    if self.next_gridlock_check.map(|t| self.time >= t) == Some(true) {
+                        self.next_gridlock_check = Some(self.time + dt);
+                        let gridlock = self.find_gridlock(dt, map);
+                        if !gridlock.is_empty() {
+                            return Some(StopReason::Gridlock(gridlock));
+                        }
                     }
+                } else if self.stop_condition_met(&self.stop_conditions[idx], map) {
+                    return Some(StopReason::Met(self.stop_conditions[idx].clone()));
                 }
             }
         }
@@ -670,6 +990,141 @@ impl Sim {
         None
     }
 
+    pub fn stop_condition_met(&self, cond: &StopCondition, map: &Map) -> bool {
+        let on = |agent: AgentID| match agent {
+            AgentID::Car(id) => self.get_draw_car(id, map).map(|c| c.on),
+            AgentID::Pedestrian(id) => self.get_draw_ped(id, map).map(|p| p.on),
+        };
+        match cond {
+            StopCondition::Gridlock(dt) => !self.find_gridlock(*dt, map).is_empty(),
+            StopCondition::AgentOnRoad(agent, r) => match on(*agent) {
+                Some(Traversable::Lane(l)) => map.get_l(l).parent == *r,
+                _ => false,
+            },
+            StopCondition::AgentInIntersection(agent, i) => match on(*agent) {
+                Some(Traversable::Turn(t)) => t.parent == *i,
+                _ => false,
+            },
+            StopCondition::TripDone(trip) => match self.trips.trip_to_agent(*trip) {
+                TripResult::TripDone => true,
+                _ => false,
+            },
+        }
+    }
+
+    // Remember when this trip finishes or aborts, so the UI can react.
+    pub fn watch_trip(&mut self, trip: Option<TripID>) {
+        self.watched_trip = trip.map(|t| (t, false));
+    }
+
+    pub fn watched_trip_done(&self) -> Option<TripID> {
+        match self.watched_trip {
+            Some((trip, true)) => Some(trip),
+            _ => None,
+        }
+    }
+
+    // Keep the last few states around, captured this often, so rewind_to can work.
+    pub fn set_snapshotting(&mut self, freq: Option<Duration>) {
+        if let Some(dt) = freq {
+            self.snapshot_every = Some((self.time + dt, dt));
+        } else {
+            self.snapshot_every = None;
+            self.snapshots.clear();
+        }
+    }
+
+    fn maybe_snapshot(&mut self) {
+        if let Some((ref mut t, dt)) = self.snapshot_every {
+            if self.time < *t {
+                return;
+            }
+            *t = self.time + dt;
+        } else {
+            return;
+        }
+        let restore = self.scheduler.before_savestate();
+        let bytes = abstutil::to_binary_bytes(self);
+        self.scheduler.after_savestate(restore);
+        self.snapshots.push_back((self.time, bytes));
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    // Restores the latest snapshot at or before the target, then simulates forwards to it.
+    // Returns false (and does nothing) if there's no snapshot early enough.
+    pub fn rewind_to(&mut self, map: &Map, target: Time) -> bool {
+        let idx = match self.snapshots.iter().rposition(|(t, _)| *t <= target) {
+            Some(idx) => idx,
+            None => {
+                return false;
+            }
+        };
+        // Snapshots after the target will be captured again while stepping forwards.
+        let mut snapshots = std::mem::replace(&mut self.snapshots, VecDeque::new());
+        snapshots.truncate(idx + 1);
+        let mut sim: Sim = abstutil::from_binary_bytes(&snapshots[idx].1);
+        sim.restore_paths(map, &mut Timer::throwaway());
+
+        // Like any savestate, the snapshot has no analytics. Keep everything recorded so far, but
+        // stop recording, so the replayed time isn't counted twice.
+        sim.analytics = std::mem::replace(&mut self.analytics, Analytics::new());
+        sim.analytics.stop_recording();
+        sim.snapshot_every = self.snapshot_every.map(|(_, dt)| (sim.time + dt, dt));
+        sim.snapshots = snapshots;
+        sim.bookmarks = std::mem::replace(&mut self.bookmarks, BTreeMap::new());
+        sim.set_stop_conditions(std::mem::replace(&mut self.stop_conditions, Vec::new()));
+        sim.watched_trip = self.watched_trip.take();
+        sim.event_log = self.event_log.take();
+        sim.dropped_trips = std::mem::replace(&mut self.dropped_trips, BTreeMap::new());
+        *self = sim;
+        if target > self.time {
+            self.normal_step(map, target - self.time);
+        }
+        true
+    }
+
+    // A full copy, without the snapshots and bookmarks, so they don't nest
+    fn copy_without_history(&mut self) -> Sim {
+        let snapshots = std::mem::replace(&mut self.snapshots, VecDeque::new());
+        let bookmarks = std::mem::replace(&mut self.bookmarks, BTreeMap::new());
+        let copy = self.clone();
+        self.snapshots = snapshots;
+        self.bookmarks = bookmarks;
+        copy
+    }
+
+    // Remembers the current state under a name, replacing any older bookmark with the same name.
+    pub fn bookmark(&mut self, name: String) {
+        let copy = self.copy_without_history();
+        self.bookmarks.insert(name, copy);
+    }
+
+    // The name and time of every bookmark, sorted by name
+    pub fn list_bookmarks(&self) -> Vec<(String, Time)> {
+        self.bookmarks
+            .iter()
+            .map(|(name, sim)| (name.clone(), sim.time))
+            .collect()
+    }
+
+    // Jumps to a bookmark without simulating anything. The bookmarks are kept, but snapshots for
+    // rewinding are dropped, since they might be from a different timeline. Returns false (and
+    // does nothing) if there's no such bookmark.
+    pub fn restore_bookmark(&mut self, name: &str) -> bool {
+        let copy = match self.bookmarks.get(name) {
+            Some(sim) => sim.clone(),
+            None => {
+                return false;
+            }
+        };
+        let bookmarks = std::mem::replace(&mut self.bookmarks, BTreeMap::new());
+        *self = copy;
+        self.bookmarks = bookmarks;
+        true
+    }
+
     pub fn dump_before_abort(&self) {
         println!(
             "********************************************************************************"
@@ -833,6 +1288,20 @@ impl Sim {
         path
     }
 
+    // Starts a fresh log; disabling throws away anything recorded so far.
+    pub fn record_events(&mut self, enable: bool) {
+        self.event_log = if enable { Some(Vec::new()) } else { None };
+    }
+
+    pub fn save_event_log(&self, path: String) {
+        abstutil::write_binary(
+            path,
+            self.event_log
+                .as_ref()
+                .expect("save_event_log without record_events"),
+        );
+    }
+
     pub fn find_previous_savestate(&self, base_time: Time) -> Option<String> {
         abstutil::find_prev_file(self.save_path(base_time))
     }
@@ -863,22 +1332,53 @@ impl Sim {
 
 // Queries of all sorts
 impl Sim {
+    pub fn vehicle_sizes(&self) -> VehicleSizes {
+        self.vehicle_sizes
+    }
+
+    pub fn parking_skip_probability(&self) -> f64 {
+        self.parking_skip_probability
+    }
+
+    pub(crate) fn parking_enabled(&self) -> bool {
+        self.enable_parking
+    }
+
     pub fn time(&self) -> Time {
         self.time
     }
 
+    // How far away the next scheduled command is. Stepping forward by exactly this much will
+    // process it. Cancelled commands may still linger in the queue, so occasionally nothing
+    // visible happens at that time.
+    pub fn time_until_next_event(&self) -> Option<Duration> {
+        self.scheduler.peek_next_time().map(|t| t - self.time)
+    }
+
     pub fn is_done(&self) -> bool {
-        self.spawner.is_done() && self.trips.is_done()
+        self.spawner.is_done() && self.spawner.num_deferred() == 0 && self.trips.is_done()
     }
 
     pub fn is_empty(&self) -> bool {
         self.time == Time::START_OF_DAY && self.is_done()
     }
 
+    // With lazy spawning, how many trips haven't been pathfound and created yet
+    pub fn num_deferred_trips(&self) -> usize {
+        self.spawner.num_deferred()
+    }
+
     // (number of finished trips, number of unfinished trips, number of active by mode)
     // prettyprinted
+    // With lazy spawning, trips that haven't been created yet count as unfinished.
     pub fn num_trips(&self) -> (usize, usize, BTreeMap<TripMode, usize>) {
-        self.trips.num_trips()
+        let (finished, unfinished, by_mode) = self.trips.num_trips();
+        (finished, unfinished + self.spawner.num_deferred(), by_mode)
+    }
+
+    // Unfinished trips that haven't departed yet, including ones lazy spawning hasn't created
+    pub fn num_trips_not_departed(&self) -> usize {
+        self.trips.num_trips_not_departed(self.time) + self.spawner.num_deferred()
     }
 
     pub fn count_trips_involving_bldg(&self, b: BuildingID) -> TripCount {
@@ -986,10 +1486,87 @@ impl Sim {
         self.trips.trip_to_agent(id)
     }
 
+    pub fn trip_to_person(&self, id: TripID) -> Option<PersonID> {
+        self.trips.trip_to_person(id)
+    }
+
+    pub fn new_person(&mut self, id: PersonID, home: Option<BuildingID>) {
+        self.trips.new_person(id, home);
+    }
+
+    pub fn get_person_state(&self, id: PersonID) -> PersonState {
+        self.trips.person_state(id)
+    }
+
+    pub fn is_person_home(&self, id: PersonID) -> bool {
+        self.trips.is_person_home(id)
+    }
+
+    // (People at home, people with a home)
+    pub fn count_people_home(&self) -> (usize, usize) {
+        self.trips.count_people_home()
+    }
+
+    pub fn describe_trip(&self, id: TripID) -> Vec<String> {
+        self.trips.describe_trip(id)
+    }
+
     pub fn trip_endpoints(&self, id: TripID) -> (TripStart, TripEnd) {
         self.trips.trip_endpoints(id)
     }
 
+    pub fn transit_wait_time(&self, id: TripID) -> Option<Duration> {
+        self.trips.transit_wait_time(id)
+    }
+
+    pub fn trip_distance(&self, id: TripID) -> Option<Distance> {
+        self.trips.trip_distance(id)
+    }
+
+    pub fn is_bus_service(&self, id: TripID) -> bool {
+        self.trips.is_bus_service(id)
+    }
+
+    // Where a time warp should aim to stop, so that a trip has finished by then. scheduled_finish
+    // is a guess, like when the trip finished in a baseline run; if it's missing or already
+    // passed, aim for the end of the day. Errors if the trip won't ever finish.
+    pub fn time_warp_target_for_trip(
+        &self,
+        id: TripID,
+        scheduled_finish: Option<Time>,
+    ) -> Result<Time, String> {
+        match self.trips.trip_to_agent(id) {
+            TripResult::TripDoesntExist => Err(format!("{} doesn't exist", id)),
+            TripResult::TripDone => match self.trips.abort_reason(id) {
+                Some(reason) => Err(format!("{} was aborted ({})", id, reason)),
+                None => Err(format!("{} already finished", id)),
+            },
+            TripResult::Ok(_) | TripResult::ModeChange => Ok(scheduled_finish
+                .filter(|t| *t > self.time)
+                .unwrap_or(Time::END_OF_DAY)),
+        }
+    }
+
+    pub fn abort_reason(&self, id: TripID) -> Option<AbortReason> {
+        self.trips.abort_reason(id)
+    }
+
+    pub fn aborted_trips_by_reason(&self) -> BTreeMap<AbortReason, usize> {
+        self.trips.aborted_trips_by_reason()
+    }
+
+    pub fn scheduler_queue_len(&self) -> usize {
+        self.scheduler.queue_len()
+    }
+
+    pub fn count_through_trips(&self) -> usize {
+        self.trips.count_through_trips()
+    }
+
+    pub fn all_trips(&self) -> Vec<(TripID, TripMode, TripStart, TripEnd, Time, Option<Time>)> {
+        self.trips.all_trips()
+    }
+
     pub fn lookup_car_id(&self, idx: usize) -> Option<CarID> {
         for vt in &[VehicleType::Car, VehicleType::Bike, VehicleType::Bus] {
             let id = CarID(idx, *vt);
@@ -1026,6 +1603,16 @@ impl Sim {
         }
     }
 
+    // Only for cars currently driving
+    pub fn get_vehicle(&self, id: CarID) -> Option<&Vehicle> {
+        self.driving.get_vehicle(id)
+    }
+
+    // Only for pedestrians currently walking
+    pub fn get_ped_speed(&self, id: PedestrianID) -> Option<Speed> {
+        self.walking.get_ped_speed(id)
+    }
+
     pub fn get_owner_of_car(&self, id: CarID) -> Option<BuildingID> {
         self.driving
             .get_owner_of_car(id)
@@ -1102,10 +1689,48 @@ impl Sim {
         results
     }
 
+    // Pedestrians per meter on each sidewalk with anybody on it right now
+    pub fn all_sidewalk_densities(&self, map: &Map) -> Vec<(LaneID, f64)> {
+        self.walking
+            .peds_per_sidewalk()
+            .consume()
+            .into_iter()
+            .map(|(l, cnt)| (l, (cnt as f64) / map.get_l(l).length().inner_meters()))
+            .collect()
+    }
+
+    pub fn sidewalk_density(&self, l: LaneID, map: &Map) -> f64 {
+        (self.walking.peds_per_sidewalk().get(l) as f64) / map.get_l(l).length().inner_meters()
+    }
+
     pub fn get_analytics(&self) -> &Analytics {
         &self.analytics
     }
 
+    // How many trips of each mode have finished so far
+    pub fn mode_counts(&self) -> BTreeMap<TripMode, usize> {
+        self.analytics.mode_counts(self.time)
+    }
+
+    // One row per finished or aborted trip, with times in seconds
+    pub fn finished_trips_csv(&self) -> String {
+        let mut csv = vec!["finished_at,trip,person,mode,duration".to_string()];
+        for (t, id, mode, dt) in &self.analytics.finished_trips {
+            csv.push(format!(
+                "{},{},{},{},{}",
+                t.inner_seconds(),
+                id.0,
+                self.trip_to_person(*id)
+                    .map(|p| p.0.to_string())
+                    .unwrap_or_else(String::new),
+                mode.map(|m| m.to_string())
+                    .unwrap_or_else(|| "aborted".to_string()),
+                dt.inner_seconds()
+            ));
+        }
+        csv.join("\n")
+    }
+
     pub fn get_agent_metadata(&self) -> Vec<AgentMetadata> {
         let mut result = self.driving.get_agent_metadata(self.time);
         result.extend(self.walking.get_agent_metadata(self.time));
@@ -1117,12 +1742,63 @@ impl Sim {
             .find_blockage_front(car, map, &self.intersections)
     }
 
+    // From the car to whoever's ultimately in front of it, each car waiting on the next
+    pub fn blockage_chain(&self, car: CarID) -> Vec<CarID> {
+        self.driving.blockage_chain(car)
+    }
+
+    // Agents that haven't moved for longer than the threshold, longest-waiting first
+    pub fn find_stuck_agents(&self, threshold: Duration) -> Vec<(AgentID, Duration)> {
+        let mut stuck: Vec<(AgentID, Duration)> = self
+            .driving
+            .find_stuck_cars(self.time, threshold)
+            .into_iter()
+            .map(|(c, dt)| (AgentID::Car(c), dt))
+            .chain(
+                self.walking
+                    .find_stuck_peds(self.time, threshold)
+                    .into_iter()
+                    .map(|(p, dt)| (AgentID::Pedestrian(p), dt)),
+            )
+            .collect();
+        stuck.sort_by_key(|(a, dt)| (std::cmp::Reverse(*dt), *a));
+        stuck
+    }
+
     // For intersections with an agent waiting beyond some threshold, return when they started
     // waiting. Sorted by earliest waiting (likely the root cause of gridlock).
     pub fn delayed_intersections(&self, threshold: Duration) -> Vec<(IntersectionID, Time)> {
         self.intersections.find_gridlock(self.time, threshold)
     }
 
+    // Starting from the intersection that's been delayed the longest, finds every intersection
+    // involved in the same jam, following the chain of blocked cars from each stuck car. Each
+    // intersection is returned with the agents stuck waiting for it. Empty if nothing is delayed
+    // past the threshold.
+    pub fn find_gridlock(
+        &self,
+        threshold: Duration,
+        map: &Map,
+    ) -> Vec<(IntersectionID, Vec<AgentID>)> {
+        let delayed = self.delayed_intersections(threshold);
+        if delayed.is_empty() {
+            return Vec::new();
+        }
+        let chains: Vec<Vec<(IntersectionID, AgentID)>> = self
+            .driving
+            .find_stuck_cars(self.time, threshold)
+            .into_iter()
+            .filter_map(|(c, _)| self.driving.gridlock_chain(c, map, &self.intersections))
+            .map(|chain| {
+                chain
+                    .into_iter()
+                    .map(|(i, c)| (i, AgentID::Car(c)))
+                    .collect()
+            })
+            .collect();
+        gridlock_component(delayed[0].0, chains)
+    }
+
     pub fn trip_spec_to_path_req(&self, spec: &TripSpec, map: &Map) -> PathRequest {
         spec.get_pathfinding_request(map, &self.parking)
     }
@@ -1132,7 +1808,8 @@ impl Sim {
 impl Sim {
     pub fn kill_stuck_car(&mut self, id: CarID, map: &Map) {
         if let Some(trip) = self.agent_to_trip(AgentID::Car(id)) {
-            self.trips.abort_trip_failed_start(trip);
+            self.trips
+                .abort_trip_failed_start(trip, AbortReason::KilledByUser);
             self.driving.kill_stuck_car(
                 id,
                 self.time,
@@ -1145,4 +1822,140 @@ impl Sim {
             println!("{} has no trip?!", id);
         }
     }
+
+    // Removes every car that's been waiting past the threshold as part of gridlock. Their trips
+    // are aborted. Returns the number of cars removed.
+    pub fn kill_all_stuck_cars(&mut self, map: &Map, threshold: Duration) -> usize {
+        // Decide everything up-front; removing one car might unblock the others.
+        let victims: Vec<CarID> = self
+            .driving
+            .find_stuck_cars(self.time, threshold)
+            .into_iter()
+            .map(|(c, _)| c)
+            .filter(|c| {
+                c.1 != VehicleType::Bus && self.driving.is_gridlocked(*c, map, &self.intersections)
+            })
+            .collect();
+        for c in &victims {
+            self.trips.abort_car_trip(*c, AbortReason::Gridlock);
+            self.driving.kill_stuck_car(
+                *c,
+                self.time,
+                map,
+                &mut self.scheduler,
+                &mut self.intersections,
+            );
+        }
+        if !victims.is_empty() {
+            println!("Forcibly killed {} stuck cars", victims.len());
+        }
+        victims.len()
+    }
+}
+
+// Reasons for time_limited_step to stop early
+#[derive(Clone, Debug, PartialEq)]
+pub enum StopCondition {
+    // Look for gridlock this often, with the same threshold for being stuck
+    Gridlock(Duration),
+    // The agent is on any lane of the road
+    AgentOnRoad(AgentID, RoadID),
+    // The agent is in the middle of a turn through the intersection
+    AgentInIntersection(AgentID, IntersectionID),
+    // The trip finished or aborted
+    TripDone(TripID),
+}
+
+impl std::fmt::Display for StopCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StopCondition::Gridlock(_) => write!(f, "there's a traffic jam"),
+            StopCondition::AgentOnRoad(a, r) => write!(f, "{} reaches {}", a, r),
+            StopCondition::AgentInIntersection(a, i) => write!(f, "{} reaches {}", a, i),
+            StopCondition::TripDone(t) => write!(f, "{} finishes", t),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StopReason {
+    // The intersections involved in gridlock (see find_gridlock)
+    Gridlock(Vec<(IntersectionID, Vec<AgentID>)>),
+    // Any condition besides gridlock
+    Met(StopCondition),
+}
+
+// Given chains of blockage (each intersection paired with an agent waiting for it), returns every
+// intersection connected to the seed through chains that share an intersection, with all agents
+// waiting for each. The seed comes first; the rest are sorted by ID.
+pub fn gridlock_component(
+    seed: IntersectionID,
+    chains: Vec<Vec<(IntersectionID, AgentID)>>,
+) -> Vec<(IntersectionID, Vec<AgentID>)> {
+    let mut component: BTreeMap<IntersectionID, BTreeSet<AgentID>> = BTreeMap::new();
+    component.insert(seed, BTreeSet::new());
+    let mut remaining = chains;
+    loop {
+        let (connected, rest): (Vec<_>, Vec<_>) = remaining
+            .into_iter()
+            .partition(|chain| chain.iter().any(|(i, _)| component.contains_key(i)));
+        if connected.is_empty() {
+            break;
+        }
+        for chain in connected {
+            for (i, a) in chain {
+                component.entry(i).or_insert_with(BTreeSet::new).insert(a);
+            }
+        }
+        remaining = rest;
+    }
+
+    let mut result = vec![(seed, component.remove(&seed).unwrap().into_iter().collect())];
+    result.extend(
+        component
+            .into_iter()
+            .map(|(i, agents)| (i, agents.into_iter().collect())),
+    );
+    result
+}
+
+// Re-simulate the scenario from scratch up to the last logged event and panic if the events
+// produced differ in any way. The RNG and options must match the original run.
+pub fn replay_event_log(
+    path: String,
+    scenario: &Scenario,
+    map: &Map,
+    opts: SimOptions,
+    rng: &mut XorShiftRng,
+    timer: &mut Timer,
+) {
+    let expected: Vec<(Time, Event)> = abstutil::read_binary(path.clone(), timer);
+    let end_time = if let Some((t, _)) = expected.last() {
+        *t
+    } else {
+        return;
+    };
+
+    let mut sim = Sim::new(map, opts, timer);
+    scenario.instantiate(&mut sim, map, rng, timer);
+    sim.record_events(true);
+    sim.timed_step(map, end_time - sim.time(), timer);
+
+    let actual = sim.event_log.unwrap();
+    for (idx, (e1, e2)) in expected.iter().zip(actual.iter()).enumerate() {
+        if e1 != e2 {
+            panic!(
+                "Replaying {} diverged at event {}: expected {:?}, but got {:?}",
+                path, idx, e1, e2
+            );
+        }
+    }
+    if expected.len() != actual.len() {
+        panic!(
+            "Replaying {} produced {} events, but {} were logged",
+            path,
+            actual.len(),
+            expected.len()
+        );
+    }
 }
@@ -1,16 +1,19 @@
 use crate::{
     AgentID, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, Event, ParkingSimState,
-    ParkingSpot, PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID,
-    TripPhaseType, Vehicle, VehicleType, WalkingSimState,
+    ParkingSpot, PedestrianID, PersonID, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState,
+    TripID, TripPhaseType, Vehicle, VehicleType, WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
-use geom::{Speed, Time};
+use geom::{Distance, Duration, Pt2D, Speed, Time};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, VecDeque};
 
+// How long somebody spends at an intermediate stop of a trip before heading to the next one
+const TIME_TO_RUN_ERRAND: Duration = Duration::const_seconds(5.0 * 60.0);
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct TripManager {
     trips: Vec<Trip>,
@@ -22,6 +25,12 @@ pub struct TripManager {
     active_trip_mode: BTreeMap<AgentID, TripID>,
     num_bus_trips: usize,
     unfinished_trips: usize,
+    // Everybody from the scenario's population, plus anybody else who's taken a trip
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    people: BTreeMap<PersonID, TrackedPerson>,
 
     events: Vec<Event>,
 }
@@ -33,13 +42,55 @@ impl TripManager {
             active_trip_mode: BTreeMap::new(),
             num_bus_trips: 0,
             unfinished_trips: 0,
+            people: BTreeMap::new(),
             events: Vec::new(),
         }
     }
 
-    pub fn new_trip(&mut self, spawned_at: Time, start: TripStart, legs: Vec<TripLeg>) -> TripID {
-        assert!(!legs.is_empty());
-        // TODO Make sure the legs constitute a valid state machine.
+    // People start the day at home.
+    pub fn new_person(&mut self, id: PersonID, home: Option<BuildingID>) {
+        let state = match home {
+            Some(b) => PersonState::Inside(b),
+            None => PersonState::Limbo,
+        };
+        self.people.insert(
+            id,
+            TrackedPerson {
+                home,
+                state: PersonState::Limbo,
+            },
+        );
+        self.set_person_state(id, state);
+    }
+
+    fn set_person_state(&mut self, id: PersonID, state: PersonState) {
+        let person = self.people.entry(id).or_insert_with(|| TrackedPerson {
+            home: None,
+            state: PersonState::Limbo,
+        });
+        if person.state != state {
+            person.state = state;
+            self.events.push(Event::PersonStateChanged(id, state));
+        }
+    }
+
+    // Updates the state of the person taking this trip, if there is one.
+    fn set_trip_person_state(&mut self, trip: TripID, state: PersonState) {
+        if let Some(person) = self.trips[trip.0].person {
+            self.set_person_state(person, state);
+        }
+    }
+
+    pub fn new_trip(
+        &mut self,
+        person: Option<PersonID>,
+        spawned_at: Time,
+        start: TripStart,
+        legs: Vec<TripLeg>,
+    ) -> TripID {
+        if let Err(err) = validate_legs(&start, &legs) {
+            panic!("Invalid legs for a trip starting at {:?}: {}", start, err);
+        }
 
         let id = TripID(self.trips.len());
         let mut mode = TripMode::Walk;
@@ -71,26 +122,30 @@ impl TripManager {
                 SidewalkPOI::Border(i) => TripEnd::Border(i),
                 SidewalkPOI::DeferredParkingSpot(_, ref goal) => match goal {
                     DrivingGoal::ParkNear(b) => TripEnd::Bldg(*b),
-                    DrivingGoal::Border(i, _) => TripEnd::Border(*i),
+                    DrivingGoal::Border(i, _) | DrivingGoal::AnyLane(i, _) => TripEnd::Border(*i),
                 },
                 _ => unreachable!(),
             },
             Some(TripLeg::Drive(_, ref goal)) => match goal {
                 DrivingGoal::ParkNear(b) => TripEnd::Bldg(*b),
-                DrivingGoal::Border(i, _) => TripEnd::Border(*i),
+                DrivingGoal::Border(i, _) | DrivingGoal::AnyLane(i, _) => TripEnd::Border(*i),
             },
             Some(TripLeg::ServeBusRoute(_, route)) => TripEnd::ServeBusRoute(*route),
             _ => unreachable!(),
         };
         let trip = Trip {
             id,
+            person,
             spawned_at,
             finished_at: None,
-            aborted: false,
+            aborted: None,
             mode,
             legs: VecDeque::from(legs),
             start,
             end,
+            waiting_for_bus_since: None,
+            transit_wait: None,
+            total_distance: Distance::ZERO,
         };
         if !trip.is_bus_trip() {
             self.unfinished_trips += 1;
@@ -101,19 +156,107 @@ impl TripManager {
 
     pub fn dynamically_override_legs(&mut self, id: TripID, legs: Vec<TripLeg>) {
         let trip = &mut self.trips[id.0];
+        if let Err(err) = validate_legs(&trip.start, &legs) {
+            panic!("Invalid legs to replace {}'s: {}", id, err);
+        }
         trip.legs = VecDeque::from(legs);
         // This is only for peds using a previously unknown parked car
         trip.mode = TripMode::Drive;
     }
 
-    pub fn agent_starting_trip_leg(&mut self, agent: AgentID, trip: TripID) {
+    // Before changing a trip's goal mid-route, make sure it's currently driving somewhere and
+    // figure out the new goal for the car. The car's route has to be changed before calling
+    // change_goal.
+    pub fn prepare_goal_change(
+        &self,
+        id: TripID,
+        new_goal: &TripEndpoint,
+        map: &Map,
+    ) -> Result<(CarID, DrivingGoal), String> {
+        let trip = &self.trips[id.0];
+        if trip.finished_at.is_some() || trip.aborted.is_some() {
+            return Err(format!("{} is already done", id));
+        }
+        let vehicle = match trip.legs.front() {
+            Some(TripLeg::Drive(vehicle, _))
+                if self.active_trip_mode.get(&AgentID::Car(vehicle.id)) == Some(&id) =>
+            {
+                vehicle
+            }
+            _ => {
+                return Err(format!("{} isn't driving right now", id));
+            }
+        };
+        if let TripEndpoint::Bldg(_) = new_goal {
+            // Somebody has to walk from wherever the car parks
+            match trip.legs.get(1) {
+                Some(TripLeg::Walk(_, _, _)) => {}
+                _ => {
+                    return Err(format!("{} has nobody to walk to a building", id));
+                }
+            }
+        }
+        let goal = new_goal
+            .driving_goal(vehicle.vehicle_type.to_constraints(), map)
+            .ok_or_else(|| format!("{:?} can't be reached by {}", new_goal, vehicle.id))?;
+        Ok((vehicle.id, goal))
+    }
+
+    // Points the driving leg of a trip at a new goal and updates whatever happens after. The
+    // lengths are of the car's whole route for this leg, before and after rerouting.
+    pub fn change_goal(
+        &mut self,
+        id: TripID,
+        new_goal: TripEndpoint,
+        driving_goal: DrivingGoal,
+        old_length: Distance,
+        new_length: Distance,
+        map: &Map,
+    ) {
+        let trip = &mut self.trips[id.0];
+        // The leg's length was counted when it started, so swap in the new route.
+        trip.total_distance = trip.total_distance - old_length + new_length;
+        let vehicle = match trip.legs.pop_front() {
+            Some(TripLeg::Drive(vehicle, _)) => vehicle,
+            _ => unreachable!(),
+        };
+        let walk = trip.legs.pop_front();
+        trip.legs.clear();
+        trip.legs.push_back(TripLeg::Drive(vehicle, driving_goal));
+        trip.end = match new_goal {
+            TripEndpoint::Bldg(b) => {
+                match walk {
+                    Some(TripLeg::Walk(ped, speed, _)) => {
+                        trip.legs.push_back(TripLeg::Walk(
+                            ped,
+                            speed,
+                            SidewalkSpot::building(b, map),
+                        ));
+                    }
+                    _ => unreachable!(),
+                }
+                TripEnd::Bldg(b)
+            }
+            TripEndpoint::Border(i) => TripEnd::Border(i),
+        };
+        let legs: Vec<TripLeg> = trip.legs.iter().cloned().collect();
+        if let Err(err) = validate_remaining_legs(None, &legs) {
+            panic!("Changing {}'s goal broke its legs: {}", id, err);
+        }
+    }
+
+    // path_length is the length of the path the agent will follow for this leg.
+    pub fn agent_starting_trip_leg(&mut self, agent: AgentID, trip: TripID, path_length: Distance) {
         assert!(!self.active_trip_mode.contains_key(&agent));
         // TODO ensure a trip only has one active agent (aka, not walking and driving at the same
         // time)
         self.active_trip_mode.insert(agent, trip);
+        // Every leg that starts is followed to the end, or else the trip is aborted.
+        self.trips[trip.0].total_distance += path_length;
         if self.trips[trip.0].is_bus_trip() {
             self.num_bus_trips += 1;
         }
+        self.set_trip_person_state(trip, PersonState::Trip(trip));
     }
 
     pub fn car_reached_parking_spot(
@@ -128,24 +271,40 @@ impl TripManager {
         self.events.push(Event::CarReachedParkingSpot(car, spot));
         let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(car)).unwrap().0];
 
-        match trip.legs.pop_front() {
-            Some(TripLeg::Drive(vehicle, DrivingGoal::ParkNear(_))) => assert_eq!(car, vehicle.id),
+        let goal = match trip.legs.pop_front() {
+            Some(TripLeg::Drive(vehicle, DrivingGoal::ParkNear(b))) => {
+                assert_eq!(car, vehicle.id);
+                b
+            }
             _ => unreachable!(),
         };
+        // As the crow flies, from where the car wound up to the building the driver wanted
+        let walk_dist = match spot {
+            ParkingSpot::Offstreet(b, _) if b == goal => Distance::ZERO,
+            _ => SidewalkSpot::parking_spot(spot, map, parking)
+                .sidewalk_pos
+                .pt(map)
+                .dist_to(SidewalkSpot::building(goal, map).sidewalk_pos.pt(map)),
+        };
+        self.events
+            .push(Event::CarParkedNearBuilding(car, goal, walk_dist));
 
         match &trip.legs[0] {
             TripLeg::Walk(_, _, to) => match (spot, &to.connection) {
-                (ParkingSpot::Offstreet(b1, _), SidewalkPOI::Building(b2)) if b1 == *b2 => {
+                (ParkingSpot::Offstreet(b1, _), SidewalkPOI::Building(b2))
+                    if b1 == *b2 && trip.legs.len() == 1 =>
+                {
                     // Do the relevant parts of ped_reached_parking_spot.
-                    assert_eq!(trip.legs.len(), 1);
                     assert!(!trip.finished_at.is_some());
                     trip.finished_at = Some(now);
                     self.unfinished_trips -= 1;
+                    let id = trip.id;
                     self.events.push(Event::TripFinished(
                         trip.id,
                         trip.mode,
                         now - trip.spawned_at,
                     ));
+                    self.set_trip_person_state(id, PersonState::Inside(b1));
                     return;
                 }
                 _ => {}
@@ -153,13 +312,35 @@ impl TripManager {
             _ => unreachable!(),
         };
 
+        // If the trip has more stops, the driver has to walk back to the car after this one.
+        let walk_back = match (&trip.legs[0], trip.legs.get(1)) {
+            (TripLeg::Walk(ped, speed, _), Some(TripLeg::Drive(ref vehicle, _)))
+                if vehicle.id == car =>
+            {
+                Some(TripLeg::Walk(
+                    *ped,
+                    *speed,
+                    SidewalkSpot::parking_spot(spot, map, parking),
+                ))
+            }
+            _ => None,
+        };
+        if let Some(leg) = walk_back {
+            trip.legs.insert(1, leg);
+            let legs: Vec<TripLeg> = trip.legs.iter().cloned().collect();
+            if let Err(err) = validate_remaining_legs(None, &legs) {
+                panic!("Walking back to the car broke {}'s legs: {}", trip.id, err);
+            }
+        }
+
         if !trip.spawn_ped(
             now,
             SidewalkSpot::parking_spot(spot, map, parking),
             map,
             scheduler,
         ) {
-            self.unfinished_trips -= 1;
+            let id = trip.id;
+            self.abort_trip(id, AbortReason::NoPath);
         }
     }
 
@@ -192,22 +373,16 @@ impl TripManager {
             // Actually, to unpark, the car's front should be where it'll wind up at the end.
             start = Position::new(start.lane(), start.dist_along() + parked_car.vehicle.length);
         }
-        let end = drive_to.goal_pos(PathConstraints::Car, map);
-        let req = PathRequest {
-            start,
-            end,
-            constraints: PathConstraints::Car,
-        };
-        let path = if let Some(p) = map.pathfind(req.clone()) {
+        let (req, maybe_path) = drive_to.pathfind(start, PathConstraints::Car, map);
+        let path = if let Some(p) = maybe_path {
             p
         } else {
             println!(
                 "Aborting {} at {} because no path for the car portion! {} to {}",
-                trip.id, now, start, end
+                trip.id, now, req.start, req.end
             );
-            self.unfinished_trips -= 1;
-            trip.aborted = true;
-            self.events.push(Event::TripAborted(trip.id, trip.mode));
+            let id = trip.id;
+            self.abort_trip(id, AbortReason::NoPath);
             return;
         };
 
@@ -251,22 +426,16 @@ impl TripManager {
             _ => unreachable!(),
         };
 
-        let end = drive_to.goal_pos(PathConstraints::Bike, map);
-        let req = PathRequest {
-            start: driving_pos,
-            end,
-            constraints: PathConstraints::Bike,
-        };
-        let path = if let Some(p) = map.pathfind(req.clone()) {
+        let (req, maybe_path) = drive_to.pathfind(driving_pos, PathConstraints::Bike, map);
+        let path = if let Some(p) = maybe_path {
             p
         } else {
             println!(
                 "Aborting {} at {} because no path for the bike portion! {} to {}",
-                trip.id, now, driving_pos, end
+                trip.id, now, req.start, req.end
             );
-            self.unfinished_trips -= 1;
-            trip.aborted = true;
-            self.events.push(Event::TripAborted(trip.id, trip.mode));
+            let id = trip.id;
+            self.abort_trip(id, AbortReason::NoPath);
             return;
         };
 
@@ -300,7 +469,8 @@ impl TripManager {
         };
 
         if !trip.spawn_ped(now, bike_rack, map, scheduler) {
-            self.unfinished_trips -= 1;
+            let id = trip.id;
+            self.abort_trip(id, AbortReason::NoPath);
         }
     }
 
@@ -310,6 +480,7 @@ impl TripManager {
         ped: PedestrianID,
         bldg: BuildingID,
         map: &Map,
+        scheduler: &mut Scheduler,
     ) {
         self.events.push(Event::PedReachedBuilding(ped, bldg));
         let trip = &mut self.trips[self
@@ -318,15 +489,29 @@ impl TripManager {
             .unwrap()
             .0];
         trip.assert_walking_leg(ped, SidewalkSpot::building(bldg, map));
-        assert!(trip.legs.is_empty());
+        if !trip.legs.is_empty() {
+            // An intermediate stop. Spend some time here, then continue the trip.
+            if !trip.spawn_ped(
+                now + TIME_TO_RUN_ERRAND,
+                SidewalkSpot::building(bldg, map),
+                map,
+                scheduler,
+            ) {
+                let id = trip.id;
+                self.abort_trip(id, AbortReason::NoPath);
+            }
+            return;
+        }
         assert!(!trip.finished_at.is_some());
         trip.finished_at = Some(now);
         self.unfinished_trips -= 1;
+        let id = trip.id;
         self.events.push(Event::TripFinished(
             trip.id,
             trip.mode,
             now - trip.spawned_at,
         ));
+        self.set_trip_person_state(id, PersonState::Inside(bldg));
     }
 
     // If no route is returned, the pedestrian boarded a bus immediately.
@@ -357,8 +542,11 @@ impl TripManager {
                 ));
                 if transit.ped_waiting_for_bus(now, ped, stop, route, stop2) {
                     trip.legs.pop_front();
+                    // The bus was already there
+                    trip.transit_wait = Some(trip.transit_wait.unwrap_or(Duration::ZERO));
                     None
                 } else {
+                    trip.waiting_for_bus_since = Some(now);
                     Some(route)
                 }
             }
@@ -375,6 +563,9 @@ impl TripManager {
         // TODO Make sure canonical pt is the bus while the ped is riding it
         let trip = &mut self.trips[self.active_trip_mode[&AgentID::Pedestrian(ped)].0];
         trip.legs.pop_front();
+        if let Some(since) = trip.waiting_for_bus_since.take() {
+            trip.transit_wait = Some(trip.transit_wait.unwrap_or(Duration::ZERO) + (now - since));
+        }
         walking.ped_boarded_bus(now, ped);
         trip.id
     }
@@ -397,7 +588,8 @@ impl TripManager {
         };
 
         if !trip.spawn_ped(now, start, map, scheduler) {
-            self.unfinished_trips -= 1;
+            let id = trip.id;
+            self.abort_trip(id, AbortReason::NoPath);
         }
     }
 
@@ -419,47 +611,61 @@ impl TripManager {
         assert!(!trip.finished_at.is_some());
         trip.finished_at = Some(now);
         self.unfinished_trips -= 1;
+        let id = trip.id;
         self.events.push(Event::TripFinished(
             trip.id,
             trip.mode,
             now - trip.spawned_at,
         ));
+        self.set_trip_person_state(id, PersonState::OffMap);
     }
 
     pub fn car_or_bike_reached_border(&mut self, now: Time, car: CarID, i: IntersectionID) {
         self.events.push(Event::CarOrBikeReachedBorder(car, i));
         let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(car)).unwrap().0];
         match trip.legs.pop_front().unwrap() {
-            TripLeg::Drive(_, DrivingGoal::Border(int, _)) => assert_eq!(i, int),
+            TripLeg::Drive(_, DrivingGoal::Border(int, _))
+            | TripLeg::Drive(_, DrivingGoal::AnyLane(int, _)) => assert_eq!(i, int),
             _ => unreachable!(),
         };
         assert!(trip.legs.is_empty());
         assert!(!trip.finished_at.is_some());
         trip.finished_at = Some(now);
         self.unfinished_trips -= 1;
+        let id = trip.id;
         self.events.push(Event::TripFinished(
             trip.id,
             trip.mode,
             now - trip.spawned_at,
         ));
+        self.set_trip_person_state(id, PersonState::OffMap);
     }
 
-    pub fn abort_trip_failed_start(&mut self, id: TripID) {
-        self.trips[id.0].aborted = true;
+    pub fn abort_trip_failed_start(&mut self, id: TripID, reason: AbortReason) {
+        self.abort_trip(id, reason);
+    }
+
+    // Every way a trip can fail winds up here, so the reason is always recorded. The trip
+    // mustn't have an active agent anymore.
+    fn abort_trip(&mut self, id: TripID, reason: AbortReason) {
+        self.trips[id.0].aborted = Some(reason);
         if !self.trips[id.0].is_bus_trip() {
             self.unfinished_trips -= 1;
         }
         self.events
             .push(Event::TripAborted(id, self.trips[id.0].mode));
+        self.set_trip_person_state(id, PersonState::Limbo);
     }
 
     pub fn abort_trip_impossible_parking(&mut self, car: CarID) {
+        self.abort_car_trip(car, AbortReason::NoParking);
+    }
+
+    // Aborts the trip of a car that's already on the map.
+    pub fn abort_car_trip(&mut self, car: CarID, reason: AbortReason) {
         let trip = self.active_trip_mode.remove(&AgentID::Car(car)).unwrap();
         assert!(!self.trips[trip.0].is_bus_trip());
-        self.trips[trip.0].aborted = true;
-        self.unfinished_trips -= 1;
-        self.events
-            .push(Event::TripAborted(trip, self.trips[trip.0].mode));
+        self.abort_trip(trip, reason);
     }
 
     pub fn active_agents(&self) -> Vec<AgentID> {
@@ -476,7 +682,7 @@ impl TripManager {
         }
         let trip = &self.trips[id.0];
 
-        if trip.finished_at.is_some() || trip.aborted {
+        if trip.finished_at.is_some() || trip.aborted.is_some() {
             return TripResult::TripDone;
         }
 
@@ -489,6 +695,47 @@ impl TripManager {
         }
     }
 
+    // None for trips not spawned from a scenario's population, like buses
+    pub fn trip_to_person(&self, id: TripID) -> Option<PersonID> {
+        self.trips[id.0].person
+    }
+
+    pub fn person_home(&self, id: PersonID) -> Option<BuildingID> {
+        self.people.get(&id).and_then(|p| p.home)
+    }
+
+    pub fn person_state(&self, id: PersonID) -> PersonState {
+        self.people
+            .get(&id)
+            .map(|p| p.state)
+            .unwrap_or(PersonState::Limbo)
+    }
+
+    pub fn is_person_home(&self, id: PersonID) -> bool {
+        match self.people.get(&id) {
+            Some(TrackedPerson {
+                home: Some(home),
+                state: PersonState::Inside(b),
+            }) => home == b,
+            _ => false,
+        }
+    }
+
+    // (People at home, people with a home)
+    pub fn count_people_home(&self) -> (usize, usize) {
+        let mut at_home = 0;
+        let mut total = 0;
+        for (id, p) in &self.people {
+            if p.home.is_some() {
+                total += 1;
+                if self.is_person_home(*id) {
+                    at_home += 1;
+                }
+            }
+        }
+        (at_home, total)
+    }
+
     // This will be None for parked cars
     pub fn agent_to_trip(&self, id: AgentID) -> Option<TripID> {
         self.active_trip_mode.get(&id).cloned()
@@ -496,17 +743,46 @@ impl TripManager {
 
     pub fn debug_trip(&self, id: AgentID) {
         if let Some(t) = self.active_trip_mode.get(&id) {
-            let trip = &self.trips[t.0];
-            println!("{} has goal {:?}", trip.id, trip.legs.back().unwrap());
+            println!("{} has legs:", t);
+            for line in self.describe_trip(*t) {
+                println!("- {}", line);
+            }
         } else {
             println!("{} has no trip, must be parked car", id);
         }
     }
 
-    // (finished trips, unfinished trips, active trips by the trip's current mode)
+    // One line per leg the trip has left. Legs are dropped as they finish, so this is the whole
+    // plan before the trip starts, and empty after it's done.
+    pub fn describe_trip(&self, id: TripID) -> Vec<String> {
+        self.trips[id.0]
+            .legs
+            .iter()
+            .map(|leg| leg.describe())
+            .collect()
+    }
+
+    // Of the unfinished trips, how many are scheduled to start after now. The rest are in progress.
+    pub fn num_trips_not_departed(&self, now: Time) -> usize {
+        self.trips
+            .iter()
+            .filter(|t| {
+                t.spawned_at > now
+                    && t.finished_at.is_none()
+                    && t.aborted.is_none()
+                    && !t.is_bus_trip()
+            })
+            .count()
+    }
+
+    // (finished trips, unfinished trips, active trips by the trip's current mode). Buses serving a
+    // route aren't counted; TripMode::Transit is only for passengers.
     pub fn num_trips(&self) -> (usize, usize, BTreeMap<TripMode, usize>) {
         let mut cnt = Counter::new();
-        for a in self.active_trip_mode.keys() {
+        for (a, trip) in &self.active_trip_mode {
+            if self.trips[trip.0].is_bus_trip() {
+                continue;
+            }
             cnt.inc(TripMode::from_agent(*a));
         }
         let per_mode = TripMode::all()
@@ -528,6 +804,17 @@ impl TripManager {
         std::mem::replace(&mut self.events, Vec::new())
     }
 
+    // Trips starting and ending at borders, just passing through the map
+    pub fn count_through_trips(&self) -> usize {
+        self.trips
+            .iter()
+            .filter(|t| match (&t.start, &t.end) {
+                (TripStart::Border(_), TripEnd::Border(_)) => true,
+                _ => false,
+            })
+            .count()
+    }
+
     // Return trip start time too
     pub fn find_trip_using_car(&self, id: CarID, home: BuildingID) -> Option<(TripID, Time)> {
         let t = self.trips.iter().find(|t| t.uses_car(id, home))?;
@@ -539,6 +826,61 @@ impl TripManager {
         (t.start.clone(), t.end.clone())
     }
 
+    // True for the trips of buses serving a route, as opposed to anybody riding them
+    pub fn is_bus_service(&self, id: TripID) -> bool {
+        self.trips[id.0].is_bus_trip()
+    }
+
+    // None if the trip hasn't been aborted
+    pub fn abort_reason(&self, id: TripID) -> Option<AbortReason> {
+        self.trips[id.0].aborted
+    }
+
+    // Bus service trips aren't counted.
+    pub fn aborted_trips_by_reason(&self) -> BTreeMap<AbortReason, usize> {
+        let mut cnt = BTreeMap::new();
+        for trip in &self.trips {
+            if let Some(reason) = trip.aborted {
+                if !trip.is_bus_trip() {
+                    *cnt.entry(reason).or_insert(0) += 1;
+                }
+            }
+        }
+        cnt
+    }
+
+    // How long the trip has waited at bus stops, if it's boarded a bus yet
+    pub fn transit_wait_time(&self, id: TripID) -> Option<Duration> {
+        self.trips[id.0].transit_wait
+    }
+
+    // How far the trip went, only once it's finished
+    pub fn trip_distance(&self, id: TripID) -> Option<Distance> {
+        let trip = &self.trips[id.0];
+        if trip.finished_at.is_some() {
+            Some(trip.total_distance)
+        } else {
+            None
+        }
+    }
+
+    // (ID, mode, start, end, spawn time, finish time) of every trip, finished or not
+    pub fn all_trips(&self) -> Vec<(TripID, TripMode, TripStart, TripEnd, Time, Option<Time>)> {
+        self.trips
+            .iter()
+            .map(|t| {
+                (
+                    t.id,
+                    t.mode,
+                    t.start.clone(),
+                    t.end.clone(),
+                    t.spawned_at,
+                    t.finished_at,
+                )
+            })
+            .collect()
+    }
+
     // TODO Refactor after wrangling the TripStart/TripEnd mess
     pub fn count_trips_involving_bldg(&self, b: BuildingID, now: Time) -> TripCount {
         self.count_trips(TripStart::Bldg(b), TripEnd::Bldg(b), now)
@@ -556,10 +898,13 @@ impl TripManager {
             to_in_progress: Vec::new(),
             to_completed: Vec::new(),
             to_unstarted: Vec::new(),
+            from_by_mode: BTreeMap::new(),
+            to_by_mode: BTreeMap::new(),
         };
         for trip in &self.trips {
             if trip.start == start {
-                if trip.aborted {
+                *cnt.from_by_mode.entry(trip.mode).or_insert(0) += 1;
+                if trip.aborted.is_some() {
                     cnt.from_aborted.push(trip.id);
                 } else if trip.finished_at.is_some() {
                     cnt.from_completed.push(trip.id);
@@ -571,7 +916,8 @@ impl TripManager {
             }
             // One trip might could towards both!
             if trip.end == end {
-                if trip.aborted {
+                *cnt.to_by_mode.entry(trip.mode).or_insert(0) += 1;
+                if trip.aborted.is_some() {
                     cnt.to_aborted.push(trip.id);
                 } else if trip.finished_at.is_some() {
                     cnt.to_completed.push(trip.id);
@@ -586,16 +932,39 @@ impl TripManager {
     }
 }
 
+// Where somebody from the scenario's population is
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PersonState {
+    Inside(BuildingID),
+    Trip(TripID),
+    // Their last trip ended at a border
+    OffMap,
+    // Their last trip aborted, or they have no home and haven't started a trip yet
+    Limbo,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct TrackedPerson {
+    home: Option<BuildingID>,
+    state: PersonState,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 struct Trip {
     id: TripID,
+    person: Option<PersonID>,
     spawned_at: Time,
     finished_at: Option<Time>,
-    aborted: bool,
+    aborted: Option<AbortReason>,
     legs: VecDeque<TripLeg>,
     mode: TripMode,
     start: TripStart,
     end: TripEnd,
+    waiting_for_bus_since: Option<Time>,
+    // Summed over every bus ridden so far. None if the trip hasn't boarded a bus.
+    transit_wait: Option<Duration>,
+    // Summed over the paths of every leg started so far. Riding a bus doesn't count.
+    total_distance: Distance,
 }
 
 impl Trip {
@@ -684,6 +1053,149 @@ pub enum TripLeg {
     ServeBusRoute(CarID, BusRouteID),
 }
 
+impl TripLeg {
+    fn describe(&self) -> String {
+        match self {
+            TripLeg::Walk(_, _, spot) => format!("walk to {}", describe_spot(spot)),
+            TripLeg::Drive(vehicle, goal) => {
+                let verb = if vehicle.vehicle_type == VehicleType::Bike {
+                    "bike"
+                } else {
+                    "drive"
+                };
+                match goal {
+                    DrivingGoal::ParkNear(b) => format!("{} to park near {}", verb, b),
+                    DrivingGoal::Border(i, _) | DrivingGoal::AnyLane(i, _) => {
+                        format!("{} to {}", verb, i)
+                    }
+                }
+            }
+            TripLeg::RideBus(_, route, stop) => format!("ride {} to {}", route, stop),
+            TripLeg::ServeBusRoute(_, route) => format!("serve {}", route),
+        }
+    }
+}
+
+// Makes sure the legs make sense in order, so the trip can't get into a state that the handlers
+// above don't expect. The error names the first offending leg.
+pub fn validate_legs(start: &TripStart, legs: &[TripLeg]) -> Result<(), String> {
+    validate_remaining_legs(Some(start), legs)
+}
+
+// Like validate_legs, but for the legs left partway through a trip. Without a start, the checks
+// about where the trip began are skipped, since the first leg begins wherever the agent is now.
+fn validate_remaining_legs(start: Option<&TripStart>, legs: &[TripLeg]) -> Result<(), String> {
+    if legs.is_empty() {
+        return Err("a trip needs at least one leg".to_string());
+    }
+    // Only vehicles appear at borders; somebody leaving a building has to walk out first.
+    if let Some(TripStart::Bldg(b)) = start {
+        match legs[0] {
+            TripLeg::Walk(_, _, _) => {}
+            _ => {
+                return Err(format!(
+                    "leg 0 ({}) can't start from {}",
+                    legs[0].describe(),
+                    b
+                ));
+            }
+        }
+    }
+    let mut ped: Option<PedestrianID> = None;
+    for (idx, leg) in legs.iter().enumerate() {
+        let bad = |problem: &str| Err(format!("leg {} ({}) {}", idx, leg.describe(), problem));
+        let next = legs.get(idx + 1);
+        let leg_ped = match leg {
+            TripLeg::Walk(p, _, _) | TripLeg::RideBus(p, _, _) => Some(*p),
+            TripLeg::Drive(_, _) | TripLeg::ServeBusRoute(_, _) => None,
+        };
+        if let Some(p) = leg_ped {
+            if ped.is_some() && ped != Some(p) {
+                return bad("is by a different pedestrian than earlier legs");
+            }
+            ped = Some(p);
+        }
+        match leg {
+            TripLeg::Walk(_, _, spot) => match spot.connection {
+                SidewalkPOI::BusStop(_) => match next {
+                    Some(TripLeg::RideBus(_, _, _)) => {}
+                    _ => {
+                        return bad("isn't followed by riding a bus");
+                    }
+                },
+                SidewalkPOI::BikeRack(_) => match next {
+                    Some(TripLeg::Drive(ref vehicle, _))
+                        if vehicle.vehicle_type == VehicleType::Bike => {}
+                    _ => {
+                        return bad("isn't followed by biking");
+                    }
+                },
+                SidewalkPOI::ParkingSpot(_) => match next {
+                    Some(TripLeg::Drive(ref vehicle, _))
+                        if vehicle.vehicle_type != VehicleType::Bike => {}
+                    _ => {
+                        return bad("isn't followed by driving");
+                    }
+                },
+                SidewalkPOI::DeferredParkingSpot(b, _) => {
+                    if next.is_some() {
+                        return bad("has to be the last leg, until the car is known");
+                    }
+                    if start.map(|s| *s != TripStart::Bldg(b)).unwrap_or(false) {
+                        return bad("doesn't start from the trip's building");
+                    }
+                }
+                SidewalkPOI::Building(_) | SidewalkPOI::Border(_) => {}
+                SidewalkPOI::SuddenlyAppear => {
+                    return bad("can't end somewhere arbitrary");
+                }
+            },
+            TripLeg::Drive(_, _) => {}
+            TripLeg::RideBus(_, _, _) => {
+                match idx.checked_sub(1).map(|i| &legs[i]) {
+                    Some(TripLeg::Walk(_, _, ref spot)) => match spot.connection {
+                        SidewalkPOI::BusStop(_) => {}
+                        _ => {
+                            return bad("isn't preceded by walking to a bus stop");
+                        }
+                    },
+                    _ => {
+                        return bad("isn't preceded by walking to a bus stop");
+                    }
+                }
+                match next {
+                    Some(TripLeg::Walk(_, _, _)) => {}
+                    _ => {
+                        return bad("isn't followed by walking");
+                    }
+                }
+            }
+            TripLeg::ServeBusRoute(_, _) => {
+                if legs.len() != 1 {
+                    return bad("has to be the only leg");
+                }
+                if let Some(TripStart::Bldg(_)) = start {
+                    return bad("has to start at a border");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn describe_spot(spot: &SidewalkSpot) -> String {
+    match spot.connection {
+        SidewalkPOI::ParkingSpot(_) | SidewalkPOI::DeferredParkingSpot(_, _) => {
+            "a parking spot".to_string()
+        }
+        SidewalkPOI::Building(b) => b.to_string(),
+        SidewalkPOI::BusStop(stop) => stop.to_string(),
+        SidewalkPOI::Border(i) => i.to_string(),
+        SidewalkPOI::BikeRack(_) => "a bike rack".to_string(),
+        SidewalkPOI::SuddenlyAppear => spot.sidewalk_pos.lane().to_string(),
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
 pub enum TripMode {
     Walk,
@@ -725,6 +1237,35 @@ impl std::fmt::Display for TripMode {
     }
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
+pub enum AbortReason {
+    // No path exists for some leg of the trip
+    NoPath,
+    // A car or bike reached its destination, but couldn't find anywhere to stop
+    NoParking,
+    // Spawning failed, like when there's no room for a car or no vehicle to borrow
+    FailedToSpawn,
+    // The trip was retracted before it started
+    Cancelled,
+    // The agent was removed while stuck in gridlock
+    Gridlock,
+    // The agent was forcibly removed through a debug tool
+    KilledByUser,
+}
+
+impl std::fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AbortReason::NoPath => write!(f, "no path"),
+            AbortReason::NoParking => write!(f, "no parking"),
+            AbortReason::FailedToSpawn => write!(f, "failed to spawn"),
+            AbortReason::Cancelled => write!(f, "cancelled"),
+            AbortReason::Gridlock => write!(f, "stuck in gridlock"),
+            AbortReason::KilledByUser => write!(f, "killed by user"),
+        }
+    }
+}
+
 // TODO Argh no, not more of these variants!
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -741,6 +1282,57 @@ pub enum TripEnd {
     ServeBusRoute(BusRouteID),
 }
 
+// One stop of a trip that visits several places. Unlike TripStart and TripEnd, this is persisted
+// in Scenarios, so it only refers to things that survive map edits.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub enum TripEndpoint {
+    Bldg(BuildingID),
+    Border(IntersectionID),
+}
+
+impl TripEndpoint {
+    pub(crate) fn start_sidewalk_spot(&self, map: &Map) -> Option<SidewalkSpot> {
+        match self {
+            TripEndpoint::Bldg(b) => Some(SidewalkSpot::building(*b, map)),
+            TripEndpoint::Border(i) => SidewalkSpot::start_at_border(*i, map),
+        }
+    }
+
+    pub(crate) fn end_sidewalk_spot(&self, map: &Map) -> Option<SidewalkSpot> {
+        match self {
+            TripEndpoint::Bldg(b) => Some(SidewalkSpot::building(*b, map)),
+            TripEndpoint::Border(i) => SidewalkSpot::end_at_border(*i, map),
+        }
+    }
+
+    pub(crate) fn driving_goal(
+        &self,
+        constraints: PathConstraints,
+        map: &Map,
+    ) -> Option<DrivingGoal> {
+        match self {
+            TripEndpoint::Bldg(b) => Some(DrivingGoal::ParkNear(*b)),
+            TripEndpoint::Border(i) => {
+                DrivingGoal::border_lanes(*i, map.get_i(*i).get_incoming_lanes(map, constraints))
+            }
+        }
+    }
+
+    pub fn pt(&self, map: &Map) -> Pt2D {
+        match self {
+            TripEndpoint::Bldg(b) => map.get_b(*b).polygon.center(),
+            TripEndpoint::Border(i) => map.get_i(*i).polygon.center(),
+        }
+    }
+
+    pub(crate) fn to_trip_start(&self) -> TripStart {
+        match self {
+            TripEndpoint::Bldg(b) => TripStart::Bldg(*b),
+            TripEndpoint::Border(i) => TripStart::Border(*i),
+        }
+    }
+}
+
 pub enum TripResult<T> {
     Ok(T),
     ModeChange,
@@ -776,6 +1368,9 @@ pub struct TripCount {
     pub to_in_progress: Vec<TripID>,
     pub to_completed: Vec<TripID>,
     pub to_unstarted: Vec<TripID>,
+    // Regardless of status
+    pub from_by_mode: BTreeMap<TripMode, usize>,
+    pub to_by_mode: BTreeMap<TripMode, usize>,
 }
 
 impl TripCount {
@@ -809,6 +1404,47 @@ impl TripCount {
                 self.to_aborted.len()
             ));
         }
+        if !self.from_by_mode.is_empty() {
+            lines.push(format!(
+                "By mode from here: {}",
+                describe_modes(&self.from_by_mode)
+            ));
+        }
+        if !self.to_by_mode.is_empty() {
+            lines.push(format!(
+                "By mode to here: {}",
+                describe_modes(&self.to_by_mode)
+            ));
+        }
         lines
     }
 }
+
+// Matches trips starting and/or ending somewhere. None matches anything.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TripEndpointFilter {
+    pub start: Option<TripStart>,
+    pub end: Option<TripEnd>,
+}
+
+impl TripEndpointFilter {
+    pub fn any() -> TripEndpointFilter {
+        TripEndpointFilter {
+            start: None,
+            end: None,
+        }
+    }
+
+    pub fn matches(&self, start: &TripStart, end: &TripEnd) -> bool {
+        self.start.as_ref().map(|s| s == start).unwrap_or(true)
+            && self.end.as_ref().map(|e| e == end).unwrap_or(true)
+    }
+}
+
+fn describe_modes(counts: &BTreeMap<TripMode, usize>) -> String {
+    counts
+        .iter()
+        .map(|(mode, cnt)| format!("{} {}", cnt, mode))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
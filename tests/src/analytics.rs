@@ -0,0 +1,644 @@
+use crate::places::{BORDER, HOME, NEIGHBOR, WORK};
+use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::{Distance, Duration, DurationHistogram, Statistic, Time};
+use map_model::{PathConstraints, Position};
+use sim::{
+    sort_finished_trips, Analytics, DrivingGoal, Event, PersonID, Scenario, SidewalkSpot, SimFlags,
+    TripID, TripMode, TripPhaseType, TripSortKey, TripSpec,
+};
+use std::collections::BTreeMap;
+
+pub fn run(t: &mut TestRunner) {
+    t.run_fast("parking_overhead", |_| {
+        let mut a = Analytics::new();
+        let t = |m: usize| Time::START_OF_DAY + Duration::minutes(m);
+        for (time, phase) in vec![
+            (t(0), TripPhaseType::Walking),
+            (t(2), TripPhaseType::Driving),
+            (t(10), TripPhaseType::Parking),
+            (t(12), TripPhaseType::Walking),
+            (t(15), TripPhaseType::Finished),
+        ] {
+            a.trip_log.push((time, TripID(0), None, phase));
+        }
+        // Only walking
+        a.trip_log
+            .push((t(0), TripID(1), None, TripPhaseType::Walking));
+        a.trip_log
+            .push((t(5), TripID(1), None, TripPhaseType::Finished));
+
+        let results = a.analyze_parking_phases();
+        assert_eq!(results.num_trips, 1);
+        assert_eq!(results.overhead.count(), 1);
+        assert!(results.overhead.describe().starts_with("1 count"));
+        assert!((results.median_overhead().unwrap() - 7.0 / 15.0).abs() < 0.01);
+        // The histograms are bucketed, so the medians are approximate
+        let near = |dt: Option<Duration>, expected: Duration| {
+            let dt = dt.unwrap();
+            let diff = if dt > expected {
+                dt - expected
+            } else {
+                expected - dt
+            };
+            diff <= Duration::seconds(1.0)
+        };
+        assert!(near(results.median_parking_time(), Duration::minutes(2)));
+        assert!(near(results.median_walking_time(), Duration::minutes(5)));
+    });
+
+    t.run_fast("analytics_diff", |_| {
+        let start = Time::START_OF_DAY;
+        let finish = |a: &mut Analytics, id: usize, mode: TripMode, dt: Duration| {
+            a.started_trips.insert(TripID(id), (start, mode));
+            a.finished_trips
+                .push((start + dt, TripID(id), Some(mode), dt));
+        };
+        let mut baseline = Analytics::new();
+        finish(&mut baseline, 0, TripMode::Drive, Duration::minutes(10));
+        finish(&mut baseline, 1, TripMode::Walk, Duration::minutes(30));
+        finish(&mut baseline, 2, TripMode::Walk, Duration::minutes(30));
+        // The driving trip got slower, one walking trip got faster, and there's a new bike trip.
+        let mut edited = Analytics::new();
+        finish(&mut edited, 0, TripMode::Drive, Duration::minutes(20));
+        finish(&mut edited, 1, TripMode::Walk, Duration::minutes(5));
+        finish(&mut edited, 3, TripMode::Bike, Duration::minutes(15));
+        // finished_trips is sorted by time
+        edited.finished_trips.sort_by_key(|(t, _, _, _)| *t);
+        baseline.finished_trips.sort_by_key(|(t, _, _, _)| *t);
+
+        let diff = edited.diff(&baseline, Time::START_OF_DAY + Duration::hours(1));
+        assert_eq!(diff.trip_count_deltas[&TripMode::Drive], 0);
+        assert_eq!(diff.trip_count_deltas[&TripMode::Walk], -1);
+        assert_eq!(diff.trip_count_deltas[&TripMode::Bike], 1);
+        assert!(diff.median_trip_time_deltas[&TripMode::Drive] > Duration::ZERO);
+        assert!(diff.median_trip_time_deltas[&TripMode::Walk] < Duration::ZERO);
+        // Not in the baseline
+        assert!(!diff.median_trip_time_deltas.contains_key(&TripMode::Bike));
+        assert_eq!(diff.num_faster, 1);
+        assert_eq!(diff.num_slower, 1);
+    });
+
+    t.run_fast("summarize_many_trips", |_| {
+        let mut analytics = Analytics::new();
+        let modes = TripMode::all();
+        for idx in 0..100_000 {
+            let start = Time::START_OF_DAY + Duration::seconds((idx / 10) as f64);
+            let dt = Duration::seconds((idx % 600) as f64);
+            let mode = modes[idx % modes.len()];
+            analytics.started_trips.insert(TripID(idx), (start, mode));
+            // Every 17th trip is aborted
+            let m = if idx % 17 == 0 { None } else { Some(mode) };
+            analytics
+                .finished_trips
+                .push((start + Duration::minutes(20), TripID(idx), m, dt));
+        }
+        // A few trips are still going
+        for idx in 100_000..100_050 {
+            analytics.started_trips.insert(
+                TripID(idx),
+                (Time::START_OF_DAY + Duration::hours(1), TripMode::Drive),
+            );
+        }
+        analytics
+            .finished_trips
+            .sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let now = Time::START_OF_DAY + Duration::hours(2);
+        // Big enough that summarize splits the work into chunks
+        let summary = analytics.summarize(now);
+
+        // Merging the histograms from each chunk shifts the buckets slightly
+        let close = |a: &DurationHistogram, b: &DurationHistogram| {
+            a.count() == b.count()
+                && Statistic::all().into_iter().all(|stat| {
+                    let (x, y) = (a.select(stat), b.select(stat));
+                    let diff = if x > y { x - y } else { y - x };
+                    diff <= Duration::seconds(1.0)
+                })
+        };
+        let (all, num_aborted, per_mode) = analytics.trip_times(now);
+        assert!(close(&summary.all, &all));
+        assert_eq!(summary.num_aborted, num_aborted);
+        for mode in TripMode::all() {
+            assert!(close(&summary.per_mode[&mode], &per_mode[&mode]));
+        }
+
+        for (mode, pts) in &summary.finished_over_time {
+            assert_eq!(pts.len(), 100);
+            for (t, cnt) in pts {
+                let expected = analytics
+                    .finished_trips
+                    .iter()
+                    .filter(|(finished, _, m, _)| finished <= t && m == mode)
+                    .count();
+                assert_eq!(*cnt, expected);
+            }
+        }
+
+        // Cached
+        assert!(analytics.summarize(now).all.seems_eq(&summary.all));
+    });
+
+    t.run_fast("active_agents_by_mode", |_| {
+        let mut analytics = Analytics::new();
+        let modes = TripMode::all();
+        for idx in 0..1000 {
+            let start = Time::START_OF_DAY + Duration::seconds((idx * 7 % 3600) as f64);
+            let mode = modes[idx % 3];
+            analytics.started_trips.insert(TripID(idx), (start, mode));
+            // Every 5th trip is still going, and every 11th is aborted
+            if idx % 5 != 0 {
+                let m = if idx % 11 == 0 { None } else { Some(mode) };
+                let dt = Duration::seconds((idx % 900) as f64);
+                analytics
+                    .finished_trips
+                    .push((start + dt, TripID(idx), m, dt));
+            }
+        }
+        analytics
+            .finished_trips
+            .sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let now = Time::START_OF_DAY + Duration::hours(2);
+        let by_mode = analytics.active_agents_by_mode(now);
+        assert_eq!(by_mode.keys().cloned().collect::<Vec<_>>(), modes);
+        // Nobody drove
+        assert!(by_mode[&TripMode::Drive].iter().all(|(_, cnt)| *cnt == 0));
+
+        // The value of a step function at some time
+        let at = |pts: &Vec<(Time, usize)>, t: Time| {
+            pts.iter()
+                .take_while(|(t2, _)| *t2 <= t)
+                .last()
+                .map(|(_, cnt)| *cnt)
+                .unwrap_or(0)
+        };
+        for (t, total) in analytics.active_agents(now) {
+            let sum: usize = by_mode.values().map(|pts| at(pts, t)).sum();
+            assert_eq!(sum, total, "at {}", t);
+        }
+    });
+
+    t.run_fast("active_agents_series", |_| {
+        let t = |m: usize| Time::START_OF_DAY + Duration::minutes(m);
+        let trip = |a: &mut Analytics, id: usize, mode: TripMode, start: Time, end: Time| {
+            a.started_trips.insert(TripID(id), (start, mode));
+            a.finished_trips
+                .push((end, TripID(id), Some(mode), end - start));
+        };
+        let mut baseline = Analytics::new();
+        trip(&mut baseline, 0, TripMode::Walk, t(0), t(10));
+        trip(&mut baseline, 1, TripMode::Drive, t(60), t(90));
+        let mut current = Analytics::new();
+        trip(&mut current, 0, TripMode::Walk, t(0), t(20));
+        trip(&mut current, 1, TripMode::Drive, t(60), t(70));
+        let now = t(30);
+
+        // Without a baseline, there's one series per mode
+        let series = current.active_agents_series(now, None);
+        assert_eq!(
+            series
+                .iter()
+                .map(|(_, m, _)| m.unwrap())
+                .collect::<Vec<_>>(),
+            TripMode::all()
+        );
+        for (label, mode, pts) in &series {
+            assert_eq!(*label, mode.unwrap().to_string());
+            assert_eq!(*pts, current.active_agents_by_mode(now)[&mode.unwrap()]);
+        }
+
+        // Against a baseline, the baseline covers the whole day, but the current simulation only
+        // goes up to now.
+        let series = current.active_agents_series(now, Some(&baseline));
+        assert_eq!(
+            series
+                .iter()
+                .map(|(label, m, _)| (label.as_str(), *m))
+                .collect::<Vec<_>>(),
+            vec![("Baseline", None), ("Current simulation", None)]
+        );
+        assert_eq!(series[0].2, baseline.active_agents(Time::END_OF_DAY));
+        assert_eq!(series[1].2, current.active_agents(now));
+        assert!(series[0].2.iter().any(|(time, _)| *time > now));
+        assert!(series[1].2.iter().all(|(time, _)| *time <= now));
+    });
+
+    t.run_slow("finished_trips_series", |_| {
+        let (map, _, _) = SimFlags::for_test("finished_trips_series").load(&mut Timer::throwaway());
+        let mut analytics = Analytics::new();
+        let modes = TripMode::all();
+        for idx in 0..500 {
+            // Several trips finish at the same time
+            let time = Time::START_OF_DAY + Duration::seconds((idx / 3) as f64);
+            let mode = modes[idx % modes.len()];
+            let ev = if idx % 7 == 0 {
+                Event::TripAborted(TripID(idx), mode)
+            } else {
+                Event::TripFinished(TripID(idx), mode, Duration::minutes(5))
+            };
+            analytics.event(ev, time, &map);
+        }
+
+        // Rebuild from scratch
+        let mut expected: BTreeMap<Option<TripMode>, Vec<(Time, usize)>> = BTreeMap::new();
+        for (t, _, m, _) in &analytics.finished_trips {
+            let count = analytics
+                .finished_trips
+                .iter()
+                .filter(|(t2, _, m2, _)| t2 <= t && m2 == m)
+                .count();
+            let series = expected.entry(*m).or_insert_with(Vec::new);
+            if series.last().map(|(t2, _)| t2 != t).unwrap_or(true) {
+                series.push((*t, count));
+            }
+        }
+
+        let mut all_modes: Vec<Option<TripMode>> = modes.into_iter().map(Some).collect();
+        all_modes.push(None);
+        for mode in all_modes {
+            assert_eq!(
+                analytics.finished_trips_series(mode),
+                expected
+                    .get(&mode)
+                    .map(|series| series.as_slice())
+                    .unwrap_or(&[])
+            );
+        }
+    });
+
+    t.run_fast("sort_finished_trips", |_| {
+        // (finish minute, trip, duration in minutes)
+        let raw = vec![(30, 2, 5), (10, 0, 20), (20, 1, 10)];
+        let trips: Vec<(Time, TripID, Option<TripMode>, Duration)> = raw
+            .into_iter()
+            .map(|(t, id, dt)| {
+                (
+                    Time::START_OF_DAY + Duration::minutes(t),
+                    TripID(id),
+                    Some(TripMode::Walk),
+                    Duration::minutes(dt),
+                )
+            })
+            .collect();
+
+        let sorted_ids = |key: TripSortKey, descending: bool| -> Vec<usize> {
+            let mut list: Vec<&(Time, TripID, Option<TripMode>, Duration)> = trips.iter().collect();
+            sort_finished_trips(&mut list, key, descending);
+            list.into_iter().map(|(_, id, _, _)| id.0).collect()
+        };
+
+        assert_eq!(sorted_ids(TripSortKey::FinishTime, false), vec![0, 1, 2]);
+        assert_eq!(sorted_ids(TripSortKey::FinishTime, true), vec![2, 1, 0]);
+        assert_eq!(sorted_ids(TripSortKey::Duration, false), vec![2, 1, 0]);
+        assert_eq!(sorted_ids(TripSortKey::Duration, true), vec![0, 1, 2]);
+        assert_eq!(sorted_ids(TripSortKey::ID, false), vec![0, 1, 2]);
+        assert_eq!(sorted_ids(TripSortKey::ID, true), vec![2, 1, 0]);
+    });
+
+    t.run_fast("trip_time_percentiles", |_| {
+        let mut analytics = Analytics::new();
+        let minutes = vec![4, 1, 5, 3, 2];
+        for (idx, m) in minutes.into_iter().enumerate() {
+            analytics.finished_trips.push((
+                Time::START_OF_DAY + Duration::minutes(10 + idx),
+                TripID(idx),
+                Some(TripMode::Walk),
+                Duration::minutes(m),
+            ));
+        }
+        // Other modes, aborted trips, and trips finishing later don't count.
+        analytics.finished_trips.push((
+            Time::START_OF_DAY + Duration::minutes(20),
+            TripID(5),
+            Some(TripMode::Drive),
+            Duration::minutes(30),
+        ));
+        analytics.finished_trips.push((
+            Time::START_OF_DAY + Duration::minutes(21),
+            TripID(6),
+            None,
+            Duration::ZERO,
+        ));
+        analytics.finished_trips.push((
+            Time::START_OF_DAY + Duration::hours(2),
+            TripID(7),
+            Some(TripMode::Walk),
+            Duration::minutes(60),
+        ));
+
+        let pcts = analytics
+            .trip_time_percentiles(TripMode::Walk, Time::START_OF_DAY + Duration::hours(1));
+        assert_eq!(pcts.len(), 11);
+        assert_eq!(pcts[0], (0.0, Duration::minutes(1)));
+        assert_eq!(pcts[5], (50.0, Duration::minutes(3)));
+        assert_eq!(pcts[10], (100.0, Duration::minutes(5)));
+        assert!(analytics
+            .trip_time_percentiles(TripMode::Bike, Time::END_OF_DAY)
+            .is_empty());
+    });
+
+    t.run_slow("signal_delays_recorded", |h| {
+        let flags = SimFlags::for_test("signal_delays_recorded");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::minutes(10));
+
+        let analytics = sim.get_analytics();
+        let mut any_delay = false;
+        for i in map.all_intersections() {
+            if let Some((min, median, max)) = analytics.delay_stats(i.id) {
+                assert!(map.maybe_get_traffic_signal(i.id).is_some());
+                assert!(min <= median && median <= max);
+                if max > Duration::ZERO {
+                    any_delay = true;
+                }
+            }
+        }
+        assert!(any_delay);
+    });
+
+    t.run_slow("distance_vs_time", |h| {
+        let flags = SimFlags::for_test("distance_vs_time");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::minutes(20));
+
+        let analytics = sim.get_analytics();
+        let finished: Vec<(TripID, Duration)> = analytics
+            .finished_trips
+            .iter()
+            .filter(|(_, _, m, _)| m.is_some())
+            .map(|(_, id, _, dt)| (*id, *dt))
+            .collect();
+        assert!(!finished.is_empty());
+        let pts = analytics.distance_vs_time(sim.time());
+        assert_eq!(
+            pts.iter()
+                .map(|(id, _, _, dt)| (*id, *dt))
+                .collect::<Vec<_>>(),
+            finished
+        );
+        assert!(pts.iter().any(|(_, _, dist, _)| *dist > Distance::ZERO));
+    });
+
+    t.run_slow("road_throughput_recorded", |h| {
+        let flags = SimFlags::for_test("road_throughput_recorded");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let route = map.get_bus_route("49").unwrap();
+        // small_run seeds all bus routes
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::minutes(10));
+
+        // The bus reaches the second stop well before this, so it must've used that road.
+        let road = map
+            .get_l(map.get_bs(route.stops[1]).driving_pos.lane())
+            .parent;
+        let counts = sim
+            .get_analytics()
+            .throughput_over_time(road, Duration::minutes(1));
+        assert!(counts.iter().map(|(_, cnt)| *cnt).sum::<usize>() > 0);
+    });
+
+    t.run_slow("finished_trips_csv", |h| {
+        let flags = SimFlags::for_test("finished_trips_csv");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        sim.schedule_person_trip(
+            Some(PersonID(7)),
+            Time::START_OF_DAY,
+            TripSpec::JustWalking {
+                start: SidewalkSpot::building(HOME, &map),
+                goal: SidewalkSpot::building(NEIGHBOR, &map),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(30)));
+
+        let csv = sim.finished_trips_csv();
+        let lines: Vec<&str> = csv.split('\n').collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "finished_at,trip,person,mode,duration");
+        let row: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(row[1..4].to_vec(), vec!["0", "7", "walk"]);
+        assert!(row[4].parse::<f64>().unwrap() > 0.0);
+    });
+
+    t.run_slow("person_delay", |h| {
+        let flags = SimFlags::for_test("person_delay");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let person = PersonID(7);
+        for (start_time, b1, b2) in vec![
+            (Time::START_OF_DAY, HOME, NEIGHBOR),
+            (Time::START_OF_DAY + Duration::minutes(15), NEIGHBOR, WORK),
+        ] {
+            sim.schedule_person_trip(
+                Some(person),
+                start_time,
+                TripSpec::JustWalking {
+                    start: SidewalkSpot::building(b1, &map),
+                    goal: SidewalkSpot::building(b2, &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.just_run_until_done(&map, Some(Duration::hours(1)));
+
+        let analytics = sim.get_analytics();
+        assert_eq!(analytics.finished_trips.len(), 2);
+        // Entering and leaving buildings alone takes longer than free-flow.
+        assert!(analytics.person_delay[&person] > Duration::ZERO);
+        assert_eq!(
+            analytics.worst_delayed_people(5),
+            vec![(person, analytics.person_delay[&person])]
+        );
+    });
+
+    t.run_slow("vmt_matches_path", |h| {
+        let flags = SimFlags::for_test("vmt_matches_path");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let start_lane = map
+            .get_i(BORDER)
+            .get_outgoing_lanes(&map, PathConstraints::Car)[0];
+        let start_pos =
+            TripSpec::spawn_car_at(Position::new(start_lane, Distance::ZERO), &map).unwrap();
+        let goal = map
+            .all_outgoing_borders()
+            .into_iter()
+            .filter(|i| i.id != BORDER)
+            .filter_map(|i| {
+                DrivingGoal::end_at_border(i.some_incoming_road(&map), PathConstraints::Car, &map)
+            })
+            .next()
+            .unwrap();
+        let spec = TripSpec::CarAppearing {
+            start_pos,
+            goal,
+            vehicle_spec: Scenario::rand_car(&mut rng),
+            ped_speed: Scenario::rand_ped_speed(&mut rng),
+        };
+        let path = map
+            .pathfind(sim.trip_spec_to_path_req(&spec, &map))
+            .unwrap();
+        sim.schedule_trip(Time::START_OF_DAY, spec, &map);
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(30)));
+
+        // The lane where the car appears isn't counted.
+        let vmt = sim.get_analytics().vmt_by_mode()[&TripMode::Drive];
+        assert!(vmt <= path.total_length());
+        assert!(vmt >= path.total_length() - map.get_l(start_lane).length());
+        assert_eq!(
+            sim.get_analytics().vmt_by_mode()[&TripMode::Walk],
+            Distance::ZERO
+        );
+    });
+
+    t.run_slow("building_occupancy_drops", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("building_occupancy_drops").load(&mut Timer::throwaway());
+        let person = PersonID(0);
+        sim.new_person(person, Some(HOME));
+        let depart = Time::START_OF_DAY + Duration::minutes(1);
+        sim.schedule_person_trip(
+            Some(person),
+            depart,
+            TripSpec::JustWalking {
+                start: SidewalkSpot::building(HOME, &map),
+                goal: SidewalkSpot::building(WORK, &map),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::minutes(2));
+
+        let analytics = sim.get_analytics();
+        assert_eq!(analytics.occupancy_at(HOME, Time::START_OF_DAY), 1);
+        assert_eq!(analytics.occupancy_at(HOME, depart), 0);
+        assert_eq!(analytics.occupancy_at(HOME, sim.time()), 0);
+    });
+
+    t.run_slow("sidewalk_crowding", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("sidewalk_crowding").load(&mut Timer::throwaway());
+        let start = SidewalkSpot::building(HOME, &map);
+        let crowded = start.sidewalk_pos.lane();
+        for _ in 0..5 {
+            sim.schedule_trip(
+                Time::START_OF_DAY,
+                TripSpec::JustWalking {
+                    start: start.clone(),
+                    goal: SidewalkSpot::building(WORK, &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        // Everybody is still leaving the building
+        sim.normal_step(&map, Duration::seconds(1.0));
+
+        let empty = map
+            .all_lanes()
+            .iter()
+            .find(|l| l.is_sidewalk() && l.id != crowded)
+            .unwrap()
+            .id;
+        assert!(sim.sidewalk_density(crowded, &map) > 0.0);
+        assert_eq!(sim.sidewalk_density(empty, &map), 0.0);
+        assert_eq!(
+            sim.get_analytics().peds_on_sidewalk_at(empty, sim.time()),
+            0
+        );
+    });
+
+    t.run_slow("trip_origins", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("trip_origins").load(&mut Timer::throwaway());
+        let walk_from = SidewalkSpot::building(HOME, &map);
+        let drive_from = map
+            .get_i(BORDER)
+            .get_outgoing_lanes(&map, PathConstraints::Car)[0];
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::JustWalking {
+                start: walk_from.clone(),
+                goal: SidewalkSpot::building(WORK, &map),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::CarAppearing {
+                start_pos: TripSpec::spawn_car_at(Position::new(drive_from, Distance::ZERO), &map)
+                    .unwrap(),
+                goal: DrivingGoal::ParkNear(WORK),
+                vehicle_spec: Scenario::rand_car(&mut rng),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::minutes(1));
+
+        let analytics = sim.get_analytics();
+        let mut modes = analytics
+            .trip_origins
+            .iter()
+            .map(|(_, mode, _)| *mode)
+            .collect::<Vec<_>>();
+        modes.sort();
+        assert_eq!(modes, vec![TripMode::Walk, TripMode::Drive]);
+        let walk_origin = analytics
+            .trip_origins
+            .iter()
+            .find(|(_, mode, _)| *mode == TripMode::Walk)
+            .unwrap()
+            .2;
+        assert_eq!(walk_origin, walk_from.sidewalk_pos.pt(&map));
+
+        let t = Time::START_OF_DAY;
+        assert_eq!(
+            analytics
+                .trip_origins_between(t, t + Duration::seconds(1.0))
+                .len(),
+            2
+        );
+        assert!(analytics
+            .trip_origins_between(t + Duration::seconds(1.0), sim.time())
+            .is_empty());
+    });
+
+    t.run_slow("mode_counts", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("mode_counts").load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        assert!(sim.mode_counts().values().all(|cnt| *cnt == 0));
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+
+        // Nothing is ongoing anymore, so trip_times only has finished trips
+        let counts = sim.mode_counts();
+        let (_, _, per_mode) = sim.get_analytics().trip_times(sim.time());
+        for mode in TripMode::all() {
+            assert_eq!(counts[&mode], per_mode[&mode].count());
+        }
+        assert!(counts.values().sum::<usize>() > 0);
+    });
+}
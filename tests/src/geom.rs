@@ -1,5 +1,5 @@
 use crate::runner::TestRunner;
-use geom::{Duration, Line, PolyLine, Pt2D};
+use geom::{exact_percentile, nearest_within, Distance, Duration, Line, PolyLine, Pt2D};
 
 #[allow(clippy::unreadable_literal)]
 pub fn run(t: &mut TestRunner) {
@@ -48,6 +48,36 @@ pub fn run(t: &mut TestRunner) {
         assert_eq!(Duration::parse("00:02:03.5"), Ok(Duration::seconds(123.5)));
         assert_eq!(Duration::parse("01:02:03.5"), Ok(Duration::seconds(3723.5)));
     });
+
+    t.run_fast("nearest_point_within_radius", |_| {
+        let pts = vec![
+            ("Trip #1", Pt2D::new(0.0, 0.0)),
+            ("Trip #2", Pt2D::new(10.0, 0.0)),
+            ("Trip #3", Pt2D::new(20.0, 5.0)),
+        ];
+        let radius = Distance::meters(15.0);
+        let nearest = |x, y| nearest_within(pts.clone(), Pt2D::new(x, y), radius);
+
+        assert_eq!(nearest(1.0, 1.0), Some("Trip #1"));
+        assert_eq!(nearest(7.0, 0.0), Some("Trip #2"));
+        assert_eq!(nearest(19.0, 4.0), Some("Trip #3"));
+        // Everything is too far away
+        assert_eq!(nearest(100.0, 100.0), None);
+    });
+
+    t.run_fast("exact_percentile", |_| {
+        assert_eq!(exact_percentile(&Vec::new(), 50.0), None);
+
+        // -50s to 49s, like trip time deltas where some got slower
+        let dts: Vec<Duration> = (-50..50).map(|x| Duration::seconds(x as f64)).collect();
+        assert_eq!(exact_percentile(&dts, 50.0), Some(Duration::seconds(-1.0)));
+        assert_eq!(exact_percentile(&dts, 90.0), Some(Duration::seconds(39.0)));
+        assert_eq!(exact_percentile(&dts, 0.0), Some(Duration::seconds(-50.0)));
+        assert_eq!(exact_percentile(&dts, 100.0), Some(Duration::seconds(49.0)));
+
+        let one = vec![Duration::minutes(2)];
+        assert_eq!(exact_percentile(&one, 90.0), Some(Duration::minutes(2)));
+    });
 }
 
 // TODO test that shifting lines and polylines is a reversible operation
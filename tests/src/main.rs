@@ -1,6 +1,8 @@
+mod analytics;
 mod geom;
 mod map_conversion;
 mod parking;
+mod places;
 mod runner;
 mod sim_completion;
 mod sim_determinism;
@@ -29,6 +31,7 @@ fn main() {
 
     let mut t = runner::TestRunner::new(flags);
 
+    analytics::run(t.suite("analytics"));
     geom::run(t.suite("geom"));
     map_conversion::run(t.suite("map_conversion"));
     parking::run(t.suite("parking"));
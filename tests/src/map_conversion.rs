@@ -1,4 +1,5 @@
 use crate::runner::TestRunner;
+use map_model::{osm, osm_matcher, Map, OsmMatch};
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("convert_osm_twice", |_| {
@@ -45,6 +46,67 @@ pub fn run(t: &mut TestRunner) {
         }
     });
 
+    t.run_slow("search_osm_secondary_only", |_| {
+        // Stand-ins for the two maps in A/B test mode
+        let primary = Map::new(
+            abstutil::path_map("montlake"),
+            false,
+            &mut abstutil::Timer::throwaway(),
+        );
+        let secondary = Map::new(
+            abstutil::path_map("23rd"),
+            false,
+            &mut abstutil::Timer::throwaway(),
+        );
+        let way_ids = |map: &Map| -> Vec<String> {
+            map.all_roads()
+                .iter()
+                .filter_map(|r| r.osm_tags.get(osm::OSM_WAY_ID).cloned())
+                .collect()
+        };
+        let primary_ids = way_ids(&primary);
+        let only_secondary = way_ids(&secondary)
+            .into_iter()
+            .find(|id| !primary_ids.contains(id))
+            .expect("every road in 23rd is also in montlake");
+
+        let query = format!("{} = {}", osm::OSM_WAY_ID, only_secondary);
+        let matches = |x: &str| x == query;
+        let per_map = vec![
+            ("primary", primary.search_osm(&matches)),
+            ("secondary", secondary.search_osm(&matches)),
+        ];
+        let labels: Vec<&str> = per_map
+            .iter()
+            .filter(|(_, ids)| !ids.is_empty())
+            .map(|(label, _)| *label)
+            .collect();
+        assert_eq!(labels, vec!["secondary"]);
+        for id in &per_map[1].1 {
+            match id {
+                OsmMatch::Road(r) => assert_eq!(
+                    secondary.get_r(*r).osm_tags.get(osm::OSM_WAY_ID),
+                    Some(&only_secondary)
+                ),
+                _ => panic!("{:?} shouldn't match a road's way ID", id),
+            }
+        }
+    });
+
+    t.run_fast("osm_matcher_regex_or_substring", |_| {
+        let (matches, regex) = osm_matcher("highway = (primary|secondary)");
+        assert!(regex);
+        assert!(matches("highway = primary"));
+        assert!(matches("highway = secondary"));
+        assert!(!matches("highway = residential"));
+
+        // Unbalanced parens aren't a valid regex, so this is a plain substring search
+        let (matches, regex) = osm_matcher("name = Foo (");
+        assert!(!regex);
+        assert!(matches("name = Foo (Bar)"));
+        assert!(!matches("name = Foo"));
+    });
+
     t.run_slow("bigger_map_loads", |_| {
         map_model::Map::new(
             abstutil::path_raw_map("23rd"),
@@ -1,4 +1,13 @@
+use crate::places::{BORDER, HOME, NEIGHBOR, WORK};
 use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::{Distance, Duration, Time};
+use map_model::{BuildingID, LaneID, LaneType, Map, PathConstraints, Position};
+use sim::{
+    AbortReason, CarID, DrivingGoal, Event, IndividTrip, ParkedCar, ParkingSpot, PersonID,
+    Scenario, SidewalkSpot, Sim, SimFlags, SpawnTrip, TripEndpoint, TripMode, TripSpec,
+    VehicleType,
+};
 /*use abstutil::Timer;
 use geom::Duration;
 use sim::{DrivingGoal, Event, ParkingSpot, Scenario, SidewalkSpot, SimFlags, TripSpec};*/
@@ -8,7 +17,322 @@ use sim::{DrivingGoal, Event, ParkingSpot, Scenario, SidewalkSpot, SimFlags, Tri
 // TODO ped walks to a garage to start driving somewhere else
 // TODO two peds leave same bldg at around the same time, contend for owned cars
 
-pub fn run(_t: &mut TestRunner) {
+pub fn run(t: &mut TestRunner) {
+    t.run_slow("garage_capacity", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("garage_capacity").load(&mut Timer::throwaway());
+        let bldg = map
+            .all_buildings()
+            .iter()
+            .find(|b| sim.get_offstreet_capacity(b.id) > 0)
+            .expect("no offstreet parking to test")
+            .id;
+        let capacity = sim.get_offstreet_capacity(bldg);
+        let road = map.get_l(map.get_b(bldg).sidewalk()).parent;
+        let road_of = |spot: ParkingSpot| match spot {
+            ParkingSpot::Onstreet(l, _) => map.get_l(l).parent,
+            ParkingSpot::Offstreet(b, _) => map.get_l(map.get_b(b).sidewalk()).parent,
+        };
+        let spots_on_road = sim
+            .get_all_parking_spots()
+            .1
+            .into_iter()
+            .filter(|spot| road_of(*spot) == road)
+            .count();
+
+        // Ask for one more car than the building's road can hold, garage included.
+        let mut scenario = Scenario::empty(&map, "garage_capacity");
+        scenario
+            .population
+            .individ_parked_cars
+            .insert(bldg, spots_on_road + 1);
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        assert_eq!(sim.get_offstreet_parked_cars(bldg).len(), capacity);
+        let cars = sim.get_parked_cars_by_owner(bldg);
+        assert_eq!(cars.len(), spots_on_road + 1);
+        assert!(cars.iter().any(|p| road_of(p.spot) != road));
+    });
+
+    t.run_slow("parking_occupancy_drops", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("parking_occupancy_drops").load(&mut Timer::throwaway());
+        let (lane, bldgs) = parking_lane_near_bldgs(&map, &sim, 1);
+        let owner = bldgs[0];
+        let num_cars = sim.get_free_spots(lane).len();
+        let cars = h.seed_parked_cars(
+            &mut sim,
+            &mut rng,
+            lane,
+            Some(owner),
+            (0..num_cars).collect(),
+        );
+
+        // Everybody drives off the map, so nobody parks again.
+        let border = map
+            .all_outgoing_borders()
+            .into_iter()
+            .find(|i| !i.get_incoming_lanes(&map, PathConstraints::Car).is_empty())
+            .unwrap();
+        let goal = DrivingGoal::Border(
+            border.id,
+            border.get_incoming_lanes(&map, PathConstraints::Car)[0],
+        );
+        for (spot, _) in cars {
+            sim.schedule_trip(
+                Time::START_OF_DAY,
+                TripSpec::UsingParkedCar {
+                    start: SidewalkSpot::building(owner, &map),
+                    spot,
+                    goal: goal.clone(),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.just_run_until_done(&map, Some(Duration::hours(1)));
+
+        let occupancy = &sim.get_analytics().parking_occupancy;
+        assert_eq!(occupancy[0].1, num_cars);
+        assert_eq!(occupancy.last().unwrap().1, 0);
+        let pct = sim.get_analytics().parking_over_time();
+        assert!(pct[0].1 > pct.last().unwrap().1);
+    });
+
+    t.run_slow("borrow_nearby_car", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("borrow_nearby_car").load(&mut Timer::throwaway());
+        let (lane, bldgs) = parking_lane_near_bldgs(&map, &sim, 2);
+        let spot = ParkingSpot::Onstreet(lane, 0);
+        let borrower = bldgs[0];
+        let unlucky = bldgs[1];
+        let owner = map
+            .all_buildings()
+            .iter()
+            .find(|b| b.id != borrower && b.id != unlucky)
+            .unwrap()
+            .id;
+        // The only car in the whole map belongs to somebody else.
+        sim.seed_parked_car(Scenario::rand_car(&mut rng), spot, Some(owner));
+
+        let (ped, _) = sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::UsingNearbyParkedCar {
+                start_bldg: borrower,
+                radius: Distance::meters(500.0),
+                goal: DrivingGoal::ParkNear(owner),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        // Nothing left to borrow
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::UsingNearbyParkedCar {
+                start_bldg: unlucky,
+                radius: Distance::meters(500.0),
+                goal: DrivingGoal::ParkNear(owner),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        // The second trip is aborted immediately.
+        assert_eq!(sim.num_trips().0, 1);
+
+        sim.run_until_expectations_met(
+            &map,
+            vec![Event::PedReachedParkingSpot(ped.unwrap(), spot)],
+            Duration::minutes(5),
+        );
+        sim.just_run_until_done(&map, Some(Duration::minutes(30)));
+    });
+
+    t.run_slow("abort_reason_no_parking", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("abort_reason_no_parking").load(&mut Timer::throwaway());
+        // Take every spot on the map, so the car has nowhere to park.
+        for spot in sim.get_all_parking_spots().1 {
+            sim.seed_parked_car(Scenario::rand_car(&mut rng), spot, None);
+        }
+
+        let start = Position::bldg_via_driving(HOME, &map).unwrap();
+        let spec = TripSpec::appearing_on_lane(
+            start,
+            &TripEndpoint::Bldg(WORK),
+            TripMode::Drive,
+            sim.vehicle_sizes(),
+            &map,
+            &mut rng,
+        )
+        .unwrap();
+        sim.schedule_trip(Time::START_OF_DAY, spec, &map);
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.just_run_until_done(&map, Some(Duration::hours(1)));
+
+        let trip = sim.all_trips()[0].0;
+        assert_eq!(sim.abort_reason(trip), Some(AbortReason::NoParking));
+        assert_eq!(
+            sim.aborted_trips_by_reason()
+                .get(&AbortReason::NoParking)
+                .cloned(),
+            Some(1)
+        );
+    });
+
+    t.run_slow("audit_parking_overlap", |h| {
+        let (map, mut sim, _) =
+            SimFlags::for_test("audit_parking_overlap").load(&mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        let (busy, quiet, goal) = (HOME, NEIGHBOR, WORK);
+        let mut scenario = Scenario::empty(&map, "audit_parking_overlap");
+        let seven = Time::START_OF_DAY + Duration::hours(7);
+        // Two people leave the same building at once, but only one car is seeded there. Another
+        // building has exactly enough.
+        for (idx, (b, depart)) in vec![(busy, seven), (busy, seven), (quiet, seven)]
+            .into_iter()
+            .enumerate()
+        {
+            scenario.population.individ_trips.push(IndividTrip {
+                person: PersonID(idx),
+                depart,
+                trip: SpawnTrip::MaybeUsingParkedCar(b, DrivingGoal::ParkNear(goal)),
+            });
+        }
+        scenario.population.individ_parked_cars.insert(busy, 1);
+        scenario.population.individ_parked_cars.insert(quiet, 1);
+
+        let audit = scenario.audit_parking(&map);
+        assert!(audit.contains(&(busy, -1)));
+        assert!(audit.contains(&(quiet, 0)));
+        // Cars only arrive at the destination, so it's never short
+        assert!(audit.contains(&(goal, 0)));
+    });
+
+    t.run_slow("check_parking_capacity", |h| {
+        let (map, mut sim, _) =
+            SimFlags::for_test("check_parking_capacity").load(&mut Timer::throwaway());
+        h.setup_done(&mut sim);
+        let total_spots = sim.get_all_parking_spots().1.len();
+
+        let mut scenario = Scenario::empty(&map, "check_parking_capacity");
+        scenario
+            .population
+            .individ_parked_cars
+            .insert(HOME, total_spots);
+        assert!(scenario.check_capacity(&sim).is_none());
+
+        scenario.population.individ_parked_cars.insert(WORK, 1);
+        assert!(scenario.check_capacity(&sim).is_some());
+    });
+
+    t.run_slow("free_spots_near_building", |h| {
+        let (map, mut sim, _) =
+            SimFlags::for_test("free_spots_near_building").load(&mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        let b = HOME;
+        let road = map.building_to_road(b);
+        let mut own_road_spots = Vec::new();
+        for (l, _) in road
+            .children_forwards
+            .iter()
+            .chain(road.children_backwards.iter())
+        {
+            own_road_spots.extend(sim.get_free_spots(*l));
+        }
+        assert!(!own_road_spots.is_empty());
+
+        let spots = sim.free_spots_near_building(b, &map, 10);
+        assert!(spots.len() > own_road_spots.len());
+        assert_eq!(spots[0..own_road_spots.len()].to_vec(), own_road_spots);
+        // Only looking at the building's own road
+        assert_eq!(sim.free_spots_near_building(b, &map, 1), own_road_spots);
+        // The order is deterministic
+        assert_eq!(sim.free_spots_near_building(b, &map, 10), spots);
+    });
+
+    t.run_slow("parking_skip_probability", |h| {
+        let b = HOME;
+        let seed_one_car = |skip_probability: f64| {
+            let mut flags = SimFlags::for_test("parking_skip_probability");
+            flags.opts.parking_skip_probability = skip_probability;
+            let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+            let mut scenario = Scenario::empty(&map, "parking_skip_probability");
+            scenario.population.individ_parked_cars.insert(b, 1);
+            scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+            let spot = sim.get_parked_cars_by_owner(b)[0].spot;
+            let road = match spot {
+                ParkingSpot::Onstreet(l, _) => map.get_l(l).parent,
+                ParkingSpot::Offstreet(b, _) => map.get_l(map.get_b(b).sidewalk()).parent,
+            };
+            (sim, road == map.building_to_road(b).id)
+        };
+
+        let (_, on_own_road) = seed_one_car(0.0);
+        assert!(on_own_road);
+        // Every road with free spots gets passed up, so the car only lands on the building's own
+        // road if nothing else has room.
+        let (mut sim, on_own_road) = seed_one_car(1.0);
+        h.setup_done(&mut sim);
+        assert!(!on_own_road);
+    });
+
+    t.run_slow("parking_walk_distance", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("parking_walk_distance").load(&mut Timer::throwaway());
+        // A building without a garage, so the car can't park right there
+        let parking_lanes = |b: BuildingID| -> Vec<LaneID> {
+            let r = map.building_to_road(b);
+            r.children_forwards
+                .iter()
+                .chain(r.children_backwards.iter())
+                .map(|(l, _)| *l)
+                .filter(|l| map.get_l(*l).is_parking())
+                .collect()
+        };
+        let goal = map
+            .all_buildings()
+            .iter()
+            .find(|b| b.parking.is_none() && !parking_lanes(b.id).is_empty())
+            .unwrap()
+            .id;
+        // Fill up the goal's road, forcing the car onto an adjacent one
+        for l in parking_lanes(goal) {
+            for spot in sim.get_free_spots(l) {
+                sim.seed_parked_car(Scenario::rand_car(&mut rng), spot, None);
+            }
+        }
+
+        let start_lane = map
+            .get_i(BORDER)
+            .get_outgoing_lanes(&map, PathConstraints::Car)[0];
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::CarAppearing {
+                start_pos: TripSpec::spawn_car_at(Position::new(start_lane, Distance::ZERO), &map)
+                    .unwrap(),
+                goal: DrivingGoal::ParkNear(goal),
+                vehicle_spec: Scenario::rand_car(&mut rng),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(30)));
+
+        let dists = sim.get_analytics().parking_walk_distances();
+        assert_eq!(dists.len(), 1);
+        assert!(dists[0] > Distance::ZERO);
+    });
+
     // TODO Lots of boilerplate between these two. Can we do better?
 
     /*t.run_slow("park_on_goal_st", |h| {
@@ -85,4 +409,50 @@ pub fn run(_t: &mut TestRunner) {
         );
         sim.just_run_until_done(&map, Some(Duration::minutes(1)));
     });*/
+
+    t.run_slow("seed_parked_cars_exact", |h| {
+        let (_, mut sim, mut rng) =
+            SimFlags::for_test("seed_parked_cars_exact").load(&mut Timer::throwaway());
+        let free = sim.get_all_parking_spots().1;
+        let cars = vec![
+            ParkedCar {
+                vehicle: Scenario::rand_car(&mut rng).make(CarID(1000, VehicleType::Car), None),
+                spot: free[0],
+            },
+            ParkedCar {
+                vehicle: Scenario::rand_car(&mut rng)
+                    .make(CarID(1001, VehicleType::Car), Some(HOME)),
+                spot: free[1],
+            },
+        ];
+        sim.seed_parked_cars_exact(cars.clone());
+        h.setup_done(&mut sim);
+
+        for p in &cars {
+            assert_eq!(sim.get_car_at_spot(p.spot), Some(p));
+        }
+        assert_eq!(sim.get_all_parking_spots().1.len(), free.len() - 2);
+    });
+}
+
+// An empty parking lane whose sidewalk has at least this many buildings along it
+fn parking_lane_near_bldgs(map: &Map, sim: &Sim, num_bldgs: usize) -> (LaneID, Vec<BuildingID>) {
+    for l in map.all_lanes() {
+        if !l.is_parking() || sim.get_free_spots(l.id).is_empty() {
+            continue;
+        }
+        if let Ok(sidewalk) = map.find_closest_lane(l.id, vec![LaneType::Sidewalk]) {
+            let bldgs: Vec<BuildingID> = map
+                .all_buildings()
+                .iter()
+                .filter(|b| b.sidewalk() == sidewalk)
+                .map(|b| b.id)
+                .take(num_bldgs)
+                .collect();
+            if bldgs.len() == num_bldgs {
+                return (l.id, bldgs);
+            }
+        }
+    }
+    panic!("No parking lane has {} buildings nearby", num_bldgs);
 }
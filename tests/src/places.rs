@@ -0,0 +1,10 @@
+use map_model::{BuildingID, IntersectionID};
+
+// Places on montlake, the map SimFlags::for_test loads, that tests use as trip endpoints. The
+// buildings are all close together and reachable from the border.
+// TODO Hardcoding IDs is fragile
+pub const HOME: BuildingID = BuildingID(319);
+pub const NEIGHBOR: BuildingID = BuildingID(320);
+pub const WORK: BuildingID = BuildingID(325);
+pub const ERRAND: BuildingID = BuildingID(330);
+pub const BORDER: IntersectionID = IntersectionID(186);
@@ -1,7 +1,13 @@
+use crate::places::{HOME, WORK};
 use crate::runner::TestRunner;
 use abstutil::Timer;
-use geom::Duration;
-use sim::{Scenario, SimFlags};
+use geom::{Distance, Duration, Time};
+use map_model::{IntersectionID, LaneID, Map, Position};
+use sim::{
+    gridlock_component, AgentID, CarID, DrivingGoal, Scenario, SidewalkSpot, SimFlags, TripSpec,
+    VehicleSpec, VehicleType,
+};
+use std::collections::{HashMap, VecDeque};
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("small_spawn_completes", |h| {
@@ -12,4 +18,255 @@ pub fn run(t: &mut TestRunner) {
         h.setup_done(&mut sim);
         sim.just_run_until_done(&map, Some(Duration::minutes(70)));
     });
+
+    t.run_slow("stuck_agents_found", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("stuck_agents_found").load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        // Somebody will wait at a red light eventually.
+        let threshold = Duration::seconds(10.0);
+        let mut stuck = Vec::new();
+        for _ in 0..30 {
+            sim.normal_step(&map, Duration::minutes(1));
+            stuck = sim.find_stuck_agents(threshold);
+            if !stuck.is_empty() {
+                break;
+            }
+        }
+        assert!(!stuck.is_empty());
+        for pair in stuck.windows(2) {
+            assert!(pair[0].1 >= pair[1].1);
+        }
+        for (_, dt) in &stuck {
+            assert!(*dt > threshold);
+        }
+
+        // Nobody has been waiting longer than the longest reported wait
+        let longest = stuck[0].1;
+        assert!(sim.find_stuck_agents(longest).is_empty());
+    });
+
+    t.run_slow("kill_all_stuck_cars", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("kill_all_stuck_cars").load(&mut Timer::throwaway());
+        // Fill each lane in a loop with a car almost as long as the lane, each trying to turn onto
+        // the next lane. Nobody has room to move.
+        let cycle = shortest_lane_cycle(&map);
+        let mut cars = Vec::new();
+        for (idx, l) in cycle.iter().enumerate() {
+            let next = cycle[(idx + 1) % cycle.len()];
+            let len = map.get_l(*l).length();
+            let (_, car) = sim.schedule_trip(
+                Time::START_OF_DAY,
+                TripSpec::CarAppearing {
+                    start_pos: Position::new(*l, len - Distance::meters(0.5)),
+                    vehicle_spec: VehicleSpec {
+                        vehicle_type: VehicleType::Car,
+                        length: len - Distance::meters(1.0),
+                        max_speed: None,
+                    },
+                    goal: DrivingGoal::Border(map.get_l(next).dst_i, next),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+            cars.push(car.unwrap());
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::minutes(1));
+
+        let (finished_before, unfinished_before, _) = sim.num_trips();
+        let num_killed = sim.kill_all_stuck_cars(&map, Duration::ZERO);
+        let (finished_after, unfinished_after, _) = sim.num_trips();
+
+        assert_eq!(num_killed, cars.len());
+        for car in &cars {
+            assert!(!sim.does_agent_exist(AgentID::Car(*car)));
+        }
+        // Aborted trips count as finished
+        assert_eq!(finished_after, finished_before + num_killed);
+        assert_eq!(unfinished_after + num_killed, unfinished_before);
+
+        // Nothing is left dangling
+        sim.normal_step(&map, Duration::minutes(10));
+    });
+
+    t.run_fast("gridlock_component", |_| {
+        let car = |id| AgentID::Car(CarID(id, VehicleType::Car));
+        let i1 = IntersectionID(1);
+        let i2 = IntersectionID(2);
+        let elsewhere = IntersectionID(3);
+
+        // Two intersections deadlocked: cars waiting at each one are blocked by the queue behind
+        // the other. The chains start from different stuck cars.
+        let chains = vec![
+            vec![(i1, car(10)), (i2, car(20))],
+            vec![(i2, car(21)), (i1, car(11))],
+            // An unrelated jam
+            vec![(elsewhere, car(30))],
+        ];
+        assert_eq!(
+            gridlock_component(i2, chains),
+            vec![(i2, vec![car(20), car(21)]), (i1, vec![car(10), car(11)])]
+        );
+
+        // Reach the deadlock transitively, through a chain that only shares one intersection
+        let chains = vec![
+            vec![(elsewhere, car(30)), (i1, car(12))],
+            vec![(i1, car(10)), (i2, car(20))],
+        ];
+        assert_eq!(
+            gridlock_component(i2, chains),
+            vec![
+                (i2, vec![car(20)]),
+                (i1, vec![car(10), car(12)]),
+                (elsewhere, vec![car(30)]),
+            ]
+        );
+    });
+
+    t.run_slow("scheduler_queue_len", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("scheduler_queue_len").load(&mut Timer::throwaway());
+        let before = sim.scheduler_queue_len();
+        for i in 1..=5 {
+            sim.schedule_trip(
+                Time::START_OF_DAY + Duration::minutes(i),
+                TripSpec::JustWalking {
+                    start: SidewalkSpot::building(HOME, &map),
+                    goal: SidewalkSpot::building(WORK, &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        let after = sim.scheduler_queue_len();
+        assert!(after >= before + 5);
+        sim.timed_step(&map, Duration::minutes(2), &mut Timer::throwaway());
+        assert!(sim.get_analytics().max_scheduler_queue_len >= after);
+    });
+
+    t.run_slow("blockage_chain", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("blockage_chain").load(&mut Timer::throwaway());
+        let lane = map
+            .all_lanes()
+            .iter()
+            .filter(|l| l.is_driving())
+            .max_by_key(|l| l.length())
+            .unwrap()
+            .id;
+        assert!(map.get_l(lane).length() > Distance::meters(60.0));
+
+        // Back to front
+        let mut cars = Vec::new();
+        for dist in vec![10.0, 30.0, 50.0] {
+            let (_, car) = sim.schedule_trip(
+                Time::START_OF_DAY,
+                TripSpec::CarAppearing {
+                    start_pos: Position::new(lane, Distance::meters(dist)),
+                    vehicle_spec: VehicleSpec {
+                        vehicle_type: VehicleType::Car,
+                        length: Distance::meters(4.0),
+                        max_speed: None,
+                    },
+                    goal: DrivingGoal::ParkNear(WORK),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+            cars.push(car.unwrap());
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::seconds(0.1));
+
+        assert_eq!(sim.blockage_chain(cars[0]), cars);
+        assert_eq!(sim.blockage_chain(cars[2]), vec![cars[2]]);
+    });
+
+    t.run_slow("lazy_spawning", |h| {
+        let mut flags = SimFlags::for_test("lazy_spawning");
+        flags.opts.lazy_spawning = true;
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        // Nothing has been pathfound or created yet, but the trips still count as unfinished
+        let deferred = sim.num_deferred_trips();
+        assert!(deferred > 0);
+        let (finished, unfinished, _) = sim.num_trips();
+        assert_eq!(finished, 0);
+        assert_eq!(unfinished, deferred);
+        assert_eq!(sim.num_trips_not_departed(), deferred);
+        assert!(!sim.is_done());
+
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+        assert_eq!(sim.num_deferred_trips(), 0);
+        let (finished, _, _) = sim.num_trips();
+        assert!(finished > 0 && finished <= deferred);
+    });
+
+    t.run_slow("trips_not_departed", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("trips_not_departed").load(&mut Timer::throwaway());
+        let mut scenario = Scenario::small_run(&map);
+        // Spread departures over an hour
+        scenario.spawn_over_time[0].stop_time = Time::START_OF_DAY + Duration::hours(1);
+        scenario.border_spawn_over_time.clear();
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        sim.normal_step(&map, Duration::minutes(1));
+        let (_, unfinished, _) = sim.num_trips();
+        let not_departed = sim.num_trips_not_departed();
+        assert!(not_departed <= unfinished);
+        assert!(not_departed > unfinished / 2);
+
+        sim.just_run_until_done(&map, Some(Duration::hours(2)));
+        assert_eq!(sim.num_trips_not_departed(), 0);
+    });
+}
+
+// The shortest loop of driving lanes connected by turns, skipping lanes too short to hold a car
+fn shortest_lane_cycle(map: &Map) -> Vec<LaneID> {
+    let usable = |l: LaneID| {
+        let lane = map.get_l(l);
+        lane.is_driving() && lane.length() > Distance::meters(10.0)
+    };
+
+    let mut best: Option<Vec<LaneID>> = None;
+    for start in map.all_lanes().iter().map(|l| l.id).filter(|l| usable(*l)) {
+        // Breadth-first back to start, remembering how each lane was reached
+        let mut prev: HashMap<LaneID, LaneID> = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        'search: while let Some(current) = queue.pop_front() {
+            for turn in map.get_turns_from_lane(current) {
+                let next = turn.id.dst;
+                if next == start {
+                    let mut cycle = vec![current];
+                    while *cycle.last().unwrap() != start {
+                        cycle.push(prev[cycle.last().unwrap()]);
+                    }
+                    cycle.reverse();
+                    if best.as_ref().map(|b| cycle.len() < b.len()).unwrap_or(true) {
+                        best = Some(cycle);
+                    }
+                    break 'search;
+                }
+                if usable(next) && !prev.contains_key(&next) {
+                    prev.insert(next, current);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    best.unwrap()
 }
@@ -1,7 +1,7 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
-use geom::Duration;
-use sim::{Scenario, Sim, SimFlags, SimOptions};
+use geom::{Duration, Pt2D};
+use sim::{replay_event_log, Scenario, Sim, SimFlags, SimOptions, VisibleEntity};
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("serialization", |_| {
@@ -15,6 +15,42 @@ pub fn run(t: &mut TestRunner) {
         assert_eq!(save1, save2);
     });
 
+    t.run_fast("visible_entities_json", |_| {
+        let entities = vec![
+            VisibleEntity {
+                kind: "Lane".to_string(),
+                id: "LaneID(3)".to_string(),
+                center: Pt2D::new(1.5, 2.0),
+            },
+            VisibleEntity {
+                kind: "Car".to_string(),
+                id: "CarID(7, Car)".to_string(),
+                center: Pt2D::new(10.0, 0.25),
+            },
+        ];
+        assert_eq!(
+            abstutil::to_json(&entities),
+            r#"[
+  {
+    "kind": "Lane",
+    "id": "LaneID(3)",
+    "center": {
+      "inner_x": 1.5,
+      "inner_y": 2.0
+    }
+  },
+  {
+    "kind": "Car",
+    "id": "CarID(7, Car)",
+    "center": {
+      "inner_x": 10.0,
+      "inner_y": 0.25
+    }
+  }
+]"#
+        );
+    });
+
     t.run_slow("from_scratch", |_| {
         println!("Creating two simulations");
         let flags = SimFlags::for_test("from_scratch_1");
@@ -110,4 +146,86 @@ pub fn run(t: &mut TestRunner) {
 
         std::fs::remove_file(sim1_save).unwrap();
     });
+
+    t.run_slow("replay_event_log", |_| {
+        let flags = SimFlags::for_test("replay_event_log");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        sim.record_events(true);
+        sim.normal_step(&map, Duration::minutes(5));
+
+        let path = format!("{}/events.bin", sim.save_dir());
+        sim.save_event_log(path.clone());
+        replay_event_log(
+            path.clone(),
+            &Scenario::small_run(&map),
+            &map,
+            SimOptions::new("replay_event_log_2"),
+            &mut flags.make_rng(),
+            &mut Timer::throwaway(),
+        );
+        std::fs::remove_file(path).unwrap();
+    });
+
+    t.run_slow("rewind", |_| {
+        let mut flags = SimFlags::for_test("rewind");
+        flags.opts.snapshot_every = Some(Duration::minutes(1));
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+
+        sim.normal_step(&map, Duration::minutes(2));
+        let trips_at_snapshot = sim.num_trips();
+        sim.normal_step(&map, Duration::seconds(30.0));
+        let trips_between_snapshots = sim.num_trips();
+        let between_snapshots = sim.clone();
+        sim.normal_step(&map, Duration::minutes(5));
+
+        let target = between_snapshots.time();
+        assert!(sim.rewind_to(&map, target));
+        assert_eq!(sim.time(), target);
+        assert_eq!(sim.num_trips(), trips_between_snapshots);
+        if sim != between_snapshots {
+            panic!("sim state differs after rewinding to {}", target);
+        }
+
+        assert!(sim.rewind_to(&map, target - Duration::seconds(30.0)));
+        assert_eq!(sim.num_trips(), trips_at_snapshot);
+
+        // Nothing was captured before the first minute
+        assert!(!sim.rewind_to(&map, target - Duration::minutes(2)));
+    });
+
+    t.run_slow("bookmarks", |_| {
+        let (map, mut sim, mut rng) = SimFlags::for_test("bookmarks").load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+
+        sim.normal_step(&map, Duration::minutes(2));
+        let early_time = sim.time();
+        let early_trips = sim.num_trips();
+        let early = sim.clone();
+        sim.bookmark("early".to_string());
+
+        sim.normal_step(&map, Duration::minutes(5));
+        let late_trips = sim.num_trips();
+        sim.bookmark("late".to_string());
+        assert_eq!(
+            sim.list_bookmarks(),
+            vec![
+                ("early".to_string(), early_time),
+                ("late".to_string(), sim.time())
+            ]
+        );
+
+        assert!(sim.restore_bookmark("early"));
+        assert_eq!(sim.time(), early_time);
+        assert_eq!(sim.num_trips(), early_trips);
+        if sim != early {
+            panic!("sim state differs after restoring a bookmark");
+        }
+
+        // Bookmarks survive jumping around
+        assert!(sim.restore_bookmark("late"));
+        assert_eq!(sim.num_trips(), late_trips);
+        assert!(!sim.restore_bookmark("nope"));
+    });
 }
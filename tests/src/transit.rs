@@ -1,7 +1,7 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
 use geom::{Duration, Time};
-use sim::{Event, Scenario, SidewalkSpot, SimFlags, TripSpec};
+use sim::{AgentID, Event, Scenario, SidewalkSpot, SimFlags, TripMode, TripSpec, TripStart};
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("bus_reaches_stops", |h| {
@@ -25,6 +25,44 @@ pub fn run(t: &mut TestRunner) {
         sim.just_run_until_done(&map, Some(Duration::minutes(11)));
     });
 
+    t.run_slow("bus_service_isnt_a_passenger", |h| {
+        let (map, mut sim, _) =
+            SimFlags::for_test("bus_service_isnt_a_passenger").load(&mut Timer::throwaway());
+        let route = map.get_bus_route("49").unwrap();
+        let buses = sim.seed_bus_route(route, &map, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::minutes(1));
+
+        for bus in &buses {
+            let trip = sim.agent_to_trip(AgentID::Car(*bus)).unwrap();
+            assert!(sim.is_bus_service(trip));
+        }
+        // Nobody's riding the bus
+        let (_, _, per_mode) = sim.num_trips();
+        assert_eq!(per_mode[&TripMode::Transit], 0);
+    });
+
+    t.run_slow("seed_buses_by_prefix", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("seed_buses_by_prefix").load(&mut Timer::throwaway());
+        let matching = map.get_bus_route("49").unwrap();
+        let other = map
+            .get_all_bus_routes()
+            .iter()
+            .find(|r| !r.name.starts_with("49"))
+            .unwrap();
+
+        let mut scenario = Scenario::empty(&map, "seed_buses_by_prefix");
+        scenario.only_seed_buses_matching = Some("49".to_string());
+        assert!(scenario.should_seed_bus_route(&matching.name));
+        assert!(!scenario.should_seed_bus_route(&other.name));
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        assert!(!sim.status_of_buses(matching.id).is_empty());
+        assert!(sim.status_of_buses(other.id).is_empty());
+    });
+
     t.run_slow("ped_uses_bus", |h| {
         let mut flags = SimFlags::for_test("ped_uses_bus");
         flags.opts.savestate_every = Some(Duration::seconds(30.0));
@@ -78,5 +116,69 @@ pub fn run(t: &mut TestRunner) {
             ],
             Duration::minutes(9),
         );
+
+        let ridership = sim.get_analytics().bus_ridership(route.id);
+        assert_eq!(ridership.len(), 2);
+        assert_eq!(ridership[&ped_stop1], (1, 0));
+        assert_eq!(ridership[&ped_stop2], (0, 1));
+    });
+
+    t.run_slow("transit_wait_time", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("transit_wait_time").load(&mut Timer::throwaway());
+        let route = map.get_bus_route("49").unwrap();
+        let buses = sim.seed_bus_route(route, &map, &mut Timer::throwaway());
+        let bus = buses[0];
+        let ped_stop1 = route.stops[1];
+        let ped_stop2 = route.stops[2];
+        let start_bldg = *map
+            .get_l(map.get_bs(ped_stop1).sidewalk_pos.lane())
+            .building_paths
+            .last()
+            .unwrap();
+        let goal_bldg = map
+            .get_l(map.get_bs(ped_stop2).sidewalk_pos.lane())
+            .building_paths[0];
+        let ped = sim
+            .schedule_trip(
+                Time::START_OF_DAY,
+                TripSpec::UsingTransit {
+                    start: SidewalkSpot::building(start_bldg, &map),
+                    route: route.id,
+                    stop1: ped_stop1,
+                    stop2: ped_stop2,
+                    goal: SidewalkSpot::building(goal_bldg, &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            )
+            .0
+            .unwrap();
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        let trip = sim
+            .all_trips()
+            .into_iter()
+            .find(|(_, mode, start, _, _, _)| {
+                *mode == TripMode::Transit && *start == TripStart::Bldg(start_bldg)
+            })
+            .unwrap()
+            .0;
+        assert_eq!(sim.transit_wait_time(trip), None);
+
+        // The ped reaches the stop before the bus does
+        sim.run_until_expectations_met(
+            &map,
+            vec![
+                Event::PedReachedBusStop(ped, ped_stop1, route.id),
+                Event::BusArrivedAtStop(bus, route.id, ped_stop1),
+                Event::PedEntersBus(ped, bus, route.id),
+            ],
+            Duration::minutes(9),
+        );
+        let wait = sim.transit_wait_time(trip).unwrap();
+        assert!(wait > Duration::ZERO);
+        assert!(wait < Duration::minutes(9));
     });
 }
@@ -1,20 +1,440 @@
+use crate::places::{BORDER, ERRAND, HOME, NEIGHBOR, WORK};
 use crate::runner::TestRunner;
-use abstutil::Timer;
-use geom::{Duration, Time};
-use map_model::{BuildingID, IntersectionID};
-use sim::{DrivingGoal, Event, Scenario, SidewalkSpot, SimFlags, TripSpec};
+use abstutil::{deduplicate_labels, Timer};
+use geojson::GeoJson;
+use geom::{Distance, Duration, GPSBounds, Line, LonLat, Pt2D, Speed, Time};
+use map_model::{
+    connectivity, BuildingID, DirectedRoadID, IntersectionID, LaneID, LaneType, PathConstraints,
+    PathRequest, Position,
+};
+use popdat::psrc::{Endpoint, Mode, Purpose};
+use popdat::{PopDat, TripEndpt};
+use sim::{
+    demand_arrow_width, demand_geojson, straight_line_dot, validate_legs, AbortReason, AgentID,
+    Analytics, BorderSpawnOverTime, BorderSpawnReport, CarAppearingStart, CarID, DrivingGoal,
+    Event, IndividTrip, OriginDestination, PedestrianID, PersonID, PersonState, Scenario,
+    SidewalkSpot, SimFlags, SpawnTrip, StopCondition, StopReason, TripEnd, TripEndpoint,
+    TripEndpointFilter, TripID, TripLeg, TripMode, TripPhaseType, TripSpec, TripStart, VehicleSpec,
+    VehicleType, WeightedNeighborhoodChoice, FOLLOWING_DISTANCE, MAX_CAR_LENGTH,
+};
+use std::collections::BTreeMap;
 
 pub fn run(t: &mut TestRunner) {
+    t.run_fast("ends_trip", |_| {
+        let trip = TripID(3);
+        let events = vec![
+            Event::TripPhaseStarting(trip, TripMode::Walk, None, TripPhaseType::Walking),
+            Event::TripFinished(TripID(2), TripMode::Walk, Duration::minutes(1)),
+            Event::BikeStoppedAtSidewalk(CarID(0, VehicleType::Bike), LaneID(0)),
+            Event::TripAborted(trip, TripMode::Walk),
+            Event::TripFinished(trip, TripMode::Walk, Duration::minutes(2)),
+        ];
+        let matches: Vec<usize> = events
+            .iter()
+            .enumerate()
+            .filter(|(_, ev)| ev.ends_trip(trip))
+            .map(|(idx, _)| idx)
+            .collect();
+        assert_eq!(matches, vec![3, 4]);
+    });
+
+    t.run_fast("straight_line_dot", |_| {
+        // 100m at 10m/s, so the trip lasts 10 seconds
+        let line = Line::new(Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0));
+        let depart = Time::START_OF_DAY + Duration::seconds(30.0);
+        let speed = Speed::meters_per_second(10.0);
+        let at = |secs: f64| {
+            straight_line_dot(
+                &line,
+                depart,
+                speed,
+                Time::START_OF_DAY + Duration::seconds(secs),
+            )
+        };
+
+        assert_eq!(at(29.0), None);
+        assert_eq!(at(30.0), Some(Pt2D::new(0.0, 0.0)));
+        assert_eq!(at(32.5), Some(Pt2D::new(25.0, 0.0)));
+        assert_eq!(at(40.0), Some(Pt2D::new(100.0, 0.0)));
+        assert_eq!(at(41.0), None);
+    });
+
+    t.run_slow("bike_from_one_way_sidewalk", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("bike_from_one_way_sidewalk").load(&mut Timer::throwaway());
+        // Find a building on a one-way street
+        let start_bldg = map
+            .all_buildings()
+            .iter()
+            .find(|b| {
+                let r = map.get_parent(b.sidewalk());
+                let has_driving = |lanes: &Vec<(LaneID, LaneType)>| {
+                    lanes.iter().any(|(_, lt)| *lt == LaneType::Driving)
+                };
+                has_driving(&r.children_forwards) != has_driving(&r.children_backwards)
+                    && r.any_on_other_side(b.sidewalk(), LaneType::Sidewalk)
+                        .is_some()
+            })
+            .expect("no buildings on a one-way street")
+            .id;
+        assert!(
+            SidewalkSpot::bike_from_bike_rack(map.get_b(start_bldg).sidewalk(), &map).is_some()
+        );
+
+        let goal_bldg = HOME;
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::UsingBike {
+                start: SidewalkSpot::building(start_bldg, &map),
+                vehicle: Scenario::rand_bike(&mut rng),
+                goal: DrivingGoal::ParkNear(goal_bldg),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        // The trip used to be skipped silently
+        let trips = sim.all_trips();
+        assert_eq!(trips.len(), 1);
+        assert_eq!(trips[0].1, TripMode::Bike);
+    });
+
+    t.run_slow("bike_rack_near_building", |_| {
+        let (map, _, _) =
+            SimFlags::for_test("bike_rack_near_building").load(&mut Timer::throwaway());
+        let bldg = map.get_b(HOME);
+        let front = bldg.front_path.sidewalk;
+
+        // With no destination, the rack is in the middle of the sidewalk
+        let midpoint = SidewalkSpot::bike_rack(front.lane(), None, &map).unwrap();
+        assert_eq!(
+            midpoint.sidewalk_pos.dist_along(),
+            map.get_l(front.lane()).length() / 2.0
+        );
+
+        // Otherwise, it's right in front of the building
+        let rack = SidewalkSpot::bike_rack(front.lane(), Some(bldg.id), &map).unwrap();
+        assert_eq!(rack.sidewalk_pos, front);
+
+        // And the bike stops lined up with it
+        let goal = DrivingGoal::ParkNear(bldg.id).goal_pos(PathConstraints::Bike, &map);
+        if map.get_parent(goal.lane()).bike_to_sidewalk(goal.lane()) == Some(front.lane()) {
+            assert_eq!(goal, front.equiv_pos(goal.lane(), Distance::ZERO, &map));
+        }
+    });
+
+    t.run_fast("weighted_neighborhood_choice", |_| {
+        let choice = WeightedNeighborhoodChoice::parse("north:3, south:1,east:0").unwrap();
+        assert_eq!(
+            choice.weights,
+            vec![
+                ("north".to_string(), 3),
+                ("south".to_string(), 1),
+                ("east".to_string(), 0)
+            ]
+        );
+        assert!(WeightedNeighborhoodChoice::parse("north:x").is_none());
+        assert!(WeightedNeighborhoodChoice::parse("north:0").is_none());
+        assert_eq!(
+            WeightedNeighborhoodChoice::parse("everywhere"),
+            Some(WeightedNeighborhoodChoice::single("everywhere"))
+        );
+
+        let mut rng = SimFlags::for_test("weighted_neighborhood_choice").make_rng();
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        let samples = 10_000;
+        for _ in 0..samples {
+            *counts
+                .entry(choice.sample(&mut rng).to_string())
+                .or_insert(0) += 1;
+        }
+        assert!(!counts.contains_key("east"));
+        // Expect 3/4 from north, with some slack
+        let north = counts["north"] as f64 / samples as f64;
+        assert!(
+            north > 0.7 && north < 0.8,
+            "north picked {} of the time",
+            north
+        );
+        assert_eq!(counts["north"] + counts["south"], samples);
+    });
+
+    t.run_fast("duplicate_trip_labels", |_| {
+        let trip = IndividTrip {
+            person: PersonID(3),
+            depart: Time::START_OF_DAY + Duration::minutes(5),
+            trip: SpawnTrip::MultiStop(
+                vec![
+                    TripEndpoint::Bldg(BuildingID(1)),
+                    TripEndpoint::Bldg(BuildingID(2)),
+                ],
+                TripMode::Walk,
+            ),
+        };
+        let trips = vec![trip.clone(), trip.clone(), trip];
+        let raw: Vec<String> = trips
+            .iter()
+            .map(|t| format!("{} at {}: {:?}", t.person, t.depart, t.trip))
+            .collect();
+        assert_eq!(raw[0], raw[1]);
+
+        let labels = deduplicate_labels(raw.clone());
+        assert_eq!(labels[0], raw[0]);
+        assert_eq!(labels[1], format!("{} (2)", raw[0]));
+        assert_eq!(labels[2], format!("{} (3)", raw[0]));
+
+        // Even if a label happens to look like a deduplicated one
+        let labels =
+            deduplicate_labels(vec!["a".to_string(), "a (2)".to_string(), "a".to_string()]);
+        assert_eq!(labels, vec!["a", "a (2)", "a (3)"]);
+    });
+
+    t.run_fast("demand_arrow_width", |_| {
+        assert_eq!(demand_arrow_width(0, 0), Distance::meters(1.0));
+        assert_eq!(demand_arrow_width(100, 100), Distance::meters(3.0));
+        // A quarter of the busiest arrow gets half the extra width
+        assert_eq!(demand_arrow_width(25, 100), Distance::meters(2.0));
+        // One outlier doesn't squash small counts down to the minimum width
+        assert!(demand_arrow_width(1, 1000) > Distance::meters(1.05));
+        let mut last = Distance::ZERO;
+        for cnt in 0..=50 {
+            let width = demand_arrow_width(cnt, 50);
+            assert!(width >= last);
+            assert!(width >= Distance::meters(1.0) && width <= Distance::meters(3.0));
+            last = width;
+        }
+    });
+
+    t.run_fast("demand_geojson", |_| {
+        let mut bounds = GPSBounds::new();
+        bounds.update(LonLat::new(-122.3, 47.6));
+        bounds.update(LonLat::new(-122.2, 47.7));
+        let home = Pt2D::new(100.0, 100.0);
+        let endpoints = vec![
+            // Only outbound, only inbound, and both
+            (Pt2D::new(200.0, 100.0), 3, 0),
+            (Pt2D::new(100.0, 200.0), 0, 2),
+            (Pt2D::new(300.0, 300.0), 4, 5),
+        ];
+
+        let features = match demand_geojson(home, endpoints, &bounds) {
+            GeoJson::FeatureCollection(fc) => fc.features,
+            x => panic!("Expected a FeatureCollection, got {:?}", x),
+        };
+        assert_eq!(features.len(), 3);
+        let counts: Vec<u64> = features
+            .iter()
+            .map(|f| f.properties.as_ref().unwrap()["count"].as_u64().unwrap())
+            .collect();
+        assert_eq!(counts, vec![3, 2, 9]);
+        for f in &features {
+            match f.geometry.as_ref().unwrap().value {
+                geojson::Value::LineString(ref pts) => assert_eq!(pts.len(), 2),
+                ref x => panic!("Expected a LineString, got {:?}", x),
+            }
+        }
+    });
+
+    t.run_fast("trip_endpoint_filter", |_| {
+        let bldg = BuildingID(3);
+        let border = IntersectionID(5);
+        let from_bldg = TripEndpointFilter {
+            start: Some(TripStart::Bldg(bldg)),
+            end: None,
+        };
+        let to_border = TripEndpointFilter {
+            start: None,
+            end: Some(TripEnd::Border(border)),
+        };
+        let both = TripEndpointFilter {
+            start: Some(TripStart::Bldg(bldg)),
+            end: Some(TripEnd::Border(border)),
+        };
+
+        let bldg_to_border = (TripStart::Bldg(bldg), TripEnd::Border(border));
+        let bldg_to_bldg = (TripStart::Bldg(bldg), TripEnd::Bldg(BuildingID(4)));
+        let border_to_border = (TripStart::Border(border), TripEnd::Border(border));
+        for (start, end) in vec![&bldg_to_border, &bldg_to_bldg, &border_to_border] {
+            assert!(TripEndpointFilter::any().matches(start, end));
+        }
+        assert!(from_bldg.matches(&bldg_to_border.0, &bldg_to_border.1));
+        assert!(from_bldg.matches(&bldg_to_bldg.0, &bldg_to_bldg.1));
+        assert!(!from_bldg.matches(&border_to_border.0, &border_to_border.1));
+        assert!(to_border.matches(&bldg_to_border.0, &bldg_to_border.1));
+        assert!(!to_border.matches(&bldg_to_bldg.0, &bldg_to_bldg.1));
+        assert!(to_border.matches(&border_to_border.0, &border_to_border.1));
+        assert!(both.matches(&bldg_to_border.0, &bldg_to_border.1));
+        assert!(!both.matches(&bldg_to_bldg.0, &bldg_to_bldg.1));
+        assert!(!both.matches(&border_to_border.0, &border_to_border.1));
+    });
+
+    t.run_slow("pass_through_trip_survives_clipping", |h| {
+        let (map, mut sim, _) =
+            SimFlags::for_test("pass_through_trip_survives_clipping").load(&mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        let bounds = map.get_gps_bounds();
+        let start = map
+            .all_incoming_borders()
+            .into_iter()
+            .find(|i| !i.get_outgoing_lanes(&map, PathConstraints::Car).is_empty())
+            .unwrap();
+        let end = map
+            .all_outgoing_borders()
+            .into_iter()
+            .find(|i| {
+                i.id != start.id && !i.get_incoming_lanes(&map, PathConstraints::Car).is_empty()
+            })
+            .unwrap();
+        let popdat = PopDat {
+            trips: vec![popdat::psrc::Trip {
+                from: Endpoint {
+                    pos: start.polygon.center().to_gps(bounds).unwrap(),
+                    osm_building: None,
+                },
+                to: Endpoint {
+                    pos: end.polygon.center().to_gps(bounds).unwrap(),
+                    osm_building: None,
+                },
+                depart_at: Time::START_OF_DAY,
+                mode: Mode::Drive,
+                person: (0, 0),
+                seq: (0, false, 0),
+                purpose: (Purpose::Work, Purpose::Home),
+                trip_time: Duration::minutes(30),
+                trip_dist: Distance::miles(10.0),
+            }],
+            parcels: BTreeMap::new(),
+        };
+
+        let (trips, _) = popdat::clip_popdat(popdat, &map, &mut Timer::throwaway());
+        assert_eq!(trips.len(), 1);
+        match (&trips[0].from, &trips[0].to) {
+            (TripEndpt::Border(i1, _), TripEndpt::Border(i2, _)) => {
+                assert_eq!(*i1, start.id);
+                assert_eq!(*i2, end.id);
+            }
+            x => panic!("Pass-through trip clipped to {:?}", x),
+        }
+        assert!(trips[0].to_spawn_trip(&map).is_some());
+    });
+
+    t.run_slow("border_trip_clipped_to_map", |h| {
+        let (map, mut sim, _) =
+            SimFlags::for_test("border_trip_clipped_to_map").load(&mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        let bounds = map.get_gps_bounds();
+        let bldg = &map.all_buildings()[0];
+        // Head off the map, well past some border
+        let border = map.all_outgoing_borders()[0].polygon.center();
+        let center = map.get_bounds().center();
+        let far_away = Pt2D::new(
+            border.x() + 3.0 * (border.x() - center.x()),
+            border.y() + 3.0 * (border.y() - center.y()),
+        );
+        let raw_time = Duration::hours(2);
+        let raw_dist = Distance::miles(5.0);
+        let popdat = PopDat {
+            trips: vec![popdat::psrc::Trip {
+                from: Endpoint {
+                    pos: bldg.label_center.to_gps(bounds).unwrap(),
+                    osm_building: Some(bldg.osm_way_id),
+                },
+                to: Endpoint {
+                    pos: far_away.forcibly_to_gps(bounds),
+                    osm_building: None,
+                },
+                depart_at: Time::START_OF_DAY,
+                mode: Mode::Walk,
+                person: (0, 0),
+                seq: (0, false, 0),
+                purpose: (Purpose::Home, Purpose::Work),
+                trip_time: raw_time,
+                trip_dist: raw_dist,
+            }],
+            parcels: BTreeMap::new(),
+        };
+
+        let (trips, _) = popdat::clip_popdat(popdat, &map, &mut Timer::throwaway());
+        assert_eq!(trips.len(), 1);
+        assert!(trips[0].trip_time < raw_time);
+        assert!(trips[0].trip_dist < raw_dist);
+        assert_eq!(trips[0].depart_at, Time::START_OF_DAY);
+    });
+
+    t.run_slow("home_from_first_trip", |h| {
+        let (map, mut sim, _) =
+            SimFlags::for_test("home_from_first_trip").load(&mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        // TODO Hardcoding IDs is fragile
+        let home = BuildingID(1);
+        let work = BuildingID(2);
+        let trip = |from, to, depart_at, seq| popdat::Trip {
+            from: TripEndpt::Building(from),
+            to: TripEndpt::Building(to),
+            depart_at,
+            purpose: (Purpose::Home, Purpose::Work),
+            mode: Mode::Walk,
+            trip_time: Duration::minutes(10),
+            trip_dist: Distance::miles(0.5),
+            person: (0, 0),
+            seq,
+        };
+        // Listed out of order on purpose
+        let trips = vec![
+            trip(
+                work,
+                home,
+                Time::START_OF_DAY + Duration::hours(17),
+                (0, true, 0),
+            ),
+            trip(
+                home,
+                work,
+                Time::START_OF_DAY + Duration::hours(7),
+                (0, false, 0),
+            ),
+        ];
+
+        let scenario = popdat::clipped_trips_to_scenario(trips, &map, &mut Timer::throwaway());
+        assert_eq!(scenario.population.people.len(), 1);
+        assert_eq!(scenario.population.people[0].home, Some(home));
+    });
+
+    t.run_slow("bike_goal_on_bike_lane", |h| {
+        let (map, mut sim, _) =
+            SimFlags::for_test("bike_goal_on_bike_lane").load(&mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        // Any building on a road with a dedicated bike lane
+        let b = map
+            .all_buildings()
+            .iter()
+            .find(|b| {
+                map.find_closest_lane(b.sidewalk(), vec![LaneType::Biking])
+                    .is_ok()
+            })
+            .unwrap()
+            .id;
+        let bike_goal = DrivingGoal::ParkNear(b).goal_pos(PathConstraints::Bike, &map);
+        assert!(map.get_l(bike_goal.lane()).is_biking());
+        let car_goal = DrivingGoal::ParkNear(b).goal_pos(PathConstraints::Car, &map);
+        assert!(map.get_l(car_goal.lane()).is_driving());
+    });
+
     t.run_slow("bike_from_border", |h| {
         let mut flags = SimFlags::for_test("bike_from_border");
         flags.opts.savestate_every = Some(Duration::seconds(30.0));
         let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
-        // TODO Hardcoding IDs is fragile
-        let goal_bldg = BuildingID(319);
+        let goal_bldg = HOME;
         let (ped, bike) = sim.schedule_trip(
             Time::START_OF_DAY,
             TripSpec::UsingBike {
-                start: SidewalkSpot::start_at_border(IntersectionID(186), &map).unwrap(),
+                start: SidewalkSpot::start_at_border(BORDER, &map).unwrap(),
                 vehicle: Scenario::rand_bike(&mut rng),
                 goal: DrivingGoal::ParkNear(goal_bldg),
                 ped_speed: Scenario::rand_ped_speed(&mut rng),
@@ -37,4 +457,1022 @@ pub fn run(t: &mut TestRunner) {
         );
         sim.just_run_until_done(&map, Some(Duration::minutes(1)));
     });
+
+    t.run_slow("cancel_future_trip", |h| {
+        let flags = SimFlags::for_test("cancel_future_trip");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        for (b1, b2) in vec![(HOME, NEIGHBOR), (NEIGHBOR, WORK), (WORK, ERRAND)] {
+            sim.schedule_trip(
+                Time::START_OF_DAY + Duration::minutes(1),
+                TripSpec::JustWalking {
+                    start: SidewalkSpot::building(b1, &map),
+                    goal: SidewalkSpot::building(b2, &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        assert!(sim.cancel_future_trip(TripID(1)));
+        assert!(!sim.cancel_future_trip(TripID(1)));
+        sim.just_run_until_done(&map, Some(Duration::minutes(30)));
+
+        let finished = &sim.get_analytics().finished_trips;
+        let mut spawned: Vec<TripID> = finished
+            .iter()
+            .filter(|(_, _, m, _)| m.is_some())
+            .map(|(_, id, _, _)| *id)
+            .collect();
+        spawned.sort();
+        assert_eq!(spawned, vec![TripID(0), TripID(2)]);
+        assert!(finished
+            .iter()
+            .any(|(_, id, m, _)| *id == TripID(1) && m.is_none()));
+        // Too late to cancel now
+        assert!(!sim.cancel_future_trip(TripID(0)));
+    });
+
+    t.run_slow("skip_to_next_event", |h| {
+        let flags = SimFlags::for_test("skip_to_next_event");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let depart = Time::START_OF_DAY + Duration::minutes(1);
+        sim.schedule_trip(
+            depart,
+            TripSpec::JustWalking {
+                start: SidewalkSpot::building(HOME, &map),
+                goal: SidewalkSpot::building(NEIGHBOR, &map),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        // Other commands (like traffic signal updates) might come first, but jumping from event
+        // to event should reach the trip without overshooting it.
+        while sim.get_analytics().started_trips.is_empty() {
+            let dt = sim
+                .time_until_next_event()
+                .expect("nothing scheduled before the trip started");
+            sim.normal_step(&map, dt);
+            assert!(sim.time() <= depart);
+        }
+        assert_eq!(sim.time(), depart);
+        assert!(sim.get_analytics().started_trips.contains_key(&TripID(0)));
+    });
+
+    t.run_slow("time_warp_target_for_trip", |h| {
+        let flags = SimFlags::for_test("time_warp_target_for_trip");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        sim.schedule_trip(
+            Time::START_OF_DAY + Duration::minutes(1),
+            TripSpec::JustWalking {
+                start: SidewalkSpot::building(HOME, &map),
+                goal: SidewalkSpot::building(NEIGHBOR, &map),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        let trip = TripID(0);
+
+        // The trip hasn't started yet. Aim for the expected finish, unless it's already passed or
+        // unknown.
+        let later = sim.time() + Duration::minutes(10);
+        assert_eq!(sim.time_warp_target_for_trip(trip, Some(later)), Ok(later));
+        assert_eq!(
+            sim.time_warp_target_for_trip(trip, Some(Time::START_OF_DAY)),
+            Ok(Time::END_OF_DAY)
+        );
+        assert_eq!(
+            sim.time_warp_target_for_trip(trip, None),
+            Ok(Time::END_OF_DAY)
+        );
+        assert!(sim.time_warp_target_for_trip(TripID(100), None).is_err());
+
+        sim.just_run_until_done(&map, Some(Duration::hours(1)));
+        assert!(sim.time_warp_target_for_trip(trip, Some(later)).is_err());
+    });
+
+    t.run_slow("time_warp_stop_conditions", |h| {
+        let flags = SimFlags::for_test("time_warp_stop_conditions");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let goal = WORK;
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::JustWalking {
+                start: SidewalkSpot::building(HOME, &map),
+                goal: SidewalkSpot::building(goal, &map),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::seconds(0.1));
+        let trip = TripID(0);
+        let ped = sim.trip_to_agent(trip).ok().unwrap();
+
+        let road = map.get_l(map.get_b(goal).sidewalk()).parent;
+        let far_away = map
+            .all_intersections()
+            .iter()
+            .max_by_key(|i| i.polygon.center().dist_to(map.get_b(goal).polygon.center()))
+            .unwrap()
+            .id;
+        sim.set_stop_conditions(vec![
+            StopCondition::AgentInIntersection(ped, far_away),
+            StopCondition::AgentOnRoad(ped, road),
+            StopCondition::TripDone(trip),
+        ]);
+        // The pedestrian reaches the destination's road before finishing, and never goes near
+        // the far intersection.
+        assert_eq!(
+            sim.time_limited_step(&map, Duration::hours(1), Duration::minutes(1)),
+            Some(StopReason::Met(StopCondition::AgentOnRoad(ped, road)))
+        );
+        assert!(sim.stop_condition_met(&StopCondition::AgentOnRoad(ped, road), &map));
+        assert!(!sim.stop_condition_met(&StopCondition::TripDone(trip), &map));
+
+        sim.set_stop_conditions(vec![StopCondition::TripDone(trip)]);
+        assert_eq!(
+            sim.time_limited_step(&map, Duration::hours(1), Duration::minutes(1)),
+            Some(StopReason::Met(StopCondition::TripDone(trip)))
+        );
+        assert!(!sim.stop_condition_met(&StopCondition::AgentOnRoad(ped, road), &map));
+    });
+
+    t.run_slow("car_appearing_from_bldg", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("car_appearing_from_bldg").load(&mut Timer::throwaway());
+        let trip = SpawnTrip::CarAppearing {
+            start: CarAppearingStart::Bldg(HOME),
+            goal: DrivingGoal::ParkNear(WORK),
+            is_bike: false,
+        };
+        let spec = trip
+            .to_trip_spec(&mut rng, &map, sim.vehicle_sizes())
+            .unwrap();
+        match spec {
+            TripSpec::CarAppearing { start_pos, .. } => {
+                let lane = map.get_l(start_pos.lane());
+                assert!(lane.is_driving());
+                // The car fits entirely on the lane
+                assert!(start_pos.dist_along() >= MAX_CAR_LENGTH);
+                assert!(start_pos.dist_along() < lane.length());
+            }
+            ref x => panic!("Expected a car to appear, got {:?}", x),
+        }
+
+        sim.schedule_trip(Time::START_OF_DAY, spec, &map);
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(30)));
+        assert_eq!(sim.get_analytics().finished_trips.len(), 1);
+        assert_eq!(sim.abort_reason(TripID(0)), None);
+    });
+
+    t.run_slow("trip_spec_from_clicks", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("trip_spec_from_clicks").load(&mut Timer::throwaway());
+        // Pretend somebody clicked the middle of the driving lane in front of one building, then
+        // another building.
+        let driving = Position::bldg_via_driving(HOME, &map).unwrap().lane();
+        let start = Position::new(driving, map.get_l(driving).length() / 2.0);
+        let goal = TripEndpoint::Bldg(WORK);
+
+        match TripSpec::appearing_on_lane(
+            start,
+            &goal,
+            TripMode::Drive,
+            sim.vehicle_sizes(),
+            &map,
+            &mut rng,
+        ) {
+            Ok(TripSpec::CarAppearing {
+                start_pos,
+                goal,
+                vehicle_spec,
+                ..
+            }) => {
+                assert_eq!(start_pos, start);
+                assert_eq!(goal, DrivingGoal::ParkNear(WORK));
+                assert_eq!(vehicle_spec.vehicle_type, VehicleType::Car);
+            }
+            x => panic!("Expected a car to appear, got {:?}", x),
+        }
+        // Pedestrians can't appear in the middle of the road
+        assert!(TripSpec::appearing_on_lane(
+            start,
+            &goal,
+            TripMode::Walk,
+            sim.vehicle_sizes(),
+            &map,
+            &mut rng
+        )
+        .is_err());
+
+        let sidewalk = map.get_b(NEIGHBOR).sidewalk();
+        let walk_start = Position::new(sidewalk, map.get_l(sidewalk).length() / 2.0);
+        let walk = TripSpec::appearing_on_lane(
+            walk_start,
+            &goal,
+            TripMode::Walk,
+            sim.vehicle_sizes(),
+            &map,
+            &mut rng,
+        )
+        .unwrap();
+        match walk {
+            TripSpec::JustWalking { ref goal, .. } => {
+                assert_eq!(*goal, SidewalkSpot::building(WORK, &map));
+            }
+            ref x => panic!("Expected a walking trip, got {:?}", x),
+        }
+
+        sim.schedule_trip(Time::START_OF_DAY, walk, &map);
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(30)));
+        assert_eq!(sim.get_analytics().finished_trips.len(), 1);
+    });
+
+    t.run_slow("mode_choice_avoids_congestion", |h| {
+        let flags = SimFlags::for_test("mode_choice_avoids_congestion");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        h.setup_done(&mut sim);
+
+        let start = HOME;
+        let ped_speed = Scenario::rand_ped_speed(&mut rng);
+        let options = vec![TripMode::Drive, TripMode::Walk];
+        // Far enough away that driving normally wins
+        let start_pt = map.get_b(start).polygon.center();
+        let goal = map
+            .all_buildings()
+            .iter()
+            .max_by_key(|b| b.polygon.center().dist_to(start_pt))
+            .map(|b| TripEndpoint::Bldg(b.id))
+            .unwrap();
+
+        let mut analytics = Analytics::new();
+        assert_eq!(
+            TripSpec::choose_mode(start, &goal, ped_speed, &options, &map, &analytics),
+            Some(TripMode::Drive)
+        );
+
+        // Jam every intersection
+        for i in map.all_intersections() {
+            analytics
+                .intersection_delays
+                .insert(i.id, vec![(Time::START_OF_DAY, Duration::hours(10))]);
+        }
+        assert_eq!(
+            TripSpec::choose_mode(start, &goal, ped_speed, &options, &map, &analytics),
+            Some(TripMode::Walk)
+        );
+    });
+
+    t.run_slow("trip_count_by_mode", |h| {
+        let flags = SimFlags::for_test("trip_count_by_mode");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let bldg = HOME;
+        for (b1, b2) in vec![(HOME, NEIGHBOR), (HOME, WORK), (NEIGHBOR, HOME)] {
+            sim.schedule_trip(
+                Time::START_OF_DAY,
+                TripSpec::JustWalking {
+                    start: SidewalkSpot::building(b1, &map),
+                    goal: SidewalkSpot::building(b2, &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+        }
+        let start_lane = map
+            .get_i(BORDER)
+            .get_outgoing_lanes(&map, PathConstraints::Car)[0];
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::CarAppearing {
+                start_pos: TripSpec::spawn_car_at(Position::new(start_lane, Distance::ZERO), &map)
+                    .unwrap(),
+                goal: DrivingGoal::ParkNear(bldg),
+                vehicle_spec: Scenario::rand_car(&mut rng),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        let cnt = sim.count_trips_involving_bldg(bldg);
+        assert_eq!(
+            cnt.from_by_mode.iter().collect::<Vec<_>>(),
+            vec![(&TripMode::Walk, &2)]
+        );
+        assert_eq!(cnt.to_by_mode.get(&TripMode::Walk), Some(&1));
+        assert_eq!(cnt.to_by_mode.get(&TripMode::Drive), Some(&1));
+        assert!(cnt
+            .describe()
+            .contains(&"By mode from here: 2 walk".to_string()));
+    });
+
+    t.run_slow("all_trips", |h| {
+        let flags = SimFlags::for_test("all_trips");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let endpoints = vec![(HOME, NEIGHBOR), (NEIGHBOR, WORK), (WORK, ERRAND)];
+        for (idx, (b1, b2)) in endpoints.iter().enumerate() {
+            sim.schedule_trip(
+                Time::START_OF_DAY + Duration::minutes(idx),
+                TripSpec::JustWalking {
+                    start: SidewalkSpot::building(*b1, &map),
+                    goal: SidewalkSpot::building(*b2, &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        let trips = sim.all_trips();
+        assert_eq!(trips.len(), endpoints.len());
+        for (idx, (id, mode, start, end, spawned_at, finished_at)) in trips.into_iter().enumerate()
+        {
+            assert_eq!(id, TripID(idx));
+            assert_eq!(mode, TripMode::Walk);
+            assert_eq!(start, TripStart::Bldg(endpoints[idx].0));
+            assert_eq!(end, TripEnd::Bldg(endpoints[idx].1));
+            assert_eq!(spawned_at, Time::START_OF_DAY + Duration::minutes(idx));
+            assert!(finished_at.is_none());
+        }
+
+        sim.just_run_until_done(&map, Some(Duration::minutes(30)));
+        assert!(sim
+            .all_trips()
+            .into_iter()
+            .all(|(_, _, _, _, _, finished_at)| finished_at.is_some()));
+    });
+
+    t.run_slow("walk_with_errands", |h| {
+        let flags = SimFlags::for_test("walk_with_errands");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let stops = vec![HOME, NEIGHBOR, WORK, ERRAND];
+        let (ped, _) = sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::MultiStop {
+                stops: stops.iter().map(|b| TripEndpoint::Bldg(*b)).collect(),
+                mode: TripMode::Walk,
+                vehicle_spec: None,
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        // Each stop is visited in order, and the trip only finishes at the last one.
+        sim.run_until_expectations_met(
+            &map,
+            stops
+                .iter()
+                .skip(1)
+                .map(|b| Event::PedReachedBuilding(ped.unwrap(), *b))
+                .collect(),
+            Duration::minutes(30),
+        );
+        assert!(sim.is_done());
+    });
+
+    t.run_slow("drive_with_errands", |h| {
+        let flags = SimFlags::for_test("drive_with_errands");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let errands = vec![HOME, NEIGHBOR];
+        let mut stops = vec![TripEndpoint::Border(BORDER)];
+        stops.extend(errands.iter().map(|b| TripEndpoint::Bldg(*b)));
+        stops.push(TripEndpoint::Bldg(WORK));
+        let (ped, _) = sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::MultiStop {
+                stops,
+                mode: TripMode::Drive,
+                vehicle_spec: Some(Scenario::rand_car(&mut rng)),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        // After parking for each errand, the driver has to walk back to the car to reach the next
+        // stop.
+        sim.run_until_expectations_met(
+            &map,
+            errands
+                .into_iter()
+                .chain(vec![WORK])
+                .map(|b| Event::PedReachedBuilding(ped.unwrap(), b))
+                .collect(),
+            Duration::hours(1),
+        );
+        assert!(sim.is_done());
+        // Finished, not aborted
+        assert_eq!(
+            sim.get_analytics()
+                .finished_trips
+                .iter()
+                .map(|(_, _, mode, _)| *mode)
+                .collect::<Vec<_>>(),
+            vec![Some(TripMode::Drive)]
+        );
+    });
+
+    t.run_slow("following_distance_packs_cars", |_| {
+        // Line up cars bumper-to-bumper on one lane, leaving a 0.5m gap between them. Only the
+        // smaller following distance leaves room for all of them.
+        let car_len = Distance::meters(5.0);
+        let spawned_cars = |following_distance: Distance| -> usize {
+            let mut flags = SimFlags::for_test("following_distance_packs_cars");
+            flags.opts.vehicle_sizes.following_distance = following_distance;
+            let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+            let lane = Position::bldg_via_driving(HOME, &map).unwrap().lane();
+            let mut num_cars = 0;
+            let mut dist = car_len;
+            while dist < map.get_l(lane).length() {
+                sim.schedule_trip(
+                    Time::START_OF_DAY,
+                    TripSpec::CarAppearing {
+                        start_pos: Position::new(lane, dist),
+                        vehicle_spec: VehicleSpec {
+                            vehicle_type: VehicleType::Car,
+                            length: car_len,
+                            max_speed: None,
+                        },
+                        goal: DrivingGoal::ParkNear(WORK),
+                        ped_speed: Scenario::rand_ped_speed(&mut rng),
+                    },
+                    &map,
+                );
+                num_cars += 1;
+                dist += car_len + Distance::meters(0.5);
+            }
+            assert!(num_cars > 1);
+            sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+            sim.timed_step(&map, Duration::seconds(0.1), &mut Timer::throwaway());
+            num_cars
+                - sim
+                    .aborted_trips_by_reason()
+                    .get(&AbortReason::FailedToSpawn)
+                    .cloned()
+                    .unwrap_or(0)
+        };
+        assert!(spawned_cars(Distance::meters(0.5)) > spawned_cars(FOLLOWING_DISTANCE));
+    });
+
+    t.run_slow("border_spawn_too_short", |_| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("border_spawn_too_short").load(&mut Timer::throwaway());
+        // Find somewhere cars can drive, but that's too short for them to appear on
+        let short_road = map
+            .all_roads()
+            .iter()
+            .flat_map(|r| {
+                vec![
+                    DirectedRoadID {
+                        id: r.id,
+                        forwards: true,
+                    },
+                    DirectedRoadID {
+                        id: r.id,
+                        forwards: false,
+                    },
+                ]
+            })
+            .find(|dr| {
+                let lanes = dr.lanes(PathConstraints::Car, &map);
+                !lanes.is_empty()
+                    && lanes
+                        .iter()
+                        .all(|l| map.get_l(*l).length() <= MAX_CAR_LENGTH)
+            })
+            .unwrap();
+
+        let mut scenario = Scenario::empty(&map, "border_spawn_too_short");
+        scenario.border_spawn_over_time.push(BorderSpawnOverTime {
+            num_peds: 0,
+            num_cars: 5,
+            num_bikes: 0,
+            percent_use_transit: 0.0,
+            start_time: Time::START_OF_DAY,
+            stop_time: Time::START_OF_DAY + Duration::minutes(10),
+            start_from_border: short_road,
+            goal: OriginDestination::EndOfRoad(short_road),
+            vehicle_spec: None,
+            ped_speed: None,
+        });
+        let reports = scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        assert_eq!(
+            reports,
+            vec![BorderSpawnReport {
+                start_from_border: short_road,
+                peds: (0, 0),
+                cars: (5, 0),
+                bikes: (0, 0),
+            }]
+        );
+        assert!(reports[0].is_short());
+        assert!(sim.is_done());
+    });
+
+    t.run_slow("person_returns_home", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("person_returns_home").load(&mut Timer::throwaway());
+        let (commuter, visitor) = (PersonID(0), PersonID(1));
+        sim.new_person(commuter, Some(HOME));
+        sim.new_person(visitor, Some(NEIGHBOR));
+        for (person, start_time, b1, b2) in vec![
+            (commuter, Time::START_OF_DAY, HOME, WORK),
+            (
+                commuter,
+                Time::START_OF_DAY + Duration::minutes(15),
+                WORK,
+                HOME,
+            ),
+            (visitor, Time::START_OF_DAY, NEIGHBOR, ERRAND),
+        ] {
+            sim.schedule_person_trip(
+                Some(person),
+                start_time,
+                TripSpec::JustWalking {
+                    start: SidewalkSpot::building(b1, &map),
+                    goal: SidewalkSpot::building(b2, &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        assert_eq!(sim.count_people_home(), (2, 2));
+
+        sim.just_run_until_done(&map, Some(Duration::hours(1)));
+        assert!(sim.is_person_home(commuter));
+        assert!(!sim.is_person_home(visitor));
+        assert_eq!(sim.get_person_state(visitor), PersonState::Inside(ERRAND));
+        assert_eq!(sim.count_people_home(), (1, 2));
+    });
+
+    t.run_slow("person_state_events", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("person_state_events").load(&mut Timer::throwaway());
+        let person = PersonID(0);
+        sim.new_person(person, Some(HOME));
+        for (start_time, b1, b2) in vec![
+            (Time::START_OF_DAY, HOME, WORK),
+            (Time::START_OF_DAY + Duration::minutes(15), WORK, HOME),
+        ] {
+            sim.schedule_person_trip(
+                Some(person),
+                start_time,
+                TripSpec::JustWalking {
+                    start: SidewalkSpot::building(b1, &map),
+                    goal: SidewalkSpot::building(b2, &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        sim.run_until_expectations_met(
+            &map,
+            vec![
+                Event::PersonStateChanged(person, PersonState::Inside(HOME)),
+                Event::PersonStateChanged(person, PersonState::Trip(TripID(0))),
+                Event::PersonStateChanged(person, PersonState::Inside(WORK)),
+                Event::PersonStateChanged(person, PersonState::Trip(TripID(1))),
+                Event::PersonStateChanged(person, PersonState::Inside(HOME)),
+            ],
+            Duration::hours(1),
+        );
+
+        let analytics = sim.get_analytics();
+        assert_eq!(
+            analytics.building_occupancy[&WORK]
+                .iter()
+                .map(|(_, delta)| *delta)
+                .collect::<Vec<_>>(),
+            vec![1, -1]
+        );
+        assert_eq!(analytics.occupancy_at(HOME, sim.time()), 1);
+        assert_eq!(analytics.occupancy_at(WORK, sim.time()), 0);
+    });
+
+    t.run_slow("border_goal_tries_any_lane", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("border_goal_tries_any_lane").load(&mut Timer::throwaway());
+        let start_lane = map
+            .get_i(BORDER)
+            .get_outgoing_lanes(&map, PathConstraints::Car)[0];
+        let start_pos =
+            TripSpec::spawn_car_at(Position::new(start_lane, Distance::ZERO), &map).unwrap();
+        let can_reach = |l: LaneID| {
+            map.pathfind(PathRequest {
+                start: start_pos,
+                end: Position::new(l, map.get_l(l).length()),
+                constraints: PathConstraints::Car,
+            })
+            .is_some()
+        };
+
+        let (i, good_lane) = map
+            .all_outgoing_borders()
+            .into_iter()
+            .filter(|i| i.id != BORDER)
+            .find_map(|i| {
+                i.get_incoming_lanes(&map, PathConstraints::Car)
+                    .into_iter()
+                    .find(|l| can_reach(*l))
+                    .map(|l| (i.id, l))
+            })
+            .unwrap();
+        // Any lane outside the main connected component that can't be reached works as the dead
+        // end.
+        let dead_end = connectivity::find_scc(&map, PathConstraints::Car)
+            .1
+            .into_iter()
+            .find(|l| !can_reach(*l))
+            .expect("no unreachable lane to use as a dead end");
+
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::CarAppearing {
+                start_pos,
+                goal: DrivingGoal::AnyLane(i, vec![dead_end, good_lane]),
+                vehicle_spec: Scenario::rand_car(&mut rng),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(30)));
+        assert_eq!(sim.get_analytics().finished_trips.len(), 1);
+        assert_eq!(sim.abort_reason(TripID(0)), None);
+    });
+
+    t.run_slow("disable_walking", |h| {
+        let mut flags = SimFlags::for_test("disable_walking");
+        flags.opts.enable_walking = false;
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let (ped, car) = sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::JustWalking {
+                start: SidewalkSpot::building(HOME, &map),
+                goal: SidewalkSpot::building(WORK, &map),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        assert_eq!(ped, None);
+        assert_eq!(car, None);
+
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        assert!(sim.is_done());
+        sim.just_run_until_done(&map, Some(Duration::minutes(1)));
+        assert!(sim.get_analytics().finished_trips.is_empty());
+    });
+
+    t.run_slow("trip_distance", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("trip_distance").load(&mut Timer::throwaway());
+        let start = SidewalkSpot::building(HOME, &map);
+        let goal = SidewalkSpot::building(WORK, &map);
+        let path = map
+            .pathfind(PathRequest {
+                start: start.sidewalk_pos,
+                end: goal.sidewalk_pos,
+                constraints: PathConstraints::Pedestrian,
+            })
+            .unwrap();
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::JustWalking {
+                start,
+                goal,
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        let trip = TripID(0);
+        assert_eq!(sim.trip_distance(trip), None);
+        sim.just_run_until_done(&map, Some(Duration::hours(1)));
+        assert_eq!(sim.trip_distance(trip), Some(path.total_length()));
+    });
+
+    t.run_slow("walk_from_lane", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("walk_from_lane").load(&mut Timer::throwaway());
+        let lane = map.get_l(map.get_b(HOME).sidewalk());
+        let dist = lane.length() / 2.0;
+        let goal = SidewalkSpot::building(WORK, &map);
+
+        assert!(
+            SpawnTrip::WalkFromLane(lane.id, lane.length() * 2.0, goal.clone())
+                .to_trip_spec(&mut rng, &map, sim.vehicle_sizes())
+                .is_none()
+        );
+
+        let spec = SpawnTrip::WalkFromLane(lane.id, dist, goal)
+            .to_trip_spec(&mut rng, &map, sim.vehicle_sizes())
+            .unwrap();
+        let (ped, _) = sim.schedule_trip(Time::START_OF_DAY, spec, &map);
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::seconds(0.1));
+
+        let pt = sim
+            .canonical_pt_for_agent(AgentID::Pedestrian(ped.unwrap()), &map)
+            .unwrap();
+        assert!(pt.dist_to(Position::new(lane.id, dist).pt(&map)) < Distance::meters(1.0));
+    });
+
+    t.run_slow("border_spawn_exact_vehicle", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("border_spawn_exact_vehicle").load(&mut Timer::throwaway());
+        let start_lane = map
+            .get_i(BORDER)
+            .get_outgoing_lanes(&map, PathConstraints::Car)[0];
+        let truck = VehicleSpec {
+            vehicle_type: VehicleType::Car,
+            length: Distance::meters(5.0),
+            max_speed: None,
+        };
+        let mut scenario = Scenario::empty(&map, "border_spawn_exact_vehicle");
+        scenario.border_spawn_over_time.push(BorderSpawnOverTime {
+            num_peds: 0,
+            num_cars: 3,
+            num_bikes: 0,
+            percent_use_transit: 0.0,
+            start_time: Time::START_OF_DAY,
+            stop_time: Time::START_OF_DAY + Duration::seconds(1.0),
+            start_from_border: map.get_l(start_lane).get_directed_parent(&map),
+            goal: OriginDestination::GotoBldg(WORK),
+            vehicle_spec: Some(truck.clone()),
+            ped_speed: None,
+        });
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::seconds(5.0));
+
+        let cars = sim
+            .active_agents()
+            .into_iter()
+            .filter_map(|a| match a {
+                AgentID::Car(c) => Some(c),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(!cars.is_empty());
+        for c in cars {
+            assert_eq!(sim.get_vehicle(c).unwrap().length, truck.length);
+        }
+    });
+
+    t.run_fast("agent_id_variants", |_| {
+        let car = CarID(3, VehicleType::Bike);
+        let ped = PedestrianID(5);
+
+        assert_eq!(AgentID::Car(car).car(), Some(car));
+        assert_eq!(AgentID::Car(car).ped(), None);
+        assert_eq!(AgentID::Car(car).as_car(), car);
+        assert_eq!(AgentID::Pedestrian(ped).ped(), Some(ped));
+        assert_eq!(AgentID::Pedestrian(ped).car(), None);
+    });
+
+    t.run_slow("count_through_trips", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("count_through_trips").load(&mut Timer::throwaway());
+        let start = BORDER;
+        let end = map
+            .all_outgoing_borders()
+            .into_iter()
+            .find(|i| i.id != start && SidewalkSpot::end_at_border(i.id, &map).is_some())
+            .unwrap()
+            .id;
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::JustWalking {
+                start: SidewalkSpot::start_at_border(start, &map).unwrap(),
+                goal: SidewalkSpot::end_at_border(end, &map).unwrap(),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::JustWalking {
+                start: SidewalkSpot::start_at_border(start, &map).unwrap(),
+                goal: SidewalkSpot::building(HOME, &map),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        assert_eq!(sim.count_through_trips(), 1);
+    });
+
+    t.run_slow("border_spawn_ped_speed", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("border_spawn_ped_speed").load(&mut Timer::throwaway());
+        let start_lane = map
+            .get_i(BORDER)
+            .get_outgoing_lanes(&map, PathConstraints::Pedestrian)[0];
+        let (low, high) = (Speed::meters_per_second(3.0), Speed::meters_per_second(3.1));
+        let mut scenario = Scenario::empty(&map, "border_spawn_ped_speed");
+        scenario.border_spawn_over_time.push(BorderSpawnOverTime {
+            num_peds: 3,
+            num_cars: 0,
+            num_bikes: 0,
+            percent_use_transit: 0.0,
+            start_time: Time::START_OF_DAY,
+            stop_time: Time::START_OF_DAY + Duration::seconds(1.0),
+            start_from_border: map.get_l(start_lane).get_directed_parent(&map),
+            goal: OriginDestination::GotoBldg(WORK),
+            vehicle_spec: None,
+            ped_speed: Some((low, high)),
+        });
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::seconds(5.0));
+
+        let peds = sim
+            .active_agents()
+            .into_iter()
+            .filter_map(|a| a.ped())
+            .collect::<Vec<_>>();
+        assert!(!peds.is_empty());
+        for p in peds {
+            let speed = sim.get_ped_speed(p).unwrap();
+            assert!(speed >= low && speed <= high);
+        }
+    });
+
+    t.run_slow("describe_trip", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("describe_trip").load(&mut Timer::throwaway());
+        let goal_bldg = HOME;
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::UsingBike {
+                start: SidewalkSpot::building(WORK, &map),
+                vehicle: Scenario::rand_bike(&mut rng),
+                goal: DrivingGoal::ParkNear(goal_bldg),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        let trip = sim
+            .all_trips()
+            .into_iter()
+            .find(|(_, mode, _, _, _, _)| *mode == TripMode::Bike)
+            .unwrap()
+            .0;
+        let lines = sim.describe_trip(trip);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "walk to a bike rack");
+        assert_eq!(lines[1], format!("bike to park near {}", goal_bldg));
+        assert_eq!(lines[2], format!("walk to {}", goal_bldg));
+    });
+
+    t.run_slow("validate_legs", |_| {
+        let (map, _, _) = SimFlags::for_test("validate_legs").load(&mut Timer::throwaway());
+        let route = &map.get_all_bus_routes()[0];
+        let (stop1, stop2) = (route.stops[0], route.stops[1]);
+        let ped = PedestrianID(0);
+        let speed = Speed::miles_per_hour(2.5);
+        let bldg = HOME;
+        let start = TripStart::Bldg(bldg);
+        let walk_to_stop = TripLeg::Walk(ped, speed, SidewalkSpot::bus_stop(stop1, &map));
+        let ride = TripLeg::RideBus(ped, route.id, stop2);
+        let walk_home = TripLeg::Walk(ped, speed, SidewalkSpot::building(bldg, &map));
+
+        assert_eq!(
+            validate_legs(
+                &start,
+                &vec![walk_to_stop.clone(), ride.clone(), walk_home.clone()]
+            ),
+            Ok(())
+        );
+
+        assert!(validate_legs(&start, &Vec::new()).is_err());
+        // Riding a bus without walking to the stop first
+        let err = validate_legs(&start, &vec![ride.clone(), walk_home.clone()]).unwrap_err();
+        assert!(err.starts_with("leg 0 "));
+        // Walking to a stop, but never boarding
+        let err = validate_legs(&start, &vec![walk_to_stop.clone()]).unwrap_err();
+        assert!(err.starts_with("leg 0 "));
+        // Stuck on the bus
+        let err = validate_legs(&start, &vec![walk_to_stop.clone(), ride.clone()]).unwrap_err();
+        assert!(err.starts_with("leg 1 "));
+        // Somebody else finishes the trip
+        let err = validate_legs(
+            &start,
+            &vec![
+                walk_to_stop,
+                ride,
+                TripLeg::Walk(PedestrianID(1), speed, SidewalkSpot::building(bldg, &map)),
+            ],
+        )
+        .unwrap_err();
+        assert!(err.starts_with("leg 2 "));
+        // Buses only serve their route
+        let err = validate_legs(
+            &TripStart::Border(BORDER),
+            &vec![
+                TripLeg::ServeBusRoute(CarID(0, VehicleType::Bus), route.id),
+                walk_home,
+            ],
+        )
+        .unwrap_err();
+        assert!(err.starts_with("leg 0 "));
+        // Nobody drives out of a building without walking to the car
+        let car = VehicleSpec {
+            vehicle_type: VehicleType::Car,
+            length: Distance::meters(4.0),
+            max_speed: None,
+        }
+        .make(CarID(0, VehicleType::Car), Some(bldg));
+        let err = validate_legs(
+            &start,
+            &vec![TripLeg::Drive(car, DrivingGoal::ParkNear(bldg))],
+        )
+        .unwrap_err();
+        assert!(err.starts_with("leg 0 "));
+    });
+
+    t.run_slow("change_trip_goal", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("change_trip_goal").load(&mut Timer::throwaway());
+        let start_lane = map
+            .get_i(BORDER)
+            .get_outgoing_lanes(&map, PathConstraints::Car)[0];
+        let start_pos =
+            TripSpec::spawn_car_at(Position::new(start_lane, Distance::ZERO), &map).unwrap();
+        let mut goals = map
+            .all_outgoing_borders()
+            .into_iter()
+            .filter(|i| i.id != BORDER)
+            .filter_map(|i| {
+                DrivingGoal::end_at_border(i.some_incoming_road(&map), PathConstraints::Car, &map)
+                    .map(|goal| (i.id, goal))
+            });
+        let (_, old_goal) = goals.next().unwrap();
+        let (new_border, _) = goals.next().unwrap();
+
+        let (_, car) = sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::CarAppearing {
+                start_pos,
+                goal: old_goal,
+                vehicle_spec: Scenario::rand_car(&mut rng),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+        sim.normal_step(&map, Duration::seconds(5.0));
+
+        let car = car.unwrap();
+        let trip = sim.agent_to_trip(AgentID::Car(car)).unwrap();
+        sim.change_trip_goal(trip, TripEndpoint::Border(new_border), &map)
+            .unwrap();
+        // Covers what's been driven so far, plus the new route
+        let rerouted_length = sim.get_path(AgentID::Car(car)).unwrap().total_length();
+        assert_eq!(sim.trip_endpoints(trip).1, TripEnd::Border(new_border));
+        // Nobody's there to walk to a building
+        assert!(sim
+            .change_trip_goal(trip, TripEndpoint::Bldg(HOME), &map)
+            .is_err());
+
+        sim.just_run_until_done(&map, Some(Duration::minutes(30)));
+        assert_eq!(
+            sim.get_analytics().finished_trips[0].2,
+            Some(TripMode::Drive)
+        );
+        assert_eq!(sim.trip_distance(trip), Some(rerouted_length));
+    });
 }